@@ -0,0 +1,34 @@
+//! Accepts Ask to Join requests from a single trusted user,
+//! and declines everyone else's.
+use discord_rich_presence::{activity, join_request::JoinDecision, DiscordIpc, DiscordIpcClient};
+
+const TRUSTED_USER_ID: &str = "53908232506183680";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = DiscordIpcClient::new("771124766517755954")?;
+    client.connect()?;
+
+    // Ask to Join is only offered for activities with a party
+    // and a join secret
+    client.set_activity(
+        activity::Activity::new()
+            .state("In a lobby")
+            .party(activity::Party::new().id("lobby-1").size([1, 4]))
+            .secrets(activity::Secrets::new().join("join-secret")),
+    )?;
+
+    client.on_join_request(|request| {
+        if request.user.id == TRUSTED_USER_ID {
+            println!("Accepting {}", request.user.username);
+            JoinDecision::Accept
+        } else {
+            println!("Declining {}", request.user.username);
+            JoinDecision::Decline
+        }
+    })?;
+
+    // Requests are answered as events are received
+    loop {
+        client.recv_event()?;
+    }
+}
@@ -0,0 +1,46 @@
+//! Receives Ask to Join events from a mio event loop, without
+//! blocking on the connection.
+//!
+//! Run with `cargo run --example mio_events --features mio`.
+#[cfg(unix)]
+use discord_rich_presence::{event::Subscription, DiscordIpc, DiscordIpcClient};
+#[cfg(unix)]
+use mio::{Events, Interest, Poll, Token};
+
+#[cfg(unix)]
+const DISCORD: Token = Token(0);
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("The mio integration is only available on Unix");
+}
+
+#[cfg(unix)]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = DiscordIpcClient::new("771124766517755954")?;
+    client.connect()?;
+    client.subscribe(&Subscription::activity_join())?;
+
+    let mut poll = Poll::new()?;
+    poll.registry()
+        .register(&mut client, DISCORD, Interest::READABLE)?;
+
+    // The handshake may already have queued events
+    while let Some(event) = client.try_recv_event()? {
+        println!("{:?}", event);
+    }
+
+    let mut events = Events::with_capacity(16);
+    loop {
+        poll.poll(&mut events, None)?;
+
+        for event in events.iter() {
+            if event.token() == DISCORD {
+                // Several events may have arrived at once
+                while let Some(event) = client.try_recv_event()? {
+                    println!("{:?}", event);
+                }
+            }
+        }
+    }
+}
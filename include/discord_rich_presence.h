@@ -0,0 +1,86 @@
+#ifndef DISCORD_RICH_PRESENCE_H
+#define DISCORD_RICH_PRESENCE_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An opaque client, created by [`drp_client_new`] and freed by
+// [`drp_client_free`]
+typedef struct DrpClient DrpClient;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a client for the application with the given ID, without
+// connecting.
+//
+// Returns `NULL` on failure.
+//
+// # Safety
+// `client_id` must be `NULL` or point to a NUL-terminated string.
+DrpClient *drp_client_new(const char *client_id);
+
+// Connects the client to Discord.
+//
+// Returns `0` on success and `-1` on failure.
+//
+// # Safety
+// `client` must be `NULL` or a client from [`drp_client_new`]
+// which hasn't been freed.
+int drp_connect(DrpClient *client);
+
+// Sets the activity, given as a JSON object.
+//
+// Returns `0` on success and `-1` on failure, including when the
+// JSON isn't a valid activity.
+//
+// # Safety
+// `client` must be `NULL` or a client from [`drp_client_new`]
+// which hasn't been freed, and `json` must be `NULL` or point to
+// a NUL-terminated string.
+int drp_set_activity_json(DrpClient *client, const char *json);
+
+// Clears the activity.
+//
+// Returns `0` on success and `-1` on failure.
+//
+// # Safety
+// `client` must be `NULL` or a client from [`drp_client_new`]
+// which hasn't been freed.
+int drp_clear_activity(DrpClient *client);
+
+// Closes the connection to Discord. The client can connect again
+// afterwards.
+//
+// Returns `0` on success and `-1` on failure.
+//
+// # Safety
+// `client` must be `NULL` or a client from [`drp_client_new`]
+// which hasn't been freed.
+int drp_close(DrpClient *client);
+
+// Frees a client, without closing its connection first. Does
+// nothing if `client` is `NULL`.
+//
+// # Safety
+// `client` must be `NULL` or a client from [`drp_client_new`]
+// which hasn't been freed, and mustn't be used afterwards.
+void drp_client_free(DrpClient *client);
+
+// Returns a description of the last failure on this thread, or
+// `NULL` if nothing has failed yet.
+//
+// The string is owned by the library, and stays valid until the
+// next call on this thread which fails.
+const char *drp_last_error_message(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // DISCORD_RICH_PRESENCE_H
@@ -1,18 +1,34 @@
 //! Provides an interface for building activities to send
 //! to Discord via [`DiscordIpc::set_activity`](crate::DiscordIpc::set_activity).
-use serde_derive::Serialize;
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Error,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    ops::Range,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 /// A struct representing a Discord rich presence activity
 ///
 /// Note that all methods return `Self`, and can be chained
 /// for fluency
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Activity<'a> {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    activity_type: Option<ActivityType>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
-    state: Option<&'a str>,
+    state: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    details: Option<&'a str>,
+    details: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     timestamps: Option<Timestamps>,
@@ -28,13 +44,35 @@ pub struct Activity<'a> {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     buttons: Option<Vec<Button<'a>>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<Cow<'a, str>>,
+
+    // Fields this crate doesn't model, kept at the top level
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
+// The keys of the fields `Activity` models, which extra fields
+// can't replace
+const ACTIVITY_FIELDS: &[&str] = &[
+    "type",
+    "state",
+    "details",
+    "timestamps",
+    "party",
+    "assets",
+    "secrets",
+    "buttons",
+    "url",
+];
+
 /// A struct representing an `Activity`'s timestamps
 ///
 /// Note that all methods return `Self`, and can be chained
 /// for fluency
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Timestamps {
     #[serde(skip_serializing_if = "Option::is_none")]
     start: Option<i64>,
@@ -47,10 +85,11 @@ pub struct Timestamps {
 ///
 /// Note that all methods return `Self`, and can be chained
 /// for fluency
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Party<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    id: Option<&'a str>,
+    id: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     size: Option<[i32; 2]>,
@@ -61,19 +100,111 @@ pub struct Party<'a> {
 ///
 /// Note that all methods return `Self`, and can be chained
 /// for fluency
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Assets<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    large_image: Option<&'a str>,
+    large_image: Option<AssetSource<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    large_text: Option<&'a str>,
+    large_text: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    small_image: Option<&'a str>,
+    small_image: Option<AssetSource<'a>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    small_text: Option<&'a str>,
+    small_text: Option<Cow<'a, str>>,
+}
+
+/// An enum representing where an `Assets` image comes from
+///
+/// Discord takes each image as a single string, which can be any
+/// of these; the variant says which was meant, so that the image
+/// is sent in the right form, and can be checked precisely with
+/// [`assets::validate`](crate::assets::validate).
+///
+/// # Example
+/// ```
+/// # use discord_rich_presence::activity::{AssetSource, Assets};
+/// let assets = Assets::new()
+///     .large_image_source(AssetSource::Url("https://example.com/cover.png".into()))
+///     .small_image_source(AssetSource::Key("logo".into()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetSource<'a> {
+    /// The name of an art asset uploaded for the application
+    Key(Cow<'a, str>),
+    /// The URL of an image, which Discord fetches itself
+    Url(Cow<'a, str>),
+    /// A path on Discord's media proxy, such as
+    /// `external/<signature>/https/example.com/cover.png`, sent as
+    /// `mp:<path>`
+    External(Cow<'a, str>),
+}
+
+impl<'a> AssetSource<'a> {
+    /// Guesses what a string sent as an image refers to: a media
+    /// proxy path if it starts with `mp:`, a URL if it starts with
+    /// `https://` or `http://`, and an asset's name otherwise
+    pub fn guess(image: impl Into<Cow<'a, str>>) -> Self {
+        let image = image.into();
+        if image.starts_with("mp:") {
+            AssetSource::External(match image {
+                Cow::Borrowed(image) => Cow::Borrowed(&image[3..]),
+                Cow::Owned(image) => Cow::Owned(image[3..].to_string()),
+            })
+        } else if image.starts_with("https://") || image.starts_with("http://") {
+            AssetSource::Url(image)
+        } else {
+            AssetSource::Key(image)
+        }
+    }
+
+    /// The string sent to Discord for the image
+    pub fn to_wire(&self) -> Cow<'_, str> {
+        match self {
+            AssetSource::Key(image) | AssetSource::Url(image) => Cow::Borrowed(image),
+            AssetSource::External(path) => Cow::Owned(format!("mp:{}", path)),
+        }
+    }
+}
+
+impl<'a> Serialize for AssetSource<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_wire())
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for AssetSource<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(AssetSource::guess(String::deserialize(deserializer)?))
+    }
+}
+
+/// The longest image key Discord accepts for an `Assets` image
+pub const MAX_IMAGE_KEY_LENGTH: usize = 256;
+
+/// An enum representing how the URL of an external image is sent
+/// to Discord, by [`Assets::external_large_image`] and
+/// [`Assets::external_small_image`]
+///
+/// Neither form is documented as stable. At the time of writing,
+/// desktop clients show an image given directly by its URL, and
+/// fetch it through Discord's media proxy themselves, while some
+/// older and mobile clients show nothing for one. The `mp:` form
+/// names the image by its path on the media proxy instead, but
+/// the paths Discord hands out carry a signature this crate can't
+/// produce, so whether an unsigned path is shown is up to the
+/// client as well. Check how the image appears in the clients
+/// that matter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageUrlMode {
+    /// Send the URL as it is
+    #[default]
+    Direct,
+    /// Send the URL's media proxy path, as
+    /// `mp:external/https/<host>/<path>`
+    MediaProxy,
 }
 
 /// A struct representing the secrets used by an
@@ -81,32 +212,111 @@ pub struct Assets<'a> {
 ///
 /// Note that all methods return `Self`, and can be chained
 /// for fluency
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Secrets<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    join: Option<&'a str>,
+    join: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    spectate: Option<&'a str>,
+    spectate: Option<Cow<'a, str>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    r#match: Option<&'a str>,
+    r#match: Option<Cow<'a, str>>,
 }
 
 /// A struct representing the buttons that are
 /// attached to an `Activity`
 ///
 /// An activity may have a maximum of 2 buttons
-#[derive(Serialize, Clone)]
+///
+/// Buttons received from Discord as part of another user's
+/// activity only carry their label, and have an empty URL
+#[derive(Serialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Button<'a> {
-    label: &'a str,
-    url: &'a str,
+    label: Cow<'a, str>,
+    url: Cow<'a, str>,
+}
+
+/// The kind of an activity, which Discord shows as the verb before
+/// the application's name, as in "Listening to ..."
+///
+/// Activities without a type are shown as [`Playing`](Self::Playing).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ActivityType {
+    /// "Playing ..."
+    Playing,
+    /// "Streaming ...", which needs a stream URL; see
+    /// [`Activity::streaming`]
+    Streaming,
+    /// "Listening to ..."
+    Listening,
+    /// "Watching ..."
+    Watching,
+    /// "Competing in ..."
+    Competing,
+    /// An activity type not known to this crate, with its raw
+    /// value
+    Unknown(u64),
+}
+
+impl ActivityType {
+    /// The value Discord knows the type by
+    pub fn value(self) -> u64 {
+        match self {
+            ActivityType::Playing => 0,
+            ActivityType::Streaming => 1,
+            ActivityType::Listening => 2,
+            ActivityType::Watching => 3,
+            ActivityType::Competing => 5,
+            ActivityType::Unknown(value) => value,
+        }
+    }
+
+    // The verb Discord shows before the application's name
+    fn verb(self) -> &'static str {
+        match self {
+            ActivityType::Streaming => "Streaming",
+            ActivityType::Listening => "Listening to",
+            ActivityType::Watching => "Watching",
+            ActivityType::Competing => "Competing in",
+            ActivityType::Playing | ActivityType::Unknown(_) => "Playing",
+        }
+    }
+}
+
+impl From<u64> for ActivityType {
+    fn from(value: u64) -> Self {
+        match value {
+            0 => ActivityType::Playing,
+            1 => ActivityType::Streaming,
+            2 => ActivityType::Listening,
+            3 => ActivityType::Watching,
+            5 => ActivityType::Competing,
+            other => ActivityType::Unknown(other),
+        }
+    }
+}
+
+impl Serialize for ActivityType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.value())
+    }
+}
+
+impl<'de> Deserialize<'de> for ActivityType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(u64::deserialize(deserializer)?.into())
+    }
 }
 
 impl<'a> Activity<'a> {
     /// Creates a new `Activity`
     pub fn new() -> Self {
         Activity {
+            activity_type: None,
             state: None,
             details: None,
             assets: None,
@@ -114,45 +324,261 @@ impl<'a> Activity<'a> {
             party: None,
             secrets: None,
             timestamps: None,
+            url: None,
+            extra: Map::new(),
+        }
+    }
+
+    /// Creates a new `Activity` shown as "Playing ...", with the
+    /// given details
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::Activity;
+    /// let activity = Activity::playing("Ranked").state("In a match");
+    /// assert_eq!(activity.to_payload_json(), r#"{"details":"Ranked","state":"In a match","type":0}"#);
+    /// ```
+    pub fn playing(details: impl Into<Cow<'a, str>>) -> Self {
+        Activity::new()
+            .activity_type(ActivityType::Playing)
+            .details(details)
+    }
+
+    /// Creates a new `Activity` shown as "Streaming ...", with the
+    /// given details, linking to the stream at `url`
+    ///
+    /// Discord only shows an activity as streaming with the URL of
+    /// a Twitch channel or YouTube video, such as
+    /// `https://twitch.tv/<channel>`; with any other URL, it is
+    /// shown as playing. Which URLs Discord accepts isn't
+    /// documented, so only https URLs are checked for here.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidStreamUrl`] if the URL isn't https.
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::Activity;
+    /// let activity = Activity::streaming("Speedrunning", "https://twitch.tv/example")?;
+    /// assert_eq!(
+    ///     activity.to_payload_json(),
+    ///     r#"{"details":"Speedrunning","type":1,"url":"https://twitch.tv/example"}"#
+    /// );
+    /// # Ok::<(), discord_rich_presence::error::Error>(())
+    /// ```
+    pub fn streaming(
+        details: impl Into<Cow<'a, str>>,
+        url: impl Into<Cow<'a, str>>,
+    ) -> Result<Self, Error> {
+        let activity = Activity::new()
+            .activity_type(ActivityType::Streaming)
+            .details(details)
+            .url(url);
+        activity.validate()?;
+        Ok(activity)
+    }
+
+    /// Creates a new `Activity` shown as "Listening to ...", with
+    /// the given state
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::Activity;
+    /// let activity = Activity::listening("by Some Artist").details("Some Track");
+    /// assert_eq!(
+    ///     activity.to_payload_json(),
+    ///     r#"{"details":"Some Track","state":"by Some Artist","type":2}"#
+    /// );
+    /// ```
+    pub fn listening(state: impl Into<Cow<'a, str>>) -> Self {
+        Activity::new()
+            .activity_type(ActivityType::Listening)
+            .state(state)
+    }
+
+    /// Creates a new `Activity` shown as "Watching ...", with the
+    /// given state
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::Activity;
+    /// let activity = Activity::watching("Season 2, Episode 5");
+    /// assert_eq!(activity.to_payload_json(), r#"{"state":"Season 2, Episode 5","type":3}"#);
+    /// ```
+    pub fn watching(state: impl Into<Cow<'a, str>>) -> Self {
+        Activity::new()
+            .activity_type(ActivityType::Watching)
+            .state(state)
+    }
+
+    /// Creates a new `Activity` shown as "Competing in ...", with
+    /// the given state
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::Activity;
+    /// let activity = Activity::competing("Grand Finals");
+    /// assert_eq!(activity.to_payload_json(), r#"{"state":"Grand Finals","type":5}"#);
+    /// ```
+    pub fn competing(state: impl Into<Cow<'a, str>>) -> Self {
+        Activity::new()
+            .activity_type(ActivityType::Competing)
+            .state(state)
+    }
+
+    /// Sets the URL of the stream a streaming activity links to
+    ///
+    /// See [`streaming`](#method.streaming) for which URLs Discord
+    /// accepts. Discord ignores the URL of other activities.
+    pub fn url(mut self, url: impl Into<Cow<'a, str>>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Sets the URL of the stream if one is given, and leaves it as
+    /// it is otherwise
+    pub fn url_opt(self, url: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match url {
+            Some(url) => self.url(url),
+            None => self,
+        }
+    }
+
+    /// Checks that Discord will show the activity as it is set
+    ///
+    /// A streaming activity needs a stream URL, which must be https,
+    /// or Discord shows it as playing instead.
+    ///
+    /// # Errors
+    /// Returns [`Error::MissingStreamUrl`] if the activity is
+    /// streaming without a URL, or [`Error::InvalidStreamUrl`] if
+    /// its URL isn't https.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.activity_type != Some(ActivityType::Streaming) {
+            return Ok(());
+        }
+
+        match self.url.as_deref() {
+            None => Err(Error::MissingStreamUrl),
+            Some(url) => match url.strip_prefix("https://") {
+                Some(location) if !location.is_empty() => Ok(()),
+                _ => Err(Error::InvalidStreamUrl {
+                    url: url.to_string(),
+                    reason: "it isn't an https URL",
+                }),
+            },
+        }
+    }
+
+    /// Sets the type of the activity
+    pub fn activity_type(mut self, activity_type: ActivityType) -> Self {
+        self.activity_type = Some(activity_type);
+        self
+    }
+
+    /// Sets the type of the activity if one is given, and leaves it
+    /// as it is otherwise
+    pub fn activity_type_opt(self, activity_type: Option<ActivityType>) -> Self {
+        match activity_type {
+            Some(activity_type) => self.activity_type(activity_type),
+            None => self,
         }
     }
 
     /// Sets the state of the activity
-    pub fn state(mut self, state: &'a str) -> Self {
-        self.state = Some(state);
+    pub fn state(mut self, state: impl Into<Cow<'a, str>>) -> Self {
+        self.state = Some(state.into());
         self
     }
 
+    /// Sets the state of the activity if one is given, and leaves
+    /// it as it is otherwise
+    pub fn state_opt(self, state: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match state {
+            Some(state) => self.state(state),
+            None => self,
+        }
+    }
+
     /// Sets the details of the activity
-    pub fn details(mut self, details: &'a str) -> Self {
-        self.details = Some(details);
+    pub fn details(mut self, details: impl Into<Cow<'a, str>>) -> Self {
+        self.details = Some(details.into());
         self
     }
 
+    /// Sets the details of the activity if one is given, and leaves
+    /// it as it is otherwise
+    pub fn details_opt(self, details: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match details {
+            Some(details) => self.details(details),
+            None => self,
+        }
+    }
+
     /// Add a `Timestamps` to this activity
-    pub fn timestamps(mut self, timestamps: Timestamps) -> Self {
-        self.timestamps = Some(timestamps);
+    ///
+    /// Anything converting into `Timestamps` can be given, such as
+    /// a `(start, end)` pair or a `start..end` range of Unix
+    /// timestamps, or a `SystemTime` to start at.
+    pub fn timestamps(mut self, timestamps: impl Into<Timestamps>) -> Self {
+        self.timestamps = Some(timestamps.into());
         self
     }
 
+    /// Sets the `Timestamps` of this activity if one is given, and
+    /// leaves it as it is otherwise
+    pub fn timestamps_opt(self, timestamps: Option<Timestamps>) -> Self {
+        match timestamps {
+            Some(timestamps) => self.timestamps(timestamps),
+            None => self,
+        }
+    }
+
     /// Add a `Party` to this activity
     pub fn party(mut self, party: Party<'a>) -> Self {
         self.party = Some(party);
         self
     }
 
+    /// Sets the `Party` of this activity if one is given, and
+    /// leaves it as it is otherwise
+    pub fn party_opt(self, party: Option<Party<'a>>) -> Self {
+        match party {
+            Some(party) => self.party(party),
+            None => self,
+        }
+    }
+
     /// Add an `Assets` to this activity
     pub fn assets(mut self, assets: Assets<'a>) -> Self {
         self.assets = Some(assets);
         self
     }
 
+    /// Sets the `Assets` of this activity if one is given, and
+    /// leaves it as it is otherwise
+    pub fn assets_opt(self, assets: Option<Assets<'a>>) -> Self {
+        match assets {
+            Some(assets) => self.assets(assets),
+            None => self,
+        }
+    }
+
     /// Add a `Secrets` to this activity
     pub fn secrets(mut self, secrets: Secrets<'a>) -> Self {
         self.secrets = Some(secrets);
         self
     }
 
+    /// Sets the `Secrets` of this activity if one is given, and
+    /// leaves it as it is otherwise
+    pub fn secrets_opt(self, secrets: Option<Secrets<'a>>) -> Self {
+        match secrets {
+            Some(secrets) => self.secrets(secrets),
+            None => self,
+        }
+    }
+
     /// Add a `Vec` of `Button`s to this activity
     ///
     /// An activity may contain no more than 2 buttons
@@ -166,6 +592,138 @@ impl<'a> Activity<'a> {
         self.buttons = Some(buttons);
         self
     }
+
+    /// Adds a field this crate doesn't model, such as one Discord
+    /// added recently, which is sent at the top level of the
+    /// activity alongside the others
+    ///
+    /// Fields Discord sends which aren't modeled are kept the same
+    /// way when an activity is deserialized.
+    ///
+    /// A key naming a modeled field, such as `state`, is ignored
+    /// and logged as a warning: the modeled field always wins. Its
+    /// method should be used instead.
+    pub fn extra_field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        let key = key.into();
+        if ACTIVITY_FIELDS.contains(&key.as_str()) {
+            log::warn!(
+                "ignoring extra activity field `{}`, which is already modeled",
+                key
+            );
+            return self;
+        }
+
+        self.extra.insert(key, value.into());
+        self
+    }
+
+    /// Serializes the activity exactly as it is sent to Discord by
+    /// [`DiscordIpc::set_activity`](crate::DiscordIpc::set_activity)
+    ///
+    /// Keys are sorted and no whitespace is added, so the result
+    /// is stable, and can be compared against snapshots
+    pub fn to_payload_json(&self) -> String {
+        serde_json::json!(self).to_string()
+    }
+
+    /// Renders a plain text approximation of how Discord shows the
+    /// activity, with times relative to now
+    ///
+    /// See [`preview_at`](#method.preview_at) for the layout.
+    pub fn preview(&self) -> String {
+        self.preview_at(&SystemClock)
+    }
+
+    /// Renders a plain text approximation of how Discord shows the
+    /// activity, with times relative to the clock's
+    ///
+    /// Each part Discord shows gets its own line, and parts which
+    /// aren't set are left out: the application's name can't be
+    /// known, so the first line is `Playing a game`, with the verb
+    /// of the activity's type, followed by the details, the state
+    /// with the party's size, the time (`12:34 elapsed` from the
+    /// start, or `03:21 left` until the end), and the buttons'
+    /// labels. Timestamps can be in seconds
+    /// or milliseconds, as with Discord.
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::{activity::{Activity, Party}, clock::SystemClock};
+    /// let activity = Activity::new()
+    ///     .details("Ranked")
+    ///     .state("In a match")
+    ///     .party(Party::new().size([2, 4]));
+    /// assert_eq!(
+    ///     activity.preview_at(&SystemClock),
+    ///     "Playing a game\nRanked\nIn a match (2 of 4)"
+    /// );
+    /// ```
+    pub fn preview_at(&self, clock: &dyn Clock) -> String {
+        let verb = self.activity_type.unwrap_or(ActivityType::Playing).verb();
+        let mut lines = vec![format!("{} a game", verb)];
+        lines.extend(self.details.as_deref().map(String::from));
+
+        let size = self
+            .party
+            .as_ref()
+            .and_then(|party| party.size)
+            .map(|[current, max]| format!("({} of {})", current, max));
+        match (self.state.as_deref(), size) {
+            (Some(state), Some(size)) => lines.push(format!("{} {}", state, size)),
+            (Some(state), None) => lines.push(state.to_string()),
+            (None, Some(size)) => lines.push(size),
+            (None, None) => (),
+        }
+
+        if let Some(timestamps) = &self.timestamps {
+            let now = clock.now_unix_ms().min(i64::MAX as u64) as i64;
+            match (timestamps.start, timestamps.end) {
+                (_, Some(end)) => lines.push(format!(
+                    "{} left",
+                    clock_time(as_millis(end).saturating_sub(now))
+                )),
+                (Some(start), None) => lines.push(format!(
+                    "{} elapsed",
+                    clock_time(now.saturating_sub(as_millis(start)))
+                )),
+                (None, None) => (),
+            }
+        }
+
+        if let Some(buttons) = self.buttons.as_ref().filter(|buttons| !buttons.is_empty()) {
+            let labels: Vec<_> = buttons
+                .iter()
+                .map(|button| format!("[{}]", button.label))
+                .collect();
+            lines.push(labels.join(" "));
+        }
+
+        lines.join("\n")
+    }
+}
+
+// Discord takes timestamps in either unit; anything below this is
+// taken to be in seconds, as it would be in 1973 in milliseconds
+const SECONDS_BELOW: i64 = 100_000_000_000;
+
+fn as_millis(timestamp: i64) -> i64 {
+    if timestamp < SECONDS_BELOW {
+        timestamp.saturating_mul(1000)
+    } else {
+        timestamp
+    }
+}
+
+// Formats a span as `mm:ss`, or `h:mm:ss` from an hour up
+fn clock_time(millis: i64) -> String {
+    let secs = millis.max(0) / 1000;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
 }
 
 impl<'a> Default for Activity<'a> {
@@ -189,11 +747,75 @@ impl Timestamps {
         self
     }
 
+    /// Sets the start time if one is given, and leaves it as it is
+    /// otherwise
+    pub fn start_opt(self, start: Option<i64>) -> Self {
+        match start {
+            Some(start) => self.start(start),
+            None => self,
+        }
+    }
+
     /// Sets the end time
     pub fn end(mut self, end: i64) -> Self {
         self.end = Some(end);
         self
     }
+
+    /// Sets the end time if one is given, and leaves it as it is
+    /// otherwise
+    pub fn end_opt(self, end: Option<i64>) -> Self {
+        match end {
+            Some(end) => self.end(end),
+            None => self,
+        }
+    }
+
+    /// Builds the timestamps of something paused after `elapsed`,
+    /// such as a track paused at 1:23
+    ///
+    /// Discord has no paused state: it counts up from the start it
+    /// was sent. The start is `elapsed` before now, and there is no
+    /// end, so the time shown when the update arrives is `elapsed`.
+    /// Since Discord keeps counting from there, a paused activity
+    /// usually also says so in its state or details.
+    ///
+    /// When playback carries on, send [`resumed`](#method.resumed)
+    /// with the same `elapsed`.
+    pub fn paused_at(elapsed: Duration) -> Self {
+        Self::paused_at_with_clock(&SystemClock, elapsed)
+    }
+
+    /// Builds the timestamps of something paused after `elapsed`,
+    /// as [`paused_at`](#method.paused_at) does, at the current
+    /// time of `clock`
+    pub fn paused_at_with_clock(clock: &dyn Clock, elapsed: Duration) -> Self {
+        Timestamps::new().start(elapsed_since(clock, elapsed))
+    }
+
+    /// Builds the timestamps of something resumed after being
+    /// paused at `elapsed`
+    ///
+    /// The start is moved forward by the time spent paused: it is
+    /// recomputed as `elapsed` before now, rather than kept from
+    /// before the pause, so the time shown picks up at `elapsed`
+    /// instead of jumping ahead by the length of the pause. With a
+    /// start `s` sent before pausing at `p`, and resuming at `r`,
+    /// the new start is `s + (r - p)`.
+    ///
+    /// Discord shows paused and resumed activities alike, so these
+    /// are the timestamps [`paused_at`](#method.paused_at) builds,
+    /// taken at the moment of resuming.
+    pub fn resumed(elapsed: Duration) -> Self {
+        Self::paused_at(elapsed)
+    }
+
+    /// Builds the timestamps of something resumed after being
+    /// paused at `elapsed`, as [`resumed`](#method.resumed) does,
+    /// at the current time of `clock`
+    pub fn resumed_with_clock(clock: &dyn Clock, elapsed: Duration) -> Self {
+        Self::paused_at_with_clock(clock, elapsed)
+    }
 }
 
 impl Default for Timestamps {
@@ -202,6 +824,119 @@ impl Default for Timestamps {
     }
 }
 
+/// Timestamps from a `(start, end)` pair, each in either seconds
+/// or milliseconds since the Unix epoch, as
+/// [`start`](Timestamps::start) and [`end`](Timestamps::end) take
+impl From<(i64, i64)> for Timestamps {
+    fn from((start, end): (i64, i64)) -> Self {
+        Timestamps::new().start(start).end(end)
+    }
+}
+
+/// Timestamps from `start..end`, each in either seconds or
+/// milliseconds since the Unix epoch, as
+/// [`start`](Timestamps::start) and [`end`](Timestamps::end) take
+impl From<Range<i64>> for Timestamps {
+    fn from(range: Range<i64>) -> Self {
+        Timestamps::new().start(range.start).end(range.end)
+    }
+}
+
+/// Timestamps starting at a time, sent in milliseconds since the
+/// Unix epoch, with no end
+///
+/// A time before the epoch is taken to be the epoch itself; use
+/// `try_from` with a `(start, end)` pair to have it rejected.
+impl From<SystemTime> for Timestamps {
+    fn from(start: SystemTime) -> Self {
+        Timestamps::new().start(unix_millis(start).unwrap_or(0))
+    }
+}
+
+/// Timestamps from a `(start, end)` pair of times, sent in
+/// milliseconds since the Unix epoch
+///
+/// # Errors
+/// Returns [`Error::InvalidTimestamps`] if either time is before
+/// the epoch, or the end is before the start.
+impl TryFrom<(SystemTime, SystemTime)> for Timestamps {
+    type Error = Error;
+
+    fn try_from((start, end): (SystemTime, SystemTime)) -> Result<Self, Error> {
+        let invalid = |reason| Error::InvalidTimestamps { reason };
+        let start = unix_millis(start).ok_or_else(|| invalid("the start is before 1970"))?;
+        let end = unix_millis(end).ok_or_else(|| invalid("the end is before 1970"))?;
+        if end < start {
+            return Err(invalid("the end is before the start"));
+        }
+
+        Ok(Timestamps::new().start(start).end(end))
+    }
+}
+
+// Milliseconds since the Unix epoch, if `time` isn't before it
+fn unix_millis(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH).ok().map(millis)
+}
+
+/// Builds the timestamps of media playing from `position`, out of
+/// a total `duration`
+///
+/// The start is `position` before now, and the end the rest of
+/// `duration` after now, both as Unix timestamps in milliseconds,
+/// so Discord shows the elapsed and remaining time. A `position`
+/// past the end is treated as the end.
+///
+/// The timestamps are only correct for as long as playback
+/// continues uninterrupted; rebuild them after seeking or
+/// pausing.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::activity::{self, Activity};
+/// # use std::time::Duration;
+/// let activity = Activity::new()
+///     .details("Listening")
+///     .timestamps(activity::progress(Duration::from_secs(30), Duration::from_secs(200)));
+/// ```
+pub fn progress(position: Duration, duration: Duration) -> Timestamps {
+    progress_at(&SystemClock, position, duration)
+}
+
+/// Builds the timestamps of media playing from `position`, as
+/// [`progress`] does, at the current time of `clock`
+pub fn progress_at(clock: &dyn Clock, position: Duration, duration: Duration) -> Timestamps {
+    let start = elapsed_since(clock, position.min(duration));
+    Timestamps::new()
+        .start(start)
+        .end(start.saturating_add(millis(duration)))
+}
+
+/// Builds the timestamps of media paused at `position`
+///
+/// Only the start is set, `position` before now, so Discord shows
+/// the time elapsed rather than a countdown to the end. This is
+/// [`Timestamps::paused_at`].
+pub fn paused_progress(position: Duration) -> Timestamps {
+    paused_progress_at(&SystemClock, position)
+}
+
+/// Builds the timestamps of media paused at `position`, as
+/// [`paused_progress`] does, at the current time of `clock`
+pub fn paused_progress_at(clock: &dyn Clock, position: Duration) -> Timestamps {
+    Timestamps::paused_at_with_clock(clock, position)
+}
+
+// The Unix timestamp, in milliseconds, `elapsed` before now
+fn elapsed_since(clock: &dyn Clock, elapsed: Duration) -> i64 {
+    let now = clock.now_unix_ms().min(i64::MAX as u64) as i64;
+    now.saturating_sub(millis(elapsed))
+}
+
+fn millis(duration: Duration) -> i64 {
+    duration.as_millis().min(i64::MAX as u128) as i64
+}
+
 impl<'a> Party<'a> {
     /// Creates a new `Party`
     pub fn new() -> Self {
@@ -212,23 +947,42 @@ impl<'a> Party<'a> {
     }
 
     /// Sets the ID of the party
-    pub fn id(mut self, id: &'a str) -> Self {
-        self.id = Some(id);
+    pub fn id(mut self, id: impl Into<Cow<'a, str>>) -> Self {
+        self.id = Some(id.into());
         self
     }
 
+    /// Sets the ID of the party if one is given, and leaves it as
+    /// it is otherwise
+    pub fn id_opt(self, id: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match id {
+            Some(id) => self.id(id),
+            None => self,
+        }
+    }
+
     /// Sets the size of the party (current and maximum)
     ///
     /// # Example
     /// ```
+    /// # use discord_rich_presence::activity::Party;
     /// // Creates a party with a current size
     /// // of 1, and a max size of 3
-    /// let party = Party::new().size([1, 3])
+    /// let party = Party::new().size([1, 3]);
     /// ```
     pub fn size(mut self, size: [i32; 2]) -> Self {
         self.size = Some(size);
         self
     }
+
+    /// Sets the size of the party if one is given, and leaves it as
+    /// it is otherwise
+    pub fn size_opt(self, size: Option<[i32; 2]>) -> Self {
+        match size {
+            Some(size) => self.size(size),
+            None => self,
+        }
+    }
 }
 
 impl<'a> Default for Party<'a> {
@@ -253,34 +1007,168 @@ impl<'a> Assets<'a> {
     ///
     /// Alternatively, the URL of the resource to be used as
     /// the large image
-    pub fn large_image(mut self, large_image: &'a str) -> Self {
-        self.large_image = Some(large_image);
+    ///
+    /// Which of these the string is gets guessed, as with
+    /// [`AssetSource::guess`]
+    pub fn large_image(mut self, large_image: impl Into<Cow<'a, str>>) -> Self {
+        self.large_image = Some(AssetSource::guess(large_image));
+        self
+    }
+
+    /// Sets the large image if one is given, and leaves it as it is
+    /// otherwise
+    pub fn large_image_opt(self, large_image: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match large_image {
+            Some(large_image) => self.large_image(large_image),
+            None => self,
+        }
+    }
+
+    /// Sets where the large image comes from
+    pub fn large_image_source(mut self, source: AssetSource<'a>) -> Self {
+        self.large_image = Some(source);
         self
     }
 
     /// Sets the text to be shown when hovering over the large
     /// image
-    pub fn large_text(mut self, large_text: &'a str) -> Self {
-        self.large_text = Some(large_text);
+    pub fn large_text(mut self, large_text: impl Into<Cow<'a, str>>) -> Self {
+        self.large_text = Some(large_text.into());
         self
     }
 
+    /// Sets the large image's hover text if one is given, and
+    /// leaves it as it is otherwise
+    pub fn large_text_opt(self, large_text: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match large_text {
+            Some(large_text) => self.large_text(large_text),
+            None => self,
+        }
+    }
+
     /// Sets the name of the art asset to be used as the small
     /// image
     ///
     /// Alternatively, the URL of the resource to be used as
     /// the small image
-    pub fn small_image(mut self, small_image: &'a str) -> Self {
-        self.small_image = Some(small_image);
+    ///
+    /// Which of these the string is gets guessed, as with
+    /// [`AssetSource::guess`]
+    pub fn small_image(mut self, small_image: impl Into<Cow<'a, str>>) -> Self {
+        self.small_image = Some(AssetSource::guess(small_image));
+        self
+    }
+
+    /// Sets the small image if one is given, and leaves it as it is
+    /// otherwise
+    pub fn small_image_opt(self, small_image: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match small_image {
+            Some(small_image) => self.small_image(small_image),
+            None => self,
+        }
+    }
+
+    /// Sets where the small image comes from
+    pub fn small_image_source(mut self, source: AssetSource<'a>) -> Self {
+        self.small_image = Some(source);
         self
     }
 
     /// Sets the text that is shown when hovering over the small
     /// image
-    pub fn small_text(mut self, small_text: &'a str) -> Self {
-        self.small_text = Some(small_text);
+    pub fn small_text(mut self, small_text: impl Into<Cow<'a, str>>) -> Self {
+        self.small_text = Some(small_text.into());
         self
     }
+
+    /// Sets the small image's hover text if one is given, and
+    /// leaves it as it is otherwise
+    pub fn small_text_opt(self, small_text: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match small_text {
+            Some(small_text) => self.small_text(small_text),
+            None => self,
+        }
+    }
+
+    /// Sets the image at the given https URL as the large image,
+    /// sent in the form `mode` chooses
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidImageUrl`] if the URL isn't https,
+    /// or the image key sent would be longer than
+    /// [`MAX_IMAGE_KEY_LENGTH`].
+    ///
+    /// # Example
+    /// ```
+    /// # use discord_rich_presence::activity::{Assets, ImageUrlMode};
+    /// let assets = Assets::new()
+    ///     .external_large_image("https://example.com/cover.png", ImageUrlMode::Direct)?;
+    /// # Ok::<(), discord_rich_presence::error::Error>(())
+    /// ```
+    pub fn external_large_image(
+        mut self,
+        url: impl Into<Cow<'a, str>>,
+        mode: ImageUrlMode,
+    ) -> Result<Self, Error> {
+        self.large_image = Some(external_image(url.into(), mode)?);
+        Ok(self)
+    }
+
+    /// Sets the image at the given https URL as the small image,
+    /// sent in the form `mode` chooses
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidImageUrl`] if the URL isn't https,
+    /// or the image key sent would be longer than
+    /// [`MAX_IMAGE_KEY_LENGTH`].
+    pub fn external_small_image(
+        mut self,
+        url: impl Into<Cow<'a, str>>,
+        mode: ImageUrlMode,
+    ) -> Result<Self, Error> {
+        self.small_image = Some(external_image(url.into(), mode)?);
+        Ok(self)
+    }
+}
+
+// Turns an https URL into the image source `mode` chooses
+fn external_image(url: Cow<'_, str>, mode: ImageUrlMode) -> Result<AssetSource<'_>, Error> {
+    let invalid = |url: Cow<'_, str>, reason| Error::InvalidImageUrl {
+        url: url.into_owned(),
+        reason,
+    };
+
+    let location = match url.strip_prefix("https://") {
+        Some(location) if !location.is_empty() => location,
+        _ => return Err(invalid(url, "it isn't an https URL")),
+    };
+    // URLs sent directly are sent as they are, and moved in once
+    // they're known to be valid
+    let proxied = match mode {
+        ImageUrlMode::Direct => None,
+        ImageUrlMode::MediaProxy => Some(AssetSource::External(Cow::Owned(format!(
+            "external/https/{}",
+            location
+        )))),
+    };
+
+    let wire_length = proxied
+        .as_ref()
+        .map_or(url.len(), |source| source.to_wire().len());
+    if wire_length > MAX_IMAGE_KEY_LENGTH {
+        return Err(invalid(url, "it is too long"));
+    }
+    Ok(proxied.unwrap_or(AssetSource::Url(url)))
+}
+
+// The image sources an activity refers to, large first
+impl<'a> Activity<'a> {
+    pub(crate) fn image_sources(&self) -> impl Iterator<Item = &AssetSource<'a>> {
+        self.assets
+            .iter()
+            .flat_map(|assets| [&assets.large_image, &assets.small_image])
+            .filter_map(Option::as_ref)
+    }
 }
 
 impl<'a> Default for Assets<'a> {
@@ -300,22 +1188,49 @@ impl<'a> Secrets<'a> {
     }
 
     /// Sets the secret for joining a game party
-    pub fn join(mut self, join: &'a str) -> Self {
-        self.join = Some(join);
+    pub fn join(mut self, join: impl Into<Cow<'a, str>>) -> Self {
+        self.join = Some(join.into());
         self
     }
 
+    /// Sets the join secret if one is given, and leaves it as it is
+    /// otherwise
+    pub fn join_opt(self, join: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match join {
+            Some(join) => self.join(join),
+            None => self,
+        }
+    }
+
     /// Sets the secret for spectating a match
-    pub fn spectate(mut self, spectate: &'a str) -> Self {
-        self.spectate = Some(spectate);
+    pub fn spectate(mut self, spectate: impl Into<Cow<'a, str>>) -> Self {
+        self.spectate = Some(spectate.into());
         self
     }
 
+    /// Sets the spectate secret if one is given, and leaves it as
+    /// it is otherwise
+    pub fn spectate_opt(self, spectate: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match spectate {
+            Some(spectate) => self.spectate(spectate),
+            None => self,
+        }
+    }
+
     /// Sets the secret for a specific, instanced match
-    pub fn r#match(mut self, r#match: &'a str) -> Self {
-        self.r#match = Some(r#match);
+    pub fn r#match(mut self, r#match: impl Into<Cow<'a, str>>) -> Self {
+        self.r#match = Some(r#match.into());
         self
     }
+
+    /// Sets the match secret if one is given, and leaves it as it
+    /// is otherwise
+    pub fn match_opt(self, r#match: Option<impl Into<Cow<'a, str>>>) -> Self {
+        match r#match {
+            Some(r#match) => self.r#match(r#match),
+            None => self,
+        }
+    }
 }
 
 impl<'a> Default for Secrets<'a> {
@@ -331,7 +1246,34 @@ impl<'a> Button<'a> {
     /// The label must be 1-32 characters long
     ///
     /// The URL must be 1-512 characters long
-    pub fn new(label: &'a str, url: &'a str) -> Self {
-        Button { label, url }
+    pub fn new(label: impl Into<Cow<'a, str>>, url: impl Into<Cow<'a, str>>) -> Self {
+        Button {
+            label: label.into(),
+            url: url.into(),
+        }
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Button<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Discord only sends the labels of other users' buttons,
+        // so both shapes need to be accepted
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawButton {
+            Full { label: String, url: String },
+            Label(String),
+        }
+
+        Ok(match RawButton::deserialize(deserializer)? {
+            RawButton::Full { label, url } => Button {
+                label: label.into(),
+                url: url.into(),
+            },
+            RawButton::Label(label) => Button {
+                label: label.into(),
+                url: Cow::Borrowed(""),
+            },
+        })
     }
 }
@@ -0,0 +1,118 @@
+//! Provides validation of the image keys an activity refers to,
+//! against the art assets uploaded for the application.
+//!
+//! Discord shows no image for a key it doesn't know, and reports
+//! nothing either, so a mistyped key is easy to miss. With the
+//! `http` feature, the application's assets can be fetched with
+//! [`fetch_application_assets`], and checked with [`validate`].
+//!
+//! # Examples
+//! ```no_run
+//! # #[cfg(feature = "http")]
+//! # {
+//! use discord_rich_presence::{
+//!     activity::{Activity, Assets},
+//!     assets,
+//! };
+//!
+//! let known = assets::fetch_application_assets("<some client id>")?;
+//! let activity = Activity::new().assets(Assets::new().large_image("logo"));
+//! assets::validate(&activity, &known)?;
+//! # }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::{
+    activity::{Activity, AssetSource},
+    error::Error,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// The base URL of Discord's HTTP API
+#[cfg(feature = "http")]
+pub const API_BASE: &str = "https://discord.com/api/v10";
+
+/// A struct representing an art asset uploaded for an application
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ApplicationAsset {
+    /// The asset's ID
+    pub id: String,
+
+    /// The asset's name, which activities refer to it by
+    pub name: String,
+
+    /// The asset's type; Discord sends `1` for small images and
+    /// `2` for large ones
+    #[serde(rename = "type")]
+    pub kind: u8,
+}
+
+impl ApplicationAsset {
+    /// Creates a new `ApplicationAsset`, such as to validate
+    /// against assets known without fetching them
+    pub fn new(id: impl Into<String>, name: impl Into<String>, kind: u8) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            kind,
+        }
+    }
+}
+
+/// Fetches the art assets uploaded for the application with the
+/// given ID.
+///
+/// The assets are public, so no authorization is needed.
+///
+/// # Errors
+/// Returns an `Err` variant if the request failed, or its
+/// response couldn't be parsed.
+#[cfg(feature = "http")]
+pub fn fetch_application_assets(
+    client_id: &str,
+) -> Result<Vec<ApplicationAsset>, Box<dyn std::error::Error>> {
+    fetch_application_assets_from(API_BASE, client_id)
+}
+
+/// Fetches the art assets uploaded for the application with the
+/// given ID, from an API other than Discord's, such as a stub in
+/// tests.
+///
+/// # Errors
+/// Returns an `Err` variant if the request failed, or its
+/// response couldn't be parsed.
+#[cfg(feature = "http")]
+pub fn fetch_application_assets_from(
+    api_base: &str,
+    client_id: &str,
+) -> Result<Vec<ApplicationAsset>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/oauth2/applications/{}/assets",
+        api_base.trim_end_matches('/'),
+        client_id
+    );
+    let response = ureq::get(&url).call()?;
+
+    Ok(serde_json::from_reader(response.into_reader())?)
+}
+
+/// Checks that every image of an activity given by key names one
+/// of the given assets. Images given by URL or media proxy path
+/// aren't checked.
+///
+/// # Errors
+/// Returns [`Error::UnknownAsset`] for the first key that names
+/// none of the assets, checking the large image first.
+pub fn validate(activity: &Activity, assets: &[ApplicationAsset]) -> Result<(), Error> {
+    for source in activity.image_sources() {
+        if let AssetSource::Key(key) = source {
+            if !assets.iter().any(|asset| asset.name == *key) {
+                return Err(Error::UnknownAsset {
+                    key: key.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
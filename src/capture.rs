@@ -0,0 +1,367 @@
+//! Provides recording of the frames a client exchanges with
+//! Discord, and replaying of recorded sessions.
+//!
+//! A [`FrameRecorder`] wraps a client's transport, and writes each
+//! frame to a capture as a line of JSON:
+//!
+//! ```json
+//! {"elapsed_ms":12,"direction":"outbound","opcode":1,"payload":{"cmd":"SET_ACTIVITY","args":{},"nonce":"..."}}
+//! ```
+//!
+//! `elapsed_ms` counts from the recorder's creation, and
+//! `direction` is either `outbound` (sent by the client) or
+//! `inbound` (sent by Discord). Secrets, such as an activity's
+//! join secret, an access token or the code of an `AUTHORIZE`
+//! response, are replaced with `"[redacted]"` before they are
+//! written, unless turned off with
+//! [`FrameRecorder::redact_secrets`].
+//!
+//! A [`FrameReplayer`] plays a capture's inbound frames back to a
+//! client, to reproduce the session without Discord.
+//!
+//! Requires the `capture` feature.
+use crate::{
+    pack_unpack::{pack, unpack},
+    transport::IpcTransport,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// What secrets are replaced with in a capture
+pub const REDACTED: &str = "[redacted]";
+
+// Keys whose string values are always secret
+const SECRET_KEYS: [&str; 3] = ["access_token", "refresh_token", "secret"];
+
+/// A transport which records every frame passing through it.
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{capture::FrameRecorder, transport::NativeTransport, DiscordIpc, DiscordIpcClient};
+/// let transport = FrameRecorder::create(NativeTransport::new(), "session.jsonl")?;
+/// let mut client = DiscordIpcClient::with_transport("<some client id>", transport);
+/// client.connect()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct FrameRecorder<T, W = File> {
+    inner: T,
+    output: W,
+    started: Instant,
+    outbound: Vec<u8>,
+    inbound: Vec<u8>,
+    redact_secrets: bool,
+}
+
+impl<T: IpcTransport> FrameRecorder<T> {
+    /// Creates a recorder wrapping `inner`, which writes its
+    /// capture to a new file at `path`.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the file couldn't be created.
+    pub fn create(inner: T, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(inner, File::create(path)?))
+    }
+}
+
+impl<T: IpcTransport, W: Write> FrameRecorder<T, W> {
+    /// Creates a recorder wrapping `inner`, which writes its
+    /// capture to `output`.
+    pub fn new(inner: T, output: W) -> Self {
+        Self {
+            inner,
+            output,
+            started: Instant::now(),
+            outbound: Vec::new(),
+            inbound: Vec::new(),
+            redact_secrets: true,
+        }
+    }
+
+    /// Sets whether secrets are redacted from the capture, which
+    /// they are by default
+    ///
+    /// Only turn this off for local debugging: a capture with
+    /// secrets holds credentials for the user's account.
+    pub fn redact_secrets(mut self, redact_secrets: bool) -> Self {
+        self.redact_secrets = redact_secrets;
+        self
+    }
+
+    /// Returns a reference to the wrapped transport.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a reference to the capture's output.
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Returns the wrapped transport and the capture's output.
+    pub fn into_parts(self) -> (T, W) {
+        (self.inner, self.output)
+    }
+
+    // Writes every complete frame buffered in the given direction
+    fn record(&mut self, outbound: bool) -> Result<()> {
+        let elapsed = self.started.elapsed();
+        let (buffer, direction) = if outbound {
+            (&mut self.outbound, "outbound")
+        } else {
+            (&mut self.inbound, "inbound")
+        };
+
+        while let Some((opcode, mut payload)) = take_frame(buffer)? {
+            if self.redact_secrets {
+                sanitize(&mut payload);
+            }
+            let line = json!({
+                "elapsed_ms": elapsed.as_millis() as u64,
+                "direction": direction,
+                "opcode": opcode,
+                "payload": payload,
+            });
+            writeln!(self.output, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: IpcTransport, W: Write> IpcTransport for FrameRecorder<T, W> {
+    fn connect(&mut self) -> Result<()> {
+        // Partial frames from an earlier connection are never
+        // completed
+        self.outbound.clear();
+        self.inbound.clear();
+        self.inner.connect()
+    }
+
+    fn connect_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.outbound.clear();
+        self.inbound.clear();
+        self.inner.connect_timeout(timeout)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.inner.read(buffer)?;
+        self.inbound.extend_from_slice(buffer);
+
+        self.record(false)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.inner.write(data)?;
+        self.outbound.extend_from_slice(data);
+
+        self.record(true)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        self.inner.wait_readable(timeout)
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        self.output.flush()?;
+        self.inner.shutdown()
+    }
+}
+
+/// A transport which plays a capture's inbound frames back to a
+/// client.
+///
+/// Inbound frames are released in their recorded order, each once
+/// the client has sent as many frames as had been sent before it
+/// was recorded. Nonces are rewritten, so that responses carry the
+/// nonces of the replaying client's commands. Frames the client
+/// sends are kept, and can be inspected with [`sent`].
+///
+/// Redacted secrets are replayed as `"[redacted]"`.
+///
+/// [`sent`]: FrameReplayer::sent
+#[derive(Debug, Default)]
+pub struct FrameReplayer {
+    entries: VecDeque<(bool, u32, Value)>,
+    nonces: HashMap<String, Value>,
+    sent: Vec<(u32, Value)>,
+    written: Vec<u8>,
+    incoming: VecDeque<u8>,
+}
+
+impl FrameReplayer {
+    /// Creates a replayer from the capture file at `path`.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the file couldn't be read, or
+    /// isn't a valid capture.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Creates a replayer from a capture.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading failed, or the capture
+    /// isn't valid.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut replayer = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut entry: Value = serde_json::from_str(&line)?;
+            let outbound = match entry["direction"].as_str() {
+                Some("outbound") => true,
+                Some("inbound") => false,
+                _ => return Err(format!("Invalid capture direction in {}", line).into()),
+            };
+            let opcode = entry["opcode"]
+                .as_u64()
+                .ok_or_else(|| format!("Invalid capture opcode in {}", line))?;
+            replayer
+                .entries
+                .push_back((outbound, opcode as u32, entry["payload"].take()));
+        }
+
+        replayer.release()?;
+        Ok(replayer)
+    }
+
+    /// Returns every frame the client has sent, as
+    /// `(opcode, payload)`.
+    pub fn sent(&self) -> &[(u32, Value)] {
+        &self.sent
+    }
+
+    /// Returns whether every recorded frame has been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty() && self.incoming.is_empty()
+    }
+
+    // Queues the inbound frames up to the next recorded outbound
+    // frame
+    fn release(&mut self) -> Result<()> {
+        while let Some((false, ..)) = self.entries.front() {
+            let (_, opcode, mut payload) = self.entries.pop_front().unwrap();
+            if let Some(nonce) = payload["nonce"].as_str() {
+                if let Some(replayed) = self.nonces.get(nonce) {
+                    payload["nonce"] = replayed.clone();
+                }
+            }
+
+            let data = payload.to_string();
+            self.incoming.extend(pack(opcode, data.len() as u32)?);
+            self.incoming.extend(data.into_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+impl IpcTransport for FrameReplayer {
+    fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        if self.incoming.len() < buffer.len() {
+            return Err("The capture has no more frames to replay".into());
+        }
+
+        for byte in buffer.iter_mut() {
+            *byte = self.incoming.pop_front().unwrap();
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.written.extend_from_slice(data);
+
+        while let Some((opcode, payload)) = take_frame(&mut self.written)? {
+            if let Some((true, _, recorded)) = self.entries.front() {
+                if let Some(nonce) = recorded["nonce"].as_str() {
+                    self.nonces
+                        .insert(nonce.to_string(), payload["nonce"].clone());
+                }
+                self.entries.pop_front();
+            }
+            self.sent.push((opcode, payload));
+            self.release()?;
+        }
+
+        Ok(())
+    }
+
+    fn wait_readable(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(!self.incoming.is_empty())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Removes the first frame from `buffer`, if it holds a whole one
+fn take_frame(buffer: &mut Vec<u8>) -> Result<Option<(u32, Value)>> {
+    if buffer.len() < 8 {
+        return Ok(None);
+    }
+    let (opcode, length) = unpack(buffer[..8].to_vec())?;
+    if buffer.len() < 8 + length as usize {
+        return Ok(None);
+    }
+
+    let frame: Vec<u8> = buffer.drain(..8 + length as usize).collect();
+    Ok(Some((opcode, serde_json::from_slice(&frame[8..])?)))
+}
+
+// Replaces every secret in a frame's payload with `REDACTED`
+fn sanitize(payload: &mut Value) {
+    // An authorization code can be exchanged for an access token
+    if payload["cmd"] == "AUTHORIZE" && payload["data"]["code"].is_string() {
+        payload["data"]["code"] = REDACTED.into();
+    }
+    redact(payload);
+}
+
+// Replaces every secret in `value` with `REDACTED`
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if key == "secrets" {
+                    redact_all(value);
+                } else if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                    *value = REDACTED.into();
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => (),
+    }
+}
+
+// Replaces every string in `value` with `REDACTED`
+fn redact_all(value: &mut Value) {
+    match value {
+        Value::String(_) => *value = REDACTED.into(),
+        Value::Object(object) => object.values_mut().for_each(redact_all),
+        Value::Array(values) => values.iter_mut().for_each(redact_all),
+        _ => (),
+    }
+}
@@ -0,0 +1,163 @@
+//! Provides a circuit breaker for connecting, set on a client with
+//! [`DiscordIpc::set_circuit_breaker`](crate::DiscordIpc::set_circuit_breaker),
+//! or on a manager with
+//! [`ManagerConfig::circuit_breaker`](crate::manager::ManagerConfig::circuit_breaker).
+//!
+//! While Discord isn't running, every attempt to connect fails at
+//! once, so a loop reconnecting whenever connecting fails spins.
+//! After a number of failures in a row, the breaker opens, and
+//! attempts are refused without touching the socket until it has
+//! cooled down. The next attempt is then let through as a probe:
+//! if it succeeds the breaker closes again, and if it fails the
+//! breaker opens for another cool-down.
+use crate::{clock::Clock, error::Error, DiscordIpc};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The state of a [`CircuitBreaker`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Attempts are let through
+    Closed,
+    /// Attempts are refused, after too many failures in a row
+    Open {
+        /// How long until an attempt is let through again
+        retry_in: Duration,
+    },
+    /// The cool-down is over, and the next attempt decides whether
+    /// the breaker closes or opens again
+    HalfOpen,
+}
+
+/// A circuit breaker stopping attempts to connect after repeated
+/// failures, for a cool-down period
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    // Failures since the last success
+    failures: u32,
+    // When the breaker last opened, if it is open or half-open
+    opened_at: Option<Instant>,
+    // The clock set with `clock`, if any
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl CircuitBreaker {
+    /// Creates a new `CircuitBreaker`, which opens for `cool_down`
+    /// after `failure_threshold` failures in a row
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            failures: 0,
+            opened_at: None,
+            clock: None,
+        }
+    }
+
+    /// Sets the clock the cool-down is measured by, in place of the
+    /// client's
+    ///
+    /// Unless set, the clock of the client the breaker is given to
+    /// is used (see [`DiscordIpc::set_clock`]). Only attempts made
+    /// through a client read this clock; the other methods are
+    /// always given the time.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    // The current time, by the breaker's clock, or `client_clock`
+    // if it has none
+    pub(crate) fn now(&self, client_clock: &dyn Clock) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now_instant(),
+            None => client_clock.now_instant(),
+        }
+    }
+
+    /// The state of the breaker at the time `now`
+    pub fn state(&self, now: Instant) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) => match (opened_at + self.cool_down).checked_duration_since(now) {
+                Some(retry_in) if !retry_in.is_zero() => CircuitState::Open { retry_in },
+                _ => CircuitState::HalfOpen,
+            },
+        }
+    }
+
+    /// The number of failures since the last success
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Asks to make an attempt at the time `now`.
+    ///
+    /// Returns `Err` with how long until an attempt is let through
+    /// if the breaker is open.
+    pub fn try_attempt(&self, now: Instant) -> Result<(), Duration> {
+        match self.state(now) {
+            CircuitState::Open { retry_in } => Err(retry_in),
+            CircuitState::Closed | CircuitState::HalfOpen => Ok(()),
+        }
+    }
+
+    /// Records an attempt which succeeded, closing the breaker.
+    pub fn record_success(&mut self) {
+        self.reset();
+    }
+
+    /// Records an attempt which failed at the time `now`, opening
+    /// the breaker once there were too many failures in a row.
+    pub fn record_failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        if self.failures >= self.failure_threshold {
+            self.opened_at = Some(now);
+        }
+    }
+
+    /// Closes the breaker, and forgets any failures.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.opened_at = None;
+    }
+}
+
+// Refuses to connect while the client's breaker is open
+pub(crate) fn check<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = client.client_state();
+    if let Some(breaker) = &state.circuit_breaker {
+        breaker
+            .try_attempt(breaker.now(&*state.clock()))
+            .map_err(|retry_in| Error::CircuitOpen { retry_in })?;
+    }
+
+    Ok(())
+}
+
+// Makes an attempt to connect if the client's breaker allows it,
+// recording the outcome
+pub(crate) fn attempt<T, F>(client: &mut T, connect: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: DiscordIpc + ?Sized,
+    F: FnOnce(&mut T) -> Result<(), Box<dyn std::error::Error>>,
+{
+    check(client)?;
+    let result = connect(client);
+
+    let state = client.client_state();
+    let client_clock = state.clock();
+    if let Some(breaker) = &mut state.circuit_breaker {
+        match &result {
+            Ok(()) => breaker.record_success(),
+            Err(_) => breaker.record_failure(breaker.now(&*client_clock)),
+        }
+    }
+    result
+}
@@ -0,0 +1,323 @@
+use crate::{
+    activity::Activity,
+    discord_ipc::{ClientState, DiscordIpc, IpcConnection},
+    stats::ConnectionStats,
+    transport::{ConnectedEndpoint, IpcTransport, NativeTransport},
+};
+use serde_json::json;
+use std::{
+    error::Error,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug)]
+/// A wrapper struct for the functionality contained in the
+/// underlying [`DiscordIpc`](trait@DiscordIpc) trait.
+///
+/// The client talks to Discord through an [`IpcTransport`], which
+/// is the platform's socket or named pipe unless another is given
+/// to [`with_transport`](DiscordIpcClient::with_transport).
+pub struct DiscordIpcClient<T = NativeTransport> {
+    /// Client ID of the IPC client.
+    pub client_id: String,
+    state: ClientState,
+    pub(crate) transport: T,
+}
+
+impl DiscordIpcClient {
+    /// Creates a new `DiscordIpcClient`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn new(client_id: &str) -> Result<Self> {
+        Ok(Self::with_transport(client_id, NativeTransport::new()))
+    }
+
+    /// Creates a new `DiscordIpcClient` which connects only to the
+    /// socket, or on Windows the named pipe, at `path`, rather than
+    /// searching for Discord's.
+    ///
+    /// This is for when Discord listens somewhere it isn't searched
+    /// for, such as inside a sandbox. See
+    /// [`NativeTransport::with_path`].
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::with_socket_path(
+    ///     "<some client id>",
+    ///     "/run/sandbox/discord-ipc-0",
+    /// );
+    /// ```
+    pub fn with_socket_path(client_id: &str, path: impl Into<PathBuf>) -> Self {
+        Self::with_transport(client_id, NativeTransport::with_path(path))
+    }
+
+    /// Searches the sockets or pipes `discord-ipc-N` with indexes in
+    /// `pipes` when connecting, rather than [`DEFAULT_PIPE_RANGE`].
+    ///
+    /// On systems with many users, Discord may end up listening
+    /// past the default range after crashing. The range is kept for
+    /// reconnecting; see [`NativeTransport::set_pipe_range`].
+    ///
+    /// [`DEFAULT_PIPE_RANGE`]: crate::transport::DEFAULT_PIPE_RANGE
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?.with_pipe_range(0..=31);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_pipe_range(mut self, pipes: RangeInclusive<u32>) -> Self {
+        self.transport.set_pipe_range(pipes);
+        self
+    }
+
+    /// Connects to the Discord instance listening on the socket or
+    /// pipe `discord-ipc-<pipe_index>`, rather than the first found.
+    ///
+    /// The index is kept, so reconnecting returns to the same
+    /// instance. See [`NativeTransport::set_pipe_index`].
+    ///
+    /// # Errors
+    /// Returns an [`Error::InvalidPipeIndex`] without connecting if
+    /// the index is out of range, or any error
+    /// [`connect`](DiscordIpc::connect) returns.
+    ///
+    /// [`Error::InvalidPipeIndex`]: crate::error::Error::InvalidPipeIndex
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let mut ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// // The second instance running, such as Discord Canary
+    /// ipc_client.connect_to(1)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn connect_to(&mut self, pipe_index: u32) -> Result<()> {
+        self.transport.set_pipe_index(Some(pipe_index))?;
+        self.connect()
+    }
+
+    /// Returns every socket or pipe path connecting tries, in order.
+    ///
+    /// Connecting goes through this same list, so it shows where
+    /// Discord was looked for when connecting fails. See
+    /// [`NativeTransport::candidate_paths`] for how it is built.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// # use std::path::PathBuf;
+    /// let ipc_client = DiscordIpcClient::with_socket_path("<some client id>", "/tmp/discord-ipc-0");
+    /// assert_eq!(ipc_client.candidate_paths(), [PathBuf::from("/tmp/discord-ipc-0")]);
+    /// ```
+    pub fn candidate_paths(&self) -> Vec<PathBuf> {
+        self.transport.candidate_paths()
+    }
+
+    /// Returns the socket or pipe the client is connected through,
+    /// and how it was found, or `None` if it isn't connected.
+    ///
+    /// This changes when the client reconnects, and is cleared when
+    /// it is closed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// assert_eq!(ipc_client.connected_endpoint(), None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn connected_endpoint(&self) -> Option<&ConnectedEndpoint> {
+        self.transport.connected_endpoint()
+    }
+
+    /// Returns the path of the socket or pipe the client is
+    /// connected through, or `None` if it isn't connected.
+    ///
+    /// This tells apart, for example, a Flatpak or Snap install
+    /// from a regular one; [`connected_endpoint`](#method.connected_endpoint)
+    /// also says how the path was found.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// let mut ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// ipc_client.connect()?;
+    /// if let Some(path) = ipc_client.connected_path() {
+    ///     println!("Connected through {}", path.display());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn connected_path(&self) -> Option<&Path> {
+        self.connected_endpoint()
+            .map(|endpoint| endpoint.path.as_path())
+    }
+}
+
+impl<T: IpcTransport> DiscordIpcClient<T> {
+    /// Creates a new `DiscordIpcClient` which connects through the
+    /// given transport.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::{transport::NativeTransport, DiscordIpcClient};
+    /// let ipc_client = DiscordIpcClient::with_transport("<some client id>", NativeTransport::new());
+    /// ```
+    pub fn with_transport(client_id: &str, transport: T) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            state: ClientState::default(),
+            transport,
+        }
+    }
+
+    /// Returns the ID of the application the client presents as.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Returns the activity Discord was last sent, as it was sent.
+    ///
+    /// This is `None` until an activity is set, and once it is
+    /// cleared or the client is closed. Updates held back by a
+    /// rate limiter aren't included until they are sent.
+    pub fn last_activity(&self) -> Option<&Activity<'static>> {
+        self.state.last_activity.as_ref()
+    }
+
+    /// Returns a snapshot of what the client has sent and received,
+    /// over its current connection and over its lifetime.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// let stats = ipc_client.stats();
+    /// assert_eq!(stats.lifetime.frames_sent, 0);
+    /// assert_eq!(stats.uptime, None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stats(&self) -> ConnectionStats {
+        let now = self.state.clock().now_instant();
+        self.state.stats.snapshot(self.state.handshaken, now)
+    }
+
+    /// Switches the client to another application.
+    ///
+    /// If the client is connected, the connection is closed and
+    /// re-established, handshaking with the new ID. The last
+    /// activity sent, any activity update held back by the rate
+    /// limiter, and any pending join request belonged to the
+    /// previous application, and are forgotten.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reconnecting or the handshake
+    /// failed. The new ID is kept either way.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect()?;
+    ///
+    /// client.set_client_id("<another client id>")?;
+    /// assert_eq!(client.client_id(), "<another client id>");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_client_id(&mut self, client_id: &str) -> Result<()> {
+        self.client_id = client_id.to_string();
+        self.state.pending_activity = None;
+        self.state.last_activity = None;
+        self.state.join_requests.clear();
+
+        if self.state.handshaken {
+            self.reconnect()?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the client holds a live connection to
+    /// Discord.
+    ///
+    /// This is true once [`connect`](DiscordIpc::connect) succeeds,
+    /// and false again once the client is closed, or reading or
+    /// writing finds that Discord has gone away. Commands sent
+    /// while it is false return an [`Error::NotConnected`].
+    ///
+    /// [`Error::NotConnected`]: crate::error::Error::NotConnected
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// assert!(!ipc_client.is_connected());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_connected(&self) -> bool {
+        self.state.is_connected()
+    }
+
+    /// Returns a reference to the client's transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Returns a mutable reference to the client's transport.
+    ///
+    /// Reading from or writing to the transport directly may leave
+    /// the connection partway through a frame.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
+
+impl<T: IpcTransport> IpcConnection for DiscordIpcClient<T> {
+    fn connect_ipc(&mut self) -> Result<()> {
+        self.transport.connect()
+    }
+
+    fn connect_ipc_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.transport.connect_timeout(timeout)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.transport.write(data)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.transport.read(buffer)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        self.transport.wait_readable(timeout)
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        // Discord is only told the client is going if it knows of it
+        if self.state.handshaken {
+            let _ = self.send(json!({}), 2);
+        }
+
+        self.state.handshaken = false;
+        self.state.last_activity = None;
+        self.transport.shutdown()
+    }
+
+    fn get_client_id(&self) -> &String {
+        &self.client_id
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        &mut self.state
+    }
+}
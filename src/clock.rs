@@ -0,0 +1,61 @@
+//! Provides the source of the current time for time-based
+//! features, such as [`RateLimiter`](crate::rate_limit::RateLimiter)
+//! and [`ManagerConfig`](crate::manager::ManagerConfig).
+//!
+//! By default, the system's clock is used. Tests can substitute a
+//! clock which only moves when told to, such as
+//! `test_util::MockClock` (with the `test-util` feature). A
+//! client's clock, set with
+//! [`DiscordIpc::set_clock`](crate::DiscordIpc::set_clock), is also
+//! used by the rate limiter, circuit breaker and persistence given
+//! to it, unless they were given one of their own.
+use std::{
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A source of the current time, which can also wait
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time, as milliseconds since the Unix
+    /// epoch
+    fn now_unix_ms(&self) -> u64;
+
+    /// Returns the current point on the monotonic clock
+    fn now_instant(&self) -> Instant;
+
+    /// Waits until `duration` has passed on this clock
+    fn sleep(&self, duration: Duration);
+}
+
+/// The system's clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// The clock components use unless given another
+pub(crate) fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+// The current time of `clock`, as a `SystemTime`
+pub(crate) fn system_time(clock: &dyn Clock) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(clock.now_unix_ms())
+}
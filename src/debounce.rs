@@ -0,0 +1,282 @@
+//! Provides debouncing for activity updates which change faster
+//! than they should be sent.
+use crate::{
+    activity::Activity,
+    clock::{self, Clock},
+    discord_ipc::{send_activity, set_activity_args},
+    manager::DiscordIpcManager,
+    pump::into_send_error,
+    shared::SharedDiscordIpcClient,
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error as StdError,
+    fmt,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn StdError>>;
+type Reply = std::result::Result<(), Box<dyn StdError + Send + Sync>>;
+type Target = Box<dyn FnMut(Option<Value>) -> Result<()> + Send>;
+
+// How often the thread checks its clock while an update is held
+// back, so that a clock moved by hand is noticed
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A struct holding back the latest of a series of values until
+/// no new value has arrived for a quiet period
+///
+/// The current time is passed to every method, so the debouncer
+/// can be driven by any clock.
+#[derive(Clone, Debug)]
+pub struct Debouncer<T> {
+    quiet_period: Duration,
+    pending: Option<(T, Instant)>,
+}
+
+impl<T> Debouncer<T> {
+    /// Creates a new `Debouncer` with the given quiet period
+    pub fn new(quiet_period: Duration) -> Self {
+        Debouncer {
+            quiet_period,
+            pending: None,
+        }
+    }
+
+    /// Replaces the held back value with `value`, received at the
+    /// time `now`, restarting the quiet period
+    pub fn update(&mut self, value: T, now: Instant) {
+        self.pending = Some((value, now));
+    }
+
+    /// Returns when the held back value is due, if there is one
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending
+            .as_ref()
+            .map(|(_, updated_at)| *updated_at + self.quiet_period)
+    }
+
+    /// Takes the held back value if it is due at the time `now`
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.take(),
+            _ => None,
+        }
+    }
+
+    /// Takes the held back value, whether it is due or not
+    pub fn take(&mut self) -> Option<T> {
+        self.pending.take().map(|(value, _)| value)
+    }
+}
+
+enum Message {
+    Update(Option<Value>),
+    Flush(Sender<Reply>),
+    Clock(Arc<dyn Clock>),
+}
+
+/// A handle to a background thread which debounces activity
+/// updates, only sending an activity once it has stayed the same
+/// for a quiet period.
+///
+/// Updates can be made at any rate; each replaces the last and
+/// restarts the quiet period, so only the final update of a burst
+/// is sent. Errors sending an update in the background are
+/// dropped, so use [`flush`] where they matter (or a
+/// [`DiscordIpcManager`], which reports them on its status
+/// channel).
+///
+/// Dropping the handle stops the thread, discarding any update
+/// not yet sent, unless [`flush_on_drop`] was set.
+///
+/// [`flush`]: #method.flush
+/// [`flush_on_drop`]: #method.flush_on_drop
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{activity::Activity, debounce::DebouncedActivityUpdater, shared::SharedDiscordIpcClient, DiscordIpcClient};
+/// # use std::time::Duration;
+/// let client = SharedDiscordIpcClient::new(DiscordIpcClient::new("<some client id>")?);
+/// client.connect()?;
+///
+/// let updater = DebouncedActivityUpdater::new(client, Duration::from_millis(500))?
+///     .flush_on_drop(true);
+/// for file in ["main.rs", "lib.rs", "error.rs"] {
+///     updater.update(Activity::new().details(file));
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct DebouncedActivityUpdater {
+    messages: Option<Sender<Message>>,
+    thread: Option<JoinHandle<()>>,
+    flush_on_drop: bool,
+}
+
+impl DebouncedActivityUpdater {
+    /// Creates a new `DebouncedActivityUpdater` sending updates
+    /// through a shared client.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the thread could not be spawned.
+    pub fn new<T>(client: SharedDiscordIpcClient<T>, quiet_period: Duration) -> Result<Self>
+    where
+        T: DiscordIpc + Send + 'static,
+    {
+        Self::spawn(
+            Box::new(move |activity| {
+                client.with_client(|client| match activity {
+                    Some(activity) => send_activity(client, set_activity_args(activity)),
+                    None => client.clear_activity(),
+                })
+            }),
+            quiet_period,
+        )
+    }
+
+    /// Creates a new `DebouncedActivityUpdater` sending updates
+    /// through a [`DiscordIpcManager`], which is shut down when
+    /// the updater is dropped.
+    ///
+    /// Updates are handed to the manager once debounced, so
+    /// [`flush`](#method.flush) doesn't wait for them to be sent.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the thread could not be spawned.
+    pub fn with_manager(manager: DiscordIpcManager, quiet_period: Duration) -> Result<Self> {
+        Self::spawn(
+            Box::new(move |activity| {
+                manager.update(activity);
+                Ok(())
+            }),
+            quiet_period,
+        )
+    }
+
+    /// Sets whether the update held back is sent when the handle
+    /// is dropped, rather than discarded
+    pub fn flush_on_drop(mut self, flush_on_drop: bool) -> Self {
+        self.flush_on_drop = flush_on_drop;
+        self
+    }
+
+    /// Sets the clock the quiet period is measured by, which
+    /// should be set before the first update
+    ///
+    /// The system's clock is used by default. The background
+    /// thread checks the clock at least every 100 milliseconds
+    /// while an update is held back, so a clock which is advanced
+    /// by hand is noticed promptly.
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        self.send(Message::Clock(clock));
+        self
+    }
+
+    /// Sets the activity once the quiet period passes without
+    /// another update.
+    pub fn update(&self, activity: Activity) {
+        self.send(Message::Update(Some(json!(activity))));
+    }
+
+    /// Clears the activity once the quiet period passes without
+    /// another update.
+    pub fn clear(&self) {
+        self.send(Message::Update(None));
+    }
+
+    /// Sends the update held back immediately, if there is one.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the update failed.
+    pub fn flush(&self) -> Result<()> {
+        let (reply, response) = mpsc::channel();
+        self.send(Message::Flush(reply));
+
+        match response.recv() {
+            Ok(result) => result.map_err(|err| err as Box<dyn StdError>),
+            // The thread has stopped, so there is nothing to flush
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn spawn(mut target: Target, quiet_period: Duration) -> Result<Self> {
+        let (messages, receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("discord-ipc-debounce".to_string())
+            .spawn(move || {
+                let mut debouncer = Debouncer::new(quiet_period);
+                let mut clock = clock::system();
+                loop {
+                    let message = match debouncer.deadline() {
+                        Some(deadline) => receiver.recv_timeout(
+                            deadline
+                                .saturating_duration_since(clock.now_instant())
+                                .min(POLL_INTERVAL),
+                        ),
+                        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+
+                    match message {
+                        Ok(Message::Update(activity)) => {
+                            debouncer.update(activity, clock.now_instant())
+                        }
+                        Ok(Message::Clock(new_clock)) => clock = new_clock,
+                        Ok(Message::Flush(reply)) => {
+                            let result = match debouncer.take() {
+                                Some(activity) => target(activity).map_err(into_send_error),
+                                None => Ok(()),
+                            };
+                            let _ = reply.send(result);
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            if let Some(activity) = debouncer.poll(clock.now_instant()) {
+                                let _ = target(activity);
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            messages: Some(messages),
+            thread: Some(thread),
+            flush_on_drop: false,
+        })
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(messages) = &self.messages {
+            let _ = messages.send(message);
+        }
+    }
+}
+
+impl Drop for DebouncedActivityUpdater {
+    fn drop(&mut self) {
+        if self.flush_on_drop {
+            let _ = self.flush();
+        }
+
+        // Disconnecting the channel tells the thread to stop
+        self.messages.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for DebouncedActivityUpdater {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebouncedActivityUpdater")
+            .field("flush_on_drop", &self.flush_on_drop)
+            .finish_non_exhaustive()
+    }
+}
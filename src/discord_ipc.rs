@@ -1,22 +1,223 @@
 use crate::{
-    activity::Activity,
+    activity::{self, Activity},
+    circuit::{self, CircuitBreaker, CircuitState},
+    clock::{self, Clock},
+    error::Error,
+    event::{CallbackId, Event, EventCallbacks, EventKind, EventStream, Subscription},
+    guard::PresenceGuard,
+    join_request::{self, JoinDecision, JoinRequest, JoinRequestHandler},
+    metrics::{self, CommandTimer},
+    nonce,
     pack_unpack::{pack, unpack},
+    persist::{self, Persistence},
+    pid,
+    pump::{self, EventPump},
+    rate_limit::{self, RateLimiter},
+    router::{read_inbound, Inbound, Router},
+    stats::Stats,
+    transport::Deadline,
 };
 use serde_json::{json, Value};
-use std::error::Error;
-use uuid::Uuid;
+use std::{
+    collections::VecDeque,
+    fmt, io, mem,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The largest frame payload, in bytes, which
+/// [`recv`](DiscordIpc::recv) will read.
+///
+/// Discord's payloads are far smaller; a header claiming more
+/// means the connection is corrupt, and reading the payload would
+/// only allocate a large buffer for nothing.
+pub const MAX_FRAME_LENGTH: u32 = 16 * 1024 * 1024;
+
+/// How many commands sent with
+/// [`send_command`](DiscordIpc::send_command) may await collection
+/// at once.
+///
+/// Responses are kept until collected, so a client which never
+/// collects them would otherwise hold on to them forever.
+pub const MAX_PENDING_COMMANDS: usize = 64;
+
+// How often a wait measured by the client's clock checks it, so
+// that a clock moved by hand is noticed
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// How many times a handshake cut off by Discord starting up is
+// retried, and how long to wait before each retry
+const HANDSHAKE_RETRIES: u32 = 3;
+const HANDSHAKE_RETRY_DELAY: Duration = Duration::from_millis(250);
 
-type Result<T> = std::result::Result<T, Box<dyn Error>>;
+/// A connection to the Discord IPC, as used by [`DiscordIpc`].
+///
+/// This is the small trait to implement for a new kind of
+/// connection: it moves bytes, and holds the [`ClientState`] the
+/// protocol needs. Every `IpcConnection` is a [`DiscordIpc`], which
+/// provides the commands on top. Implementations of a new way to
+/// reach Discord's socket or pipe should usually implement
+/// [`IpcTransport`](crate::transport::IpcTransport) instead, and
+/// use it through a [`DiscordIpcClient`](struct@crate::DiscordIpcClient).
+///
+/// These methods are rarely called directly; calling `write`
+/// or `read` in the middle of a command corrupts the connection.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::{activity::Activity, ClientState, DiscordIpc, IpcConnection};
+/// # use std::time::Duration;
+/// # type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+/// // A connection that discards everything written to it
+/// struct Discard {
+///     client_id: String,
+///     state: ClientState,
+/// }
+///
+/// impl IpcConnection for Discard {
+///     fn get_client_id(&self) -> &String {
+///         &self.client_id
+///     }
+///
+///     fn connect_ipc(&mut self) -> Result<()> {
+///         Ok(())
+///     }
+///
+///     fn client_state(&mut self) -> &mut ClientState {
+///         &mut self.state
+///     }
+///
+///     fn write(&mut self, _data: &[u8]) -> Result<()> {
+///         Ok(())
+///     }
+///
+///     fn read(&mut self, _buffer: &mut [u8]) -> Result<()> {
+///         Err("nothing to read".into())
+///     }
+///
+///     fn wait_readable(&mut self, _timeout: Duration) -> Result<bool> {
+///         Ok(false)
+///     }
+///
+///     fn disconnect(&mut self) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut discard = Discard {
+///     client_id: "<some client id>".to_string(),
+///     state: ClientState::default(),
+/// };
+/// // Nothing ever answers the handshake, so it never connects
+/// assert!(discard.connect().is_err());
+/// assert!(!discard.client_state().is_connected());
+/// assert!(discard
+///     .set_activity_no_wait(Activity::new().state("Unseen"))
+///     .is_err());
+/// ```
+pub trait IpcConnection {
+    /// The ID of the application the connection is for
+    fn get_client_id(&self) -> &String;
+
+    /// Opens the connection, without handshaking.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection couldn't be
+    /// opened.
+    fn connect_ipc(&mut self) -> Result<()>;
+
+    /// Opens the connection, without handshaking, giving up after
+    /// `timeout`.
+    ///
+    /// The default ignores the timeout, which suits connections
+    /// which open without blocking.
+    ///
+    /// # Errors
+    /// Returns an [`Error::Timeout`] if the connection wasn't opened
+    /// in time, or an `Err` variant if it couldn't be opened.
+    fn connect_ipc_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let _ = timeout;
+        self.connect_ipc()
+    }
+
+    /// The protocol's state for this connection
+    fn client_state(&mut self) -> &mut ClientState;
+
+    /// Writes bytes to the connection. Each call is given a whole
+    /// frame.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if writing failed.
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Fills `buffer` from the connection, blocking until it is
+    /// full.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading failed, or the
+    /// connection closed first.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()>;
+
+    /// Waits up to `timeout` for data to become available to read,
+    /// returning whether any did.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection failed.
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool>;
+
+    /// Closes the connection, as [`DiscordIpc::close`] does.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if closing failed.
+    fn disconnect(&mut self) -> Result<()>;
+}
 
 /// A client that connects to and communicates with the Discord IPC.
 ///
-/// Implemented via the [`DiscordIpcClient`](struct@crate::DiscordIpcClient) struct.
-pub trait DiscordIpc {
+/// Implemented for every [`IpcConnection`], including the
+/// [`DiscordIpcClient`](struct@crate::DiscordIpcClient) struct.
+///
+/// # Thread safety
+///
+/// Every method takes `&mut self`, so a client is used by one
+/// caller at a time. [`DiscordIpcClient`](struct@crate::DiscordIpcClient)
+/// is `Send`, and can be moved to another thread or shared behind
+/// a `Mutex`; to send commands from several threads while events
+/// are being received, use [`spawn_event_pump`](#method.spawn_event_pump).
+///
+/// The trait is object safe. Boxed clients and mutable references
+/// to clients implement it too, so a `Box<dyn DiscordIpc + Send>`
+/// or a `&mut dyn DiscordIpc` can be used anywhere a client is
+/// expected, including with the methods unavailable on trait
+/// objects themselves.
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+/// let mut client: Box<dyn DiscordIpc + Send> =
+///     Box::new(DiscordIpcClient::new("<some client id>")?);
+/// client.connect()?;
+///
+/// let (pump, events) = client.spawn_event_pump()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait DiscordIpc: IpcConnection {
     /// Connects the client to the Discord IPC.
     ///
     /// This method attempts to first establish a connection,
     /// and then sends a handshake.
     ///
+    /// While Discord is starting up, it accepts connections but
+    /// closes them before answering the handshake. When the
+    /// connection is closed that way, it is retried a few times,
+    /// shortly apart. A handshake Discord rejects, for example for
+    /// an invalid client ID, is not retried.
+    ///
     /// # Errors
     ///
     /// Returns an `Err` variant if the client
@@ -24,15 +225,99 @@ pub trait DiscordIpc {
     /// send a handshake.
     ///
     /// # Examples
-    /// ```
-    /// let mut client = discord_rich_presence::new_client("<some client id>")?;
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
     /// client.connect()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     fn connect(&mut self) -> Result<()> {
-        self.connect_ipc()?;
-        self.send_handshake()?;
+        connect_within(self, None)
+    }
 
-        Ok(())
+    /// Connects the client to the Discord IPC like
+    /// [`connect`](#method.connect), giving up after `timeout`, as
+    /// measured by the client's clock (see
+    /// [`set_clock`](#method.set_clock)). A timeout too long to be
+    /// represented, such as [`Duration::MAX`], never gives up.
+    ///
+    /// The time limit covers searching for Discord's socket or pipe,
+    /// even one which never answers, and waiting for each frame of
+    /// the handshake, including its retries while Discord starts
+    /// up. Re-sending an activity kept by the client's
+    /// [`Persistence`] once connected isn't covered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Timeout`] if the client didn't connect in
+    /// time, leaving it disconnected, or any error
+    /// [`connect`](#method.connect) returns.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # use std::time::Duration;
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect_with_timeout(Duration::from_secs(2))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn connect_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let clock = self.client_state().clock();
+        connect_within(self, Deadline::after(timeout, clock).as_ref())
+    }
+
+    /// Connects the client to the Discord IPC like
+    /// [`connect`](#method.connect), trying up to `attempts` times
+    /// until it succeeds.
+    ///
+    /// This suits starting alongside Discord, before it has created
+    /// its socket or pipe. The sockets or pipes are searched for
+    /// afresh on each attempt. The first retry waits `backoff`, and
+    /// each one after waits twice as long as the last. At least one
+    /// attempt is made.
+    ///
+    /// A handshake Discord rejects, as with an invalid client ID,
+    /// isn't retried, and its [`Error::ConnectionClosed`] is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::RetriesExhausted`] with the number of
+    /// attempts and the last one's error if every attempt failed.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # use std::time::Duration;
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// // Waits up to 0.5 + 1 + 2 + 4 seconds between five attempts
+    /// client.connect_with_retries(5, Duration::from_millis(500))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn connect_with_retries(&mut self, attempts: u32, backoff: Duration) -> Result<()> {
+        let attempts = attempts.max(1);
+        let mut delay = backoff;
+        let mut made = 0;
+        loop {
+            made += 1;
+            let err = match self.connect() {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+            if let Some(Error::ConnectionClosed { .. }) = err.downcast_ref::<Error>() {
+                return Err(err);
+            }
+            if made == attempts {
+                return Err(Error::RetriesExhausted {
+                    attempts: made,
+                    last: pump::into_send_error(err),
+                }
+                .into());
+            }
+
+            self.client_state().clock().sleep(delay);
+            delay = delay.saturating_mul(2);
+        }
     }
 
     /// Reconnects to the Discord IPC.
@@ -47,51 +332,52 @@ pub trait DiscordIpc {
     /// send a handshake.
     ///
     /// # Examples
-    /// ```
-    /// let mut client = discord_rich_presence::new_client("<some client id>")?;
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
     /// client.connect()?;
     ///
     /// client.close()?;
     /// client.reconnect()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     fn reconnect(&mut self) -> Result<()> {
-        self.close()?;
-        self.connect_ipc()?;
-        self.send_handshake()?;
-
-        Ok(())
+        circuit::check(self)?;
+        metrics::increment(metrics::RECONNECTS);
+        self.client_state().stats.reconnected();
+        if self.client_state().handshaken {
+            self.close()?;
+        } else {
+            // There is nothing to close, but Discord has forgotten
+            // the activity all the same
+            self.client_state().last_activity = None;
+        }
+        connect_within(self, None)
     }
 
-    #[doc(hidden)]
-    fn get_client_id(&self) -> &String;
-
-    #[doc(hidden)]
-    fn connect_ipc(&mut self) -> Result<()>;
-
     /// Handshakes the Discord IPC.
     ///
     /// This method sends the handshake signal to the IPC.
     /// It is usually not called manually, as it is automatically
     /// called by [`connect`] and/or [`reconnect`].
     ///
+    /// Once Discord accepts the handshake, it dispatches a `READY`
+    /// event, which is queued as an [`Event::Ready`]. The budget
+    /// of the client's [`RateLimiter`] is refilled.
+    ///
     /// [`connect`]: #method.connect
     /// [`reconnect`]: #method.reconnect
+    /// [`Event::Ready`]: crate::event::Event::Ready
+    /// [`RateLimiter`]: crate::rate_limit::RateLimiter
     ///
     /// # Errors
     ///
-    /// Returns an `Err` variant if sending the handshake failed.
+    /// Returns an `Err` variant if sending the handshake failed,
+    /// or an [`Error::ConnectionClosed`] if Discord rejected it.
+    ///
+    /// [`Error::ConnectionClosed`]: crate::error::Error::ConnectionClosed
     fn send_handshake(&mut self) -> Result<()> {
-        self.send(
-            json!({
-                "v": 1,
-                "client_id": self.get_client_id()
-            }),
-            0,
-        )?;
-        // TODO: Return an Err if the handshake is rejected
-        self.recv()?;
-
-        Ok(())
+        handshake(self, None)
     }
 
     /// Sends JSON data to the Discord IPC.
@@ -103,23 +389,27 @@ pub trait DiscordIpc {
     /// Returns an `Err` variant if writing to the socket failed
     ///
     /// # Examples
-    /// ```
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
     /// let payload = serde_json::json!({ "field": "value" });
     /// client.send(payload, 0)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     fn send(&mut self, data: Value, opcode: u8) -> Result<()> {
         let data_string = data.to_string();
-        let header = pack(opcode.into(), data_string.len() as u32)?;
+        let mut frame = pack(opcode.into(), data_string.len() as u32)?;
+        frame.extend_from_slice(data_string.as_bytes());
 
-        self.write(&header)?;
-        self.write(data_string.as_bytes())?;
+        if let Err(err) = self.write(&frame) {
+            return Err(connection_lost(self, err));
+        }
+        metrics::frame(metrics::FRAMES_SENT, opcode.into());
+        self.client_state().stats.frame_sent(opcode, frame.len());
 
         Ok(())
     }
 
-    #[doc(hidden)]
-    fn write(&mut self, data: &[u8]) -> Result<()>;
-
     /// Receives an opcode and JSON data from the Discord IPC.
     ///
     /// This method returns any data received from the IPC.
@@ -127,78 +417,1395 @@ pub trait DiscordIpc {
     ///
     /// # Errors
     /// Returns an `Err` variant if reading the socket was
-    /// unsuccessful.
+    /// unsuccessful, if the payload isn't valid JSON, an
+    /// [`Error::FrameTooLarge`] if the frame is longer than
+    /// [`MAX_FRAME_LENGTH`], or an [`Error::SocketClosed`] if
+    /// Discord closed the connection before the frame began, after
+    /// which the client is no longer connected.
+    ///
+    /// [`Error::FrameTooLarge`]: crate::error::Error::FrameTooLarge
+    /// [`Error::SocketClosed`]: crate::error::Error::SocketClosed
     ///
     /// # Examples
-    /// ```
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient, IpcConnection};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
     /// client.connect_ipc()?;
     /// client.send_handshake()?;
     ///
     /// println!("{:?}", client.recv()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     fn recv(&mut self) -> Result<(u32, Value)> {
         let mut header = [0; 8];
 
-        self.read(&mut header)?;
+        // The first byte is read on its own, so that the connection
+        // closing between frames can be told from it closing part
+        // way through one
+        if let Err(err) = self.read(&mut header[..1]) {
+            if is_closed(&*err) {
+                self.client_state().handshaken = false;
+                return Err(Error::SocketClosed.into());
+            }
+            return Err(err);
+        }
+        if let Err(err) = self.read(&mut header[1..]) {
+            return Err(connection_lost(self, err));
+        }
         let (op, length) = unpack(header.to_vec())?;
+        if length > MAX_FRAME_LENGTH {
+            return Err(Error::FrameTooLarge { length }.into());
+        }
 
         let mut data = vec![0u8; length as usize];
-        self.read(&mut data)?;
+        if let Err(err) = self.read(&mut data) {
+            return Err(connection_lost(self, err));
+        }
 
         let response = String::from_utf8(data.to_vec())?;
         let json_data = serde_json::from_str::<Value>(&response)?;
+        metrics::frame(metrics::FRAMES_RECEIVED, op);
+        self.client_state()
+            .stats
+            .frame_received(header.len() + length as usize);
 
         Ok((op, json_data))
     }
 
-    #[doc(hidden)]
-    fn read(&mut self, buffer: &mut [u8]) -> Result<()>;
+    /// Receives the next event dispatched by Discord.
+    ///
+    /// Events already queued (for example, those read while
+    /// waiting for the response to a [`command`]) are returned
+    /// first. Otherwise, this method blocks until an event is
+    /// read, discarding any command responses read in the
+    /// meantime.
+    ///
+    /// Events are only dispatched for the `READY` and `ERROR`
+    /// events, and for events the client has [`subscribe`]d to.
+    ///
+    /// Before the event is returned, it is passed to the
+    /// callbacks registered with [`on_event`] and
+    /// [`on_any_event`], and join requests are passed to the
+    /// handler registered with [`on_join_request`].
+    ///
+    /// [`command`]: #method.command
+    /// [`subscribe`]: #method.subscribe
+    /// [`on_event`]: #method.on_event
+    /// [`on_any_event`]: #method.on_any_event
+    /// [`on_join_request`]: #method.on_join_request
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful, or an [`Error::ConnectionClosed`] if Discord
+    /// closed the connection.
+    ///
+    /// [`Error::ConnectionClosed`]: crate::error::Error::ConnectionClosed
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{event::Subscription, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.subscribe(&Subscription::activity_join())?;
+    /// loop {
+    ///     println!("{:?}", client.recv_event()?);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn recv_event(&mut self) -> Result<Event> {
+        let event = match self.client_state().events.pop_front() {
+            Some(event) => event,
+            None => loop {
+                if let Some(event) = read_event(self)? {
+                    break event;
+                }
+            },
+        };
+        self.client_state().callbacks.dispatch(&event);
+        if let Some((cmd, args)) = join_request::answer(self, &event)? {
+            join_request::ignore_rejection(self.command(cmd, args))?;
+        }
+
+        Ok(event)
+    }
+
+    /// Receives the next event if one can be read without
+    /// blocking, or returns `None`.
+    ///
+    /// Events are handled as with [`recv_event`]. This is meant
+    /// for clients driven by an event loop: once the connection is
+    /// reported readable, call this until it returns `None`, as
+    /// several events may have arrived at once.
+    ///
+    /// [`recv_event`]: #method.recv_event
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful, or an [`Error::ConnectionClosed`] if Discord
+    /// closed the connection.
+    ///
+    /// [`Error::ConnectionClosed`]: crate::error::Error::ConnectionClosed
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// while let Some(event) = client.try_recv_event()? {
+    ///     println!("{:?}", event);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn try_recv_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            if !self.client_state().events.is_empty() {
+                return self.recv_event().map(Some);
+            }
+            if !self.wait_readable(Duration::ZERO)? {
+                return Ok(None);
+            }
+            if let Some(event) = read_event(self)? {
+                self.client_state().events.push_back(event);
+            }
+        }
+    }
+
+    /// Returns a blocking iterator over the events dispatched by
+    /// Discord.
+    ///
+    /// Each item is received with [`recv_event`], blocking until
+    /// one is available. The iterator ends once Discord closes the
+    /// connection normally; if the connection fails or is closed
+    /// with an error, the error is yielded as a final `Err`.
+    ///
+    /// [`recv_event`]: #method.recv_event
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{event::Subscription, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.subscribe(&Subscription::activity_join())?;
+    /// for event in client.events() {
+    ///     println!("{:?}", event?);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn events(&mut self) -> EventStream<'_, Self>
+    where
+        Self: Sized,
+    {
+        EventStream::new(self)
+    }
+
+    /// Registers a callback to be called with every event of the
+    /// given kind.
+    ///
+    /// Callbacks are called by [`recv_event`] (on the thread
+    /// calling it) before it returns the event, in the order they
+    /// were registered. As callbacks do not have access to the
+    /// client, they cannot call back into it; actions based on an
+    /// event should be taken once `recv_event` returns.
+    ///
+    /// Registering a callback does not [`subscribe`] to the event.
+    ///
+    /// Returns an ID which can be passed to [`remove_callback`].
+    ///
+    /// [`recv_event`]: #method.recv_event
+    /// [`subscribe`]: #method.subscribe
+    /// [`remove_callback`]: #method.remove_callback
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{event::{EventKind, Subscription}, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.subscribe(&Subscription::speaking_start().channel("123"))?;
+    /// client.on_event(EventKind::SpeakingStart, |event| println!("{:?}", event));
+    ///
+    /// loop {
+    ///     client.recv_event()?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn on_event<F>(&mut self, kind: EventKind, callback: F) -> CallbackId
+    where
+        F: FnMut(&Event) + Send + 'static,
+        Self: Sized,
+    {
+        self.client_state()
+            .callbacks
+            .add(Some(kind), Box::new(callback))
+    }
+
+    /// Registers a callback to be called with every event,
+    /// including `READY`, `ERROR`, and unknown events.
+    ///
+    /// See [`on_event`] for when callbacks are called.
+    ///
+    /// [`on_event`]: #method.on_event
+    fn on_any_event<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(&Event) + Send + 'static,
+        Self: Sized,
+    {
+        self.client_state().callbacks.add(None, Box::new(callback))
+    }
+
+    /// Removes a callback registered with [`on_event`] or
+    /// [`on_any_event`].
+    ///
+    /// Returns whether a callback was removed.
+    ///
+    /// [`on_event`]: #method.on_event
+    /// [`on_any_event`]: #method.on_any_event
+    fn remove_callback(&mut self, id: CallbackId) -> bool {
+        self.client_state().callbacks.remove(id)
+    }
+
+    /// Sends an RPC command to the Discord IPC, and waits for
+    /// its response.
+    ///
+    /// The command is tagged with a fresh nonce, and frames are
+    /// read until the response carrying that nonce arrives, in
+    /// whatever order responses are sent. Pings are answered and
+    /// events received in the meantime are queued for
+    /// [`recv_event`], and responses to commands sent with
+    /// [`send_command`] are kept for collection, while responses
+    /// nobody is waiting on (such as those to earlier
+    /// [`set_activity`] calls) are discarded.
+    ///
+    /// Returns the `data` field of the response.
+    ///
+    /// [`recv_event`]: #method.recv_event
+    /// [`send_command`]: #method.send_command
+    /// [`set_activity`]: #method.set_activity
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command or reading
+    /// the response failed, or an [`Error::CommandError`] if
+    /// Discord responded with an `ERROR` event.
+    ///
+    /// [`Error::CommandError`]: crate::error::Error::CommandError
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// let data = client.command("GET_GUILDS", serde_json::json!({}))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn command(&mut self, cmd: &str, args: Value) -> Result<Value> {
+        run_command(self, cmd, args, None)
+    }
+
+    /// Sends an RPC command to the Discord IPC without waiting for
+    /// its response, returning the nonce its response is collected
+    /// by.
+    ///
+    /// Several commands can be sent this way before any response
+    /// arrives, rather than waiting a round trip for each. Their
+    /// responses are collected with [`poll_response`] or
+    /// [`wait_response`], in any order. Responses read by any
+    /// method of the client are kept until collected, or until the
+    /// client handshakes again, after which they never arrive.
+    ///
+    /// [`poll_response`]: #method.poll_response
+    /// [`wait_response`]: #method.wait_response
+    ///
+    /// # Errors
+    /// Returns an [`Error::TooManyPendingCommands`] if
+    /// [`MAX_PENDING_COMMANDS`] commands already await collection,
+    /// or an `Err` variant if sending the command failed.
+    ///
+    /// [`Error::TooManyPendingCommands`]: crate::error::Error::TooManyPendingCommands
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+    /// # use serde_json::json;
+    /// # use std::time::Duration;
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// let nonces = ["1", "2", "3"]
+    ///     .iter()
+    ///     .map(|id| client.send_command("GET_CHANNEL", json!({ "channel_id": id })))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// for nonce in &nonces {
+    ///     let channel = client.wait_response(nonce, Duration::from_secs(5))?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn send_command(&mut self, cmd: &str, args: Value) -> Result<Nonce> {
+        ensure_connected(self)?;
+        if self.client_state().router.len() >= MAX_PENDING_COMMANDS {
+            return Err(Error::TooManyPendingCommands {
+                limit: MAX_PENDING_COMMANDS,
+            }
+            .into());
+        }
+
+        let (nonce, data) = command_payload(cmd, args, None);
+        self.send(data, 1)?;
+        self.client_state()
+            .router
+            .register(nonce.clone(), Awaiting::Collection(None));
+        Ok(Nonce(nonce))
+    }
+
+    /// Collects the response to a command sent with
+    /// [`send_command`], reading whatever frames have arrived
+    /// without blocking, or returns `None` if it hasn't arrived.
+    ///
+    /// Returns the `data` field of the response, as [`command`]
+    /// does. Once collected, the nonce is forgotten.
+    ///
+    /// [`send_command`]: #method.send_command
+    /// [`command`]: #method.command
+    ///
+    /// # Errors
+    /// Returns an [`Error::CommandError`] if Discord responded with
+    /// an `ERROR` event, an [`Error::UnknownNonce`] if no command
+    /// sent with the nonce awaits collection, or an `Err` variant
+    /// if reading from the socket failed.
+    ///
+    /// [`Error::CommandError`]: crate::error::Error::CommandError
+    /// [`Error::UnknownNonce`]: crate::error::Error::UnknownNonce
+    fn poll_response(&mut self, nonce: &Nonce) -> Result<Option<Value>> {
+        self.wait_response(nonce, Duration::ZERO)
+    }
+
+    /// Collects the response to a command sent with
+    /// [`send_command`], waiting up to `timeout` for it to arrive,
+    /// or returns `None` if it doesn't. A timeout too long to be
+    /// represented, such as [`Duration::MAX`], waits indefinitely.
+    ///
+    /// Events read in the meantime are queued for [`recv_event`],
+    /// and responses to other commands are kept for their own
+    /// collection.
+    ///
+    /// [`send_command`]: #method.send_command
+    /// [`recv_event`]: #method.recv_event
+    ///
+    /// # Errors
+    /// Returns any error [`poll_response`] returns.
+    ///
+    /// [`poll_response`]: #method.poll_response
+    fn wait_response(&mut self, nonce: &Nonce, timeout: Duration) -> Result<Option<Value>> {
+        let clock = self.client_state().clock();
+        let deadline = clock.now_instant().checked_add(timeout);
+        loop {
+            if let Some(data) = collect(&mut self.client_state().router, nonce)? {
+                return Ok(Some(data));
+            }
+            // Waits are cut short to notice a clock moved by hand
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(clock.now_instant()),
+                None => Duration::MAX,
+            };
+            if !self.wait_readable(remaining.min(CLOCK_CHECK_INTERVAL))? {
+                if remaining <= CLOCK_CHECK_INTERVAL {
+                    return Ok(None);
+                }
+                continue;
+            }
+            if let Some(event) = read_event(self)? {
+                self.client_state().events.push_back(event);
+            }
+        }
+    }
+
+    /// Subscribes to an event, so that Discord starts
+    /// dispatching it to this client.
+    ///
+    /// # Errors
+    /// Returns an [`Error::MissingSubscriptionArgument`] if the
+    /// subscription lacks an argument its event requires, or an
+    /// `Err` variant if sending the command failed.
+    ///
+    /// [`Error::MissingSubscriptionArgument`]: crate::error::Error::MissingSubscriptionArgument
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{event::Subscription, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.subscribe(&Subscription::speaking_start().channel("123"))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn subscribe(&mut self, subscription: &Subscription) -> Result<()> {
+        subscription.validate()?;
+        run_command(
+            self,
+            "SUBSCRIBE",
+            subscription.args(),
+            Some(subscription.kind()),
+        )?;
+
+        Ok(())
+    }
+
+    /// Unsubscribes from an event, so that Discord stops
+    /// dispatching it to this client.
+    ///
+    /// The subscription must carry the same arguments it was
+    /// subscribed with.
+    ///
+    /// # Errors
+    /// Returns an [`Error::MissingSubscriptionArgument`] if the
+    /// subscription lacks an argument its event requires, or an
+    /// `Err` variant if sending the command failed.
+    ///
+    /// [`Error::MissingSubscriptionArgument`]: crate::error::Error::MissingSubscriptionArgument
+    fn unsubscribe(&mut self, subscription: &Subscription) -> Result<()> {
+        subscription.validate()?;
+        run_command(
+            self,
+            "UNSUBSCRIBE",
+            subscription.args(),
+            Some(subscription.kind()),
+        )?;
+
+        Ok(())
+    }
+
+    /// Sends an invite to join the user's game to a user who
+    /// asked to join.
+    ///
+    /// Sends the `SEND_ACTIVITY_JOIN_INVITE` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn send_activity_join_invite(&mut self, user_id: &str) -> Result<()> {
+        self.command("SEND_ACTIVITY_JOIN_INVITE", json!({ "user_id": user_id }))?;
+
+        Ok(())
+    }
+
+    /// Rejects a user's request to join the user's game.
+    ///
+    /// Sends the `CLOSE_ACTIVITY_REQUEST` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn close_activity_request(&mut self, user_id: &str) -> Result<()> {
+        self.command("CLOSE_ACTIVITY_REQUEST", json!({ "user_id": user_id }))?;
+
+        Ok(())
+    }
+
+    /// Registers a handler deciding how to answer Ask to Join
+    /// requests, and subscribes to `ACTIVITY_JOIN_REQUEST`.
+    ///
+    /// As requests are received by [`recv_event`], the handler
+    /// is called, and the request is accepted or declined
+    /// accordingly. Requests answered with [`JoinDecision::Defer`]
+    /// are kept in [`pending_join_requests`] until answered or
+    /// expired. Registering a new handler replaces the old one.
+    ///
+    /// If Discord rejects an answer (most likely because the
+    /// request expired), the request is dropped silently.
+    ///
+    /// [`recv_event`]: #method.recv_event
+    /// [`pending_join_requests`]: #method.pending_join_requests
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if subscribing failed.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{join_request::JoinDecision, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.on_join_request(|request| {
+    ///     if request.user.id == "53908232506183680" {
+    ///         JoinDecision::Accept
+    ///     } else {
+    ///         JoinDecision::Decline
+    ///     }
+    /// })?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn on_join_request<F>(&mut self, handler: F) -> Result<()>
+    where
+        F: FnMut(&JoinRequest) -> JoinDecision + Send + 'static,
+        Self: Sized,
+    {
+        self.subscribe(&Subscription::activity_join_request())?;
+        self.client_state().join_handler = Some(Box::new(handler));
+
+        Ok(())
+    }
+
+    /// Returns the join requests which were deferred by the
+    /// handler registered with [`on_join_request`], and have
+    /// not yet expired, oldest first.
+    ///
+    /// [`on_join_request`]: #method.on_join_request
+    fn pending_join_requests(&mut self) -> &[JoinRequest] {
+        let state = self.client_state();
+        let clock = state.clock();
+        let pending = &mut state.join_requests;
+        pending.retain(|request| !request.is_expired_at(&*clock));
+
+        pending
+    }
 
-    /// Sets a Discord activity.
+    /// Accepts a pending join request from the given user.
     ///
-    /// This method is an abstraction of [`send`],
+    /// # Errors
+    /// Returns an [`Error::JoinRequestExpired`] if there is no
+    /// pending request from the user, or an `Err` variant if
+    /// sending the invite failed.
+    ///
+    /// [`Error::JoinRequestExpired`]: crate::error::Error::JoinRequestExpired
+    fn accept_join_request(&mut self, user_id: &str) -> Result<()> {
+        let request = join_request::take_pending(self, user_id)?;
+        self.send_activity_join_invite(&request.user.id)
+    }
+
+    /// Declines a pending join request from the given user.
+    ///
+    /// # Errors
+    /// Returns an [`Error::JoinRequestExpired`] if there is no
+    /// pending request from the user, or an `Err` variant if
+    /// declining the request failed.
+    ///
+    /// [`Error::JoinRequestExpired`]: crate::error::Error::JoinRequestExpired
+    fn decline_join_request(&mut self, user_id: &str) -> Result<()> {
+        let request = join_request::take_pending(self, user_id)?;
+        self.close_activity_request(&request.user.id)
+    }
+
+    /// Sets a Discord activity, and waits for Discord to accept it.
+    ///
+    /// This method is an abstraction of [`command`],
     /// wrapping it such that only an activity payload
     /// is required.
     ///
-    /// [`send`]: #method.send
+    /// If the client has a [`RateLimiter`] whose budget is spent,
+    /// the update is delayed, refused, or held back, depending on
+    /// its [`RateLimitPolicy`].
+    ///
+    /// [`command`]: #method.command
+    /// [`RateLimiter`]: crate::rate_limit::RateLimiter
+    /// [`RateLimitPolicy`]: crate::rate_limit::RateLimitPolicy
     ///
     /// # Errors
-    /// Returns an `Err` variant if sending the payload failed.
+    /// Returns an `Err` variant if sending the payload or reading
+    /// the response failed, an [`Error::CommandError`] if
+    /// Discord rejected the activity, or an
+    /// [`Error::RateLimitedLocally`] if the rate limiter refused
+    /// the update.
+    ///
+    /// [`Error::CommandError`]: crate::error::Error::CommandError
+    /// [`Error::RateLimitedLocally`]: crate::error::Error::RateLimitedLocally
     fn set_activity(&mut self, activity_payload: Activity) -> Result<()> {
-        let data = json!({
-            "cmd": "SET_ACTIVITY",
-            "args": {
-                "pid": std::process::id(),
-                "activity": activity_payload
-            },
-            "nonce": Uuid::new_v4().to_string()
-        });
-        self.send(data, 1)?;
+        send_activity(self, set_activity_args(json!(activity_payload)))
+    }
 
-        Ok(())
+    /// Sets a Discord activity, returning a guard which clears it
+    /// when dropped.
+    ///
+    /// The activity is set as with [`set_activity`], and can be
+    /// replaced through the guard with [`PresenceGuard::update`].
+    /// To leave the activity set, use [`PresenceGuard::forget`].
+    ///
+    /// [`set_activity`]: #method.set_activity
+    /// [`PresenceGuard::update`]: crate::guard::PresenceGuard::update
+    /// [`PresenceGuard::forget`]: crate::guard::PresenceGuard::forget
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if setting the activity failed, in
+    /// which case no guard is created.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect()?;
+    /// {
+    ///     let mut presence = client.set_activity_scoped(Activity::new().state("In a match"))?;
+    ///     presence.update(Activity::new().state("Winning"))?;
+    /// } // The activity is cleared here
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn set_activity_scoped(&mut self, activity_payload: Activity) -> Result<PresenceGuard<'_, Self>>
+    where
+        Self: Sized,
+    {
+        self.set_activity(activity_payload)?;
+        Ok(PresenceGuard::new(self))
+    }
+
+    /// Sets a Discord activity without waiting for Discord's
+    /// response.
+    ///
+    /// This returns as soon as the payload is written, saving a
+    /// round trip. The response is discarded when it is read
+    /// later, so if Discord rejects the activity, the error is
+    /// never reported. Updates are rate limited as with
+    /// [`set_activity`].
+    ///
+    /// [`set_activity`]: #method.set_activity
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed, or
+    /// if the rate limiter refused the update.
+    fn set_activity_no_wait(&mut self, activity_payload: Activity) -> Result<()> {
+        send_activity_no_wait(self, set_activity_args(json!(activity_payload)))
+    }
+
+    /// Changes the activity Discord was last sent, and sends it
+    /// again.
+    ///
+    /// The last activity sent is cloned and passed to `f`. If `f`
+    /// leaves it unchanged, nothing is sent; otherwise it is set as
+    /// with [`set_activity`]. Activities held back by a rate
+    /// limiter haven't been sent, so aren't the starting point.
+    ///
+    /// [`set_activity`]: #method.set_activity
+    ///
+    /// # Errors
+    /// Returns an [`Error::NoActivitySet`] if no activity has been
+    /// sent, or the activity was cleared or the client closed
+    /// since, or any error [`set_activity`] returns.
+    ///
+    /// [`Error::NoActivitySet`]: crate::error::Error::NoActivitySet
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+    /// # let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect()?;
+    /// client.set_activity(Activity::new().details("Listening").state("First track"))?;
+    ///
+    /// let track = String::from("Second track");
+    /// client.update_activity(|activity| *activity = std::mem::take(activity).state(&track))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn update_activity<'a>(&mut self, f: impl FnOnce(&mut Activity<'a>)) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let last: Activity<'a> = match &self.client_state().last_activity {
+            Some(activity) => activity.clone(),
+            None => return Err(Error::NoActivitySet.into()),
+        };
+
+        let mut activity = last.clone();
+        f(&mut activity);
+        if json!(activity) == json!(last) {
+            return Ok(());
+        }
+        self.set_activity(activity)
+    }
+
+    /// Replaces the timestamps of the activity Discord was last
+    /// sent with those of media playing from `position`, out of a
+    /// total `duration`, and sends it again.
+    ///
+    /// The timestamps are built by [`activity::progress`]; call
+    /// this whenever playback seeks or resumes.
+    ///
+    /// [`activity::progress`]: crate::activity::progress
+    ///
+    /// # Errors
+    /// Returns any error [`update_activity`] returns.
+    ///
+    /// [`update_activity`]: #method.update_activity
+    fn update_progress(&mut self, position: Duration, duration: Duration) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let timestamps = activity::progress(position, duration);
+        self.update_activity(|activity| *activity = mem::take(activity).timestamps(timestamps))
+    }
+
+    /// Replaces the timestamps of the activity Discord was last
+    /// sent with those of media paused at `position`, and sends it
+    /// again.
+    ///
+    /// The timestamps are built by [`activity::paused_progress`],
+    /// which has no end, so Discord shows no countdown.
+    ///
+    /// [`activity::paused_progress`]: crate::activity::paused_progress
+    ///
+    /// # Errors
+    /// Returns any error [`update_activity`] returns.
+    ///
+    /// [`update_activity`]: #method.update_activity
+    fn update_progress_paused(&mut self, position: Duration) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let timestamps = activity::paused_progress(position);
+        self.update_activity(|activity| *activity = mem::take(activity).timestamps(timestamps))
     }
 
     /// Works the same as as [`set_activity`] but clears activity instead.
-    /// 
+    ///
+    /// The `SET_ACTIVITY` command sent has no `activity` argument,
+    /// rather than a null one.
+    ///
+    /// Clearing is never delayed or refused by the client's
+    /// [`RateLimiter`], so that the activity can always be cleared
+    /// before closing, and it drops any update held back.
+    ///
     /// [`set_activity`]: #method.set_activity
-    /// 
+    /// [`RateLimiter`]: crate::rate_limit::RateLimiter
+    ///
     /// # Errors
-    /// Returns an `Err` variant if sending the payload failed.
+    /// Returns an `Err` variant if sending the payload or reading
+    /// the response failed.
     fn clear_activity(&mut self) -> Result<()> {
-        let data = json!({
-            "cmd": "SET_ACTIVITY",
-            "args": {
-                "pid": std::process::id(),
-                "activity": None::<()>
-            },
-            "nonce": Uuid::new_v4().to_string()
-        });
-        
-        self.send(data, 1)?;
+        ensure_connected(self)?;
+        rate_limit::bypass(self);
+        self.command("SET_ACTIVITY", set_activity_args(Value::Null))?;
+        let client_id = self.get_client_id().clone();
+        let state = self.client_state();
+        state.last_activity = None;
+        state
+            .stats
+            .activity_updated(clock::system_time(&*state.clock()));
+        if let Some(persistence) = &state.persistence {
+            persistence.save(&client_id, None, &*state.clock());
+        }
 
         Ok(())
     }
 
+    /// Sets the rate limiter applied to activity updates, or
+    /// removes it.
+    ///
+    /// Clients have no rate limiter by default.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::{rate_limit::{RateLimiter, RateLimitPolicy}, DiscordIpc, DiscordIpcClient};
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.set_rate_limiter(Some(RateLimiter::discord().policy(RateLimitPolicy::Coalesce)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn set_rate_limiter(&mut self, limiter: Option<RateLimiter>) {
+        let state = self.client_state();
+        state.rate_limiter = limiter;
+        state.pending_activity = None;
+    }
+
+    /// Sets the circuit breaker applied to connecting, or removes
+    /// it.
+    ///
+    /// While the breaker is open, [`connect`](#method.connect) and
+    /// [`reconnect`](#method.reconnect) return an
+    /// [`Error::CircuitOpen`](crate::error::Error::CircuitOpen)
+    /// without attempting to connect. Clients have no circuit
+    /// breaker by default.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::{circuit::CircuitBreaker, DiscordIpc, DiscordIpcClient};
+    /// # use std::time::Duration;
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.set_circuit_breaker(Some(CircuitBreaker::new(3, Duration::from_secs(30))));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn set_circuit_breaker(&mut self, breaker: Option<CircuitBreaker>) {
+        self.client_state().circuit_breaker = breaker;
+    }
+
+    /// Sets the clock the client measures time by.
+    ///
+    /// This covers the waits between connection retries, the
+    /// timeouts of [`connect_with_timeout`](#method.connect_with_timeout)
+    /// and [`wait_response`](#method.wait_response), the ages of
+    /// join requests, and the times in [`ConnectionStats`]. The
+    /// client's [`RateLimiter`], [`CircuitBreaker`] and
+    /// [`Persistence`] use it too, unless they were given a clock
+    /// of their own. Waiting on the socket or pipe while connecting
+    /// is always measured in real time.
+    ///
+    /// Clients use the system's clock by default. With a clock
+    /// which only moves when told to, such as
+    /// `test_util::MockClock`, retries don't wait for real.
+    ///
+    /// [`ConnectionStats`]: crate::stats::ConnectionStats
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::{clock::SystemClock, DiscordIpc, DiscordIpcClient};
+    /// # use std::sync::Arc;
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.set_clock(Arc::new(SystemClock));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.client_state().clock = Some(clock);
+    }
+
+    /// Returns the state of the client's circuit breaker, such as
+    /// to show how long until connecting is retried. A client
+    /// without a circuit breaker is always
+    /// [`Closed`](CircuitState::Closed).
+    fn circuit_state(&mut self) -> CircuitState {
+        let state = self.client_state();
+        match &state.circuit_breaker {
+            Some(breaker) => breaker.state(breaker.now(&*state.clock())),
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// Saves every activity Discord accepts, and clearing it, to
+    /// a store, or stops saving.
+    ///
+    /// With [`Persistence::restore_on_connect`], the saved activity
+    /// is also set again whenever the client connects, as with
+    /// [`set_activity`](#method.set_activity), if it is recent
+    /// enough. Connecting still succeeds if setting it fails, which
+    /// is only logged.
+    ///
+    /// Clients don't persist the activity by default. See the
+    /// [`persist`](crate::persist) module.
+    fn persist_to(&mut self, persistence: Option<Persistence>) {
+        self.client_state().persistence = persistence;
+    }
+
+    /// Sends the activity update held back by a rate limiter with
+    /// the [`Coalesce`] policy, if there is one and the budget
+    /// allows.
+    ///
+    /// Returns whether no update is left held back.
+    ///
+    /// [`Coalesce`]: crate::rate_limit::RateLimitPolicy::Coalesce
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload or reading
+    /// the response failed, or if Discord rejected the activity.
+    fn flush_activity(&mut self) -> Result<bool> {
+        if self.client_state().pending_activity.is_some() {
+            ensure_connected(self)?;
+        }
+        let args = match self.client_state().pending_activity.take() {
+            Some(args) => args,
+            None => return Ok(true),
+        };
+        if !rate_limit::acquire(self, &args)? {
+            return Ok(false);
+        }
+        self.command("SET_ACTIVITY", args.clone())?;
+        remember_activity(self, &args);
+
+        Ok(true)
+    }
+
+    /// Moves the client onto a background thread, which reads
+    /// from the connection continuously.
+    ///
+    /// Returns an [`EventPump`], through which commands can be
+    /// sent, and a channel receiving every event read. Command
+    /// responses are routed back to the callers waiting on them,
+    /// so commands never consume events and vice versa. Callbacks
+    /// registered with [`on_event`] and [`on_join_request`] are
+    /// called on the background thread. Activity updates go through
+    /// the client's rate limiter and persistence as they do without
+    /// the pump, and responses to commands sent with
+    /// [`send_command`] are kept for [`EventPump::poll_response`].
+    ///
+    /// Dropping the `EventPump` closes the connection and stops
+    /// the thread, and the event channel disconnects once the
+    /// thread stops.
+    ///
+    /// [`EventPump`]: crate::pump::EventPump
+    /// [`EventPump::poll_response`]: crate::pump::EventPump::poll_response
+    /// [`send_command`]: #method.send_command
+    /// [`on_event`]: #method.on_event
+    /// [`on_join_request`]: #method.on_join_request
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the thread could not be spawned.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect()?;
+    ///
+    /// let (pump, events) = client.spawn_event_pump()?;
+    /// pump.set_activity(Activity::new().state("Hello world!"))?;
+    /// for event in events {
+    ///     println!("{:?}", event);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn spawn_event_pump(self) -> Result<(EventPump, Receiver<Event>)>
+    where
+        Self: Sized + Send + 'static,
+    {
+        pump::spawn(self)
+    }
+
     /// Closes the Discord IPC connection. Implementation is dependent on platform.
-    fn close(&mut self) -> Result<()>;
+    ///
+    /// The client is no longer [connected](ClientState::is_connected)
+    /// afterwards, even if disconnecting failed.
+    fn close(&mut self) -> Result<()> {
+        let result = self.disconnect();
+        self.client_state().handshaken = false;
+        result
+    }
+}
+
+impl<T: IpcConnection + ?Sized> DiscordIpc for T {}
+
+impl<T: IpcConnection + ?Sized> IpcConnection for Box<T> {
+    fn get_client_id(&self) -> &String {
+        (**self).get_client_id()
+    }
+
+    fn connect_ipc(&mut self) -> Result<()> {
+        (**self).connect_ipc()
+    }
+
+    fn connect_ipc_timeout(&mut self, timeout: Duration) -> Result<()> {
+        (**self).connect_ipc_timeout(timeout)
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        (**self).client_state()
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        (**self).write(data)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        (**self).read(buffer)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        (**self).wait_readable(timeout)
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        (**self).disconnect()
+    }
+}
+
+impl<T: IpcConnection + ?Sized> IpcConnection for &mut T {
+    fn get_client_id(&self) -> &String {
+        (**self).get_client_id()
+    }
+
+    fn connect_ipc(&mut self) -> Result<()> {
+        (**self).connect_ipc()
+    }
+
+    fn connect_ipc_timeout(&mut self, timeout: Duration) -> Result<()> {
+        (**self).connect_ipc_timeout(timeout)
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        (**self).client_state()
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        (**self).write(data)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        (**self).read(buffer)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        (**self).wait_readable(timeout)
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        (**self).disconnect()
+    }
+}
+
+/// State kept by [`DiscordIpc`] for a connection, such as the
+/// events not yet received and the rate limiter.
+///
+/// An [`IpcConnection`] holds one, starting from the default, and
+/// otherwise leaves it alone.
+#[derive(Default)]
+pub struct ClientState {
+    pub(crate) events: VecDeque<Event>,
+    pub(crate) callbacks: EventCallbacks,
+    pub(crate) join_handler: Option<JoinRequestHandler>,
+    pub(crate) join_requests: Vec<JoinRequest>,
+    pub(crate) router: Router<Awaiting>,
+    pub(crate) rate_limiter: Option<RateLimiter>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) pending_activity: Option<Value>,
+    // The activity Discord was last sent
+    pub(crate) last_activity: Option<Activity<'static>>,
+    // Whether a handshake was accepted since the client was last
+    // closed, or lost its connection
+    pub(crate) handshaken: bool,
+    pub(crate) persistence: Option<Persistence>,
+    pub(crate) stats: Stats,
+    // The clock set with `set_clock`, if any
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+}
+
+impl ClientState {
+    // The clock retries and response timeouts are measured by
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone().unwrap_or_else(clock::system)
+    }
+
+    /// Whether the connection is live: a handshake was accepted,
+    /// and since then the client wasn't closed and the connection
+    /// wasn't found to be lost.
+    pub fn is_connected(&self) -> bool {
+        self.handshaken
+    }
+}
+
+impl fmt::Debug for ClientState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientState")
+            .field("events", &self.events)
+            .field("callbacks", &self.callbacks.len())
+            .field("join_handler", &self.join_handler.is_some())
+            .field("join_requests", &self.join_requests)
+            .field("router", &self.router)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("pending_activity", &self.pending_activity)
+            .field("last_activity", &self.last_activity)
+            .field("handshaken", &self.handshaken)
+            .field("persistence", &self.persistence)
+            .field("stats", &self.stats)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+/// The nonce of a command sent with
+/// [`send_command`](DiscordIpc::send_command), by which its
+/// response is collected
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Nonce(String);
+
+impl Nonce {
+    /// The nonce as sent to Discord
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// What a command registered with the client's router awaits
+#[derive(Debug)]
+pub(crate) enum Awaiting {
+    // Its response, which `command` is blocked on
+    Response,
+    // Collection, along with its response once that arrives
+    Collection(Option<pump::Reply>),
+    // An event pump's caller, if anyone waits on the response
+    Forward(Option<Sender<pump::Reply>>),
+}
+
+// Sends a command, optionally scoped to an event, and waits for
+// the response carrying the same nonce
+fn run_command<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    cmd: &str,
+    args: Value,
+    evt: Option<EventKind>,
+) -> Result<Value> {
+    ensure_connected(client)?;
+    let (nonce, data) = command_payload(cmd, args, evt);
+    client
+        .client_state()
+        .router
+        .register(nonce.clone(), Awaiting::Response);
+    let timer = CommandTimer::start();
+
+    let result = client.send(data, 1).and_then(|_| loop {
+        match read_inbound(client)? {
+            Inbound::Response { nonce, result } => {
+                if let Some(result) = deliver(&mut client.client_state().router, nonce, result) {
+                    timer.finish(cmd);
+                    break result;
+                }
+            }
+            Inbound::Event(event) => client.client_state().events.push_back(event),
+        }
+    });
+    // Forget the command if reading its response failed
+    client.client_state().router.take(&nonce.into());
+
+    result
+}
+
+// Routes a response to the command awaiting it. The response a
+// command is blocked on is returned, as only one command blocks at
+// a time; one to a pipelined command is kept until collected, one
+// to an event pump's caller is passed on, and any other is dropped.
+pub(crate) fn deliver(
+    router: &mut Router<Awaiting>,
+    nonce: Value,
+    result: Result<Value>,
+) -> Option<Result<Value>> {
+    let awaiting = match router.take(&nonce)? {
+        Awaiting::Response => return Some(result),
+        Awaiting::Forward(reply) => {
+            if let Some(reply) = reply {
+                let _ = reply.send(result.map_err(pump::into_send_error));
+            }
+            return None;
+        }
+        Awaiting::Collection(None) => {
+            Awaiting::Collection(Some(result.map_err(pump::into_send_error)))
+        }
+        // A repeated response doesn't replace the first
+        awaiting => awaiting,
+    };
+    if let Value::String(nonce) = nonce {
+        router.register(nonce, awaiting);
+    }
+    None
+}
+
+// Takes the response to a pipelined command if it has arrived
+pub(crate) fn collect(router: &mut Router<Awaiting>, nonce: &Nonce) -> Result<Option<Value>> {
+    match router.take(&nonce.as_str().into()) {
+        Some(Awaiting::Collection(Some(reply))) => reply.map(Some).map_err(|err| err as _),
+        Some(awaiting) => {
+            router.register(nonce.0.clone(), awaiting);
+            Ok(None)
+        }
+        None => Err(Error::UnknownNonce {
+            nonce: nonce.0.clone(),
+        }
+        .into()),
+    }
+}
+
+// Reads the next frame which is an event or a command response,
+// returning the event, or routing the response with `deliver`
+fn read_event<T: DiscordIpc + ?Sized>(client: &mut T) -> Result<Option<Event>> {
+    match read_inbound(client)? {
+        Inbound::Event(event) => Ok(Some(event)),
+        Inbound::Response { nonce, result } => {
+            deliver(&mut client.client_state().router, nonce, result);
+            Ok(None)
+        }
+    }
+}
+
+// Builds the payload of a command, returning it along with its nonce
+pub(crate) fn command_payload(cmd: &str, args: Value, evt: Option<EventKind>) -> (String, Value) {
+    let nonce = nonce::generate();
+    let mut data = json!({
+        "cmd": cmd,
+        "args": args,
+        "nonce": nonce
+    });
+    if let Some(evt) = evt {
+        data["evt"] = evt.as_str().into();
+    }
+
+    (nonce, data)
+}
+
+// Builds the arguments of a `SET_ACTIVITY` command, which clears
+// the activity if it is null. Clearing leaves the activity out
+// entirely, as some servers, such as older Discord builds and arRPC,
+// keep showing the old activity when sent an explicit null.
+pub(crate) fn set_activity_args(activity: Value) -> Value {
+    let mut args = json!({ "pid": pid::pid() });
+    if !activity.is_null() {
+        args["activity"] = activity;
+    }
+    args
+}
+
+// Sends a `SET_ACTIVITY` command with the given arguments, subject
+// to the client's rate limiter, without waiting for the response
+pub(crate) fn send_activity_no_wait<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    args: Value,
+) -> Result<()> {
+    ensure_connected(client)?;
+    if rate_limit::acquire(client, &args)? {
+        let (_, data) = command_payload("SET_ACTIVITY", args.clone(), None);
+        client.send(data, 1)?;
+        remember_activity(client, &args);
+    }
+
+    Ok(())
+}
+
+// Sends a `SET_ACTIVITY` command with the given arguments, subject
+// to the client's rate limiter
+pub(crate) fn send_activity<T: DiscordIpc + ?Sized>(client: &mut T, args: Value) -> Result<()> {
+    ensure_connected(client)?;
+    if rate_limit::acquire(client, &args)? {
+        client.command("SET_ACTIVITY", args.clone())?;
+        remember_activity(client, &args);
+    }
+
+    Ok(())
+}
+
+// Remembers the activity of a `SET_ACTIVITY` command sent to
+// Discord, as it was sent
+fn remember_activity<T: DiscordIpc + ?Sized>(client: &mut T, args: &Value) {
+    let client_id = client.get_client_id().clone();
+    let state = client.client_state();
+    state.last_activity = serde_json::from_value(args["activity"].clone()).ok();
+    state
+        .stats
+        .activity_updated(clock::system_time(&*state.clock()));
+
+    if let Some(persistence) = &state.persistence {
+        persistence.save(&client_id, state.last_activity.clone(), &*state.clock());
+    }
+}
+
+// Connects as `connect` does, giving up once `deadline` has
+// passed, if there is one
+fn connect_within<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    deadline: Option<&Deadline>,
+) -> Result<()> {
+    circuit::attempt(client, |client| {
+        metrics::increment(metrics::CONNECT_ATTEMPTS);
+        connect_and_handshake(client, deadline)?;
+        metrics::increment(metrics::CONNECTS);
+        Ok(())
+    })?;
+
+    persist::restore(client);
+    Ok(())
+}
+
+// Connects and handshakes, retrying when Discord closes the
+// connection without answering, as it does while starting up
+fn connect_and_handshake<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    deadline: Option<&Deadline>,
+) -> Result<()> {
+    client.client_state().stats.connecting();
+    open_connection(client, deadline)?;
+
+    let mut retries = 0;
+    loop {
+        match handshake(client, deadline) {
+            Err(err) if retries < HANDSHAKE_RETRIES && is_hang_up(&*err) => {
+                retries += 1;
+                let _ = client.disconnect();
+                let delay = match deadline {
+                    Some(deadline) => HANDSHAKE_RETRY_DELAY.min(deadline.remaining()?),
+                    None => HANDSHAKE_RETRY_DELAY,
+                };
+                client.client_state().clock().sleep(delay);
+                open_connection(client, deadline)?;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn open_connection<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    deadline: Option<&Deadline>,
+) -> Result<()> {
+    match deadline {
+        Some(deadline) => client.connect_ipc_timeout(deadline.remaining()?),
+        None => client.connect_ipc(),
+    }
+}
+
+// Sends the handshake and waits for Discord to accept it. With a
+// deadline, each frame is waited for only until it passes, after
+// which the half-open connection is closed.
+fn handshake<T: DiscordIpc + ?Sized>(client: &mut T, deadline: Option<&Deadline>) -> Result<()> {
+    // Commands sent over an earlier connection will never be
+    // answered
+    client.client_state().router = Router::default();
+    client.send(
+        json!({
+            "v": 1,
+            "client_id": client.get_client_id()
+        }),
+        0,
+    )?;
+
+    loop {
+        if let Some(deadline) = deadline {
+            // Waits are cut short to notice a clock moved by hand
+            let readable = match deadline.remaining() {
+                Ok(remaining) => client.wait_readable(remaining.min(CLOCK_CHECK_INTERVAL))?,
+                Err(_) => {
+                    let _ = client.disconnect();
+                    return Err(deadline.expired().into());
+                }
+            };
+            if !readable {
+                continue;
+            }
+        }
+
+        if let Inbound::Event(event) = read_inbound(client)? {
+            let ready = matches!(event, Event::Ready(_));
+            client.client_state().events.push_back(event);
+            if ready {
+                let state = client.client_state();
+                state.handshaken = true;
+                state.stats.connected(state.clock().now_instant());
+                if let Some(limiter) = &mut state.rate_limiter {
+                    limiter.reset();
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Fails with `Error::NotConnected` unless the client is connected,
+// so that commands fail before they are rate limited or written
+fn ensure_connected<T: DiscordIpc + ?Sized>(client: &mut T) -> Result<()> {
+    if !client.client_state().handshaken {
+        return Err(Error::NotConnected.into());
+    }
+    Ok(())
+}
+
+// Marks the client as no longer connected if `err` means the
+// connection is gone, returning `err`
+fn connection_lost<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    err: Box<dyn std::error::Error>,
+) -> Box<dyn std::error::Error> {
+    if is_hang_up(&*err) {
+        client.client_state().handshaken = false;
+    }
+    err
+}
+
+// Whether an error means the other end closed the connection,
+// rather than rejecting what was sent with a `CLOSE` frame
+fn is_hang_up(err: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(Error::SocketClosed) = err.downcast_ref::<Error>() {
+        return true;
+    }
+    matches!(
+        err.downcast_ref::<io::Error>().map(io::Error::kind),
+        Some(
+            io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        )
+    )
+}
+
+// Whether a read failed because the other end closed the connection
+// cleanly; Windows reports a closed pipe as broken
+fn is_closed(err: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        err.downcast_ref::<io::Error>().map(io::Error::kind),
+        Some(io::ErrorKind::UnexpectedEof | io::ErrorKind::BrokenPipe)
+    )
 }
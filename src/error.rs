@@ -0,0 +1,350 @@
+//! Typed errors returned by the client.
+//!
+//! All fallible methods return a `Box<dyn Error>`. Errors that
+//! originate from this crate can be recovered as an [`Error`]
+//! with [`downcast_ref`](std::error::Error#method.downcast_ref).
+use crate::event::EventKind;
+use std::{fmt, io, ops::RangeInclusive, path::PathBuf, time::Duration};
+
+/// An error produced by the Discord IPC client
+#[derive(Debug)]
+pub enum Error {
+    /// A connection wasn't attempted, as the client's
+    /// [`CircuitBreaker`](crate::circuit::CircuitBreaker) is open
+    /// after too many failures in a row
+    CircuitOpen {
+        /// How long until an attempt is let through
+        retry_in: Duration,
+    },
+    /// Discord responded to a command with an `ERROR` event
+    CommandError {
+        /// The RPC error code sent by Discord
+        code: u64,
+        /// The human-readable message sent by Discord
+        message: String,
+    },
+    /// None of the sockets or pipes Discord listens on could be
+    /// connected to, and not only because Discord isn't running or
+    /// access was denied, or the one path given explicitly couldn't
+    /// be
+    ///
+    /// See [`Error::from_connect_attempts`] for when this is
+    /// returned rather than [`Error::DiscordNotRunning`] or
+    /// [`Error::PermissionDenied`]. Its source is the last attempt's
+    /// error.
+    ConnectFailed {
+        /// Each path tried, in order, and why connecting to it
+        /// failed
+        attempts: Vec<(PathBuf, io::Error)>,
+    },
+    /// Discord closed the connection, for example because it
+    /// rejected the handshake
+    ConnectionClosed {
+        /// The close code sent by Discord
+        code: u64,
+        /// The human-readable reason sent by Discord
+        message: String,
+    },
+    /// Discord isn't running: none of the sockets or pipes it
+    /// listens on exist, or the ones left behind are no longer
+    /// listened on
+    DiscordNotRunning,
+    /// The background thread of an
+    /// [`EventPump`](crate::pump::EventPump) has stopped, because
+    /// the connection was closed or failed
+    EventPumpStopped,
+    /// A frame's header claimed a length above
+    /// [`MAX_FRAME_LENGTH`](crate::MAX_FRAME_LENGTH), so it wasn't
+    /// read. The rest of the frame is left unread, so the client
+    /// must reconnect before it can be used again
+    FrameTooLarge {
+        /// The length claimed by the header
+        length: u32,
+    },
+    /// A URL couldn't be used as an external image
+    InvalidImageUrl {
+        /// The URL
+        url: String,
+        /// Why it couldn't be used
+        reason: &'static str,
+    },
+    /// A pipe index outside the range of `discord-ipc-N` sockets or
+    /// pipes searched was asked for
+    InvalidPipeIndex {
+        /// The index asked for
+        index: u32,
+        /// The indexes searched
+        pipes: RangeInclusive<u32>,
+    },
+    /// A URL couldn't be used as the stream of a streaming activity
+    InvalidStreamUrl {
+        /// The URL
+        url: String,
+        /// Why it couldn't be used
+        reason: &'static str,
+    },
+    /// Times couldn't be used as an activity's
+    /// [`Timestamps`](crate::activity::Timestamps)
+    InvalidTimestamps {
+        /// Why they couldn't be used
+        reason: &'static str,
+    },
+    /// A join request could not be answered, because it expired
+    /// or was never received
+    JoinRequestExpired {
+        /// The ID of the user the request was expected from
+        user_id: String,
+    },
+    /// A [`DiscordIpcManager`](crate::manager::DiscordIpcManager)
+    /// was shut down before carrying out a request
+    ManagerStopped,
+    /// A streaming activity has no stream URL, without which
+    /// Discord shows it as playing
+    MissingStreamUrl,
+    /// A [`Subscription`](crate::event::Subscription) is missing
+    /// an argument its event requires
+    MissingSubscriptionArgument {
+        /// The event being subscribed to
+        event: EventKind,
+        /// The name of the missing argument
+        argument: &'static str,
+    },
+    /// An activity couldn't be updated, because none has been sent,
+    /// or the activity was cleared or the client closed since
+    NoActivitySet,
+    /// A command wasn't sent, as the client isn't connected: it
+    /// never connected, was closed, or lost its connection
+    NotConnected,
+    /// The socket or pipe named by the
+    /// [`IPC_PATH_VAR`](crate::transport::IPC_PATH_VAR) environment
+    /// variable couldn't be connected to
+    PathOverrideFailed {
+        /// Each path tried, in order, and why connecting to it
+        /// failed
+        attempts: Vec<(PathBuf, io::Error)>,
+    },
+    /// A socket or pipe Discord may listen on exists, but access to
+    /// it was denied, as when a sandbox or the socket's permissions
+    /// keep the process out
+    PermissionDenied {
+        /// The first path access to was denied
+        path: PathBuf,
+        /// The error connecting to it failed with
+        source: io::Error,
+    },
+    /// An activity update was refused by the client's
+    /// [`RateLimiter`](crate::rate_limit::RateLimiter), as its
+    /// budget is spent
+    RateLimitedLocally {
+        /// How long until an update can be made
+        retry_in: Duration,
+    },
+    /// Connecting with
+    /// [`connect_with_retries`](crate::DiscordIpc::connect_with_retries)
+    /// failed on every attempt
+    RetriesExhausted {
+        /// How many attempts were made
+        attempts: u32,
+        /// The error the last attempt failed with
+        last: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Discord closed the connection between frames, as it does
+    /// when it quits, rather than part way through one
+    SocketClosed,
+    /// Connecting with
+    /// [`connect_with_timeout`](crate::DiscordIpc::connect_with_timeout)
+    /// took longer than it allowed, whether searching for Discord's
+    /// socket or pipe or waiting for it to answer the handshake
+    Timeout {
+        /// The time connecting was allowed
+        timeout: Duration,
+    },
+    /// A command couldn't be sent with
+    /// [`send_command`](crate::DiscordIpc::send_command), as the
+    /// most commands allowed already await collection
+    TooManyPendingCommands {
+        /// The most commands which may await collection, which is
+        /// [`MAX_PENDING_COMMANDS`](crate::MAX_PENDING_COMMANDS)
+        limit: usize,
+    },
+    /// An activity refers to an image by a key which names none of
+    /// the application's uploaded assets
+    UnknownAsset {
+        /// The unknown key
+        key: String,
+    },
+    /// A response was collected by a nonce no command awaiting
+    /// collection was sent with, for example because its response
+    /// was already collected, or the client has reconnected since
+    UnknownNonce {
+        /// The unknown nonce
+        nonce: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CircuitOpen { retry_in } => write!(
+                f,
+                "Not connecting after repeated failures, retry in {}ms",
+                retry_in.as_millis()
+            ),
+            Error::CommandError { code, message } => {
+                write!(f, "Discord returned error {}: {}", code, message)
+            }
+            Error::ConnectFailed { attempts } => {
+                write!(f, "Couldn't connect to the Discord IPC socket")?;
+                // Paths Discord doesn't listen on are only named when
+                // no other failure explains it, as when just one
+                // explicit path was tried
+                let all_not_running = attempts.iter().all(|(_, err)| means_not_running(err));
+                let mut failures = attempts
+                    .iter()
+                    .filter(|(_, err)| all_not_running || !means_not_running(err));
+                if let Some((path, err)) = failures.next() {
+                    write!(f, ": {}: {}", path.display(), err)?;
+                }
+                for (path, err) in failures {
+                    write!(f, "; {}: {}", path.display(), err)?;
+                }
+                Ok(())
+            }
+            Error::ConnectionClosed { code, message } => {
+                write!(f, "Discord closed the connection ({}): {}", code, message)
+            }
+            Error::DiscordNotRunning => write!(f, "Discord isn't running"),
+            Error::EventPumpStopped => write!(f, "The event pump thread has stopped"),
+            Error::FrameTooLarge { length } => write!(
+                f,
+                "Frame of {} bytes exceeds the maximum of {} bytes",
+                length,
+                crate::MAX_FRAME_LENGTH
+            ),
+            Error::InvalidImageUrl { url, reason } => {
+                write!(f, "Can't use {:?} as an image, as {}", url, reason)
+            }
+            Error::InvalidPipeIndex { index, pipes } => write!(
+                f,
+                "Pipe index {} is out of range, as only discord-ipc-{} to discord-ipc-{} are searched",
+                index,
+                pipes.start(),
+                pipes.end()
+            ),
+            Error::InvalidStreamUrl { url, reason } => {
+                write!(f, "Can't use {:?} as a stream URL, as {}", url, reason)
+            }
+            Error::InvalidTimestamps { reason } => {
+                write!(f, "Can't use the times as timestamps, as {}", reason)
+            }
+            Error::JoinRequestExpired { user_id } => {
+                write!(f, "No pending join request from user {}", user_id)
+            }
+            Error::ManagerStopped => write!(f, "The connection manager has shut down"),
+            Error::MissingStreamUrl => write!(f, "A streaming activity needs a stream URL"),
+            Error::MissingSubscriptionArgument { event, argument } => {
+                write!(f, "Subscribing to {} requires a {}", event, argument)
+            }
+            Error::NoActivitySet => write!(f, "No activity has been set to update"),
+            Error::NotConnected => write!(f, "The client isn't connected to Discord"),
+            Error::PathOverrideFailed { attempts } => {
+                write!(
+                    f,
+                    "Couldn't connect to the Discord IPC socket set by {}",
+                    crate::transport::IPC_PATH_VAR
+                )?;
+                for (i, (path, err)) in attempts.iter().enumerate() {
+                    let separator = if i == 0 { ":" } else { ";" };
+                    write!(f, "{} {}: {}", separator, path.display(), err)?;
+                }
+                Ok(())
+            }
+            Error::PermissionDenied { path, source } => write!(
+                f,
+                "Permission denied connecting to the Discord IPC socket {}: {}",
+                path.display(),
+                source
+            ),
+            Error::RateLimitedLocally { retry_in } => write!(
+                f,
+                "Too many activity updates, retry in {}ms",
+                retry_in.as_millis()
+            ),
+            Error::RetriesExhausted { attempts, last } => write!(
+                f,
+                "Couldn't connect to Discord after {} attempts: {}",
+                attempts, last
+            ),
+            Error::SocketClosed => write!(f, "Discord closed the connection"),
+            Error::Timeout { timeout } => write!(
+                f,
+                "Couldn't connect to Discord within {}ms",
+                timeout.as_millis()
+            ),
+            Error::TooManyPendingCommands { limit } => write!(
+                f,
+                "{} commands already await collection, the most allowed",
+                limit
+            ),
+            Error::UnknownAsset { key } => {
+                write!(f, "Key {:?} not found among uploaded assets", key)
+            }
+            Error::UnknownNonce { nonce } => {
+                write!(f, "No command awaits collection by nonce {:?}", nonce)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ConnectFailed { attempts } | Error::PathOverrideFailed { attempts } => attempts
+                .last()
+                .map(|(_, err)| err as &(dyn std::error::Error + 'static)),
+            Error::PermissionDenied { source, .. } => Some(source),
+            Error::RetriesExhausted { last, .. } => Some(&**last),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Classifies the failed attempts to connect to each of the
+    /// sockets or pipes Discord may listen on.
+    ///
+    /// If no path was tried, or every path was either missing or
+    /// refused the connection (as a socket left behind by a Discord
+    /// which exited does), Discord isn't running, and
+    /// [`Error::DiscordNotRunning`] is returned. Otherwise, if access
+    /// to any path was denied, as by a sandbox, an
+    /// [`Error::PermissionDenied`] names the first such path. Any
+    /// other failure means something else is wrong, and every
+    /// attempt is kept in an [`Error::ConnectFailed`].
+    ///
+    /// [`NativeTransport`](crate::transport::NativeTransport)
+    /// reports its failures this way, and other transports which
+    /// search several paths can too.
+    pub fn from_connect_attempts(mut attempts: Vec<(PathBuf, io::Error)>) -> Self {
+        if attempts.iter().all(|(_, err)| means_not_running(err)) {
+            return Error::DiscordNotRunning;
+        }
+        let denied = attempts
+            .iter()
+            .position(|(_, err)| err.kind() == io::ErrorKind::PermissionDenied);
+        match denied {
+            Some(index) => {
+                let (path, source) = attempts.swap_remove(index);
+                Error::PermissionDenied { path, source }
+            }
+            None => Error::ConnectFailed { attempts },
+        }
+    }
+}
+
+fn means_not_running(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
@@ -0,0 +1,414 @@
+//! Provides the events that can be subscribed to over the
+//! Discord IPC, and the events dispatched by Discord.
+use crate::{error::Error, models::ConnectionInfo, DiscordIpc};
+use serde_json::{Map, Value};
+use std::{fmt, iter::FusedIterator};
+
+// The close code Discord sends when closing the connection normally
+const CLOSE_NORMAL: u64 = 1000;
+
+/// An enum representing an event dispatched by Discord
+///
+/// Events are queued as they are read, and can be consumed
+/// with [`DiscordIpc::recv_event`](crate::DiscordIpc::recv_event).
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// `READY`: the handshake was accepted. Dispatched after
+    /// every successful handshake.
+    Ready(ConnectionInfo),
+    /// `ERROR`: an error occurred outside the context of any
+    /// specific command
+    Error {
+        /// The RPC error code sent by Discord
+        code: u64,
+        /// The human-readable message sent by Discord
+        message: String,
+    },
+    /// A subscribed event, along with its data
+    Dispatch {
+        /// The kind of event
+        kind: EventKind,
+        /// The data sent with the event
+        data: Value,
+    },
+    /// An event not known to this crate, along with its data
+    Unknown {
+        /// The name Discord used for the event
+        name: String,
+        /// The data sent with the event
+        data: Value,
+    },
+}
+
+/// An identifier for a callback registered with
+/// [`DiscordIpc::on_event`](crate::DiscordIpc::on_event), used to
+/// remove it again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CallbackId(pub(crate) u64);
+
+pub(crate) type EventCallback = Box<dyn FnMut(&Event) + Send>;
+
+// The callbacks registered on a client, in registration order
+#[derive(Default)]
+pub(crate) struct EventCallbacks {
+    next_id: u64,
+    callbacks: Vec<(CallbackId, Option<EventKind>, EventCallback)>,
+}
+
+impl EventCallbacks {
+    pub(crate) fn add(&mut self, kind: Option<EventKind>, callback: EventCallback) -> CallbackId {
+        let id = CallbackId(self.next_id);
+        self.next_id += 1;
+        self.callbacks.push((id, kind, callback));
+
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: CallbackId) -> bool {
+        let len = self.callbacks.len();
+        self.callbacks
+            .retain(|(callback_id, _, _)| *callback_id != id);
+
+        self.callbacks.len() != len
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    // Calls every callback registered for the event's kind, and
+    // every catch-all callback
+    pub(crate) fn dispatch(&mut self, event: &Event) {
+        let event_kind = match event {
+            Event::Dispatch { kind, .. } => Some(*kind),
+            _ => None,
+        };
+
+        for (_, kind, callback) in &mut self.callbacks {
+            if kind.is_none() || *kind == event_kind {
+                callback(event);
+            }
+        }
+    }
+}
+
+impl Event {
+    // Builds an event from the `evt` and `data` fields of a
+    // `DISPATCH` frame
+    pub(crate) fn from_dispatch(name: &str, mut data: Value) -> Self {
+        match name {
+            "READY" => match serde_json::from_value(data.clone()) {
+                Ok(info) => Event::Ready(info),
+                Err(_) => Event::Unknown {
+                    name: name.to_string(),
+                    data,
+                },
+            },
+            "ERROR" => Event::Error {
+                code: data["code"].as_u64().unwrap_or_default(),
+                message: data["message"].as_str().unwrap_or_default().to_string(),
+            },
+            _ => match EventKind::from_name(name) {
+                Some(kind) => Event::Dispatch {
+                    kind,
+                    data: data.take(),
+                },
+                None => Event::Unknown {
+                    name: name.to_string(),
+                    data,
+                },
+            },
+        }
+    }
+}
+
+/// A blocking iterator over the events dispatched by Discord,
+/// created by [`DiscordIpc::events`](crate::DiscordIpc::events)
+///
+/// Each item is read with
+/// [`DiscordIpc::recv_event`](crate::DiscordIpc::recv_event), so
+/// callbacks are called and pings are answered as usual. The
+/// iterator ends once Discord closes the connection normally, and
+/// yields a final `Err` if the connection failed or was closed
+/// with an error.
+pub struct EventStream<'a, T: DiscordIpc + ?Sized> {
+    client: &'a mut T,
+    finished: bool,
+}
+
+impl<'a, T: DiscordIpc + ?Sized> EventStream<'a, T> {
+    pub(crate) fn new(client: &'a mut T) -> Self {
+        Self {
+            client,
+            finished: false,
+        }
+    }
+}
+
+impl<'a, T: DiscordIpc + ?Sized> Iterator for EventStream<'a, T> {
+    type Item = Result<Event, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.client.recv_event() {
+            Ok(event) => Some(Ok(event)),
+            Err(err) => {
+                self.finished = true;
+                match err.downcast_ref::<Error>() {
+                    Some(Error::ConnectionClosed { code, .. }) if *code == CLOSE_NORMAL => None,
+                    _ => Some(Err(err)),
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: DiscordIpc + ?Sized> FusedIterator for EventStream<'a, T> {}
+
+impl<'a, T: DiscordIpc + ?Sized> fmt::Debug for EventStream<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream")
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An enum representing the kinds of events dispatched by Discord
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// `GUILD_STATUS`: a guild's status was updated
+    GuildStatus,
+    /// `GUILD_CREATE`: the user joined a guild
+    GuildCreate,
+    /// `CHANNEL_CREATE`: a channel was created
+    ChannelCreate,
+    /// `VOICE_CHANNEL_SELECT`: the user joined or left a voice channel
+    VoiceChannelSelect,
+    /// `VOICE_STATE_CREATE`: a user joined a voice channel
+    VoiceStateCreate,
+    /// `VOICE_STATE_UPDATE`: a user's voice state changed
+    VoiceStateUpdate,
+    /// `VOICE_STATE_DELETE`: a user left a voice channel
+    VoiceStateDelete,
+    /// `VOICE_SETTINGS_UPDATE`: the user's voice settings changed
+    VoiceSettingsUpdate,
+    /// `VOICE_CONNECTION_STATUS`: the voice connection status changed
+    VoiceConnectionStatus,
+    /// `SPEAKING_START`: a user started speaking in a voice channel
+    SpeakingStart,
+    /// `SPEAKING_STOP`: a user stopped speaking in a voice channel
+    SpeakingStop,
+    /// `MESSAGE_CREATE`: a message was sent in a text channel
+    MessageCreate,
+    /// `MESSAGE_UPDATE`: a message was edited in a text channel
+    MessageUpdate,
+    /// `MESSAGE_DELETE`: a message was deleted in a text channel
+    MessageDelete,
+    /// `NOTIFICATION_CREATE`: the user received a notification
+    NotificationCreate,
+    /// `ACTIVITY_JOIN`: the user accepted an invite to join a game
+    ActivityJoin,
+    /// `ACTIVITY_SPECTATE`: the user accepted an invite to spectate a game
+    ActivitySpectate,
+    /// `ACTIVITY_JOIN_REQUEST`: another user asked to join the user's game
+    ActivityJoinRequest,
+}
+
+impl EventKind {
+    /// Every event kind that can be subscribed to
+    pub const ALL: [EventKind; 18] = [
+        EventKind::GuildStatus,
+        EventKind::GuildCreate,
+        EventKind::ChannelCreate,
+        EventKind::VoiceChannelSelect,
+        EventKind::VoiceStateCreate,
+        EventKind::VoiceStateUpdate,
+        EventKind::VoiceStateDelete,
+        EventKind::VoiceSettingsUpdate,
+        EventKind::VoiceConnectionStatus,
+        EventKind::SpeakingStart,
+        EventKind::SpeakingStop,
+        EventKind::MessageCreate,
+        EventKind::MessageUpdate,
+        EventKind::MessageDelete,
+        EventKind::NotificationCreate,
+        EventKind::ActivityJoin,
+        EventKind::ActivitySpectate,
+        EventKind::ActivityJoinRequest,
+    ];
+
+    /// Returns the name Discord uses for this event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::GuildStatus => "GUILD_STATUS",
+            EventKind::GuildCreate => "GUILD_CREATE",
+            EventKind::ChannelCreate => "CHANNEL_CREATE",
+            EventKind::VoiceChannelSelect => "VOICE_CHANNEL_SELECT",
+            EventKind::VoiceStateCreate => "VOICE_STATE_CREATE",
+            EventKind::VoiceStateUpdate => "VOICE_STATE_UPDATE",
+            EventKind::VoiceStateDelete => "VOICE_STATE_DELETE",
+            EventKind::VoiceSettingsUpdate => "VOICE_SETTINGS_UPDATE",
+            EventKind::VoiceConnectionStatus => "VOICE_CONNECTION_STATUS",
+            EventKind::SpeakingStart => "SPEAKING_START",
+            EventKind::SpeakingStop => "SPEAKING_STOP",
+            EventKind::MessageCreate => "MESSAGE_CREATE",
+            EventKind::MessageUpdate => "MESSAGE_UPDATE",
+            EventKind::MessageDelete => "MESSAGE_DELETE",
+            EventKind::NotificationCreate => "NOTIFICATION_CREATE",
+            EventKind::ActivityJoin => "ACTIVITY_JOIN",
+            EventKind::ActivitySpectate => "ACTIVITY_SPECTATE",
+            EventKind::ActivityJoinRequest => "ACTIVITY_JOIN_REQUEST",
+        }
+    }
+
+    /// Looks up an event kind by the name Discord uses for it
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.as_str() == name)
+    }
+
+    /// Returns the argument which must be supplied when
+    /// subscribing to this event, if any
+    pub fn required_argument(&self) -> Option<&'static str> {
+        match self {
+            EventKind::GuildStatus => Some("guild_id"),
+            EventKind::VoiceStateCreate
+            | EventKind::VoiceStateUpdate
+            | EventKind::VoiceStateDelete
+            | EventKind::SpeakingStart
+            | EventKind::SpeakingStop
+            | EventKind::MessageCreate
+            | EventKind::MessageUpdate
+            | EventKind::MessageDelete => Some("channel_id"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A struct representing a subscription to an event, along
+/// with the arguments that scope it
+///
+/// # Example
+/// ```
+/// # use discord_rich_presence::event::Subscription;
+/// // Subscribes to users starting to speak in channel 123
+/// let subscription = Subscription::speaking_start().channel("123");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Subscription {
+    kind: EventKind,
+    channel_id: Option<String>,
+    guild_id: Option<String>,
+}
+
+macro_rules! subscription_constructors {
+    ($($name:ident => $kind:ident),* $(,)?) => {
+        $(
+            #[doc = concat!("Creates a new subscription to `", stringify!($kind), "` events")]
+            pub fn $name() -> Self {
+                Self::new(EventKind::$kind)
+            }
+        )*
+    };
+}
+
+impl Subscription {
+    /// Creates a new `Subscription` to the given kind of event
+    pub fn new(kind: EventKind) -> Self {
+        Subscription {
+            kind,
+            channel_id: None,
+            guild_id: None,
+        }
+    }
+
+    subscription_constructors! {
+        guild_status => GuildStatus,
+        guild_create => GuildCreate,
+        channel_create => ChannelCreate,
+        voice_channel_select => VoiceChannelSelect,
+        voice_state_create => VoiceStateCreate,
+        voice_state_update => VoiceStateUpdate,
+        voice_state_delete => VoiceStateDelete,
+        voice_settings_update => VoiceSettingsUpdate,
+        voice_connection_status => VoiceConnectionStatus,
+        speaking_start => SpeakingStart,
+        speaking_stop => SpeakingStop,
+        message_create => MessageCreate,
+        message_update => MessageUpdate,
+        message_delete => MessageDelete,
+        notification_create => NotificationCreate,
+        activity_join => ActivityJoin,
+        activity_spectate => ActivitySpectate,
+        activity_join_request => ActivityJoinRequest,
+    }
+
+    /// Sets the ID of the channel to receive events from
+    ///
+    /// Required by the voice state, speaking, and message events
+    pub fn channel(mut self, channel_id: &str) -> Self {
+        self.channel_id = Some(channel_id.to_string());
+        self
+    }
+
+    /// Sets the ID of the guild to receive events from
+    ///
+    /// Required by `GUILD_STATUS`
+    pub fn guild(mut self, guild_id: &str) -> Self {
+        self.guild_id = Some(guild_id.to_string());
+        self
+    }
+
+    /// Returns the kind of event subscribed to
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Returns the arguments sent alongside the subscription
+    pub fn args(&self) -> Value {
+        let mut args = Map::new();
+        if let Some(channel_id) = &self.channel_id {
+            args.insert("channel_id".to_string(), channel_id.as_str().into());
+        }
+        if let Some(guild_id) = &self.guild_id {
+            args.insert("guild_id".to_string(), guild_id.as_str().into());
+        }
+
+        Value::Object(args)
+    }
+
+    /// Checks that the arguments required by the event are present
+    ///
+    /// # Errors
+    /// Returns an [`Error::MissingSubscriptionArgument`] naming
+    /// the missing argument.
+    ///
+    /// [`Error::MissingSubscriptionArgument`]: crate::error::Error::MissingSubscriptionArgument
+    pub fn validate(&self) -> Result<(), Error> {
+        let argument = match self.kind.required_argument() {
+            Some(argument) => argument,
+            None => return Ok(()),
+        };
+
+        let present = match argument {
+            "guild_id" => self.guild_id.is_some(),
+            _ => self.channel_id.is_some(),
+        };
+        if !present {
+            return Err(Error::MissingSubscriptionArgument {
+                event: self.kind,
+                argument,
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Provides a C interface to the client, for use from other
+//! languages.
+//!
+//! The interface is deliberately small: a client is created,
+//! connected, given activities as JSON, and freed. The JSON is the
+//! same as [`Activity`]'s serialized form, so none of the builders
+//! need mirroring in C. The declarations are in
+//! `include/discord_rich_presence.h`, generated with `cbindgen`.
+//!
+//! The library is built with
+//! `cargo rustc --release --features ffi --crate-type cdylib`
+//! (or `staticlib`).
+//!
+//! # Conventions
+//! - Strings passed in are NUL-terminated UTF-8; any other
+//!   encoding is rejected as an error.
+//! - Functions which can fail return `0` on success and `-1` on
+//!   failure, after which [`drp_last_error_message`] describes the
+//!   failure.
+//! - Clients are opaque, and must only be used from one thread at
+//!   a time.
+//! - Panics never cross into C; one is reported as a failure.
+use crate::{activity::Activity, DiscordIpc, DiscordIpcClient};
+use std::{
+    cell::RefCell,
+    error::Error,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An opaque client, created by [`drp_client_new`] and freed by
+/// [`drp_client_free`]
+#[derive(Debug)]
+pub struct DrpClient {
+    client: DiscordIpcClient,
+}
+
+/// Creates a client for the application with the given ID, without
+/// connecting.
+///
+/// Returns `NULL` on failure.
+///
+/// # Safety
+/// `client_id` must be `NULL` or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drp_client_new(client_id: *const c_char) -> *mut DrpClient {
+    catch(ptr::null_mut(), || {
+        let client_id = to_str(client_id, "client_id")?;
+        let client = DiscordIpcClient::new(client_id)?;
+
+        Ok(Box::into_raw(Box::new(DrpClient { client })))
+    })
+}
+
+/// Connects the client to Discord.
+///
+/// Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+/// `client` must be `NULL` or a client from [`drp_client_new`]
+/// which hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn drp_connect(client: *mut DrpClient) -> c_int {
+    with_client(client, |client| client.connect())
+}
+
+/// Sets the activity, given as a JSON object.
+///
+/// Returns `0` on success and `-1` on failure, including when the
+/// JSON isn't a valid activity.
+///
+/// # Safety
+/// `client` must be `NULL` or a client from [`drp_client_new`]
+/// which hasn't been freed, and `json` must be `NULL` or point to
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drp_set_activity_json(
+    client: *mut DrpClient,
+    json: *const c_char,
+) -> c_int {
+    with_client(client, |client| {
+        let activity: Activity = serde_json::from_str(to_str(json, "json")?)?;
+        client.set_activity(activity)
+    })
+}
+
+/// Clears the activity.
+///
+/// Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+/// `client` must be `NULL` or a client from [`drp_client_new`]
+/// which hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn drp_clear_activity(client: *mut DrpClient) -> c_int {
+    with_client(client, |client| client.clear_activity())
+}
+
+/// Closes the connection to Discord. The client can connect again
+/// afterwards.
+///
+/// Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+/// `client` must be `NULL` or a client from [`drp_client_new`]
+/// which hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn drp_close(client: *mut DrpClient) -> c_int {
+    with_client(client, |client| client.close())
+}
+
+/// Frees a client, without closing its connection first. Does
+/// nothing if `client` is `NULL`.
+///
+/// # Safety
+/// `client` must be `NULL` or a client from [`drp_client_new`]
+/// which hasn't been freed, and mustn't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn drp_client_free(client: *mut DrpClient) {
+    if !client.is_null() {
+        catch((), || {
+            drop(Box::from_raw(client));
+            Ok(())
+        });
+    }
+}
+
+/// Returns a description of the last failure on this thread, or
+/// `NULL` if nothing has failed yet.
+///
+/// The string is owned by the library, and stays valid until the
+/// next call on this thread which fails.
+#[no_mangle]
+pub extern "C" fn drp_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+// Runs `f` on the client, reporting its outcome as a return code
+unsafe fn with_client(
+    client: *mut DrpClient,
+    f: impl FnOnce(&mut DiscordIpcClient) -> Result<(), Box<dyn Error>>,
+) -> c_int {
+    catch(-1, || {
+        let client = client.as_mut().ok_or("client is NULL")?;
+        f(&mut client.client)?;
+        Ok(0)
+    })
+}
+
+// Runs `f`, keeping errors and panics from crossing into C. Both
+// are recorded as the last error, and `failed` is returned instead
+fn catch<T>(failed: T, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> T {
+    let message = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(err)) => err.to_string(),
+        Err(payload) => match payload.downcast_ref::<&str>() {
+            Some(reason) => format!("panicked: {}", reason),
+            None => match payload.downcast_ref::<String>() {
+                Some(reason) => format!("panicked: {}", reason),
+                None => "panicked".to_owned(),
+            },
+        },
+    };
+
+    // Interior NULs can't be represented, so are dropped
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    failed
+}
+
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, Box<dyn Error>> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", name).into());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} is not valid UTF-8", name).into())
+}
@@ -0,0 +1,67 @@
+//! Provides a guard keeping an activity set for as long as a scope
+//! lasts, created by [`DiscordIpc::set_activity_scoped`].
+use crate::{activity::Activity, DiscordIpc};
+use std::{error::Error, fmt};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A guard which clears the activity when dropped, created by
+/// [`DiscordIpc::set_activity_scoped`](crate::DiscordIpc::set_activity_scoped)
+///
+/// The guard borrows the client for as long as it lives; the
+/// client can still be used through [`client`](#method.client).
+/// Clearing the activity is best effort: any error is ignored,
+/// and nothing is sent if the client was closed meanwhile.
+#[must_use = "dropping the guard clears the activity immediately"]
+pub struct PresenceGuard<'a, T: DiscordIpc + ?Sized> {
+    client: &'a mut T,
+    armed: bool,
+}
+
+impl<'a, T: DiscordIpc + ?Sized> PresenceGuard<'a, T> {
+    pub(crate) fn new(client: &'a mut T) -> Self {
+        Self {
+            client,
+            armed: true,
+        }
+    }
+
+    /// Replaces the activity, which is still cleared when the
+    /// guard is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if setting the activity failed, as
+    /// with [`DiscordIpc::set_activity`].
+    pub fn update(&mut self, activity: Activity) -> Result<()> {
+        self.client.set_activity(activity)
+    }
+
+    /// Returns the client the guard borrows.
+    pub fn client(&mut self) -> &mut T {
+        self.client
+    }
+
+    /// Drops the guard without clearing the activity, leaving it
+    /// set.
+    pub fn forget(mut self) {
+        self.armed = false;
+    }
+}
+
+impl<'a, T: DiscordIpc + ?Sized> Drop for PresenceGuard<'a, T> {
+    fn drop(&mut self) {
+        if !self.armed || !self.client.client_state().handshaken {
+            return;
+        }
+
+        let _ = self.client.clear_activity();
+    }
+}
+
+impl<'a, T: DiscordIpc + ?Sized> fmt::Debug for PresenceGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PresenceGuard")
+            .field("armed", &self.armed)
+            .finish()
+    }
+}
@@ -1,17 +1,35 @@
-use crate::discord_ipc::DiscordIpc;
-use serde_json::json;
-use std::os::unix::net::UnixStream;
+use crate::{
+    clock, error,
+    transport::{
+        self, ConnectedEndpoint, Deadline, EndpointSource, EnvProvider, IpcTransport, SystemEnv,
+        DEFAULT_PIPE_RANGE, IPC_PATH_VAR,
+    },
+    DiscordIpcClient,
+};
+use std::os::unix::{
+    ffi::OsStrExt,
+    io::{AsRawFd, FromRawFd, RawFd},
+    net::UnixStream,
+};
 use std::{
-    env::var,
     error::Error,
-    io::{Read, Write},
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    mem,
     net::Shutdown,
-    path::PathBuf,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
 };
 
 // Environment keys to search for the Discord pipe
 const ENV_KEYS: [&str; 4] = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"];
 
+// Directories searched when none of `ENV_KEYS` is set, as on BSDs
+// and minimal containers, where Discord falls back to them too
+const FALLBACK_DIRS: [&str; 2] = ["/tmp", "/var/tmp"];
+
 const APP_SUBPATHS: [&str; 4] = [
     "",
     "app/com.discordapp.Discord/",
@@ -21,73 +39,433 @@ const APP_SUBPATHS: [&str; 4] = [
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
-#[allow(dead_code)]
-#[derive(Debug)]
-/// A wrapper struct for the functionality contained in the
-/// underlying [`DiscordIpc`](trait@DiscordIpc) trait.
-pub struct DiscordIpcClient {
-    /// Client ID of the IPC client.
-    pub client_id: String,
-    connected: bool,
+// The connection's underlying stream
+pub(crate) type Stream = UnixStream;
+
+// How long to wait before connecting again to a socket whose
+// listener has no room for another connection
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+// Connects to the socket at `path`, before `deadline` if there is
+// one
+fn open(
+    path: &Path,
+    deadline: Option<&Deadline>,
+) -> std::result::Result<io::Result<UnixStream>, error::Error> {
+    match deadline {
+        Some(deadline) => match connect_nonblocking(path, deadline) {
+            Ok(Some(socket)) => Ok(Ok(socket)),
+            Ok(None) => Err(deadline.expired()),
+            Err(err) => Ok(Err(err)),
+        },
+        None => Ok(UnixStream::connect(path)),
+    }
+}
+
+// Connects to the socket at `path` without blocking, returning
+// `None` if `deadline` passes first. A listener which isn't
+// accepting connections refuses with `EAGAIN` once its backlog is
+// full, rather than leaving the connection in progress, so
+// connecting is tried again until it has room.
+fn connect_nonblocking(path: &Path, deadline: &Deadline) -> io::Result<Option<UnixStream>> {
+    let (addr, len) = socket_addr(path)?;
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The stream owns the descriptor from here on, closing it if
+    // connecting fails
+    let socket = unsafe { UnixStream::from_raw_fd(fd) };
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    socket.set_nonblocking(true)?;
+
+    loop {
+        let remaining = match deadline.remaining() {
+            Ok(remaining) => remaining,
+            Err(_) => return Ok(None),
+        };
+        let result = unsafe {
+            libc::connect(
+                fd,
+                &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+                len,
+            )
+        };
+        if result == 0 {
+            break;
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EAGAIN) => thread::sleep(BUSY_RETRY_DELAY.min(remaining)),
+            Some(libc::EINPROGRESS) => {
+                if !poll(fd, libc::POLLOUT, remaining)? {
+                    return Ok(None);
+                }
+                if let Some(err) = socket.take_error()? {
+                    return Err(err);
+                }
+                break;
+            }
+            _ => return Err(err),
+        }
+    }
+
+    socket.set_nonblocking(false)?;
+    Ok(Some(socket))
+}
+
+// The address of the socket at `path`, and its length
+fn socket_addr(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    // The path must leave room for its terminating NUL
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.len() >= addr.sun_path.len() || bytes.contains(&0) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "socket path is too long or contains a NUL byte",
+        ));
+    }
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+
+    let len = mem::size_of::<libc::sockaddr_un>() - addr.sun_path.len() + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+// Waits up to `timeout` for `fd` to be ready for `events`,
+// returning whether it was. Hang-ups and errors also count as
+// ready, so that the next read or write reports them
+fn poll(fd: RawFd, events: libc::c_short, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    // Rounded up, so that a wait under a millisecond still waits
+    let millis = timeout.as_micros().div_ceil(1000);
+    let millis = millis.min(libc::c_int::MAX as u128) as libc::c_int;
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            ready => return Ok(ready > 0),
+        }
+    }
+}
+
+/// The platform's connection to Discord: a Unix socket, found
+/// through the runtime directory.
+pub struct NativeTransport {
+    path: Option<PathBuf>,
+    env: Box<dyn EnvProvider>,
     socket: Option<UnixStream>,
+    endpoint: Option<ConnectedEndpoint>,
+    pipe_index: Option<u32>,
+    pipe_range: RangeInclusive<u32>,
 }
 
-impl DiscordIpcClient {
-    /// Creates a new `DiscordIpcClient`.
+impl NativeTransport {
+    /// Creates a new, unconnected `NativeTransport`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, unconnected `NativeTransport`, which searches
+    /// for Discord's socket using the variables of `env` rather
+    /// than the process's environment.
     ///
     /// # Examples
     /// ```
-    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// # use discord_rich_presence::transport::NativeTransport;
+    /// # use std::{collections::HashMap, path::PathBuf};
+    /// let env: HashMap<_, _> = [("TMPDIR".to_string(), "/tmp".to_string())].into();
+    /// let transport = NativeTransport::with_env(env);
+    /// assert_eq!(transport.candidate_paths()[0], PathBuf::from("/tmp/discord-ipc-0"));
     /// ```
-    pub fn new(client_id: &str) -> Result<Self> {
-        let client = Self {
-            client_id: client_id.to_string(),
-            connected: false,
-            socket: None,
-        };
+    pub fn with_env(env: impl EnvProvider + 'static) -> Self {
+        Self {
+            env: Box::new(env),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a new, unconnected `NativeTransport`, which connects
+    /// to the socket at `path` instead of searching for Discord's.
+    ///
+    /// If connecting to it fails, [`connect`](IpcTransport::connect)
+    /// returns an [`Error::ConnectFailed`](error::Error::ConnectFailed)
+    /// naming it, without searching elsewhere.
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn from_stream(stream: Stream) -> Self {
+        Self {
+            socket: Some(stream),
+            ..Self::default()
+        }
+    }
 
-        Ok(client)
+    pub(crate) fn stream(&self) -> Option<&Stream> {
+        self.socket.as_ref()
     }
 
-    fn get_pipe_pattern() -> PathBuf {
-        let mut path = String::new();
+    // The open socket or pipe, or `Error::NotConnected` if there
+    // isn't one
+    fn stream_mut(&mut self) -> std::result::Result<&mut Stream, error::Error> {
+        self.socket.as_mut().ok_or(error::Error::NotConnected)
+    }
 
-        for key in &ENV_KEYS {
-            match var(key) {
-                Ok(val) => {
-                    path = val;
-                    break;
+    /// Restricts the search to the socket or pipe `discord-ipc-N`
+    /// with the given index, or with `None`, searches all of them
+    /// again.
+    ///
+    /// This picks one of several Discord instances running at once,
+    /// such as Stable and Canary, which each listen on their own
+    /// index. The restriction applies to every later connection.
+    ///
+    /// # Errors
+    /// Returns an [`Error::InvalidPipeIndex`](error::Error::InvalidPipeIndex)
+    /// if the index is outside the range searched, leaving the
+    /// search unchanged.
+    pub fn set_pipe_index(&mut self, index: Option<u32>) -> std::result::Result<(), error::Error> {
+        if let Some(index) = index.filter(|index| !self.pipe_range.contains(index)) {
+            return Err(error::Error::InvalidPipeIndex {
+                index,
+                pipes: self.pipe_range.clone(),
+            });
+        }
+        self.pipe_index = index;
+        Ok(())
+    }
+
+    /// Sets the indexes of the sockets or pipes `discord-ipc-N`
+    /// searched, [`DEFAULT_PIPE_RANGE`] unless set.
+    ///
+    /// Every later connection searches this range, unless
+    /// [`set_pipe_index`](#method.set_pipe_index) chose a single
+    /// index. An empty range searches nothing.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::transport::NativeTransport;
+    /// let mut transport = NativeTransport::new();
+    /// transport.set_pipe_range(0..=31);
+    /// assert!(transport.set_pipe_index(Some(15)).is_ok());
+    /// ```
+    pub fn set_pipe_range(&mut self, pipes: RangeInclusive<u32>) {
+        self.pipe_range = pipes;
+    }
+
+    /// Returns the paths [`connect`](IpcTransport::connect) tries,
+    /// in order.
+    ///
+    /// Unless a path was given to [`with_path`](#method.with_path),
+    /// or one is set by [`IPC_PATH_VAR`], these are the sockets
+    /// `discord-ipc-0` to `discord-ipc-9`, or those in the range
+    /// set with [`set_pipe_range`](#method.set_pipe_range), or only
+    /// the one chosen with [`set_pipe_index`](#method.set_pipe_index), in the
+    /// directory named by the first of `XDG_RUNTIME_DIR`,
+    /// `TMPDIR`, `TMP` and `TEMP` which is set, each looked for
+    /// directly in the directory, then in the Flatpak and Snap
+    /// subdirectories, before the next socket. If none of those
+    /// variables is set, `/tmp` is searched, then `/var/tmp`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::transport::NativeTransport;
+    /// # use std::{collections::HashMap, path::PathBuf};
+    /// let transport = NativeTransport::with_env(HashMap::new());
+    /// let paths = transport.candidate_paths();
+    /// assert_eq!(paths[0], PathBuf::from("/tmp/discord-ipc-0"));
+    /// assert_eq!(paths[40], PathBuf::from("/var/tmp/discord-ipc-0"));
+    /// ```
+    pub fn candidate_paths(&self) -> Vec<PathBuf> {
+        self.candidates()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Returns the socket the transport is connected to, and how it
+    /// was found, or `None` if it isn't connected.
+    pub fn connected_endpoint(&self) -> Option<&ConnectedEndpoint> {
+        self.endpoint.as_ref()
+    }
+
+    fn candidates(&self) -> Vec<(PathBuf, EndpointSource)> {
+        if let Some(path) = &self.path {
+            return vec![(path.clone(), EndpointSource::Explicit)];
+        }
+        if let Some(value) = self.env.get(IPC_PATH_VAR).filter(|value| !value.is_empty()) {
+            return transport::override_candidates(&value, self.pipes());
+        }
+
+        let pipes = self.pipes();
+        self.get_pipe_patterns()
+            .into_iter()
+            .flat_map(|(env_key, pattern)| {
+                pipes
+                    .clone()
+                    .flat_map(|index| APP_SUBPATHS.iter().map(move |subpath| (index, *subpath)))
+                    .map(move |(index, subpath)| {
+                        let path = pattern.join(subpath).join(format!("discord-ipc-{}", index));
+                        let source = EndpointSource::Discovered {
+                            env_key,
+                            subpath,
+                            index,
+                        };
+                        (path, source)
+                    })
+            })
+            .collect()
+    }
+
+    // The indexes of the sockets or pipes searched
+    fn pipes(&self) -> RangeInclusive<u32> {
+        match self.pipe_index {
+            Some(index) => index..=index,
+            None => self.pipe_range.clone(),
+        }
+    }
+
+    // Connects as `connect` does, giving up once `deadline` has
+    // passed, if there is one
+    fn connect_within(&mut self, deadline: Option<&Deadline>) -> Result<()> {
+        self.endpoint = None;
+
+        if let Some(path) = &self.path {
+            // Not searching further, so that a wrong path is reported
+            // as such rather than as Discord not running
+            let socket = open(path, deadline)?.map_err(|err| error::Error::ConnectFailed {
+                attempts: vec![(path.clone(), err)],
+            })?;
+            log::debug!("Connected to the socket {} given", path.display());
+            self.socket = Some(socket);
+            self.endpoint = Some(ConnectedEndpoint {
+                path: path.clone(),
+                source: EndpointSource::Explicit,
+            });
+            return Ok(());
+        }
+
+        let candidates = self.candidates();
+        let overridden = matches!(candidates.first(), Some((_, EndpointSource::Overridden)));
+        let mut attempts = Vec::new();
+        for (path, source) in candidates {
+            match open(&path, deadline)? {
+                Ok(socket) => {
+                    log::debug!("Connected to the socket {} ({:?})", path.display(), source);
+                    self.socket = Some(socket);
+                    self.endpoint = Some(ConnectedEndpoint { path, source });
+                    return Ok(());
                 }
-                Err(_e) => continue,
+                Err(err) => attempts.push((path, err)),
             }
         }
-        PathBuf::from(path)
+
+        if overridden {
+            return Err(error::Error::PathOverrideFailed { attempts }.into());
+        }
+        Err(error::Error::from_connect_attempts(attempts).into())
+    }
+
+    // The directories to search, in order, and the variable naming
+    // them, if any
+    fn get_pipe_patterns(&self) -> Vec<(Option<&'static str>, PathBuf)> {
+        let dir = ENV_KEYS.iter().find_map(|key| {
+            self.env
+                .get(key)
+                .map(|path| (Some(*key), PathBuf::from(path)))
+        });
+        match dir {
+            Some(dir) => vec![dir],
+            None => FALLBACK_DIRS
+                .iter()
+                .map(|dir| (None, PathBuf::from(dir)))
+                .collect(),
+        }
     }
 }
 
-impl DiscordIpc for DiscordIpcClient {
-    fn connect_ipc(&mut self) -> Result<()> {
-        for i in 0..10 {
-            for subpath in APP_SUBPATHS {
-                let path = DiscordIpcClient::get_pipe_pattern()
-                    .join(subpath)
-                    .join(format!("discord-ipc-{}", i));
-
-                match UnixStream::connect(&path) {
-                    Ok(socket) => {
-                        self.socket = Some(socket);
-                        return Ok(());
-                    }
-                    Err(_) => continue,
-                }
-            }
+impl Default for NativeTransport {
+    fn default() -> Self {
+        Self {
+            path: None,
+            env: Box::new(SystemEnv),
+            socket: None,
+            endpoint: None,
+            pipe_index: None,
+            pipe_range: DEFAULT_PIPE_RANGE,
         }
+    }
+}
+
+impl fmt::Debug for NativeTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeTransport")
+            .field("path", &self.path)
+            .field("socket", &self.socket)
+            .field("pipe_index", &self.pipe_index)
+            .field("pipe_range", &self.pipe_range)
+            .field(
+                "endpoint",
+                &self.endpoint.as_ref().map(|endpoint| &endpoint.path),
+            )
+            .finish()
+    }
+}
+
+impl DiscordIpcClient {
+    /// Returns the raw file descriptor of the socket the client is
+    /// connected through, or `None` if it has never connected.
+    ///
+    /// This allows the connection to be registered with an
+    /// existing event loop (for example with `poll(2)`), to learn
+    /// when an event can be received. The descriptor is only valid
+    /// until the client is closed or reconnected, and must not be
+    /// read from or written to directly.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::DiscordIpcClient;
+    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// assert_eq!(ipc_client.raw_fd(), None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.transport.stream().map(|socket| socket.as_raw_fd())
+    }
+}
+
+impl IpcTransport for NativeTransport {
+    fn connect(&mut self) -> Result<()> {
+        self.connect_within(None)
+    }
 
-        Err("Couldn't connect to the Discord IPC socket".into())
+    fn connect_timeout(&mut self, timeout: Duration) -> Result<()> {
+        // The socket is waited on in real time, whatever clock the
+        // client measures its own timeout by
+        self.connect_within(Deadline::after(timeout, clock::system()).as_ref())
     }
 
     fn write(&mut self, data: &[u8]) -> Result<()> {
-        let socket = self.socket.as_mut().expect("Client not connected");
+        let socket = self.stream_mut()?;
 
         socket.write_all(data)?;
 
@@ -95,18 +473,28 @@ impl DiscordIpc for DiscordIpcClient {
     }
 
     fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
-        let socket = self.socket.as_mut().unwrap();
+        let socket = self.stream_mut()?;
 
         socket.read_exact(buffer)?;
 
         Ok(())
     }
 
-    fn close(&mut self) -> Result<()> {
-        let data = json!({});
-        if self.send(data, 2).is_ok() {}
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        let socket = self.stream_mut()?;
+
+        // The socket's flags are left alone, as they are shared
+        // with the write half of a split client
+        Ok(poll(socket.as_raw_fd(), libc::POLLIN, timeout)?)
+    }
 
-        let socket = self.socket.as_mut().unwrap();
+    fn shutdown(&mut self) -> Result<()> {
+        self.endpoint = None;
+        // There is nothing to shut down if the client never connected
+        let socket = match self.socket.as_mut() {
+            Some(socket) => socket,
+            None => return Ok(()),
+        };
 
         socket.flush()?;
         match socket.shutdown(Shutdown::Both) {
@@ -116,8 +504,54 @@ impl DiscordIpc for DiscordIpcClient {
 
         Ok(())
     }
+}
 
-    fn get_client_id(&self) -> &String {
-        &self.client_id
+/// Allows the client's socket to be registered with a mio
+/// [`Poll`](mio::Poll), to learn when an event can be received.
+///
+/// Once the client has connected, register it for
+/// [`Interest::READABLE`](mio::Interest::READABLE). mio reports
+/// readiness once per batch of incoming data, so whenever the
+/// client is reported readable, call
+/// [`try_recv_event`](crate::DiscordIpc::try_recv_event) until it returns
+/// `Ok(None)`.
+///
+/// The socket is replaced when the client reconnects, so the
+/// client must then be registered again. Registering a client
+/// which has never connected fails with
+/// [`ErrorKind::NotConnected`].
+///
+/// This is only available on Unix, as the Windows client's pipe
+/// isn't opened for the overlapped I/O mio relies on.
+#[cfg(feature = "mio")]
+impl mio::event::Source for DiscordIpcClient {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.socket_fd()?).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.socket_fd()?).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.socket_fd()?).deregister(registry)
+    }
+}
+
+#[cfg(feature = "mio")]
+impl DiscordIpcClient {
+    fn socket_fd(&self) -> std::io::Result<RawFd> {
+        self.raw_fd()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotConnected, "Client not connected"))
     }
 }
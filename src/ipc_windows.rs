@@ -1,63 +1,425 @@
-use crate::discord_ipc::DiscordIpc;
-use serde_json::json;
+use crate::{
+    clock, error,
+    transport::{
+        self, ConnectedEndpoint, Deadline, EndpointSource, EnvProvider, IpcTransport, SystemEnv,
+        DEFAULT_PIPE_RANGE, IPC_PATH_VAR,
+    },
+    DiscordIpcClient,
+};
 use std::{
     error::Error,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
-    os::windows::fs::OpenOptionsExt,
-    path::PathBuf,
+    ffi::{c_void, OsStr},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+    os::windows::{
+        ffi::OsStrExt,
+        fs::OpenOptionsExt,
+        io::{AsRawHandle, RawHandle},
+    },
+    path::{Path, PathBuf},
+    ptr, slice, thread,
+    time::{Duration, Instant},
 };
 
+#[link(name = "kernel32")]
+extern "system" {
+    fn PeekNamedPipe(
+        named_pipe: *mut c_void,
+        buffer: *mut c_void,
+        buffer_size: u32,
+        bytes_read: *mut u32,
+        total_bytes_avail: *mut u32,
+        bytes_left_this_message: *mut u32,
+    ) -> i32;
+    fn GetCurrentProcessId() -> u32;
+    fn ProcessIdToSessionId(process_id: u32, session_id: *mut u32) -> i32;
+    fn LocalFree(memory: *mut c_void) -> *mut c_void;
+    fn WaitNamedPipeW(named_pipe_name: *const u16, timeout: u32) -> i32;
+}
+
+// Every instance of the pipe is in use
+const ERROR_PIPE_BUSY: i32 = 231;
+// `WaitNamedPipeW` timed out
+const ERROR_SEM_TIMEOUT: i32 = 121;
+// Discord closed its end of the pipe
+const ERROR_BROKEN_PIPE: i32 = 109;
+const ERROR_PIPE_NOT_CONNECTED: i32 = 233;
+
+#[link(name = "userenv")]
+extern "system" {
+    fn DeriveAppContainerSidFromAppContainerName(
+        app_container_name: *const u16,
+        sid: *mut *mut c_void,
+    ) -> i32;
+}
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn ConvertSidToStringSidW(sid: *mut c_void, string_sid: *mut *mut u16) -> i32;
+    fn FreeSid(sid: *mut c_void) -> *mut c_void;
+}
+
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
-#[allow(dead_code)]
-#[derive(Debug)]
-/// A wrapper struct for the functionality contained in the
-/// underlying [`DiscordIpc`](trait@DiscordIpc) trait.
-pub struct DiscordIpcClient {
-    /// Client ID of the IPC client.
-    pub client_id: String,
-    connected: bool,
+// The connection's underlying pipe
+pub(crate) type Stream = File;
+
+// Opens the pipe at `path`, before `deadline` if there is one. A
+// pipe whose instances are all in use is waited on until one is
+// free or the deadline passes.
+fn open(
+    path: &Path,
+    deadline: Option<&Deadline>,
+) -> std::result::Result<io::Result<File>, error::Error> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Ok(OpenOptions::new().access_mode(0x3).open(path)),
+    };
+    loop {
+        let remaining = deadline.remaining()?;
+        match OpenOptions::new().access_mode(0x3).open(path) {
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                if let Err(err) = wait_for_pipe(path, remaining) {
+                    if err.raw_os_error() == Some(ERROR_SEM_TIMEOUT) {
+                        return Err(deadline.expired());
+                    }
+                    return Ok(Err(err));
+                }
+            }
+            opened => return Ok(opened),
+        }
+    }
+}
+
+// Waits up to `timeout` for an instance of the pipe at `path` to be
+// free to open
+fn wait_for_pipe(path: &Path, timeout: Duration) -> io::Result<()> {
+    // `WaitNamedPipeW` only accepts names under `\\.\pipe\`
+    let path = path.as_os_str().to_string_lossy();
+    let path = match path.strip_prefix(r"\\?\") {
+        Some(rest) => format!(r"\\.\{}", rest),
+        None => path.into_owned(),
+    };
+    let name: Vec<u16> = OsStr::new(&path).encode_wide().chain(Some(0)).collect();
+
+    // Rounded up, short of `NMPWAIT_USE_DEFAULT_WAIT` and
+    // `NMPWAIT_WAIT_FOREVER`
+    let millis = timeout.as_micros().div_ceil(1000);
+    let millis = millis.clamp(1, u32::MAX as u128 - 1) as u32;
+    if unsafe { WaitNamedPipeW(name.as_ptr(), millis) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The platform's connection to Discord: a named pipe.
+pub struct NativeTransport {
+    path: Option<PathBuf>,
+    env: Box<dyn EnvProvider>,
     socket: Option<File>,
+    endpoint: Option<ConnectedEndpoint>,
+    pipe_index: Option<u32>,
+    pipe_range: RangeInclusive<u32>,
 }
 
-impl DiscordIpcClient {
-    /// Creates a new `DiscordIpcClient`.
+impl NativeTransport {
+    /// Creates a new, unconnected `NativeTransport`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, unconnected `NativeTransport`, which searches
+    /// for Discord's pipe using the variables of `env` rather than
+    /// the process's environment.
     ///
     /// # Examples
     /// ```
-    /// let ipc_client = DiscordIpcClient::new("<some client id>")?;
+    /// # use discord_rich_presence::transport::{NativeTransport, IPC_PATH_VAR};
+    /// # use std::{collections::HashMap, path::PathBuf};
+    /// let pipe = r"\\?\pipe\my-custom-ipc";
+    /// let env: HashMap<_, _> = [(IPC_PATH_VAR.to_string(), pipe.to_string())].into();
+    /// let transport = NativeTransport::with_env(env);
+    /// assert_eq!(transport.candidate_paths(), vec![PathBuf::from(pipe)]);
     /// ```
-    pub fn new(client_id: &str) -> Result<Self> {
-        let client = Self {
-            client_id: client_id.to_string(),
-            connected: false,
-            socket: None,
-        };
+    pub fn with_env(env: impl EnvProvider + 'static) -> Self {
+        Self {
+            env: Box::new(env),
+            ..Self::default()
+        }
+    }
 
-        Ok(client)
+    /// Creates a new, unconnected `NativeTransport`, which connects
+    /// to the pipe at `path` instead of searching for Discord's.
+    ///
+    /// If connecting to it fails, [`connect`](IpcTransport::connect)
+    /// returns an [`Error::ConnectFailed`](error::Error::ConnectFailed)
+    /// naming it, without searching elsewhere.
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn from_stream(stream: Stream) -> Self {
+        Self {
+            socket: Some(stream),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn stream(&self) -> Option<&Stream> {
+        self.socket.as_ref()
+    }
+
+    // The open socket or pipe, or `Error::NotConnected` if there
+    // isn't one
+    fn stream_mut(&mut self) -> std::result::Result<&mut Stream, error::Error> {
+        self.socket.as_mut().ok_or(error::Error::NotConnected)
+    }
+
+    /// Restricts the search to the socket or pipe `discord-ipc-N`
+    /// with the given index, or with `None`, searches all of them
+    /// again.
+    ///
+    /// This picks one of several Discord instances running at once,
+    /// such as Stable and Canary, which each listen on their own
+    /// index. The restriction applies to every later connection.
+    ///
+    /// # Errors
+    /// Returns an [`Error::InvalidPipeIndex`](error::Error::InvalidPipeIndex)
+    /// if the index is outside the range searched, leaving the
+    /// search unchanged.
+    pub fn set_pipe_index(&mut self, index: Option<u32>) -> std::result::Result<(), error::Error> {
+        if let Some(index) = index.filter(|index| !self.pipe_range.contains(index)) {
+            return Err(error::Error::InvalidPipeIndex {
+                index,
+                pipes: self.pipe_range.clone(),
+            });
+        }
+        self.pipe_index = index;
+        Ok(())
     }
-}
 
-impl DiscordIpc for DiscordIpcClient {
-    fn connect_ipc(&mut self) -> Result<()> {
-        for i in 0..10 {
-            let path = PathBuf::from(format!(r"\\?\pipe\discord-ipc-{}", i));
+    /// Sets the indexes of the sockets or pipes `discord-ipc-N`
+    /// searched, [`DEFAULT_PIPE_RANGE`] unless set.
+    ///
+    /// Every later connection searches this range, unless
+    /// [`set_pipe_index`](#method.set_pipe_index) chose a single
+    /// index. An empty range searches nothing.
+    ///
+    /// # Examples
+    /// ```
+    /// # use discord_rich_presence::transport::NativeTransport;
+    /// let mut transport = NativeTransport::new();
+    /// transport.set_pipe_range(0..=31);
+    /// assert!(transport.set_pipe_index(Some(15)).is_ok());
+    /// ```
+    pub fn set_pipe_range(&mut self, pipes: RangeInclusive<u32>) {
+        self.pipe_range = pipes;
+    }
+
+    /// Returns the paths [`connect`](IpcTransport::connect) tries,
+    /// in order.
+    ///
+    /// Unless a path was given to [`with_path`](#method.with_path),
+    /// or one is set by [`IPC_PATH_VAR`], these are the pipes
+    /// `discord-ipc-0` to `discord-ipc-9`, or those in the range
+    /// set with [`set_pipe_range`](#method.set_pipe_range), or only
+    /// the one chosen with [`set_pipe_index`](#method.set_pipe_index), each
+    /// looked for as a regular install creates it, then in the
+    /// AppContainer of each Discord package installed from the
+    /// Microsoft Store, as listed by
+    /// [`windows_pipe_paths`](crate::transport::windows_pipe_paths).
+    pub fn candidate_paths(&self) -> Vec<PathBuf> {
+        self.candidates()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Returns the pipe the transport is connected to, and how it
+    /// was found, or `None` if it isn't connected.
+    pub fn connected_endpoint(&self) -> Option<&ConnectedEndpoint> {
+        self.endpoint.as_ref()
+    }
 
-            match OpenOptions::new().access_mode(0x3).open(&path) {
+    fn candidates(&self) -> Vec<(PathBuf, EndpointSource)> {
+        if let Some(path) = &self.path {
+            return vec![(path.clone(), EndpointSource::Explicit)];
+        }
+        if let Some(value) = self.env.get(IPC_PATH_VAR).filter(|value| !value.is_empty()) {
+            return transport::override_candidates(&value, self.pipes());
+        }
+
+        transport::windows_pipe_candidates(
+            session_id(),
+            &discord_package_sids(&*self.env),
+            self.pipes(),
+        )
+    }
+
+    // The indexes of the sockets or pipes searched
+    fn pipes(&self) -> RangeInclusive<u32> {
+        match self.pipe_index {
+            Some(index) => index..=index,
+            None => self.pipe_range.clone(),
+        }
+    }
+
+    // Connects as `connect` does, giving up once `deadline` has
+    // passed, if there is one
+    fn connect_within(&mut self, deadline: Option<&Deadline>) -> Result<()> {
+        self.endpoint = None;
+
+        if let Some(path) = &self.path {
+            // Not searching further, so that a wrong path is reported
+            // as such rather than as Discord not running
+            let socket = open(path, deadline)?.map_err(|err| error::Error::ConnectFailed {
+                attempts: vec![(path.clone(), err)],
+            })?;
+            log::debug!("Connected to the pipe {} given", path.display());
+            self.socket = Some(socket);
+            self.endpoint = Some(ConnectedEndpoint {
+                path: path.clone(),
+                source: EndpointSource::Explicit,
+            });
+            return Ok(());
+        }
+
+        let candidates = self.candidates();
+        let overridden = matches!(candidates.first(), Some((_, EndpointSource::Overridden)));
+        let mut attempts = Vec::new();
+        for (path, source) in candidates {
+            match open(&path, deadline)? {
                 Ok(handle) => {
+                    log::debug!("Connected to the pipe {} ({:?})", path.display(), source);
                     self.socket = Some(handle);
+                    self.endpoint = Some(ConnectedEndpoint { path, source });
                     return Ok(());
                 }
-                Err(_) => continue,
+                Err(err) => attempts.push((path, err)),
             }
         }
 
-        Err("Couldn't connect to the Discord IPC socket".into())
+        if overridden {
+            return Err(error::Error::PathOverrideFailed { attempts }.into());
+        }
+        Err(error::Error::from_connect_attempts(attempts).into())
+    }
+}
+
+// The session the process runs in, under which the pipes of
+// AppContainers are named
+fn session_id() -> Option<u32> {
+    let mut session_id = 0;
+    let success = unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id) };
+    (success != 0).then_some(session_id)
+}
+
+// The SIDs of the AppContainers of the Discord packages installed
+// from the Microsoft Store, found by the directories packages keep
+// in the user's local app data, which are named after them
+fn discord_package_sids(env: &dyn EnvProvider) -> Vec<String> {
+    let packages = match env.get("LOCALAPPDATA") {
+        Some(dir) => PathBuf::from(dir).join("Packages"),
+        None => return Vec::new(),
+    };
+    let entries = match fs::read_dir(packages) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.to_lowercase().contains("discord"))
+        .filter_map(|name| app_container_sid(&name))
+        .collect()
+}
+
+// Derives the SID of a package's AppContainer from the package's
+// family name, as a string such as `S-1-15-2-...`
+fn app_container_sid(family_name: &str) -> Option<String> {
+    let name: Vec<u16> = OsStr::new(family_name)
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+
+    let mut sid = ptr::null_mut();
+    if unsafe { DeriveAppContainerSidFromAppContainerName(name.as_ptr(), &mut sid) } < 0 {
+        return None;
+    }
+    let mut string_sid = ptr::null_mut();
+    let converted = unsafe { ConvertSidToStringSidW(sid, &mut string_sid) } != 0;
+    unsafe { FreeSid(sid) };
+    if !converted {
+        return None;
+    }
+
+    let string = unsafe {
+        let length = (0..).take_while(|&i| *string_sid.add(i) != 0).count();
+        String::from_utf16(slice::from_raw_parts(string_sid, length)).ok()
+    };
+    unsafe { LocalFree(string_sid.cast()) };
+    string
+}
+
+impl Default for NativeTransport {
+    fn default() -> Self {
+        Self {
+            path: None,
+            env: Box::new(SystemEnv),
+            socket: None,
+            endpoint: None,
+            pipe_index: None,
+            pipe_range: DEFAULT_PIPE_RANGE,
+        }
+    }
+}
+
+impl fmt::Debug for NativeTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeTransport")
+            .field("path", &self.path)
+            .field("socket", &self.socket)
+            .field("pipe_index", &self.pipe_index)
+            .field("pipe_range", &self.pipe_range)
+            .field(
+                "endpoint",
+                &self.endpoint.as_ref().map(|endpoint| &endpoint.path),
+            )
+            .finish()
+    }
+}
+
+impl DiscordIpcClient {
+    /// Returns the raw handle of the named pipe the client is
+    /// connected through, or `None` if it has never connected.
+    ///
+    /// The handle is only valid until the client is closed or
+    /// reconnected, and must not be read from or written to
+    /// directly.
+    pub fn raw_handle(&self) -> Option<RawHandle> {
+        self.transport.stream().map(|socket| socket.as_raw_handle())
+    }
+}
+
+impl IpcTransport for NativeTransport {
+    fn connect(&mut self) -> Result<()> {
+        self.connect_within(None)
+    }
+
+    fn connect_timeout(&mut self, timeout: Duration) -> Result<()> {
+        // The pipe is waited on in real time, whatever clock the
+        // client measures its own timeout by
+        self.connect_within(Deadline::after(timeout, clock::system()).as_ref())
     }
 
     fn write(&mut self, data: &[u8]) -> Result<()> {
-        let socket = self.socket.as_mut().expect("Client not connected");
+        let socket = self.stream_mut()?;
 
         socket.write_all(data)?;
 
@@ -65,24 +427,64 @@ impl DiscordIpc for DiscordIpcClient {
     }
 
     fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
-        let socket = self.socket.as_mut().unwrap();
+        let socket = self.stream_mut()?;
 
         socket.read_exact(buffer)?;
 
         Ok(())
     }
 
-    fn close(&mut self) -> Result<()> {
-        let data = json!({});
-        if self.send(data, 2).is_ok() {}
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        let socket = self.stream_mut()?;
+        // A timeout too long to be represented waits indefinitely
+        let deadline = Instant::now().checked_add(timeout);
 
-        let socket = self.socket.as_mut().unwrap();
-        socket.flush()?;
+        // Named pipes can't be read with a timeout, so poll for
+        // available bytes instead
+        loop {
+            let mut available = 0;
+            let success = unsafe {
+                PeekNamedPipe(
+                    socket.as_raw_handle(),
+                    ptr::null_mut(),
+                    0,
+                    ptr::null_mut(),
+                    &mut available,
+                    ptr::null_mut(),
+                )
+            };
+            if success == 0 {
+                let err = io::Error::last_os_error();
+                // A closed pipe counts as readable, so that the next
+                // read reports it
+                if matches!(
+                    err.raw_os_error(),
+                    Some(ERROR_BROKEN_PIPE | ERROR_PIPE_NOT_CONNECTED)
+                ) {
+                    return Ok(true);
+                }
+                return Err(err.into());
+            }
 
-        Ok(())
+            if available > 0 {
+                return Ok(true);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
     }
 
-    fn get_client_id(&self) -> &String {
-        &self.client_id
+    fn shutdown(&mut self) -> Result<()> {
+        self.endpoint = None;
+        // There is nothing to shut down if the client never connected
+        let socket = match self.socket.as_mut() {
+            Some(socket) => socket,
+            None => return Ok(()),
+        };
+        socket.flush()?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,156 @@
+//! Provides the types used to respond to Ask to Join requests,
+//! via [`DiscordIpc::on_join_request`](crate::DiscordIpc::on_join_request).
+use crate::{
+    clock::{self, Clock, SystemClock},
+    discord_ipc::DiscordIpc,
+    error::Error,
+    event::{Event, EventKind},
+    models::User,
+};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime};
+
+/// How long Discord keeps a join request open before it expires
+pub const JOIN_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A struct representing a request from another user to join
+/// the user's game, received via an `ACTIVITY_JOIN_REQUEST` event
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct JoinRequest {
+    /// The user asking to join
+    pub user: User,
+
+    /// The time at which the request was received, by the client's
+    /// clock
+    pub received_at: SystemTime,
+}
+
+impl JoinRequest {
+    /// Creates a new `JoinRequest`, such as to test a handler
+    pub fn new(user: User, received_at: SystemTime) -> Self {
+        Self { user, received_at }
+    }
+
+    /// Whether Discord has expired the request, which happens
+    /// [`JOIN_REQUEST_TIMEOUT`] after it was sent
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(&SystemClock)
+    }
+
+    /// Whether Discord has expired the request, as
+    /// [`is_expired`](#method.is_expired) tells, at the current time
+    /// of `clock`
+    pub fn is_expired_at(&self, clock: &dyn Clock) -> bool {
+        clock::system_time(clock)
+            .duration_since(self.received_at)
+            .is_ok_and(|elapsed| elapsed >= JOIN_REQUEST_TIMEOUT)
+    }
+}
+
+/// An enum representing the response to a [`JoinRequest`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinDecision {
+    /// Accept the request, sending the user an invite
+    Accept,
+    /// Decline the request
+    Decline,
+    /// Leave the request pending, to be answered later with
+    /// [`DiscordIpc::accept_join_request`] or
+    /// [`DiscordIpc::decline_join_request`]
+    Defer,
+}
+
+pub(crate) type JoinRequestHandler = Box<dyn FnMut(&JoinRequest) -> JoinDecision + Send>;
+
+// Passes a received join request to the registered handler,
+// returning the command which carries out its decision, if any
+pub(crate) fn answer<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    event: &Event,
+) -> Result<Option<(&'static str, Value)>, Box<dyn std::error::Error>> {
+    let data = match event {
+        Event::Dispatch {
+            kind: EventKind::ActivityJoinRequest,
+            data,
+        } => data,
+        _ => return Ok(None),
+    };
+
+    if client.client_state().join_handler.is_none() {
+        return Ok(None);
+    }
+    // The request is parsed before the handler is taken, so that a
+    // malformed one doesn't leave the handler unregistered
+    let request = JoinRequest::new(
+        serde_json::from_value(data["user"].clone())?,
+        clock::system_time(&*client.client_state().clock()),
+    );
+    let mut handler = match client.client_state().join_handler.take() {
+        Some(handler) => handler,
+        None => return Ok(None),
+    };
+    let decision = handler(&request);
+    client.client_state().join_handler = Some(handler);
+
+    let args = json!({ "user_id": request.user.id });
+    Ok(match decision {
+        JoinDecision::Accept => Some(("SEND_ACTIVITY_JOIN_INVITE", args)),
+        JoinDecision::Decline => Some(("CLOSE_ACTIVITY_REQUEST", args)),
+        JoinDecision::Defer => {
+            let pending = &mut client.client_state().join_requests;
+            pending.retain(|pending| pending.user.id != request.user.id);
+            pending.push(request);
+            None
+        }
+    })
+}
+
+// The RPC error code Discord responds with when the user a
+// request came from can't be answered, because the request
+// expired or was never made
+const INVALID_USER: u64 = 4010;
+
+// Discord rejecting the answer to a request means it can no
+// longer be answered, which leaves nothing for the caller to do.
+// Any other error is passed on
+pub(crate) fn ignore_rejection(
+    result: Result<Value, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Err(err)
+            if !matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::CommandError {
+                    code: INVALID_USER,
+                    ..
+                })
+            ) =>
+        {
+            Err(err)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Removes a pending request, failing if it has expired or was
+// never received
+pub(crate) fn take_pending<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    user_id: &str,
+) -> Result<JoinRequest, Error> {
+    let state = client.client_state();
+    let clock = state.clock();
+    let pending = &mut state.join_requests;
+    pending.retain(|request| !request.is_expired_at(&*clock));
+
+    match pending
+        .iter()
+        .position(|request| request.user.id == user_id)
+    {
+        Some(index) => Ok(pending.remove(index)),
+        None => Err(Error::JoinRequestExpired {
+            user_id: user_id.to_string(),
+        }),
+    }
+}
@@ -6,7 +6,7 @@
 //! manner.
 //!
 //! # Hello world
-//! ```
+//! ```no_run
 //! use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
 //!
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -15,14 +15,59 @@
 //!
 //!     let payload = activity::Activity::new().state("Hello world!");
 //!     client.set_activity(payload)?;
+//!     Ok(())
 //! }
 //! ```
+//!
+//! # Stability
+//!
+//! Discord keeps adding fields to activities and to the objects it
+//! sends. So that they can be added in minor releases, the structs
+//! modeling them are `#[non_exhaustive]`: activities are built with
+//! their `new()` methods and setters, and models received from
+//! Discord are read through their public fields, which can't be
+//! exhaustively destructured. New fields, setters and constructors
+//! are not considered breaking changes.
 #![deny(missing_docs)]
 
+mod client;
 mod discord_ipc;
+mod nonce;
 mod pack_unpack;
+mod router;
 pub use discord_ipc::*;
 pub mod activity;
+pub mod assets;
+#[cfg(feature = "capture")]
+pub mod capture;
+pub mod circuit;
+pub mod clock;
+pub mod debounce;
+pub mod error;
+pub mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod guard;
+pub mod join_request;
+pub mod manager;
+pub mod metrics;
+pub mod models;
+pub mod multi_app;
+pub mod party;
+pub mod persist;
+pub mod pid;
+pub mod prelude;
+pub mod presence;
+pub mod pump;
+pub mod rate_limit;
+pub mod shared;
+pub mod split;
+pub mod stats;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod transport;
+#[cfg(feature = "undocumented")]
+pub mod undocumented;
 
 #[cfg(unix)]
 mod ipc_unix;
@@ -34,7 +79,7 @@ mod ipc_windows;
 #[cfg(windows)]
 use ipc_windows as ipc;
 
-pub use ipc::DiscordIpcClient;
+pub use client::DiscordIpcClient;
 
 #[deprecated(since = "0.2.0", note = "use DiscordIpcClient::new() instead")]
 /// Creates a new client to connect to the Discord IPC. Functionally
@@ -42,8 +87,10 @@ pub use ipc::DiscordIpcClient;
 ///
 /// # Examples
 /// ```
-/// let ipc_client = discord_ipc_client::new_client("<some client id>")?;
+/// # #![allow(deprecated)]
+/// let ipc_client = discord_rich_presence::new_client("<some client id>")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn new_client(client_id: &str) -> Result<impl DiscordIpc, Box<dyn std::error::Error>> {
-    ipc::DiscordIpcClient::new(client_id)
+    DiscordIpcClient::new(client_id)
 }
@@ -0,0 +1,769 @@
+//! Provides a connection manager, which keeps the user's activity
+//! up to date from a background thread.
+use crate::{
+    activity::Activity,
+    circuit::{CircuitBreaker, CircuitState},
+    clock::{self, Clock},
+    discord_ipc::{set_activity_args, DiscordIpc},
+    error::Error,
+    persist::Persistence,
+    pump::into_send_error,
+    rate_limit::RateLimiter,
+    router::read_inbound,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error as StdError,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn StdError>>;
+
+/// The result of a request to a [`DiscordIpcManager`], sent once
+/// the request has been carried out
+pub type Completion = std::result::Result<(), Box<dyn StdError + Send + Sync>>;
+
+// How often an idle connection is checked for having closed
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How far off a wait too long to be represented is put instead,
+// which is as good as never
+const FAR_FUTURE: Duration = Duration::from_secs(30 * 365 * 24 * 60 * 60);
+
+// How long a termination handler waits for the activity to be
+// cleared before exiting
+#[cfg(feature = "ctrlc")]
+const TERMINATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The exit code after an interruption, as shells report for SIGINT
+#[cfg(feature = "ctrlc")]
+const TERMINATION_EXIT_CODE: i32 = 130;
+
+/// A struct representing the configuration of a
+/// [`DiscordIpcManager`]
+///
+/// Note that all methods return `Self`, and can be chained
+/// for fluency
+#[derive(Clone, Debug)]
+pub struct ManagerConfig {
+    rate_limiter: RateLimiter,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    circuit_breaker: Option<CircuitBreaker>,
+    restore_on_reconnect: bool,
+    activity_ttl: Option<Duration>,
+    persistence: Option<Persistence>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ManagerConfig {
+    /// Creates a new `ManagerConfig`, which follows Discord's rate
+    /// limit for updates, and retries failed connections after 1
+    /// second, backing off to once a minute
+    pub fn new() -> Self {
+        ManagerConfig {
+            rate_limiter: RateLimiter::discord(),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            circuit_breaker: None,
+            restore_on_reconnect: true,
+            activity_ttl: None,
+            persistence: None,
+            clock: clock::system(),
+        }
+    }
+
+    /// Sets the rate limiter deciding when updates are sent
+    ///
+    /// Updates made once its budget is spent wait, and are
+    /// replaced by any update made meanwhile, whatever its
+    /// [`RateLimitPolicy`](crate::rate_limit::RateLimitPolicy).
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Sets the minimum time between two activity updates
+    ///
+    /// This is shorthand for a [`rate_limiter`](#method.rate_limiter)
+    /// allowing one update per `update_interval`.
+    pub fn update_interval(self, update_interval: Duration) -> Self {
+        self.rate_limiter(RateLimiter::new(1, update_interval))
+    }
+
+    /// Sets the delay before retrying a failed connection, which
+    /// doubles after every failure up to `max`
+    pub fn reconnect_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.min_backoff = min;
+        self.max_backoff = max.max(min);
+        self
+    }
+
+    /// Stops retrying failed connections for a while once they
+    /// failed too often in a row, which happens only with backoff
+    /// by default
+    ///
+    /// While the breaker is open, no connection is attempted, and
+    /// the [`ManagerStatus::ConnectFailed`] which opened it reports
+    /// when the next attempt is made. The breaker's own clock is
+    /// ignored in favor of the manager's.
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Sets whether the last activity is set again after
+    /// reconnecting, which it is by default
+    ///
+    /// Discord forgets the activity when the connection is lost.
+    /// Without restoring, the activity stays unset until the next
+    /// update, and a lost connection is only re-established for
+    /// one.
+    pub fn restore_on_reconnect(mut self, restore_on_reconnect: bool) -> Self {
+        self.restore_on_reconnect = restore_on_reconnect;
+        self
+    }
+
+    /// Sets how long an activity is kept without being updated,
+    /// which is forever by default
+    ///
+    /// See [`DiscordIpcManager::set_activity_ttl`].
+    pub fn activity_ttl(mut self, activity_ttl: Duration) -> Self {
+        self.activity_ttl = Some(activity_ttl);
+        self
+    }
+
+    /// Saves every activity Discord accepts, and clearing it, to
+    /// a store
+    ///
+    /// With [`Persistence::restore_on_connect`], an activity saved
+    /// recently enough is set once the manager starts, connecting
+    /// to do so, unless another is set first. See the
+    /// [`persist`](crate::persist) module.
+    pub fn persist_to(mut self, persistence: Persistence) -> Self {
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Sets the clock reconnection backoff, the circuit breaker,
+    /// the rate limiter and the activity's time to live are
+    /// measured by, as well as the age of a persisted activity,
+    /// unless its [`Persistence`] has a clock of its own
+    ///
+    /// The client keeps its own clock (see [`DiscordIpc::set_clock`])
+    /// for what it measures itself, such as its statistics.
+    ///
+    /// The background thread checks the clock at least every 100
+    /// milliseconds while it has something to wait for, so a clock
+    /// which is advanced by hand is noticed promptly.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A change in the state of a [`DiscordIpcManager`], sent on the
+/// status channel returned by [`DiscordIpcManager::spawn`]
+#[derive(Debug)]
+pub enum ManagerStatus {
+    /// The manager connected to Discord
+    Connected,
+    /// Connecting to Discord failed, and will be retried
+    ConnectFailed {
+        /// The error connecting failed with
+        error: Box<dyn StdError + Send + Sync>,
+        /// How long until the next attempt
+        retry_in: Duration,
+    },
+    /// The connection was lost, and will be re-established
+    Disconnected(Box<dyn StdError + Send + Sync>),
+    /// Discord rejected an activity update
+    UpdateFailed(Box<dyn StdError + Send + Sync>),
+    /// The activity wasn't updated within its time to live, and is
+    /// being cleared
+    ActivityExpired,
+}
+
+enum Message {
+    Update {
+        activity: Option<Value>,
+        done: Sender<Completion>,
+    },
+    SetTtl {
+        ttl: Option<Duration>,
+        done: Sender<Completion>,
+    },
+    Shutdown {
+        done: Sender<Completion>,
+    },
+    Terminate {
+        done: Sender<Completion>,
+    },
+}
+
+/// A handle to a client owned by a background thread, which
+/// keeps the user's activity set.
+///
+/// The thread connects when the first activity is set, and
+/// reconnects with backoff whenever the connection is lost,
+/// restoring the last activity once it does. Updates are sent as
+/// allowed by the [`rate_limiter`]. At most one update waits to be
+/// sent: while disconnected or rate limited, each update (including
+/// clearing the activity) replaces the one waiting, so only the
+/// latest is sent.
+///
+/// Every method returns immediately, with a channel receiving the
+/// result once the request has been carried out, which can be
+/// ignored. Failures are also reported on the status channel.
+///
+/// Dropping the manager, like [`shutdown`], closes the connection
+/// and stops the thread.
+///
+/// [`rate_limiter`]: ManagerConfig::rate_limiter
+/// [`shutdown`]: #method.shutdown
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{activity::Activity, manager::DiscordIpcManager, DiscordIpcClient};
+/// let client = DiscordIpcClient::new("<some client id>")?;
+/// let (manager, statuses) = DiscordIpcManager::spawn(client)?;
+///
+/// manager.update_activity(Activity::new().state("Hello world!"));
+/// for status in statuses {
+///     println!("{:?}", status);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct DiscordIpcManager {
+    messages: Sender<Message>,
+}
+
+impl DiscordIpcManager {
+    /// Moves the client onto a background thread, managed with
+    /// the default [`ManagerConfig`].
+    ///
+    /// The client does not need to be connected. Returns the
+    /// manager, and a channel receiving its status changes.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the thread could not be spawned.
+    pub fn spawn<T>(client: T) -> Result<(Self, Receiver<ManagerStatus>)>
+    where
+        T: DiscordIpc + Send + 'static,
+    {
+        Self::with_config(client, ManagerConfig::new())
+    }
+
+    /// Moves the client onto a background thread, managed with
+    /// the given configuration.
+    ///
+    /// See [`spawn`](#method.spawn).
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the thread could not be spawned.
+    pub fn with_config<T>(
+        client: T,
+        config: ManagerConfig,
+    ) -> Result<(Self, Receiver<ManagerStatus>)>
+    where
+        T: DiscordIpc + Send + 'static,
+    {
+        let (messages, message_receiver) = mpsc::channel();
+        let (statuses, status_receiver) = mpsc::channel();
+
+        let now = config.clock.now_instant();
+        let queue = UpdateQueue {
+            remember: config.restore_on_reconnect,
+            ..UpdateQueue::default()
+        };
+        let mut worker = Worker {
+            client,
+            rate_limiter: config.rate_limiter.clone(),
+            circuit_breaker: config.circuit_breaker.clone(),
+            has_activity: false,
+            ttl: config.activity_ttl,
+            expires_at: None,
+            next_attempt: now,
+            next_update: now,
+            backoff: config.min_backoff,
+            config,
+            statuses,
+            connected: false,
+            queue,
+        };
+        worker.restore_saved();
+        thread::Builder::new()
+            .name("discord-ipc-manager".to_string())
+            .spawn(move || worker.run(&message_receiver))?;
+
+        Ok((Self { messages }, status_receiver))
+    }
+
+    /// Sets the user's activity, connecting first if needed.
+    ///
+    /// The returned channel receives `Ok` once Discord accepted
+    /// the update (or a later one replacing it), and an `Err` if
+    /// Discord rejected it or the manager shut down first.
+    pub fn update_activity(&self, activity: Activity) -> Receiver<Completion> {
+        self.update(Some(json!(activity)))
+    }
+
+    /// Clears the user's activity.
+    ///
+    /// See [`update_activity`](#method.update_activity).
+    pub fn clear(&self) -> Receiver<Completion> {
+        self.update(None)
+    }
+
+    /// Clears the activity if it isn't updated again within `ttl`,
+    /// or stops doing so if `ttl` is `None`.
+    ///
+    /// This keeps a stale activity from staying shown when the
+    /// program updating it freezes or fails, while the connection
+    /// stays up. The time starts again with each activity set, and
+    /// from now for the activity currently set. Expiring clears the
+    /// activity as [`clear`](#method.clear) does, following the
+    /// rate limiter, and is reported on the status channel.
+    /// Clearing the activity or shutting down stops it expiring. A
+    /// `ttl` too long to be represented, such as [`Duration::MAX`],
+    /// never expires.
+    ///
+    /// The returned channel receives `Ok` once the time to live
+    /// applies.
+    pub fn set_activity_ttl(&self, ttl: Option<Duration>) -> Receiver<Completion> {
+        let (done, completion) = mpsc::channel();
+        let _ = self.messages.send(Message::SetTtl { ttl, done });
+
+        completion
+    }
+
+    /// Closes the connection, and stops the thread.
+    ///
+    /// Updates which have not been sent yet are dropped. The
+    /// returned channel receives the result of closing the
+    /// connection.
+    pub fn shutdown(self) -> Receiver<Completion> {
+        let (done, completion) = mpsc::channel();
+        // If the thread has stopped, `done` is dropped, which
+        // disconnects the channel
+        let _ = self.messages.send(Message::Shutdown { done });
+
+        completion
+    }
+
+    /// Clears the activity at once, closes the connection, and
+    /// stops the thread, for when the process is about to exit.
+    ///
+    /// Unlike [`clear`](#method.clear), clearing doesn't wait for
+    /// the rate limiter or a connection, and updates which have not
+    /// been sent yet fail with [`Error::ManagerStopped`]. The
+    /// manager is unusable afterwards. The returned channel
+    /// receives the result of closing the connection.
+    pub fn terminate(&self) -> Receiver<Completion> {
+        terminate(&self.messages)
+    }
+
+    /// Installs a handler for the process being interrupted or
+    /// terminated, which [`terminate`](#method.terminate)s the
+    /// manager and then exits the process.
+    ///
+    /// This keeps the activity from staying shown after a program
+    /// is stopped with Ctrl-C. The handler runs on a thread of its
+    /// own, not in the signal handler itself, and only asks the
+    /// manager's thread to clear the activity. It waits for that
+    /// for up to a second, and then exits with code 130, whichever
+    /// signal was received. Other threads get no chance to clean
+    /// up, so programs with cleanup of their own should handle
+    /// signals themselves and call `terminate` instead.
+    ///
+    /// The handler covers SIGINT, SIGTERM and SIGHUP on Unix, and
+    /// Ctrl-C and Ctrl-Break in a Windows console. A process which
+    /// is killed (with SIGKILL, or from the Task Manager) or
+    /// crashes can't clean up, and leaves the activity shown until
+    /// Discord notices the connection closed.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the handler couldn't be
+    /// installed, which includes when the process already has one:
+    /// only one handler can be installed per process, by this or
+    /// any other use of the `ctrlc` crate.
+    #[cfg(feature = "ctrlc")]
+    pub fn clear_on_termination(&self) -> Result<()> {
+        let messages = self.messages.clone();
+        ctrlc::set_handler(move || {
+            let _ = terminate(&messages).recv_timeout(TERMINATION_TIMEOUT);
+            std::process::exit(TERMINATION_EXIT_CODE);
+        })?;
+
+        Ok(())
+    }
+
+    pub(crate) fn update(&self, activity: Option<Value>) -> Receiver<Completion> {
+        let (done, completion) = mpsc::channel();
+        let _ = self.messages.send(Message::Update { activity, done });
+
+        completion
+    }
+}
+
+fn terminate(messages: &Sender<Message>) -> Receiver<Completion> {
+    let (done, completion) = mpsc::channel();
+    let _ = messages.send(Message::Terminate { done });
+
+    completion
+}
+
+// The instant `wait` after `now`, or `FAR_FUTURE` after it if that
+// can't be represented
+fn later(now: Instant, wait: Duration) -> Instant {
+    now.checked_add(wait)
+        .or_else(|| now.checked_add(FAR_FUTURE))
+        .unwrap_or(now)
+}
+
+// Holds the latest activity update not yet sent, replacing it as
+// newer updates arrive, along with everyone waiting on it
+#[derive(Default)]
+struct UpdateQueue {
+    // The update waiting to be sent, where `None` clears the activity
+    pending: Option<Option<Value>>,
+    waiting: Vec<Sender<Completion>>,
+    // The last activity Discord accepted, if it is to be restored
+    current: Option<Value>,
+    remember: bool,
+}
+
+impl UpdateQueue {
+    fn push(&mut self, activity: Option<Value>, done: Sender<Completion>) {
+        self.pending = Some(activity);
+        self.waiting.push(done);
+    }
+
+    fn take(&mut self) -> Option<Option<Value>> {
+        self.pending.take()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    // Whether there is an update to send, or an activity which
+    // would be lost without a connection
+    fn wants_connection(&self) -> bool {
+        self.pending.is_some() || self.current.is_some()
+    }
+
+    // Discord forgets the activity when the connection closes, so
+    // it is sent again unless something newer is waiting
+    fn restore(&mut self) {
+        if self.pending.is_none() {
+            self.pending = self.current.clone().map(Some);
+        }
+    }
+
+    // Puts back an update which could not be sent, unless a newer
+    // one arrived meanwhile
+    fn retry(&mut self, activity: Option<Value>) {
+        if self.pending.is_none() {
+            self.pending = Some(activity);
+        }
+    }
+
+    fn complete(&mut self, activity: Option<Value>) {
+        if self.remember {
+            self.current = activity;
+        }
+        for done in self.waiting.drain(..) {
+            let _ = done.send(Ok(()));
+        }
+    }
+
+    fn fail(&mut self, err: impl Fn() -> Error) {
+        for done in self.waiting.drain(..) {
+            let _ = done.send(Err(Box::new(err())));
+        }
+    }
+}
+
+// The state of the background thread
+struct Worker<T> {
+    client: T,
+    config: ManagerConfig,
+    rate_limiter: RateLimiter,
+    circuit_breaker: Option<CircuitBreaker>,
+    statuses: Sender<ManagerStatus>,
+    connected: bool,
+    queue: UpdateQueue,
+    backoff: Duration,
+    next_attempt: Instant,
+    // When the rate limiter next allows an update
+    next_update: Instant,
+    // Whether the last update set an activity, rather than clearing it
+    has_activity: bool,
+    ttl: Option<Duration>,
+    // When the activity set is cleared, unless updated before
+    expires_at: Option<Instant>,
+}
+
+impl<T: DiscordIpc> Worker<T> {
+    fn run(&mut self, messages: &Receiver<Message>) {
+        loop {
+            let message = match self.next_wake() {
+                Some(wake) => {
+                    let wait = wake.saturating_duration_since(self.now());
+                    messages.recv_timeout(wait.min(POLL_INTERVAL))
+                }
+                None => messages.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match message {
+                Ok(Message::Update { activity, done }) => {
+                    self.has_activity = activity.is_some();
+                    self.arm();
+                    self.queue.push(activity, done);
+                }
+                Ok(Message::SetTtl { ttl, done }) => {
+                    self.ttl = ttl;
+                    self.arm();
+                    let _ = done.send(Ok(()));
+                }
+                Ok(Message::Shutdown { done }) => {
+                    let _ = done.send(self.shutdown());
+                    return;
+                }
+                Ok(Message::Terminate { done }) => {
+                    let _ = done.send(self.terminate());
+                    return;
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = self.shutdown();
+                    return;
+                }
+            }
+
+            self.tick();
+        }
+    }
+
+    // When there is next something to do, if anything
+    fn next_wake(&self) -> Option<Instant> {
+        let wake = if !self.connected {
+            self.queue.wants_connection().then_some(self.next_attempt)
+        } else if self.queue.is_empty() {
+            Some(self.now() + POLL_INTERVAL)
+        } else {
+            Some((self.now() + POLL_INTERVAL).min(self.next_update))
+        };
+
+        match (wake, self.expires_at) {
+            (Some(wake), Some(expires_at)) => Some(wake.min(expires_at)),
+            (wake, expires_at) => wake.or(expires_at),
+        }
+    }
+
+    // Queues the activity saved before the last restart, if it is
+    // to be restored
+    fn restore_saved(&mut self) {
+        let client_id = self.client.get_client_id();
+        let activity = match self
+            .config
+            .persistence
+            .as_ref()
+            .and_then(|persistence| persistence.restorable(client_id, &*self.config.clock))
+        {
+            Some(activity) => activity,
+            None => return,
+        };
+
+        // Nobody waits on the restored activity either
+        let (done, _) = mpsc::channel();
+        self.queue.push(Some(json!(activity)), done);
+        self.has_activity = true;
+        self.arm();
+    }
+
+    // Restarts the activity's time to live, or stops it if there is
+    // no activity. A time to live too long to be represented never
+    // expires
+    fn arm(&mut self) {
+        self.expires_at = match (self.has_activity, self.ttl) {
+            (true, Some(ttl)) => self.now().checked_add(ttl),
+            _ => None,
+        };
+    }
+
+    fn tick(&mut self) {
+        let now = self.now();
+        if self.expires_at.is_some_and(|expires_at| now >= expires_at) {
+            self.expires_at = None;
+            self.has_activity = false;
+            // Nobody waits on the clear, so its completion is dropped
+            let (done, _) = mpsc::channel();
+            self.queue.push(None, done);
+            self.report(ManagerStatus::ActivityExpired);
+        }
+
+        if !self.connected {
+            if !self.queue.wants_connection() || now < self.next_attempt {
+                return;
+            }
+            if let Some(breaker) = &self.circuit_breaker {
+                if let Err(wait) = breaker.try_attempt(now) {
+                    self.next_attempt = later(now, wait);
+                    return;
+                }
+            }
+            self.connect();
+        }
+        if !self.connected {
+            return;
+        }
+
+        if self.queue.is_empty() || now < self.next_update {
+            return self.poll();
+        }
+        match self.rate_limiter.try_acquire(now) {
+            Ok(()) => self.update(),
+            Err(wait) => self.next_update = later(now, wait),
+        }
+    }
+
+    fn connect(&mut self) {
+        match self.client.connect() {
+            Ok(()) => {
+                // Nothing consumes events, so the READY event is dropped
+                self.client.client_state().events.clear();
+                self.connected = true;
+                self.backoff = self.config.min_backoff;
+                if let Some(breaker) = &mut self.circuit_breaker {
+                    breaker.record_success();
+                }
+                self.rate_limiter.reset();
+                self.next_update = self.now();
+                self.queue.restore();
+                self.report(ManagerStatus::Connected);
+            }
+            Err(err) => {
+                let now = self.now();
+                let mut retry_in = self.backoff;
+                if let Some(breaker) = &mut self.circuit_breaker {
+                    breaker.record_failure(now);
+                    if let CircuitState::Open {
+                        retry_in: cool_down,
+                    } = breaker.state(now)
+                    {
+                        retry_in = retry_in.max(cool_down);
+                    }
+                }
+                self.next_attempt = later(now, retry_in);
+                self.backoff = self.backoff.saturating_mul(2).min(self.config.max_backoff);
+                self.report(ManagerStatus::ConnectFailed {
+                    error: into_send_error(err),
+                    retry_in,
+                });
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let activity = self.queue.take().unwrap_or_default();
+        let args = set_activity_args(activity.clone().unwrap_or_default());
+
+        match self.client.command("SET_ACTIVITY", args) {
+            Ok(_) => {
+                if let Some(persistence) = &self.config.persistence {
+                    let saved = activity
+                        .clone()
+                        .and_then(|activity| serde_json::from_value(activity).ok());
+                    persistence.save(self.client.get_client_id(), saved, &*self.config.clock);
+                }
+                self.queue.complete(activity);
+            }
+            Err(err) => match err.downcast_ref::<Error>() {
+                Some(Error::CommandError { code, message }) => {
+                    self.queue.fail(|| Error::CommandError {
+                        code: *code,
+                        message: message.clone(),
+                    });
+                    self.report(ManagerStatus::UpdateFailed(into_send_error(err)));
+                }
+                // The update is sent again once reconnected
+                _ => {
+                    self.queue.retry(activity);
+                    self.disconnect(err);
+                }
+            },
+        }
+    }
+
+    // Reads anything Discord sent while idle, to notice the
+    // connection closing
+    fn poll(&mut self) {
+        loop {
+            let result = self
+                .client
+                .wait_readable(Duration::ZERO)
+                .and_then(|readable| match readable {
+                    true => read_inbound(&mut self.client).map(|_| true),
+                    false => Ok(false),
+                });
+
+            match result {
+                Ok(true) => continue,
+                Ok(false) => return,
+                Err(err) => return self.disconnect(err),
+            }
+        }
+    }
+
+    // A lost connection, including Discord closing it between
+    // frames, is retried at once, with backoff only once that fails
+    fn disconnect(&mut self, err: Box<dyn StdError>) {
+        self.connected = false;
+        self.next_attempt = self.now();
+        self.report(ManagerStatus::Disconnected(into_send_error(err)));
+    }
+
+    fn shutdown(&mut self) -> Completion {
+        self.queue.fail(|| Error::ManagerStopped);
+        if !self.connected {
+            return Ok(());
+        }
+
+        self.connected = false;
+        self.client.close().map_err(into_send_error)
+    }
+
+    fn terminate(&mut self) -> Completion {
+        // There is no time to wait for the rate limiter, and the
+        // saved activity is kept to be restored on the next start
+        if self.connected {
+            let args = set_activity_args(Value::Null);
+            let _ = self.client.command("SET_ACTIVITY", args);
+        }
+
+        self.shutdown()
+    }
+
+    fn now(&self) -> Instant {
+        self.config.clock.now_instant()
+    }
+
+    fn report(&self, status: ManagerStatus) {
+        // The receiver may have been dropped if statuses aren't wanted
+        let _ = self.statuses.send(status);
+    }
+}
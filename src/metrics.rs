@@ -0,0 +1,105 @@
+//! Provides the names of the metrics recorded by clients.
+//!
+//! With the `metrics` feature, clients record the metrics below
+//! through the [`metrics`](https://docs.rs/metrics) facade, to
+//! whichever recorder the application installs. Without it,
+//! nothing is recorded, and recording costs nothing.
+//!
+//! | Name | Kind | Labels |
+//! |------|------|--------|
+//! | [`CONNECT_ATTEMPTS`] | counter | |
+//! | [`CONNECTS`] | counter | |
+//! | [`RECONNECTS`] | counter | |
+//! | [`FRAMES_SENT`] | counter | `opcode` |
+//! | [`FRAMES_RECEIVED`] | counter | `opcode` |
+//! | [`COMMAND_DURATION`] | histogram | `cmd` |
+//! | [`RATE_LIMITED`] | counter | `policy` |
+//!
+//! The names and labels are kept stable between releases, and
+//! are only changed as a breaking change.
+use crate::rate_limit::RateLimitPolicy;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Attempts to connect, counted by
+/// [`DiscordIpc::connect`](crate::DiscordIpc::connect) and
+/// [`DiscordIpc::reconnect`](crate::DiscordIpc::reconnect)
+pub const CONNECT_ATTEMPTS: &str = "discord_ipc_connect_attempts_total";
+
+/// Connections whose handshake Discord accepted
+pub const CONNECTS: &str = "discord_ipc_connects_total";
+
+/// Calls to [`DiscordIpc::reconnect`](crate::DiscordIpc::reconnect)
+pub const RECONNECTS: &str = "discord_ipc_reconnects_total";
+
+/// Frames written to Discord, labelled with their `opcode`
+pub const FRAMES_SENT: &str = "discord_ipc_frames_sent_total";
+
+/// Frames read from Discord, labelled with their `opcode`
+pub const FRAMES_RECEIVED: &str = "discord_ipc_frames_received_total";
+
+/// Seconds from sending a command to reading its response,
+/// labelled with the command's name as `cmd`
+pub const COMMAND_DURATION: &str = "discord_ipc_command_duration_seconds";
+
+/// Activity updates a rate limiter delayed, refused or held back,
+/// labelled with its `policy`: `block`, `error` or `coalesce`
+pub const RATE_LIMITED: &str = "discord_ipc_rate_limited_total";
+
+#[cfg(feature = "metrics")]
+pub(crate) fn increment(name: &'static str) {
+    ::metrics::counter!(name).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn increment(_name: &'static str) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn frame(name: &'static str, opcode: u32) {
+    ::metrics::counter!(name, "opcode" => opcode.to_string()).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn frame(_name: &'static str, _opcode: u32) {}
+
+// Measures how long a command takes, if metrics are recorded
+pub(crate) struct CommandTimer {
+    #[cfg(feature = "metrics")]
+    started: Instant,
+}
+
+impl CommandTimer {
+    #[inline(always)]
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            started: Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn finish(self, cmd: &str) {
+        ::metrics::histogram!(COMMAND_DURATION, "cmd" => cmd.to_string())
+            .record(self.started.elapsed());
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[inline(always)]
+    pub(crate) fn finish(self, _cmd: &str) {}
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn rate_limited(policy: RateLimitPolicy) {
+    let policy = match policy {
+        RateLimitPolicy::Block => "block",
+        RateLimitPolicy::Error => "error",
+        RateLimitPolicy::Coalesce => "coalesce",
+    };
+    ::metrics::counter!(RATE_LIMITED, "policy" => policy).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn rate_limited(_policy: RateLimitPolicy) {}
@@ -0,0 +1,301 @@
+//! Provides the Discord objects returned by the IPC in
+//! command responses and events.
+//!
+//! These models are only ever received from Discord, so all
+//! fields are public, and any fields Discord sends that are not
+//! modeled here are ignored.
+//!
+//! The models are `#[non_exhaustive]`, so that fields Discord adds
+//! can be modeled without a breaking release. Outside this crate
+//! they can't be built with struct literals; to build one, such as
+//! in tests, start from its `Default` and set the fields needed.
+use serde_derive::{Deserialize, Serialize};
+
+/// A struct representing a Discord user
+///
+/// Appears in the [`ConnectionInfo`] of the `READY` event, as
+/// the author of a [`Message`], in a [`VoiceState`], and in
+/// `GET_RELATIONSHIPS` responses (with the `undocumented` feature)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct User {
+    /// The user's ID
+    pub id: String,
+
+    /// The user's username
+    pub username: String,
+
+    /// The user's legacy 4-digit tag, which is `"0"` for users
+    /// that have migrated to unique usernames
+    #[serde(default)]
+    pub discriminator: Option<String>,
+
+    /// The user's display name, if one is set
+    #[serde(default)]
+    pub global_name: Option<String>,
+
+    /// The user's avatar hash, if they have an avatar
+    #[serde(default)]
+    pub avatar: Option<String>,
+
+    /// Whether the user is a bot account
+    #[serde(default)]
+    pub bot: bool,
+}
+
+/// A struct representing the information Discord sends when
+/// a handshake is accepted
+///
+/// Carried by the `READY` event
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// The version of the RPC protocol in use
+    pub v: u32,
+
+    /// The configuration of the RPC server
+    #[serde(default)]
+    pub config: RpcServerConfig,
+
+    /// The user logged into the Discord client, if any
+    #[serde(default)]
+    pub user: Option<User>,
+}
+
+/// A struct representing the configuration of the RPC server
+///
+/// Part of a [`ConnectionInfo`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RpcServerConfig {
+    /// The CDN host used by the client
+    #[serde(default)]
+    pub cdn_host: Option<String>,
+
+    /// The API endpoint used by the client
+    #[serde(default)]
+    pub api_endpoint: Option<String>,
+
+    /// The environment the client is running in
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+impl User {
+    /// Returns the URL of the user's avatar, at the given size
+    /// in pixels (a power of 2 between 16 and 4096)
+    ///
+    /// Users without an avatar get the URL of the default avatar
+    /// Discord assigns them
+    pub fn avatar_url(&self, size: u16) -> String {
+        match &self.avatar {
+            Some(hash) => {
+                let extension = if hash.starts_with("a_") { "gif" } else { "png" };
+                format!(
+                    "https://cdn.discordapp.com/avatars/{}/{}.{}?size={}",
+                    self.id, hash, extension, size
+                )
+            }
+            None => {
+                // Migrated users are assigned a default avatar based on
+                // their ID, and legacy users based on their discriminator
+                let index = match self.discriminator.as_deref() {
+                    Some(discriminator) if discriminator != "0" => {
+                        discriminator.parse::<u64>().unwrap_or_default() % 5
+                    }
+                    _ => (self.id.parse::<u64>().unwrap_or_default() >> 22) % 6,
+                };
+                format!("https://cdn.discordapp.com/embed/avatars/{}.png", index)
+            }
+        }
+    }
+}
+
+/// A struct representing a guild, as sent over the IPC
+///
+/// Appears in `GET_GUILD` and `GET_GUILDS` responses, and in
+/// `GUILD_STATUS` and `GUILD_CREATE` events
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PartialGuild {
+    /// The guild's ID
+    pub id: String,
+
+    /// The guild's name
+    pub name: String,
+
+    /// The URL of the guild's icon, if it has one
+    #[serde(default)]
+    pub icon_url: Option<String>,
+}
+
+/// A struct representing a channel
+///
+/// Appears in `GET_CHANNEL` responses, and (without the
+/// voice states and messages) in `GET_CHANNELS` responses,
+/// `SELECT_VOICE_CHANNEL` responses, and `CHANNEL_CREATE` events
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Channel {
+    /// The channel's ID
+    pub id: String,
+
+    /// The ID of the guild the channel belongs to, if any
+    #[serde(default)]
+    pub guild_id: Option<String>,
+
+    /// The channel's name
+    pub name: String,
+
+    /// The channel's type (e.g. 0 for a text channel,
+    /// and 2 for a voice channel)
+    #[serde(rename = "type")]
+    pub kind: u64,
+
+    /// The channel's topic
+    #[serde(default)]
+    pub topic: Option<String>,
+
+    /// The bitrate of a voice channel
+    #[serde(default)]
+    pub bitrate: Option<u64>,
+
+    /// The user limit of a voice channel, where 0 means no limit
+    #[serde(default)]
+    pub user_limit: Option<u64>,
+
+    /// The channel's sorting position
+    #[serde(default)]
+    pub position: Option<i64>,
+
+    /// The voice states of the users in a voice channel
+    #[serde(default)]
+    pub voice_states: Vec<VoiceState>,
+
+    /// The most recent messages in a text channel
+    #[serde(default)]
+    pub messages: Vec<Message>,
+}
+
+/// A struct representing a message in a text channel
+///
+/// Appears in `GET_CHANNEL` responses, and in `MESSAGE_CREATE`,
+/// `MESSAGE_UPDATE`, and `MESSAGE_DELETE` events (where only
+/// the ID is guaranteed to be present)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Message {
+    /// The message's ID
+    pub id: String,
+
+    /// The message's author
+    #[serde(default)]
+    pub author: Option<User>,
+
+    /// The author's nickname in the guild, if they have one
+    #[serde(default)]
+    pub nick: Option<String>,
+
+    /// The content of the message
+    #[serde(default)]
+    pub content: Option<String>,
+
+    /// The time at which the message was sent, as an ISO 8601
+    /// timestamp
+    #[serde(default)]
+    pub timestamp: Option<String>,
+
+    /// The time at which the message was last edited, as an ISO
+    /// 8601 timestamp
+    #[serde(default)]
+    pub edited_timestamp: Option<String>,
+
+    /// Whether the message was sent with text-to-speech
+    #[serde(default)]
+    pub tts: bool,
+
+    /// Whether the message mentions everyone
+    #[serde(default)]
+    pub mention_everyone: bool,
+
+    /// Whether the message is pinned
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// Whether the author is blocked by the user
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// A struct representing a user's state in a voice channel
+///
+/// Appears in `GET_CHANNEL` and `SELECT_VOICE_CHANNEL`
+/// responses, and in `VOICE_STATE_CREATE`, `VOICE_STATE_UPDATE`,
+/// and `VOICE_STATE_DELETE` events
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct VoiceState {
+    /// The server-side voice state of the user
+    pub voice_state: VoiceStateFlags,
+
+    /// The user
+    pub user: User,
+
+    /// The user's nickname in the guild, if they have one
+    #[serde(default)]
+    pub nick: Option<String>,
+
+    /// The volume the current user hears the user at
+    #[serde(default)]
+    pub volume: Option<f32>,
+
+    /// Whether the current user has locally muted the user
+    #[serde(default)]
+    pub mute: bool,
+
+    /// The left and right pan the current user hears the user at
+    #[serde(default)]
+    pub pan: Option<Pan>,
+}
+
+/// A struct representing the mute and deafen state of a user
+/// in a voice channel
+///
+/// Part of a [`VoiceState`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VoiceStateFlags {
+    /// Whether the user is muted by the guild
+    #[serde(default)]
+    pub mute: bool,
+
+    /// Whether the user is deafened by the guild
+    #[serde(default)]
+    pub deaf: bool,
+
+    /// Whether the user has muted themselves
+    #[serde(default)]
+    pub self_mute: bool,
+
+    /// Whether the user has deafened themselves
+    #[serde(default)]
+    pub self_deaf: bool,
+
+    /// Whether the user is suppressed (e.g. in a stage channel)
+    #[serde(default)]
+    pub suppress: bool,
+}
+
+/// A struct representing the stereo pan of a user in a voice
+/// channel, with each side between 0.0 and 1.0
+///
+/// Part of a [`VoiceState`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Pan {
+    /// The volume of the left side
+    pub left: f32,
+
+    /// The volume of the right side
+    pub right: f32,
+}
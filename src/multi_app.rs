@@ -0,0 +1,235 @@
+//! Provides [`MultiAppPresence`], which shows the activity of
+//! whichever of several applications is active, over a single
+//! connection.
+use crate::{
+    activity::Activity,
+    clock::Clock,
+    debounce::Debouncer,
+    transport::{IpcTransport, NativeTransport},
+    DiscordIpc, DiscordIpcClient, IpcConnection,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A rich presence shared between several applications, each with
+/// its own application ID, of which one is shown at a time
+///
+/// The activity of every application is remembered, and switching
+/// to an application re-handshakes with its ID (see
+/// [`DiscordIpcClient::set_client_id`]) and sets its activity.
+/// Before switching, the previous application's activity is
+/// cleared, so two presences are never shown at once.
+///
+/// Switches requested with [`focus`](#method.focus) are debounced:
+/// one only happens once no other was requested for a quiet
+/// period, so focus flapping between applications doesn't
+/// reconnect each time. [`poll`](#method.poll) carries out a switch
+/// which is due.
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{activity::Activity, multi_app::MultiAppPresence, DiscordIpcClient};
+/// # use std::time::Duration;
+/// let mut presence =
+///     MultiAppPresence::new(DiscordIpcClient::new("<some client id>")?, Duration::from_secs(2));
+/// presence.set_activity("<some client id>", Activity::new().state("In the menu"))?;
+/// presence.set_activity("<another client id>", Activity::new().state("In a match"))?;
+///
+/// presence.focus(Some("<another client id>"));
+/// loop {
+///     presence.poll()?;
+///     // ...
+/// #   break;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct MultiAppPresence<T = NativeTransport> {
+    client: DiscordIpcClient<T>,
+    activities: HashMap<String, Activity<'static>>,
+    // The application shown, once switched to
+    active: Option<String>,
+    switches: Debouncer<Option<String>>,
+    // The clock set with `clock`, if any
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl<T: IpcTransport> MultiAppPresence<T> {
+    /// Creates a new `MultiAppPresence` using the given client,
+    /// which does not need to be connected, and switching once
+    /// focus has stayed on an application for `quiet_period`.
+    ///
+    /// No application is active until one is switched to.
+    pub fn new(client: DiscordIpcClient<T>, quiet_period: Duration) -> Self {
+        Self {
+            client,
+            activities: HashMap::new(),
+            active: None,
+            switches: Debouncer::new(quiet_period),
+            clock: None,
+        }
+    }
+
+    /// Sets the clock the quiet period is measured by, in place of
+    /// the client's
+    ///
+    /// Unless set, the clock of the client given to
+    /// [`new`](#method.new) is used (see [`DiscordIpc::set_clock`]).
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The ID of the application shown, if any
+    pub fn active_app(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// When a requested switch is due, if one is waiting
+    pub fn deadline(&self) -> Option<Instant> {
+        self.switches.deadline()
+    }
+
+    /// Returns a reference to the client.
+    pub fn client(&self) -> &DiscordIpcClient<T> {
+        &self.client
+    }
+
+    /// Remembers the activity of an application, setting it at once
+    /// if the application is active.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the application is active, and
+    /// setting the activity failed. It is remembered either way.
+    pub fn set_activity(&mut self, client_id: &str, activity: Activity) -> Result<()> {
+        let activity: Activity<'static> = serde_json::from_value(serde_json::to_value(activity)?)?;
+        self.activities
+            .insert(client_id.to_string(), activity.clone());
+
+        if self.is_active(client_id) {
+            self.client.set_activity(activity)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the activity of an application, clearing it at once
+    /// if the application is active.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the application is active, and
+    /// clearing the activity failed.
+    pub fn clear_activity(&mut self, client_id: &str) -> Result<()> {
+        self.activities.remove(client_id);
+
+        if self.is_active(client_id) {
+            self.client.clear_activity()?;
+        }
+        Ok(())
+    }
+
+    /// Asks to switch to the application with the given ID, or to
+    /// none with `None`, once no other switch is asked for within
+    /// the quiet period.
+    ///
+    /// Asking to switch back to the active application before the
+    /// switch happens cancels it.
+    pub fn focus(&mut self, client_id: Option<&str>) {
+        let now = self.now();
+        self.switches.update(client_id.map(str::to_string), now);
+    }
+
+    /// Carries out a requested switch if it is due, returning
+    /// whether the active application changed.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if switching failed, in which case
+    /// no application is active, and the switch is retried once
+    /// the quiet period passes again.
+    pub fn poll(&mut self) -> Result<bool> {
+        let now = self.now();
+        match self.switches.poll(now) {
+            Some(target) if target != self.active => {
+                if let Err(err) = self.switch(target.as_deref()) {
+                    self.switches.update(target, now);
+                    return Err(err);
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Switches to the application with the given ID, or to none
+    /// with `None`, at once, dropping any requested switch.
+    ///
+    /// The active application's activity is cleared, then the
+    /// client handshakes with the new ID, connecting if needed,
+    /// and the new application's activity is set, if it has one.
+    /// Switching to none closes the connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if clearing, reconnecting or
+    /// setting the activity failed. No application is active
+    /// afterwards.
+    pub fn switch(&mut self, client_id: Option<&str>) -> Result<()> {
+        self.switches.take();
+        if self.active.as_deref() == client_id {
+            return Ok(());
+        }
+
+        // Whatever happens next, the previous application is no
+        // longer shown
+        if self.active.take().is_some() {
+            self.client.clear_activity()?;
+        }
+
+        let client_id = match client_id {
+            Some(client_id) => client_id,
+            None => return self.client.close(),
+        };
+        self.client.set_client_id(client_id)?;
+        if !self.client.client_state().handshaken {
+            self.client.connect()?;
+        }
+        if let Some(activity) = self.activities.get(client_id) {
+            self.client.set_activity(activity.clone())?;
+        }
+
+        self.active = Some(client_id.to_string());
+        Ok(())
+    }
+
+    /// Returns the client, closing nothing.
+    pub fn into_client(self) -> DiscordIpcClient<T> {
+        self.client
+    }
+
+    fn is_active(&self, client_id: &str) -> bool {
+        self.active.as_deref() == Some(client_id)
+    }
+
+    // The current time, by the clock set or the client's
+    fn now(&mut self) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now_instant(),
+            None => self.client.client_state().clock().now_instant(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MultiAppPresence<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiAppPresence")
+            .field("client", &self.client)
+            .field("activities", &self.activities)
+            .field("active", &self.active)
+            .field("switches", &self.switches)
+            .finish()
+    }
+}
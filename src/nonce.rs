@@ -0,0 +1,36 @@
+// Generates the nonces matching commands to their responses.
+//
+// A nonce only has to be unique among the commands awaiting a
+// response on one connection, but nonces are unique across
+// processes either way: with the `uuid` feature they are random
+// v4 UUIDs, and without it they combine the process ID, the time
+// the process first made one, and a counter.
+
+#[cfg(feature = "uuid")]
+pub(crate) fn generate() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(not(feature = "uuid"))]
+pub(crate) fn generate() -> String {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            OnceLock,
+        },
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    static STARTED: OnceLock<u128> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let started = STARTED.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or(0)
+    });
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{:08x}-{:x}-{:x}", std::process::id(), started, count)
+}
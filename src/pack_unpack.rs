@@ -13,6 +13,9 @@ pub fn pack(opcode: u32, data_len: u32) -> Result<Vec<u8>, Box<dyn Error>> {
 }
 
 pub fn unpack(data: Vec<u8>) -> Result<(u32, u32), Box<dyn Error>> {
+    if data.len() != 2 * std::mem::size_of::<u32>() {
+        return Err(format!("Frame header must be 8 bytes, got {}", data.len()).into());
+    }
     let data = data.as_slice();
     let (opcode, header) = data.split_at(std::mem::size_of::<u32>());
 
@@ -0,0 +1,135 @@
+//! Provides [`PartySession`], which keeps the party and join secret
+//! of a joinable game consistent across activity updates.
+use crate::{
+    activity::{Activity, Party, Secrets},
+    event::{Event, EventKind},
+    nonce,
+};
+
+/// A struct representing a joinable party, from the moment it is
+/// opened until it is closed
+///
+/// The session generates the party's ID and join secret, and
+/// attaches them to each activity set while it lasts with
+/// [`apply`](#method.apply). Whoever accepts an invite sends the
+/// secret back in an `ACTIVITY_JOIN` event, which can be checked
+/// against the session with [`accepts`](#method.accepts).
+///
+/// With the `uuid` feature, IDs and secrets are random. Without
+/// it they are only unique, and should not be relied on to keep
+/// anyone from joining.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::{activity::Activity, party::PartySession};
+/// let mut session = PartySession::new();
+/// session.set_size(1, 4);
+///
+/// let activity = session.apply(Activity::new().state("In a lobby"));
+/// # let _ = activity;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartySession {
+    id: String,
+    secret: Option<String>,
+    size: Option<[i32; 2]>,
+}
+
+impl PartySession {
+    /// Opens a new session, with a new party ID and join secret
+    pub fn new() -> Self {
+        Self {
+            id: nonce::generate(),
+            secret: Some(nonce::generate()),
+            size: None,
+        }
+    }
+
+    /// The ID of the party
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The current join secret, or `None` once the session is
+    /// closed
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref()
+    }
+
+    /// Whether the session is still open to joining
+    pub fn is_open(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// Sets the size of the party (current and maximum)
+    pub fn set_size(&mut self, current: i32, max: i32) {
+        self.size = Some([current, max]);
+    }
+
+    /// Replaces the join secret with a new one, so that invites
+    /// sent with the previous secret are no longer accepted.
+    /// Does nothing once the session is closed.
+    pub fn rotate_secret(&mut self) {
+        if self.is_open() {
+            self.secret = Some(nonce::generate());
+        }
+    }
+
+    /// Closes the session to joining: activities no longer carry
+    /// a join secret, and no secret is accepted. The party itself
+    /// is kept.
+    pub fn close(&mut self) {
+        self.secret = None;
+    }
+
+    /// Returns the `Party` to attach to an activity
+    pub fn party(&self) -> Party<'_> {
+        let party = Party::new().id(&self.id);
+        match self.size {
+            Some(size) => party.size(size),
+            None => party,
+        }
+    }
+
+    /// Returns the `Secrets` to attach to an activity, or `None`
+    /// once the session is closed
+    pub fn secrets(&self) -> Option<Secrets<'_>> {
+        self.secret().map(|secret| Secrets::new().join(secret))
+    }
+
+    /// Attaches the party, and the join secret while the session
+    /// is open, to an activity
+    pub fn apply<'a>(&'a self, activity: Activity<'a>) -> Activity<'a> {
+        let activity = activity.party(self.party());
+        match self.secrets() {
+            Some(secrets) => activity.secrets(secrets),
+            None => activity,
+        }
+    }
+
+    /// Whether the session is open, and the secret is its current
+    /// join secret
+    pub fn is_valid_secret(&self, secret: &str) -> bool {
+        self.secret() == Some(secret)
+    }
+
+    /// Whether the event is an `ACTIVITY_JOIN` carrying the
+    /// current join secret
+    pub fn accepts(&self, event: &Event) -> bool {
+        match event {
+            Event::Dispatch {
+                kind: EventKind::ActivityJoin,
+                data,
+            } => data["secret"]
+                .as_str()
+                .is_some_and(|secret| self.is_valid_secret(secret)),
+            _ => false,
+        }
+    }
+}
+
+impl Default for PartySession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,244 @@
+//! Provides persistence of the activity across restarts, so that a
+//! program which is restarted can show its activity again at once.
+//!
+//! A client or manager given a [`Persistence`] saves the activity
+//! to its [`PresenceStore`] whenever Discord accepts an update.
+//! With [`restore_on_connect`](Persistence::restore_on_connect),
+//! the saved activity is set again once connected, unless it was
+//! cleared, set with another client ID, or is older than the given
+//! age.
+//!
+//! # Examples
+//! ```no_run
+//! # use discord_rich_presence::{persist::{FileStore, Persistence}, DiscordIpc, DiscordIpcClient};
+//! # use std::{sync::Arc, time::Duration};
+//! let mut client = DiscordIpcClient::new("<some client id>")?;
+//! client.persist_to(Some(
+//!     Persistence::new(Arc::new(FileStore::new("/var/lib/my-app/presence.json")))
+//!         .restore_on_connect(Duration::from_secs(6 * 60 * 60)),
+//! ));
+//!
+//! // Sets the activity saved before the last restart, if any
+//! client.connect()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::{activity::Activity, clock::Clock, DiscordIpc};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A struct representing the activity last accepted by Discord, as
+/// saved to a [`PresenceStore`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct SavedPresence {
+    /// The client ID the activity was set with. It is only
+    /// restored by a client with the same ID
+    #[serde(default)]
+    pub client_id: String,
+
+    /// The activity, or `None` if it was cleared
+    pub activity: Option<Activity<'static>>,
+
+    /// When the activity was set, as milliseconds since the Unix
+    /// epoch
+    pub saved_at: u64,
+}
+
+impl SavedPresence {
+    /// Creates a new `SavedPresence`, as a store implementation
+    /// might when loading
+    pub fn new(client_id: &str, activity: Option<Activity<'static>>, saved_at: u64) -> Self {
+        Self {
+            client_id: client_id.to_owned(),
+            activity,
+            saved_at,
+        }
+    }
+
+    /// How long ago the activity was set, by the given clock
+    pub fn age(&self, clock: &dyn Clock) -> Duration {
+        Duration::from_millis(clock.now_unix_ms().saturating_sub(self.saved_at))
+    }
+}
+
+/// Somewhere the activity can be saved, and loaded from after a
+/// restart
+pub trait PresenceStore: fmt::Debug + Send + Sync {
+    /// Loads the saved activity, or `None` if nothing was saved
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the saved activity couldn't be
+    /// read.
+    fn load(&self) -> Result<Option<SavedPresence>>;
+
+    /// Saves the activity, replacing whatever was saved before
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the activity couldn't be saved.
+    fn save(&self, presence: &SavedPresence) -> Result<()>;
+}
+
+/// A [`PresenceStore`] keeping the activity in a JSON file
+///
+/// The file is replaced as a whole on every save, so it is never
+/// left half written.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Creates a new `FileStore`, saving to the file at `path`
+    ///
+    /// The file's directory must exist; the file is created on
+    /// the first save.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The file the activity is saved to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl PresenceStore for FileStore {
+    fn load(&self) -> Result<Option<SavedPresence>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, presence: &SavedPresence) -> Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        // The temporary file is removed if it can't take the place
+        // of the saved activity, rather than left behind
+        let result = fs::write(&temporary, serde_json::to_vec(presence)?)
+            .and_then(|()| fs::rename(&temporary, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        Ok(result?)
+    }
+}
+
+/// A struct representing how the activity is persisted, given to
+/// [`DiscordIpc::persist_to`](crate::DiscordIpc::persist_to) or
+/// [`ManagerConfig::persist_to`](crate::manager::ManagerConfig::persist_to)
+#[derive(Clone, Debug)]
+pub struct Persistence {
+    store: Arc<dyn PresenceStore>,
+    restore_max_age: Option<Duration>,
+    // The clock set with `clock`, if any
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl Persistence {
+    /// Creates a new `Persistence`, saving to the given store, and
+    /// not restoring the saved activity
+    pub fn new(store: Arc<dyn PresenceStore>) -> Self {
+        Persistence {
+            store,
+            restore_max_age: None,
+            clock: None,
+        }
+    }
+
+    /// Sets the saved activity again once connected, if it was set
+    /// at most `max_age` ago
+    pub fn restore_on_connect(mut self, max_age: Duration) -> Self {
+        self.restore_max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the clock the saved activity's age is measured by, in
+    /// place of the client's or manager's
+    ///
+    /// Unless set, the clock of the client or manager the
+    /// persistence is given to is used (see
+    /// [`DiscordIpc::set_clock`] and
+    /// [`ManagerConfig::clock`](crate::manager::ManagerConfig::clock)).
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The store the activity is saved to
+    pub fn store(&self) -> &Arc<dyn PresenceStore> {
+        &self.store
+    }
+
+    // Saves the activity Discord accepted for `client_id`, at the
+    // time of the persistence's clock, or `owner_clock` if it has
+    // none. Saving is best effort, as failing to save shouldn't
+    // fail the update itself, so failures are only logged
+    pub(crate) fn save(
+        &self,
+        client_id: &str,
+        activity: Option<Activity<'static>>,
+        owner_clock: &dyn Clock,
+    ) {
+        let now = self.clock.as_deref().unwrap_or(owner_clock).now_unix_ms();
+        let saved = SavedPresence::new(client_id, activity, now);
+        if let Err(err) = self.store.save(&saved) {
+            log::warn!("couldn't save the activity: {}", err);
+        }
+    }
+
+    // The saved activity to restore for `client_id`, if restoring
+    // is enabled and it is recent enough by the persistence's
+    // clock, or `owner_clock` if it has none
+    pub(crate) fn restorable(
+        &self,
+        client_id: &str,
+        owner_clock: &dyn Clock,
+    ) -> Option<Activity<'static>> {
+        let max_age = self.restore_max_age?;
+        let saved = match self.store.load() {
+            Ok(saved) => saved?,
+            Err(err) => {
+                log::warn!("couldn't load the saved activity: {}", err);
+                return None;
+            }
+        };
+
+        if saved.client_id != client_id {
+            return None;
+        }
+        if saved.age(self.clock.as_deref().unwrap_or(owner_clock)) > max_age {
+            return None;
+        }
+        saved.activity
+    }
+}
+
+// Sets the saved activity again after connecting, if there is one
+// to restore. Restoring is best effort, as the connection itself
+// succeeded, so failures are only logged
+pub(crate) fn restore<T: DiscordIpc + ?Sized>(client: &mut T) {
+    let client_id = client.get_client_id().clone();
+    let state = client.client_state();
+    let activity = match &state.persistence {
+        Some(persistence) => persistence.restorable(&client_id, &*state.clock()),
+        None => None,
+    };
+
+    if let Some(activity) = activity {
+        if let Err(err) = client.set_activity(activity) {
+            log::warn!("couldn't restore the saved activity: {}", err);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Provides the process ID sent to Discord with activities and
+//! other commands naming this process.
+//!
+//! Discord looks the process up by that ID. Inside a sandbox with
+//! its own PID namespace, such as Flatpak's, where the process is
+//! often PID 2, [`std::process::id`] is the ID within the sandbox,
+//! which means nothing to Discord outside it. On Linux, when
+//! `/proc/self/status` lists the ID the process has in an outer
+//! namespace, that ID is sent instead; otherwise the ID within the
+//! sandbox is sent. [`set_pid`] overrides either.
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        OnceLock,
+    },
+};
+
+// The ID set with `set_pid`, or 0 if none is; no process has ID 0
+static OVERRIDE: AtomicU32 = AtomicU32::new(0);
+static DETECTED: OnceLock<u32> = OnceLock::new();
+
+/// The process ID sent to Discord
+///
+/// This is the ID set with [`set_pid`] if there is one, and
+/// otherwise the ID detected as described in the
+/// [module documentation](self).
+pub fn pid() -> u32 {
+    match OVERRIDE.load(Ordering::Relaxed) {
+        0 => *DETECTED.get_or_init(detect),
+        pid => pid,
+    }
+}
+
+/// Sets the process ID sent to Discord by every client in the
+/// process, or with `None`, goes back to detecting it
+///
+/// This is for when the application knows better, for example a
+/// launcher reporting the game it started.
+pub fn set_pid(pid: Option<u32>) {
+    OVERRIDE.store(pid.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Reads the ID of a process in the outermost PID namespace
+/// visible, from the contents of its `/proc/<pid>/status`
+///
+/// The `NSpid` line lists the process' ID in each namespace it is
+/// in, outermost first. Returns `None` if there is no such line,
+/// as before Linux 4.1.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::pid::outermost_pid;
+/// let status = "Name:\tgame\nPid:\t2\nNSpid:\t48213\t2\n";
+/// assert_eq!(outermost_pid(status), Some(48213));
+/// ```
+pub fn outermost_pid(status: &str) -> Option<u32> {
+    let ids = status
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))?;
+    ids.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn detect() -> u32 {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| outermost_pid(&status))
+        .unwrap_or_else(process::id)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect() -> u32 {
+    process::id()
+}
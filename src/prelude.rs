@@ -0,0 +1,33 @@
+//! Re-exports the items most programs need, so that they can be
+//! imported at once.
+//!
+//! This includes the [`DiscordIpc`] trait, which must be in scope
+//! to call any of the client's methods, such as `connect`.
+//!
+//! # Examples
+//! ```no_run
+//! use discord_rich_presence::prelude::*;
+//!
+//! let mut client = DiscordIpcClient::new("<some client id>")?;
+//! client.connect()?;
+//!
+//! client.set_activity(
+//!     Activity::new()
+//!         .state("Hello world!")
+//!         .timestamps(Timestamps::new().start(1_700_000_000))
+//!         .assets(Assets::new().large_image("logo")),
+//! )?;
+//! if let Ok(Event::Ready(ready)) = client.recv_event() {
+//!     println!("Ready: {:?}", ready);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+pub use crate::{
+    activity::{Activity, ActivityType, Assets, Button, Party, Secrets, Timestamps},
+    error::Error,
+    event::{Event, EventKind, Subscription},
+    join_request::{JoinDecision, JoinRequest},
+    models::{Channel, Message, PartialGuild, User, VoiceState},
+    rate_limit::{RateLimitPolicy, RateLimiter},
+    DiscordIpc, DiscordIpcClient,
+};
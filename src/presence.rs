@@ -0,0 +1,123 @@
+//! Provides [`RichPresence`], the simplest way to keep an
+//! application's activity shown reliably.
+use crate::{
+    activity::Activity,
+    manager::{DiscordIpcManager, ManagerConfig},
+    DiscordIpc, DiscordIpcClient,
+};
+use std::{error::Error, time::Duration};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+// How long shutting down waits for the activity to be cleared
+const CLEAR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A rich presence kept up to date from a background thread,
+/// whatever happens to Discord.
+///
+/// The connection is made once an activity is first set. When it
+/// is lost, for example because Discord restarted, it is
+/// re-established with backoff and the activity is set again.
+/// Updates follow Discord's rate limit, and only the latest of a
+/// burst is sent. None of this needs anything from the caller:
+/// setting and clearing return immediately, and never fail.
+///
+/// This is a [`DiscordIpcManager`] with the details hidden; use
+/// one directly to learn of failures as they happen.
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{activity::Activity, presence::RichPresence};
+/// let presence = RichPresence::new("<some client id>")?;
+/// presence.set(Activity::new().state("Hello world!"));
+///
+/// // ...
+///
+/// presence.shutdown()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct RichPresence {
+    manager: DiscordIpcManager,
+}
+
+impl RichPresence {
+    /// Creates a new `RichPresence` for the application with the
+    /// given ID, configured with the default [`ManagerConfig`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the background thread could
+    /// not be spawned.
+    pub fn new(client_id: &str) -> Result<Self> {
+        Self::with_config(client_id, ManagerConfig::new())
+    }
+
+    /// Creates a new `RichPresence` for the application with the
+    /// given ID, with the given reconnection, rate limiting and
+    /// restoring behaviour.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the background thread could
+    /// not be spawned.
+    pub fn with_config(client_id: &str, config: ManagerConfig) -> Result<Self> {
+        Self::with_client(DiscordIpcClient::new(client_id)?, config)
+    }
+
+    /// Creates a new `RichPresence` keeping the activity of the
+    /// given client, which does not need to be connected.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the background thread could
+    /// not be spawned.
+    pub fn with_client<T>(client: T, config: ManagerConfig) -> Result<Self>
+    where
+        T: DiscordIpc + Send + 'static,
+    {
+        let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+        Ok(Self { manager })
+    }
+
+    /// Shows the activity, replacing any shown before.
+    pub fn set(&self, activity: Activity) {
+        self.manager.update_activity(activity);
+    }
+
+    /// Stops showing an activity.
+    pub fn clear(&self) {
+        self.manager.clear();
+    }
+
+    /// Clears the activity and exits the process when it is
+    /// interrupted or terminated, such as with Ctrl-C.
+    ///
+    /// See [`DiscordIpcManager::clear_on_termination`] for how,
+    /// and its limits.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the handler couldn't be
+    /// installed, for example because the process already has one.
+    #[cfg(feature = "ctrlc")]
+    pub fn clear_on_termination(&self) -> Result<()> {
+        self.manager.clear_on_termination()
+    }
+
+    /// Clears the activity, closes the connection, and stops the
+    /// background thread.
+    ///
+    /// Clearing waits up to a second, so that shutting down isn't
+    /// held up while Discord can't be reached. Dropping the
+    /// `RichPresence` also closes the connection, without clearing
+    /// the activity first.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if closing the connection failed.
+    pub fn shutdown(self) -> Result<()> {
+        let _ = self.manager.clear().recv_timeout(CLEAR_TIMEOUT);
+
+        match self.manager.shutdown().recv() {
+            Ok(result) => result.map_err(|err| err as Box<dyn Error>),
+            // The thread has stopped, so there is nothing to close
+            Err(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Provides a background thread which owns a client's connection,
+//! created with [`DiscordIpc::spawn_event_pump`](crate::DiscordIpc::spawn_event_pump).
+use crate::{
+    activity::Activity,
+    discord_ipc::{
+        collect, command_payload, deliver, send_activity, send_activity_no_wait, set_activity_args,
+        Awaiting, DiscordIpc, Nonce,
+    },
+    error::Error,
+    event::{Event, EventKind, Subscription},
+    join_request,
+    router::{read_inbound, Inbound},
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error as StdError,
+    sync::mpsc::{self, Receiver, RecvError, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn StdError>>;
+pub(crate) type Reply = SendResult<Value>;
+type SendResult<T> = std::result::Result<T, Box<dyn StdError + Send + Sync>>;
+
+// How long the thread waits for incoming data before checking
+// for requests again
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+enum Request {
+    Command {
+        cmd: String,
+        args: Value,
+        evt: Option<EventKind>,
+        reply: Sender<Reply>,
+    },
+    SetActivity {
+        args: Value,
+        wait: bool,
+        reply: Sender<Reply>,
+    },
+    ClearActivity {
+        reply: Sender<Reply>,
+    },
+    Collect {
+        nonce: Nonce,
+        reply: Sender<SendResult<Option<Value>>>,
+    },
+    Close {
+        reply: Sender<Reply>,
+    },
+}
+
+/// A handle to a client running on a background thread, created
+/// with [`DiscordIpc::spawn_event_pump`].
+///
+/// Every method sends a request to the thread, and blocks until
+/// the thread has carried it out. The handle can be shared
+/// between threads, and requests from several threads are
+/// carried out in the order they are received.
+///
+/// Dropping the handle closes the connection, and waits for
+/// the thread to stop.
+#[derive(Debug)]
+pub struct EventPump {
+    requests: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventPump {
+    /// Sends an RPC command, and waits for its response.
+    ///
+    /// See [`DiscordIpc::command`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command or reading
+    /// the response failed, if Discord responded with an error, or
+    /// an [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn command(&self, cmd: &str, args: Value) -> Result<Value> {
+        self.request(|reply| Request::Command {
+            cmd: cmd.to_string(),
+            args,
+            evt: None,
+            reply,
+        })
+    }
+
+    /// Subscribes to an event.
+    ///
+    /// See [`DiscordIpc::subscribe`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the subscription is invalid,
+    /// if sending the command failed, or an
+    /// [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn subscribe(&self, subscription: &Subscription) -> Result<()> {
+        self.subscription_command("SUBSCRIBE", subscription)
+    }
+
+    /// Unsubscribes from an event.
+    ///
+    /// See [`DiscordIpc::unsubscribe`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the subscription is invalid,
+    /// if sending the command failed, or an
+    /// [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn unsubscribe(&self, subscription: &Subscription) -> Result<()> {
+        self.subscription_command("UNSUBSCRIBE", subscription)
+    }
+
+    /// Sets a Discord activity, and waits for Discord to accept it.
+    ///
+    /// See [`DiscordIpc::set_activity`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed, if
+    /// Discord rejected the activity, or an
+    /// [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn set_activity(&self, activity_payload: Activity) -> Result<()> {
+        self.request(|reply| Request::SetActivity {
+            args: set_activity_args(json!(activity_payload)),
+            wait: true,
+            reply,
+        })?;
+
+        Ok(())
+    }
+
+    /// Sets a Discord activity without waiting for Discord's
+    /// response.
+    ///
+    /// See [`DiscordIpc::set_activity_no_wait`]. This still waits
+    /// for the thread to write the payload.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed, or
+    /// an [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn set_activity_no_wait(&self, activity_payload: Activity) -> Result<()> {
+        self.request(|reply| Request::SetActivity {
+            args: set_activity_args(json!(activity_payload)),
+            wait: false,
+            reply,
+        })?;
+
+        Ok(())
+    }
+
+    /// Clears the Discord activity.
+    ///
+    /// See [`DiscordIpc::clear_activity`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload or reading
+    /// the response failed, or an [`Error::EventPumpStopped`] if
+    /// the thread stopped first.
+    pub fn clear_activity(&self) -> Result<()> {
+        self.request(|reply| Request::ClearActivity { reply })?;
+
+        Ok(())
+    }
+
+    /// Collects the response to a command sent with
+    /// [`DiscordIpc::send_command`] before the pump was spawned, or
+    /// returns `None` if it hasn't arrived.
+    ///
+    /// See [`DiscordIpc::poll_response`]. The thread reads every
+    /// frame as it arrives, so this doesn't wait.
+    ///
+    /// # Errors
+    /// Returns any error [`DiscordIpc::poll_response`] returns, or
+    /// an [`Error::EventPumpStopped`] if the thread stopped first.
+    pub fn poll_response(&self, nonce: &Nonce) -> Result<Option<Value>> {
+        self.request(|reply| Request::Collect {
+            nonce: nonce.clone(),
+            reply,
+        })
+    }
+
+    /// Closes the connection, and waits for the thread to stop.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if closing the connection failed,
+    /// or an [`Error::EventPumpStopped`] if the thread had already
+    /// stopped.
+    pub fn close(self) -> Result<()> {
+        self.request(|reply| Request::Close { reply })?;
+
+        Ok(())
+    }
+
+    fn subscription_command(&self, cmd: &str, subscription: &Subscription) -> Result<()> {
+        subscription.validate()?;
+        self.request(|reply| Request::Command {
+            cmd: cmd.to_string(),
+            args: subscription.args(),
+            evt: Some(subscription.kind()),
+            reply,
+        })?;
+
+        Ok(())
+    }
+
+    fn request<R>(&self, request: impl FnOnce(Sender<SendResult<R>>) -> Request) -> Result<R> {
+        let (reply, response) = mpsc::channel();
+        let requests = self.requests.as_ref().ok_or(Error::EventPumpStopped)?;
+        requests
+            .send(request(reply))
+            .map_err(|_| Error::EventPumpStopped)?;
+
+        match response.recv() {
+            Ok(result) => result.map_err(|err| err as Box<dyn StdError>),
+            Err(RecvError) => Err(Error::EventPumpStopped.into()),
+        }
+    }
+}
+
+impl Drop for EventPump {
+    fn drop(&mut self) {
+        // Disconnecting the channel tells the thread to stop
+        self.requests.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub(crate) fn spawn<T: DiscordIpc + Send + 'static>(
+    mut client: T,
+) -> Result<(EventPump, Receiver<Event>)> {
+    let (requests, request_receiver) = mpsc::channel();
+    let (event_sender, events) = mpsc::channel();
+
+    let thread = thread::Builder::new()
+        .name("discord-ipc-event-pump".to_string())
+        .spawn(move || {
+            let _ = run(&mut client, &request_receiver, &event_sender);
+
+            // Anything still waiting will never get its response
+            for awaiting in client.client_state().router.drain() {
+                if let Awaiting::Forward(Some(reply)) = awaiting {
+                    let _ = reply.send(Err(Error::EventPumpStopped.into()));
+                }
+            }
+        })?;
+
+    let pump = EventPump {
+        requests: Some(requests),
+        thread: Some(thread),
+    };
+    Ok((pump, events))
+}
+
+// The body of the thread, which returns once the handle is
+// dropped or closed, or the connection fails. Responses are routed
+// by the client's own router, so that commands the client sent
+// before the pump was spawned, and commands it sends on the
+// thread, have theirs delivered alongside the pump's
+fn run<T: DiscordIpc>(
+    client: &mut T,
+    requests: &Receiver<Request>,
+    events: &Sender<Event>,
+) -> Result<()> {
+    loop {
+        // Events read by the client while carrying out a request,
+        // or before the pump was spawned
+        while let Some(event) = client.client_state().events.pop_front() {
+            forward_event(client, event, events)?;
+        }
+
+        let request = match requests.try_recv() {
+            Ok(request) => request,
+            Err(TryRecvError::Empty) => {
+                if client.wait_readable(POLL_INTERVAL)? {
+                    match read_inbound(client)? {
+                        Inbound::Response { nonce, result } => {
+                            deliver(&mut client.client_state().router, nonce, result);
+                        }
+                        Inbound::Event(event) => forward_event(client, event, events)?,
+                    }
+                }
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => return client.close(),
+        };
+
+        match request {
+            Request::Command {
+                cmd,
+                args,
+                evt,
+                reply,
+            } => {
+                let (nonce, data) = command_payload(&cmd, args, evt);
+                match client.send(data, 1) {
+                    Ok(()) => {
+                        client
+                            .client_state()
+                            .router
+                            .register(nonce, Awaiting::Forward(Some(reply)));
+                    }
+                    Err(err) => {
+                        let _ = reply.send(Err(into_send_error(err)));
+                    }
+                }
+            }
+            Request::SetActivity { args, wait, reply } => {
+                let result = if wait {
+                    send_activity(client, args)
+                } else {
+                    send_activity_no_wait(client, args)
+                };
+                let _ = reply.send(result.map(|_| Value::Null).map_err(into_send_error));
+            }
+            Request::ClearActivity { reply } => {
+                let result = client.clear_activity().map(|_| Value::Null);
+                let _ = reply.send(result.map_err(into_send_error));
+            }
+            Request::Collect { nonce, reply } => {
+                let result = collect(&mut client.client_state().router, &nonce);
+                let _ = reply.send(result.map_err(into_send_error));
+            }
+            Request::Close { reply } => {
+                let result = client.close().map(|_| Value::Null);
+                let _ = reply.send(result.map_err(into_send_error));
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Passes an event to the client's callbacks and join request
+// handler, then to the event channel
+fn forward_event<T: DiscordIpc>(
+    client: &mut T,
+    event: Event,
+    events: &Sender<Event>,
+) -> Result<()> {
+    client.client_state().callbacks.dispatch(&event);
+    if let Some((cmd, args)) = join_request::answer(client, &event)? {
+        // Nobody waits on the response, so errors are dropped
+        let (nonce, data) = command_payload(cmd, args, None);
+        client.send(data, 1)?;
+        client
+            .client_state()
+            .router
+            .register(nonce, Awaiting::Forward(None));
+    }
+
+    // The receiver may have been dropped if events aren't wanted
+    let _ = events.send(event);
+
+    Ok(())
+}
+
+// Makes an error sendable to the thread waiting on it, keeping
+// its type where possible
+pub(crate) fn into_send_error(err: Box<dyn StdError>) -> Box<dyn StdError + Send + Sync> {
+    let err = match err.downcast::<Error>() {
+        Ok(err) => return err,
+        Err(err) => err,
+    };
+    match err.downcast::<std::io::Error>() {
+        Ok(err) => err,
+        Err(err) => err.to_string().into(),
+    }
+}
@@ -0,0 +1,180 @@
+//! Provides a rate limiter for activity updates, set on a client
+//! with [`DiscordIpc::set_rate_limiter`](crate::DiscordIpc::set_rate_limiter).
+use crate::{clock::Clock, error::Error, metrics, DiscordIpc};
+use serde_json::Value;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// What happens to an activity update made once a
+/// [`RateLimiter`]'s budget is spent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitPolicy {
+    /// Block until the update can be sent
+    Block,
+    /// Return an [`Error::RateLimitedLocally`](crate::error::Error::RateLimitedLocally)
+    Error,
+    /// Hold the update back, replacing any update already held
+    /// back, until it is sent by
+    /// [`DiscordIpc::flush_activity`](crate::DiscordIpc::flush_activity)
+    Coalesce,
+}
+
+/// A token bucket limiting how often the activity is updated
+///
+/// The bucket holds up to `capacity` updates, and refills at
+/// `capacity` updates per `period`, so a burst of `capacity`
+/// updates can be sent at once.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    capacity: u32,
+    refill: Duration,
+    tokens: u32,
+    // When the next token started refilling, if the bucket isn't full
+    refilled_at: Option<Instant>,
+    policy: RateLimitPolicy,
+    // The clock set with `clock`, if any
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl RateLimiter {
+    /// Creates a new `RateLimiter` allowing `capacity` updates
+    /// per `period`, which blocks once the budget is spent
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        let capacity = capacity.max(1);
+        RateLimiter {
+            capacity,
+            refill: period / capacity,
+            tokens: capacity,
+            refilled_at: None,
+            policy: RateLimitPolicy::Block,
+            clock: None,
+        }
+    }
+
+    /// Creates a new `RateLimiter` following Discord's limit of 5
+    /// updates per 20 seconds
+    pub fn discord() -> Self {
+        Self::new(5, Duration::from_secs(20))
+    }
+
+    /// Sets what happens to updates made once the budget is spent
+    pub fn policy(mut self, policy: RateLimitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets the clock the budget refills by, and which blocked
+    /// updates wait on, in place of the client's
+    ///
+    /// Unless set, the clock of the client the limiter is given to
+    /// is used (see [`DiscordIpc::set_clock`]). Only updates made
+    /// through a client read this clock;
+    /// [`try_acquire`](#method.try_acquire) is always given the
+    /// time.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Takes one update from the budget at the time `now`.
+    ///
+    /// Returns `Err` with how long until an update is available
+    /// if the budget is spent.
+    pub fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill_until(now);
+        match self.refilled_at {
+            Some(refilled_at) if self.tokens == 0 => {
+                Err((refilled_at + self.refill).saturating_duration_since(now))
+            }
+            _ => {
+                self.tokens -= 1;
+                self.refilled_at.get_or_insert(now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Refills the budget entirely.
+    pub fn reset(&mut self) {
+        self.tokens = self.capacity;
+        self.refilled_at = None;
+    }
+
+    fn refill_until(&mut self, now: Instant) {
+        let refilled_at = match self.refilled_at {
+            Some(refilled_at) => refilled_at,
+            None => return,
+        };
+        if self.refill.is_zero() {
+            return self.reset();
+        }
+
+        let elapsed = now.saturating_duration_since(refilled_at);
+        let refilled = (elapsed.as_nanos() / self.refill.as_nanos()).min(u32::MAX as u128) as u32;
+        self.tokens = self.tokens.saturating_add(refilled).min(self.capacity);
+        if self.tokens == self.capacity {
+            self.refilled_at = None;
+        } else {
+            self.refilled_at = Some(refilled_at + self.refill * refilled);
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::discord()
+    }
+}
+
+// Takes an update from the client's budget for a `SET_ACTIVITY`
+// command with the given arguments, following the limiter's
+// policy. Returns false if the update was held back
+pub(crate) fn acquire<T: DiscordIpc + ?Sized>(
+    client: &mut T,
+    args: &Value,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let state = client.client_state();
+    let client_clock = state.clock();
+    let limiter = match &mut state.rate_limiter {
+        Some(limiter) => limiter,
+        None => return Ok(true),
+    };
+    let clock = limiter.clock.clone().unwrap_or(client_clock);
+
+    loop {
+        match limiter.try_acquire(clock.now_instant()) {
+            Ok(()) => {
+                // This update supersedes any held back
+                state.pending_activity = None;
+                return Ok(true);
+            }
+            Err(wait) => {
+                metrics::rate_limited(limiter.policy);
+                match limiter.policy {
+                    RateLimitPolicy::Block => clock.sleep(wait),
+                    RateLimitPolicy::Error => {
+                        return Err(Error::RateLimitedLocally { retry_in: wait }.into())
+                    }
+                    RateLimitPolicy::Coalesce => {
+                        state.pending_activity = Some(args.clone());
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Takes an update from the client's budget if there is one, and
+// drops any update held back, without ever waiting
+pub(crate) fn bypass<T: DiscordIpc + ?Sized>(client: &mut T) {
+    let state = client.client_state();
+    state.pending_activity = None;
+    let client_clock = state.clock();
+    if let Some(limiter) = &mut state.rate_limiter {
+        let clock = limiter.clock.as_ref().unwrap_or(&client_clock);
+        let _ = limiter.try_acquire(clock.now_instant());
+    }
+}
@@ -0,0 +1,103 @@
+// Classifies frames read from the Discord IPC, and routes command
+// responses to whoever is waiting on them
+use crate::{discord_ipc::DiscordIpc, error::Error, event::Event};
+use serde_json::Value;
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// A classified frame received from the Discord IPC
+pub(crate) enum Inbound {
+    Response { nonce: Value, result: Result<Value> },
+    Event(Event),
+}
+
+// Reads frames until one is either a command response or an
+// event, answering pings along the way
+pub(crate) fn read_inbound<T: DiscordIpc + ?Sized>(client: &mut T) -> Result<Inbound> {
+    loop {
+        let (opcode, mut payload) = client.recv()?;
+        match opcode {
+            // CLOSE
+            2 => {
+                return Err(Error::ConnectionClosed {
+                    code: payload["code"].as_u64().unwrap_or_default(),
+                    message: payload["message"].as_str().unwrap_or_default().to_string(),
+                }
+                .into());
+            }
+            // PING
+            3 => client.send(payload, 4)?,
+            _ if payload["cmd"] == "DISPATCH" => {
+                let name = payload["evt"].as_str().unwrap_or_default().to_string();
+                return Ok(Inbound::Event(Event::from_dispatch(
+                    &name,
+                    take(&mut payload, "data"),
+                )));
+            }
+            _ => {
+                let result = if payload["evt"] == "ERROR" {
+                    client.client_state().stats.command_failed();
+                    let data = &payload["data"];
+                    Err(Error::CommandError {
+                        code: data["code"].as_u64().unwrap_or_default(),
+                        message: data["message"].as_str().unwrap_or_default().to_string(),
+                    }
+                    .into())
+                } else {
+                    Ok(take(&mut payload, "data"))
+                };
+
+                return Ok(Inbound::Response {
+                    nonce: take(&mut payload, "nonce"),
+                    result,
+                });
+            }
+        }
+    }
+}
+
+// Takes a key's value out of a payload, which isn't necessarily
+// an object
+fn take(payload: &mut Value, key: &str) -> Value {
+    payload.get_mut(key).map(Value::take).unwrap_or_default()
+}
+
+// The commands awaiting a response, keyed by nonce, along with
+// the slot each response should be delivered to
+#[derive(Debug)]
+pub(crate) struct Router<S> {
+    pending: HashMap<String, S>,
+}
+
+impl<S> Router<S> {
+    // Registers a command, so that its response is routed to `slot`
+    pub(crate) fn register(&mut self, nonce: String, slot: S) {
+        self.pending.insert(nonce, slot);
+    }
+
+    // Takes the slot waiting on the response with the given nonce,
+    // if any; responses nobody is waiting on should be dropped
+    pub(crate) fn take(&mut self, nonce: &Value) -> Option<S> {
+        nonce.as_str().and_then(|nonce| self.pending.remove(nonce))
+    }
+
+    // How many commands are awaiting a response
+    pub(crate) fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Takes every slot still waiting, whose responses will never
+    // arrive
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = S> + '_ {
+        self.pending.drain().map(|(_, slot)| slot)
+    }
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+}
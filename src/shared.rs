@@ -0,0 +1,217 @@
+//! Provides a client handle which can be cloned and shared
+//! between threads.
+use crate::{
+    activity::Activity,
+    event::{Event, Subscription},
+    DiscordIpc, DiscordIpcClient,
+};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A cheaply cloneable handle to a client, which can be shared
+/// between threads.
+///
+/// Every clone refers to the same client, and every method locks
+/// it for the duration of the call. If two threads call methods at
+/// the same time, one blocks until the other's call has returned;
+/// in particular, a thread calling [`recv_event`] holds the client
+/// until an event is received, blocking every other thread. To
+/// receive events while other threads send commands, use
+/// [`DiscordIpc::spawn_event_pump`] instead.
+///
+/// If a call panics (for example, in an event callback), the
+/// connection may have been left partway through a frame, so the
+/// next call sending to Discord reconnects first, if the client was
+/// connected. Closing or connecting the client, or accessing it
+/// with [`with_client`](#method.with_client), never reconnects.
+///
+/// [`recv_event`]: #method.recv_event
+///
+/// # Examples
+/// ```no_run
+/// # use discord_rich_presence::{activity::Activity, shared::SharedDiscordIpcClient, DiscordIpcClient};
+/// let client = SharedDiscordIpcClient::new(DiscordIpcClient::new("<some client id>")?);
+/// client.connect()?;
+///
+/// let music = client.clone();
+/// std::thread::spawn(move || {
+///     let _ = music.set_activity(Activity::new().state("Listening"));
+/// });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SharedDiscordIpcClient<T = DiscordIpcClient> {
+    client: Arc<Mutex<T>>,
+}
+
+impl<T: DiscordIpc> SharedDiscordIpcClient<T> {
+    /// Creates a new `SharedDiscordIpcClient` wrapping the given
+    /// client
+    pub fn new(client: T) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+        }
+    }
+
+    /// Locks the client, and calls `f` with it.
+    ///
+    /// This gives access to the parts of the [`DiscordIpc`] API
+    /// not re-exposed by this handle. Other threads are blocked
+    /// until `f` returns. After a panic, `f` is given the client as
+    /// the panic left it, without reconnecting.
+    pub fn with_client<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut *self.lock_as_is())
+    }
+
+    /// Connects the client to the Discord IPC.
+    ///
+    /// See [`DiscordIpc::connect`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if connecting failed.
+    pub fn connect(&self) -> Result<()> {
+        self.restart(|client| client.connect())
+    }
+
+    /// Reconnects the client to the Discord IPC.
+    ///
+    /// See [`DiscordIpc::reconnect`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reconnecting failed.
+    pub fn reconnect(&self) -> Result<()> {
+        self.restart(|client| client.reconnect())
+    }
+
+    /// Receives the next event dispatched by Discord.
+    ///
+    /// See [`DiscordIpc::recv_event`]. The client is locked until
+    /// an event is received.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if receiving the event failed.
+    pub fn recv_event(&self) -> Result<Event> {
+        self.lock()?.recv_event()
+    }
+
+    /// Sends an RPC command, and waits for its response.
+    ///
+    /// See [`DiscordIpc::command`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command or reading
+    /// the response failed, or if Discord responded with an error.
+    pub fn command(&self, cmd: &str, args: Value) -> Result<Value> {
+        self.lock()?.command(cmd, args)
+    }
+
+    /// Subscribes to an event.
+    ///
+    /// See [`DiscordIpc::subscribe`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the subscription is invalid, or
+    /// if sending the command failed.
+    pub fn subscribe(&self, subscription: &Subscription) -> Result<()> {
+        self.lock()?.subscribe(subscription)
+    }
+
+    /// Unsubscribes from an event.
+    ///
+    /// See [`DiscordIpc::unsubscribe`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the subscription is invalid, or
+    /// if sending the command failed.
+    pub fn unsubscribe(&self, subscription: &Subscription) -> Result<()> {
+        self.lock()?.unsubscribe(subscription)
+    }
+
+    /// Sets a Discord activity.
+    ///
+    /// See [`DiscordIpc::set_activity`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed.
+    pub fn set_activity(&self, activity_payload: Activity) -> Result<()> {
+        self.lock()?.set_activity(activity_payload)
+    }
+
+    /// Clears the Discord activity.
+    ///
+    /// See [`DiscordIpc::clear_activity`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed.
+    pub fn clear_activity(&self) -> Result<()> {
+        self.lock()?.clear_activity()
+    }
+
+    /// Closes the Discord IPC connection.
+    ///
+    /// See [`DiscordIpc::close`]. The connection is closed for
+    /// every clone of the handle.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if closing the connection failed.
+    pub fn close(&self) -> Result<()> {
+        self.restart(|client| client.close())
+    }
+
+    // Locks the client for a call which sends to Discord
+    fn lock(&self) -> Result<MutexGuard<'_, T>> {
+        match self.client.lock() {
+            Ok(client) => Ok(client),
+            Err(poisoned) => {
+                // The panicking call may have left a frame half
+                // written or read, so a connected client starts over
+                // with a fresh connection. The lock stays poisoned
+                // until this succeeds, so a failure is retried next
+                // call
+                let mut client = poisoned.into_inner();
+                if client.client_state().is_connected() {
+                    client.connect()?;
+                }
+                self.client.clear_poison();
+
+                Ok(client)
+            }
+        }
+    }
+
+    // Calls `f`, which replaces or closes the connection, with the
+    // client as it is. Once it succeeds, a frame a panic left half
+    // written or read is gone with the old connection
+    fn restart(&self, f: impl FnOnce(&mut T) -> Result<()>) -> Result<()> {
+        f(&mut *self.lock_as_is())?;
+        self.client.clear_poison();
+        Ok(())
+    }
+
+    // Locks the client as it is, leaving recovering from a panic to
+    // the next call which sends to Discord
+    fn lock_as_is(&self) -> MutexGuard<'_, T> {
+        self.client.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Clone for SharedDiscordIpcClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            client: Arc::clone(&self.client),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SharedDiscordIpcClient<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedDiscordIpcClient")
+            .field("handles", &Arc::strong_count(&self.client))
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,356 @@
+//! Provides separately owned read and write halves of a client,
+//! created with [`DiscordIpcClient::split`].
+use crate::{
+    activity::Activity,
+    discord_ipc::{command_payload, set_activity_args, ClientState, DiscordIpc, IpcConnection},
+    error,
+    event::Event,
+    ipc::Stream,
+    pack_unpack::pack,
+    rate_limit, DiscordIpcClient,
+};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+impl DiscordIpcClient {
+    /// Splits a connected client into a [`ReadHalf`], which
+    /// receives frames and events, and a [`WriteHalf`], which
+    /// sends frames and activity updates.
+    ///
+    /// The halves can be moved to different threads, so that one
+    /// thread can block on receiving events while another sends
+    /// updates. One reader and one writer may be used at the same
+    /// time; each half takes `&mut self`, so neither can be used
+    /// from two threads at once. Frames are always written whole,
+    /// so the writer's frames never interleave with the pings and
+    /// join request answers sent by the reader.
+    ///
+    /// Callbacks and the join request handler stay with the read
+    /// half, while the client's
+    /// [`RateLimiter`](crate::rate_limit::RateLimiter), last
+    /// activity and [`Persistence`](crate::persist::Persistence)
+    /// move to the write half, which saves the activities it sets.
+    /// Use [`ReadHalf::reunite`] to get the client back, with the
+    /// statistics of both halves added up.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the client isn't connected, or
+    /// if the connection couldn't be duplicated.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+    /// # use std::thread;
+    /// let mut client = DiscordIpcClient::new("<some client id>")?;
+    /// client.connect()?;
+    /// let (mut reader, mut writer) = client.split()?;
+    ///
+    /// thread::spawn(move || {
+    ///     while let Ok(event) = reader.recv_event() {
+    ///         println!("{:?}", event);
+    ///     }
+    /// });
+    /// writer.set_activity_no_wait(Activity::new().state("Split"))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn split(mut self) -> Result<(ReadHalf, WriteHalf)> {
+        // The transport keeps its stream after the client is closed
+        if !self.client_state().handshaken {
+            return Err(error::Error::NotConnected.into());
+        }
+        let stream = self
+            .transport
+            .stream()
+            .ok_or(error::Error::NotConnected)?
+            .try_clone()?;
+        let stream = Arc::new(Mutex::new(stream));
+
+        let state = self.client_state();
+        let writer_state = ClientState {
+            rate_limiter: state.rate_limiter.take(),
+            pending_activity: state.pending_activity.take(),
+            last_activity: state.last_activity.take(),
+            persistence: state.persistence.take(),
+            handshaken: state.handshaken,
+            clock: state.clock.clone(),
+            ..ClientState::default()
+        };
+
+        let write = WriteHalf {
+            client_id: self.client_id.clone(),
+            state: writer_state,
+            stream: Arc::clone(&stream),
+        };
+        let read = ReadHalf {
+            client: self,
+            stream,
+        };
+        Ok((read, write))
+    }
+}
+
+/// The receiving half of a client, created with
+/// [`DiscordIpcClient::split`].
+///
+/// Events are handled as with the whole client: callbacks are
+/// called and join requests answered before an event is
+/// returned, and pings are answered as they are read.
+pub struct ReadHalf {
+    client: DiscordIpcClient,
+    stream: Arc<Mutex<Stream>>,
+}
+
+impl ReadHalf {
+    /// Receives an opcode and JSON data from the Discord IPC.
+    ///
+    /// See [`DiscordIpc::recv`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful.
+    pub fn recv(&mut self) -> Result<(u32, Value)> {
+        self.reader().recv()
+    }
+
+    /// Receives an opcode and JSON data if a frame can be read
+    /// without blocking, or returns `None`.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful.
+    pub fn try_recv(&mut self) -> Result<Option<(u32, Value)>> {
+        let mut reader = self.reader();
+        if !reader.wait_readable(Duration::ZERO)? {
+            return Ok(None);
+        }
+
+        reader.recv().map(Some)
+    }
+
+    /// Receives the next event dispatched by Discord.
+    ///
+    /// See [`DiscordIpc::recv_event`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful, or an
+    /// [`Error::ConnectionClosed`](crate::error::Error::ConnectionClosed)
+    /// if Discord closed the connection.
+    pub fn recv_event(&mut self) -> Result<Event> {
+        self.reader().recv_event()
+    }
+
+    /// Receives the next event if one can be read without
+    /// blocking, or returns `None`.
+    ///
+    /// See [`DiscordIpc::try_recv_event`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if reading the socket was
+    /// unsuccessful, or an
+    /// [`Error::ConnectionClosed`](crate::error::Error::ConnectionClosed)
+    /// if Discord closed the connection.
+    pub fn try_recv_event(&mut self) -> Result<Option<Event>> {
+        self.reader().try_recv_event()
+    }
+
+    /// Joins the two halves back into the client they were split
+    /// from.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the halves were split from
+    /// different clients.
+    pub fn reunite(self, write: WriteHalf) -> Result<DiscordIpcClient> {
+        if !Arc::ptr_eq(&self.stream, &write.stream) {
+            return Err("The halves were split from different clients".into());
+        }
+
+        let mut client = self.client;
+        let state = client.client_state();
+        state.rate_limiter = write.state.rate_limiter;
+        state.pending_activity = write.state.pending_activity;
+        state.last_activity = write.state.last_activity;
+        state.persistence = write.state.persistence;
+        state.stats.merge(write.state.stats);
+        Ok(client)
+    }
+
+    fn reader(&mut self) -> Reader<'_> {
+        Reader {
+            client: &mut self.client,
+            stream: &self.stream,
+        }
+    }
+}
+
+impl fmt::Debug for ReadHalf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadHalf")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+/// The sending half of a client, created with
+/// [`DiscordIpcClient::split`].
+pub struct WriteHalf {
+    client_id: String,
+    state: ClientState,
+    stream: Arc<Mutex<Stream>>,
+}
+
+impl WriteHalf {
+    /// Sends JSON data to the Discord IPC.
+    ///
+    /// See [`DiscordIpc::send`].
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if writing to the socket failed.
+    pub fn send(&mut self, data: Value, opcode: u8) -> Result<()> {
+        write_frame(&self.stream, data, opcode)
+    }
+
+    /// Sets a Discord activity without waiting for Discord's
+    /// response.
+    ///
+    /// See [`DiscordIpc::set_activity_no_wait`]. The response is
+    /// read, and discarded, by the read half.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed, or
+    /// if the rate limiter refused the update.
+    pub fn set_activity_no_wait(&mut self, activity_payload: Activity) -> Result<()> {
+        Writer(self).set_activity_no_wait(activity_payload)
+    }
+
+    /// Clears the Discord activity without waiting for Discord's
+    /// response.
+    ///
+    /// Clearing is never delayed or refused by the rate limiter.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the payload failed.
+    pub fn clear_activity_no_wait(&mut self) -> Result<()> {
+        if !self.state.is_connected() {
+            return Err(error::Error::NotConnected.into());
+        }
+        rate_limit::bypass(&mut Writer(self));
+        let (_, data) = command_payload("SET_ACTIVITY", set_activity_args(Value::Null), None);
+
+        self.send(data, 1)
+    }
+}
+
+impl fmt::Debug for WriteHalf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteHalf")
+            .field("client_id", &self.client_id)
+            .field("rate_limiter", &self.state.rate_limiter)
+            .finish()
+    }
+}
+
+// Writes a whole frame while holding the lock, so that frames
+// from both halves never interleave
+fn write_frame(stream: &Mutex<Stream>, data: Value, opcode: u8) -> Result<()> {
+    let data_string = data.to_string();
+    let mut frame = pack(opcode.into(), data_string.len() as u32)?;
+    frame.extend_from_slice(data_string.as_bytes());
+
+    // A panic can't leave a frame half written, so the stream is
+    // still usable
+    let mut stream = stream.lock().unwrap_or_else(|err| err.into_inner());
+    stream.write_all(&frame)?;
+
+    Ok(())
+}
+
+// The read half as a client, which reads through the original
+// connection and writes through the shared one
+struct Reader<'a> {
+    client: &'a mut DiscordIpcClient,
+    stream: &'a Mutex<Stream>,
+}
+
+impl IpcConnection for Reader<'_> {
+    fn get_client_id(&self) -> &String {
+        self.client.get_client_id()
+    }
+
+    fn connect_ipc(&mut self) -> Result<()> {
+        Err("A split client can't reconnect".into())
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        self.client.client_state()
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let mut stream = self.stream.lock().unwrap_or_else(|err| err.into_inner());
+        stream.write_all(data)?;
+
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        // A blocking read of a Windows pipe also blocks writes to
+        // it, so only read once data has arrived
+        #[cfg(windows)]
+        while !self.client.wait_readable(Duration::from_millis(10))? {}
+
+        self.client.read(buffer)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        self.client.wait_readable(timeout)
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        Err("A split client must be reunited before closing".into())
+    }
+}
+
+// The write half as a client, so that updates go through its
+// rate limiter
+struct Writer<'a>(&'a mut WriteHalf);
+
+impl IpcConnection for Writer<'_> {
+    fn get_client_id(&self) -> &String {
+        &self.0.client_id
+    }
+
+    fn connect_ipc(&mut self) -> Result<()> {
+        Err("A split client can't reconnect".into())
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        &mut self.0.state
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let mut stream = self.0.stream.lock().unwrap_or_else(|err| err.into_inner());
+        stream.write_all(data)?;
+
+        Ok(())
+    }
+
+    fn read(&mut self, _buffer: &mut [u8]) -> Result<()> {
+        Err("The write half can't read".into())
+    }
+
+    fn wait_readable(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        Err("A split client must be reunited before closing".into())
+    }
+}
@@ -0,0 +1,134 @@
+//! Provides [`ConnectionStats`], a snapshot of what a client has
+//! sent and received, for introspection without a metrics backend.
+//!
+//! Clients keep these counts whether or not the `metrics` feature
+//! is enabled; see [`metrics`](crate::metrics) for recording them
+//! elsewhere.
+use std::time::{Duration, Instant, SystemTime};
+
+/// Counts of a client's traffic with Discord
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Counters {
+    /// Frames written to Discord
+    pub frames_sent: u64,
+    /// Frames read from Discord
+    pub frames_received: u64,
+    /// Bytes written to Discord, headers included
+    pub bytes_sent: u64,
+    /// Bytes read from Discord, headers included
+    pub bytes_received: u64,
+    /// Commands sent, whether or not their response was awaited
+    pub commands: u64,
+    /// Command responses in which Discord reported an error
+    pub command_failures: u64,
+}
+
+/// A snapshot of a client's statistics, returned by
+/// [`DiscordIpcClient::stats`](crate::DiscordIpcClient::stats)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    /// Counted since the client last connected or reconnected
+    pub connection: Counters,
+    /// Counted since the client was created, across every
+    /// connection
+    pub lifetime: Counters,
+    /// Calls to [`DiscordIpc::reconnect`](crate::DiscordIpc::reconnect)
+    pub reconnects: u64,
+    /// When an activity update was last sent successfully, if ever,
+    /// by the client's clock
+    pub last_activity_update: Option<SystemTime>,
+    /// How long the client has been connected, by the client's
+    /// clock, or `None` if it isn't
+    pub uptime: Option<Duration>,
+}
+
+impl Counters {
+    fn add(&mut self, other: &Counters) {
+        self.frames_sent += other.frames_sent;
+        self.frames_received += other.frames_received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.commands += other.commands;
+        self.command_failures += other.command_failures;
+    }
+}
+
+// The statistics a client keeps in its `ClientState`
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    connection: Counters,
+    lifetime: Counters,
+    reconnects: u64,
+    last_activity_update: Option<SystemTime>,
+    connected_at: Option<Instant>,
+}
+
+impl Stats {
+    pub(crate) fn frame_sent(&mut self, opcode: u8, bytes: usize) {
+        self.count(|counters| {
+            counters.frames_sent += 1;
+            counters.bytes_sent += bytes as u64;
+            if opcode == 1 {
+                counters.commands += 1;
+            }
+        });
+    }
+
+    pub(crate) fn frame_received(&mut self, bytes: usize) {
+        self.count(|counters| {
+            counters.frames_received += 1;
+            counters.bytes_received += bytes as u64;
+        });
+    }
+
+    pub(crate) fn command_failed(&mut self) {
+        self.count(|counters| counters.command_failures += 1);
+    }
+
+    pub(crate) fn reconnected(&mut self) {
+        self.reconnects += 1;
+    }
+
+    pub(crate) fn activity_updated(&mut self, now: SystemTime) {
+        self.last_activity_update = Some(now);
+    }
+
+    // Starts counting a new connection
+    pub(crate) fn connecting(&mut self) {
+        self.connection = Counters::default();
+        self.connected_at = None;
+    }
+
+    pub(crate) fn connected(&mut self, now: Instant) {
+        self.connected_at = Some(now);
+    }
+
+    // Adds what was counted by the write half of a split client,
+    // once it is reunited
+    pub(crate) fn merge(&mut self, other: Stats) {
+        self.connection.add(&other.connection);
+        self.lifetime.add(&other.lifetime);
+        self.reconnects += other.reconnects;
+        self.last_activity_update = self.last_activity_update.max(other.last_activity_update);
+    }
+
+    pub(crate) fn snapshot(&self, connected: bool, now: Instant) -> ConnectionStats {
+        ConnectionStats {
+            connection: self.connection,
+            lifetime: self.lifetime,
+            reconnects: self.reconnects,
+            last_activity_update: self.last_activity_update,
+            uptime: self
+                .connected_at
+                .filter(|_| connected)
+                .map(|connected_at| now.saturating_duration_since(connected_at)),
+        }
+    }
+
+    fn count(&mut self, f: impl Fn(&mut Counters)) {
+        f(&mut self.connection);
+        f(&mut self.lifetime);
+    }
+}
@@ -0,0 +1,570 @@
+//! Provides a scriptable stand-in for Discord, for testing code
+//! built on this crate without a running Discord client.
+//!
+//! Requires the `test-util` feature.
+use crate::{
+    clock::Clock,
+    ipc::Stream,
+    pack_unpack::{pack, unpack},
+    transport::{IpcTransport, NativeTransport},
+    DiscordIpcClient,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A frame, as its opcode and JSON payload
+pub type Frame = (u32, Value);
+
+type Responder = Box<dyn FnMut(u32, &Value) -> Vec<Frame> + Send>;
+
+// How long the server waits for incoming data before checking
+// whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Distinguishes the servers started by one process
+static SERVERS: AtomicUsize = AtomicUsize::new(0);
+
+/// A fake Discord, listening on a Unix socket (or a named pipe on
+/// Windows) and speaking the real framing.
+///
+/// Connections are accepted one at a time, on a background thread.
+/// Every frame received is recorded, and answered by the server's
+/// responder: by default, handshakes are answered with a `READY`
+/// dispatch, and commands with an empty success response (see
+/// [`default_response`]). Whenever the server sends a `CLOSE`
+/// frame, it closes the connection afterwards, and waits for the
+/// next one.
+///
+/// Dropping the server stops the thread.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::{activity::Activity, test_util::MockDiscordServer, DiscordIpc};
+/// let server = MockDiscordServer::start()?;
+/// let mut client = server.client("<some client id>");
+/// client.connect()?;
+/// client.set_activity(Activity::new().state("Testing"))?;
+///
+/// let (_, payload) = server.received().pop().unwrap();
+/// assert_eq!(payload["args"]["activity"]["state"], "Testing");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct MockDiscordServer {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct Script {
+    responder: Option<Responder>,
+    responses: VecDeque<Vec<Frame>>,
+    delay: Option<Duration>,
+    hang_up: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    stopped: AtomicBool,
+    closing: AtomicBool,
+    connections: AtomicUsize,
+    connection: Mutex<Option<Stream>>,
+    received: Mutex<Vec<Frame>>,
+    received_changed: Condvar,
+    script: Mutex<Script>,
+}
+
+impl MockDiscordServer {
+    /// Starts a server, listening on a new socket.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the socket couldn't be created.
+    pub fn start() -> Result<Self> {
+        let path = Listener::unique_path();
+        let listener = Listener::bind(&path)?;
+        let shared = Arc::new(Shared::default());
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("mock-discord-server".to_string())
+                .spawn(move || serve(listener, &shared))?
+        };
+
+        Ok(Self {
+            path,
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the path of the server's socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a transport which connects to the server.
+    pub fn transport(&self) -> NativeTransport {
+        NativeTransport::with_path(&self.path)
+    }
+
+    /// Returns a client which connects to the server.
+    pub fn client(&self, client_id: &str) -> DiscordIpcClient {
+        DiscordIpcClient::with_transport(client_id, self.transport())
+    }
+
+    /// Replaces the responder used to answer received frames.
+    ///
+    /// The responder is called with each frame's opcode and
+    /// payload, and returns the frames to send back.
+    /// [`default_response`] can be used as a fallback.
+    pub fn respond_with(&self, responder: impl FnMut(u32, &Value) -> Vec<Frame> + Send + 'static) {
+        self.script().responder = Some(Box::new(responder));
+    }
+
+    /// Queues frames to answer the next command with, instead of
+    /// calling the responder.
+    ///
+    /// Each call queues the answer to one command. Any `nonce`
+    /// of `null` in the frames is replaced with the command's.
+    pub fn push_response(&self, frames: Vec<Frame>) {
+        self.script().responses.push_back(frames);
+    }
+
+    /// Waits for `delay` before sending the next answer.
+    pub fn delay_next_response(&self, delay: Duration) {
+        self.script().delay = Some(delay);
+    }
+
+    /// Closes the connection on receiving the next frame, without
+    /// answering it or sending a `CLOSE` frame, as Discord does
+    /// with handshakes while it is starting up.
+    pub fn hang_up_next_frame(&self) {
+        self.script().hang_up = true;
+    }
+
+    /// Sends a frame on the current connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if no client is connected, or if
+    /// writing to the connection failed.
+    pub fn send_frame(&self, opcode: u32, payload: Value) -> Result<()> {
+        send_frame(&self.shared, opcode, &payload)
+    }
+
+    /// Sends an unsolicited event on the current connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if no client is connected, or if
+    /// writing to the connection failed.
+    pub fn send_event(&self, evt: &str, data: Value) -> Result<()> {
+        let payload = json!({ "cmd": "DISPATCH", "evt": evt, "data": data, "nonce": null });
+        self.send_frame(1, payload)
+    }
+
+    /// Sends a `CLOSE` frame with the given code, and closes the
+    /// current connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if no client is connected, or if
+    /// writing to the connection failed.
+    pub fn close_connection(&self, code: u64, message: &str) -> Result<()> {
+        self.send_frame(2, json!({ "code": code, "message": message }))?;
+        self.shared.closing.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Returns every frame received so far, across all connections.
+    pub fn received(&self) -> Vec<Frame> {
+        lock(&self.shared.received).clone()
+    }
+
+    /// Waits up to `timeout` until at least `count` frames have
+    /// been received, and returns every frame received so far.
+    pub fn wait_for_frames(&self, count: usize, timeout: Duration) -> Vec<Frame> {
+        let received = lock(&self.shared.received);
+        let (received, _) = self
+            .shared
+            .received_changed
+            .wait_timeout_while(received, timeout, |received| received.len() < count)
+            .unwrap_or_else(|err| err.into_inner());
+
+        received.clone()
+    }
+
+    /// Returns how many connections the server has accepted.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    fn script(&self) -> MutexGuard<'_, Script> {
+        lock(&self.shared.script)
+    }
+}
+
+impl Drop for MockDiscordServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+
+        // Wake the thread if it is waiting for a connection
+        let _ = self.transport().connect();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        Listener::remove(&self.path);
+    }
+}
+
+impl fmt::Debug for MockDiscordServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockDiscordServer")
+            .field("path", &self.path)
+            .field("connections", &self.connections())
+            .finish()
+    }
+}
+
+/// Builds a successful response to `request` carrying `data`.
+pub fn response(request: &Value, data: Value) -> Frame {
+    (
+        1,
+        json!({
+            "cmd": request["cmd"],
+            "data": data,
+            "evt": null,
+            "nonce": request["nonce"]
+        }),
+    )
+}
+
+/// The responder used when none is given: handshakes are answered
+/// with a `READY` dispatch, and commands with an empty success
+/// response.
+pub fn default_response(opcode: u32, request: &Value) -> Vec<Frame> {
+    match opcode {
+        0 => vec![(
+            1,
+            json!({
+                "cmd": "DISPATCH",
+                "evt": "READY",
+                "data": { "v": 1, "config": {}, "user": { "id": "1", "username": "mock" } },
+                "nonce": null
+            }),
+        )],
+        1 => vec![response(request, Value::Null)],
+        _ => Vec::new(),
+    }
+}
+
+/// A clock which only moves when advanced, for testing time-based
+/// features without waiting.
+///
+/// Sleeping on the clock advances it by the time slept, so code
+/// which waits returns immediately. The clock is shared through an
+/// `Arc`, and can be given to anything taking a
+/// [`Clock`](crate::clock::Clock).
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::{clock::Clock, rate_limit::RateLimiter, test_util::MockClock};
+/// # use std::time::Duration;
+/// let clock = MockClock::new();
+/// let limiter = RateLimiter::discord().clock(clock.clone());
+///
+/// let start = clock.now_instant();
+/// clock.advance(Duration::from_secs(20));
+/// assert_eq!(clock.now_instant() - start, Duration::from_secs(20));
+/// ```
+#[derive(Debug)]
+pub struct MockClock {
+    started: Instant,
+    unix_ms: u64,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    /// Creates a new `MockClock`, starting at the Unix epoch
+    pub fn new() -> Arc<Self> {
+        Self::at_unix_ms(0)
+    }
+
+    /// Creates a new `MockClock`, starting `unix_ms` milliseconds
+    /// after the Unix epoch
+    pub fn at_unix_ms(unix_ms: u64) -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            unix_ms,
+            elapsed: Mutex::new(Duration::ZERO),
+        })
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *lock(&self.elapsed) += duration;
+    }
+
+    /// Returns how far the clock has moved since it was created
+    pub fn elapsed(&self) -> Duration {
+        *lock(&self.elapsed)
+    }
+}
+
+impl Clock for MockClock {
+    fn now_unix_ms(&self) -> u64 {
+        self.unix_ms + self.elapsed().as_millis() as u64
+    }
+
+    fn now_instant(&self) -> Instant {
+        self.started + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+// Accepts connections until the server is dropped
+fn serve(listener: Listener, shared: &Shared) {
+    while !shared.stopped.load(Ordering::SeqCst) {
+        let stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(_) => break,
+        };
+        if shared.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => continue,
+        };
+
+        shared.closing.store(false, Ordering::SeqCst);
+        *lock(&shared.connection) = Some(writer);
+        shared.connections.fetch_add(1, Ordering::SeqCst);
+
+        let mut transport = NativeTransport::from_stream(stream);
+        let _ = serve_connection(&mut transport, shared);
+        lock(&shared.connection).take();
+    }
+}
+
+// Answers frames on one connection until either side closes it
+fn serve_connection(transport: &mut NativeTransport, shared: &Shared) -> Result<()> {
+    loop {
+        if shared.stopped.load(Ordering::SeqCst) || shared.closing.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if !transport.wait_readable(POLL_INTERVAL)? {
+            continue;
+        }
+
+        let mut header = [0; 8];
+        transport.read(&mut header)?;
+        let (opcode, length) = unpack(header.to_vec())?;
+        let mut data = vec![0; length as usize];
+        transport.read(&mut data)?;
+        let payload: Value = serde_json::from_slice(&data)?;
+
+        if mem::take(&mut lock(&shared.script).hang_up) {
+            lock(&shared.received).push((opcode, payload));
+            shared.received_changed.notify_all();
+            return Ok(());
+        }
+
+        let (replies, delay) = {
+            let mut script = lock(&shared.script);
+            let scripted = match opcode {
+                1 => script.responses.pop_front(),
+                _ => None,
+            };
+            let replies = match scripted {
+                Some(mut frames) => {
+                    for (_, frame) in &mut frames {
+                        if frame.get("nonce") == Some(&Value::Null) {
+                            frame["nonce"] = payload["nonce"].clone();
+                        }
+                    }
+                    frames
+                }
+                None => match &mut script.responder {
+                    Some(responder) => responder(opcode, &payload),
+                    None => default_response(opcode, &payload),
+                },
+            };
+            let delay = if replies.is_empty() {
+                None
+            } else {
+                script.delay.take()
+            };
+            (replies, delay)
+        };
+
+        {
+            let mut received = lock(&shared.received);
+            received.push((opcode, payload));
+            shared.received_changed.notify_all();
+        }
+
+        if let Some(delay) = delay {
+            let deadline = Instant::now() + delay;
+            while Instant::now() < deadline && !shared.stopped.load(Ordering::SeqCst) {
+                thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+            }
+        }
+        for (reply_opcode, reply) in replies {
+            send_frame(shared, reply_opcode, &reply)?;
+            if reply_opcode == 2 {
+                return Ok(());
+            }
+        }
+        if opcode == 2 {
+            return Ok(());
+        }
+    }
+}
+
+fn send_frame(shared: &Shared, opcode: u32, payload: &Value) -> Result<()> {
+    let data = payload.to_string();
+    let mut frame = pack(opcode, data.len() as u32)?;
+    frame.extend_from_slice(data.as_bytes());
+
+    let mut connection = lock(&shared.connection);
+    let stream = connection
+        .as_mut()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "No client is connected"))?;
+    stream.write_all(&frame)?;
+
+    Ok(())
+}
+
+// The server's state stays consistent if a responder panics, so
+// poisoning is ignored
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(unix)]
+struct Listener(std::os::unix::net::UnixListener);
+
+#[cfg(unix)]
+impl Listener {
+    fn unique_path() -> PathBuf {
+        let server = SERVERS.fetch_add(1, Ordering::SeqCst);
+        std::env::temp_dir().join(format!(
+            "discord-ipc-mock-{}-{}",
+            std::process::id(),
+            server
+        ))
+    }
+
+    fn bind(path: &Path) -> io::Result<Self> {
+        // A socket left behind by an earlier process blocks binding
+        let _ = std::fs::remove_file(path);
+        std::os::unix::net::UnixListener::bind(path).map(Self)
+    }
+
+    fn accept(&self) -> io::Result<Stream> {
+        self.0.accept().map(|(stream, _)| stream)
+    }
+
+    fn remove(path: &Path) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(windows)]
+struct Listener(Vec<u16>);
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateNamedPipeW(
+        name: *const u16,
+        open_mode: u32,
+        pipe_mode: u32,
+        max_instances: u32,
+        out_buffer_size: u32,
+        in_buffer_size: u32,
+        default_timeout: u32,
+        security_attributes: *mut std::ffi::c_void,
+    ) -> *mut std::ffi::c_void;
+
+    fn ConnectNamedPipe(
+        named_pipe: *mut std::ffi::c_void,
+        overlapped: *mut std::ffi::c_void,
+    ) -> i32;
+}
+
+#[cfg(windows)]
+impl Listener {
+    const PIPE_ACCESS_DUPLEX: u32 = 0x3;
+    const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+    const ERROR_PIPE_CONNECTED: i32 = 535;
+
+    fn unique_path() -> PathBuf {
+        let server = SERVERS.fetch_add(1, Ordering::SeqCst);
+        PathBuf::from(format!(
+            r"\\.\pipe\discord-ipc-mock-{}-{}",
+            std::process::id(),
+            server
+        ))
+    }
+
+    fn bind(path: &Path) -> io::Result<Self> {
+        use std::os::windows::ffi::OsStrExt;
+
+        let name = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        Ok(Self(name))
+    }
+
+    // Creates a pipe instance, and waits for a client to open it
+    fn accept(&self) -> io::Result<Stream> {
+        use std::os::windows::io::FromRawHandle;
+
+        let handle = unsafe {
+            CreateNamedPipeW(
+                self.0.as_ptr(),
+                Self::PIPE_ACCESS_DUPLEX,
+                0,
+                Self::PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if handle as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = unsafe { std::fs::File::from_raw_handle(handle) };
+
+        let connected = unsafe { ConnectNamedPipe(handle, std::ptr::null_mut()) };
+        if connected == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(Self::ERROR_PIPE_CONNECTED) {
+                return Err(err);
+            }
+        }
+
+        Ok(stream)
+    }
+
+    // Pipes disappear with their last handle
+    fn remove(_path: &Path) {}
+}
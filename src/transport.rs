@@ -0,0 +1,320 @@
+//! Provides the connection a [`DiscordIpcClient`] talks to Discord
+//! through.
+//!
+//! By default, a client connects through the platform's socket or
+//! named pipe, with [`NativeTransport`]. Any other [`IpcTransport`]
+//! can be given to [`DiscordIpcClient::with_transport`], for
+//! example an in-memory stand-in for Discord in tests.
+//!
+//! [`DiscordIpcClient`]: crate::DiscordIpcClient
+//! [`DiscordIpcClient::with_transport`]: crate::DiscordIpcClient::with_transport
+use crate::{clock::Clock, error};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub use crate::ipc::NativeTransport;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A byte stream connected to Discord.
+///
+/// The transport only moves bytes; framing, the handshake and
+/// everything built on top of them is handled by the client.
+pub trait IpcTransport {
+    /// Opens the connection, replacing any previous one.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection couldn't be
+    /// opened.
+    fn connect(&mut self) -> Result<()>;
+
+    /// Opens the connection like [`connect`](#tymethod.connect),
+    /// giving up after `timeout`.
+    ///
+    /// The default ignores the timeout, which suits transports
+    /// whose connections open without blocking.
+    ///
+    /// # Errors
+    /// Returns an [`Error::Timeout`](crate::error::Error::Timeout)
+    /// if the connection wasn't opened in time, or an `Err` variant
+    /// if it couldn't be opened.
+    fn connect_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let _ = timeout;
+        self.connect()
+    }
+
+    /// Fills `buffer` completely, blocking until enough data has
+    /// been read.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection failed or was
+    /// closed before `buffer` was filled.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()>;
+
+    /// Writes all of `data`.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection failed.
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Waits up to `timeout` for data to become available to read,
+    /// returning whether any did. A zero timeout checks without
+    /// waiting.
+    ///
+    /// A closed connection counts as readable, so that the next
+    /// read reports it.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if the connection failed.
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool>;
+
+    /// Flushes and closes the connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if flushing the connection failed.
+    fn shutdown(&mut self) -> Result<()>;
+}
+
+impl<T: IpcTransport + ?Sized> IpcTransport for Box<T> {
+    fn connect(&mut self) -> Result<()> {
+        (**self).connect()
+    }
+
+    fn connect_timeout(&mut self, timeout: Duration) -> Result<()> {
+        (**self).connect_timeout(timeout)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        (**self).read(buffer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        (**self).write(data)
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        (**self).wait_readable(timeout)
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        (**self).shutdown()
+    }
+}
+
+/// The socket or pipe a [`NativeTransport`] connected to, and how
+/// it was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectedEndpoint {
+    /// The path of the socket or pipe
+    pub path: PathBuf,
+
+    /// How the path was found
+    pub source: EndpointSource,
+}
+
+/// An enum representing how a [`ConnectedEndpoint`] was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointSource {
+    /// It was given to `NativeTransport::with_path`
+    Explicit,
+    /// It was named by the [`IPC_PATH_VAR`] environment variable
+    Overridden,
+    /// It was found by searching where Discord listens
+    Discovered {
+        /// The environment variable naming the directory searched,
+        /// or `None` if none was set and `/tmp` or `/var/tmp` was
+        /// searched instead, and always on Windows, where pipes
+        /// aren't in a directory
+        env_key: Option<&'static str>,
+        /// The subdirectory an install listens in, such as
+        /// `snap.discord/`, or [`APP_CONTAINER_SUBPATH`] for a
+        /// Microsoft Store install on Windows, or `""` for a
+        /// regular install
+        subpath: &'static str,
+        /// The index of the Discord instance, the `N` of
+        /// `discord-ipc-N`
+        index: u32,
+    },
+}
+
+/// The environment variable which, when set, overrides where
+/// [`NativeTransport`] looks for Discord's socket or pipe.
+///
+/// If it names a directory, the sockets `discord-ipc-N` in it are
+/// tried, for each index the transport searches; otherwise it
+/// names the only socket or pipe tried, such as
+/// `\\?\pipe\my-custom-ipc` on Windows. Nothing else is searched,
+/// and if connecting fails,
+/// [`Error::PathOverrideFailed`](crate::error::Error::PathOverrideFailed)
+/// is returned. A path given to `NativeTransport::with_path` takes
+/// precedence.
+pub const IPC_PATH_VAR: &str = "DISCORD_IPC_PATH";
+
+// The paths to try when `IPC_PATH_VAR` is set to `value`, with
+// `pipes` the indexes searched for in a directory
+pub(crate) fn override_candidates(
+    value: &str,
+    pipes: RangeInclusive<u32>,
+) -> Vec<(PathBuf, EndpointSource)> {
+    let path = PathBuf::from(value);
+    if !path.is_dir() && !value.ends_with(['/', '\\']) {
+        return vec![(path, EndpointSource::Overridden)];
+    }
+
+    pipes
+        .map(|index| {
+            let path = path.join(format!("discord-ipc-{}", index));
+            (path, EndpointSource::Overridden)
+        })
+        .collect()
+}
+
+/// The subpath of an [`EndpointSource::Discovered`] pipe in the
+/// AppContainer of a Microsoft Store install of Discord, on Windows
+pub const APP_CONTAINER_SUBPATH: &str = "AppContainerNamedObjects";
+
+/// The indexes of the sockets or pipes `discord-ipc-N` searched by
+/// default, those Discord normally listens on.
+///
+/// Discord may end up further along after crashes on systems with
+/// many users; [`NativeTransport::set_pipe_range`] searches more.
+pub const DEFAULT_PIPE_RANGE: RangeInclusive<u32> = 0..=9;
+
+/// Lists the named pipes searched for Discord's on Windows, in
+/// order, given the ID of the session the process runs in and the
+/// SIDs of the AppContainers of installed Discord packages.
+///
+/// The pipes are `discord-ipc-0` to `discord-ipc-9`. For each, the
+/// pipe of a regular install is tried first, then the pipe in each
+/// package's AppContainer, where a Microsoft Store install listens,
+/// before the next pipe. Without a session ID, only the pipes of
+/// regular installs are listed.
+///
+/// On Windows, [`NativeTransport`] finds the session ID and SIDs
+/// itself; this shows where it looks for them.
+///
+/// # Examples
+/// ```
+/// # use discord_rich_presence::transport::windows_pipe_paths;
+/// # use std::path::PathBuf;
+/// let paths = windows_pipe_paths(Some(1), &["S-1-15-2-1"]);
+/// assert_eq!(paths[0], PathBuf::from(r"\\?\pipe\discord-ipc-0"));
+/// assert_eq!(
+///     paths[1],
+///     PathBuf::from(r"\\?\pipe\Sessions\1\AppContainerNamedObjects\S-1-15-2-1\discord-ipc-0")
+/// );
+/// assert_eq!(paths.len(), 20);
+/// ```
+pub fn windows_pipe_paths<S: AsRef<str>>(
+    session_id: Option<u32>,
+    package_sids: &[S],
+) -> Vec<PathBuf> {
+    windows_pipe_candidates(session_id, package_sids, DEFAULT_PIPE_RANGE)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+// The pipes listed by `windows_pipe_paths`, for the instances in
+// `pipes`, along with how each was found
+pub(crate) fn windows_pipe_candidates<S: AsRef<str>>(
+    session_id: Option<u32>,
+    package_sids: &[S],
+    pipes: RangeInclusive<u32>,
+) -> Vec<(PathBuf, EndpointSource)> {
+    let mut candidates = Vec::new();
+    for index in pipes {
+        let name = format!("discord-ipc-{}", index);
+        let source = |subpath| EndpointSource::Discovered {
+            env_key: None,
+            subpath,
+            index,
+        };
+
+        candidates.push((PathBuf::from(format!(r"\\?\pipe\{}", name)), source("")));
+        let session_id = match session_id {
+            Some(session_id) => session_id,
+            None => continue,
+        };
+        for sid in package_sids {
+            let path = format!(
+                r"\\?\pipe\Sessions\{}\{}\{}\{}",
+                session_id,
+                APP_CONTAINER_SUBPATH,
+                sid.as_ref(),
+                name
+            );
+            candidates.push((PathBuf::from(path), source(APP_CONTAINER_SUBPATH)));
+        }
+    }
+    candidates
+}
+
+/// A source of environment variables, which [`NativeTransport`]
+/// reads to find Discord's socket or pipe.
+///
+/// The real environment is read through [`SystemEnv`]. Any other
+/// provider can be given to `NativeTransport::with_env`, to search
+/// a fabricated environment instead.
+pub trait EnvProvider: Send + Sync {
+    /// Returns the value of the variable `key`, or `None` if it
+    /// isn't set or isn't valid unicode
+    fn get(&self, key: &str) -> Option<String>;
+}
+
+/// The process's environment
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemEnv;
+
+impl EnvProvider for SystemEnv {
+    fn get(&self, key: &str) -> Option<String> {
+        env::var(key).ok()
+    }
+}
+
+impl EnvProvider for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<String> {
+        HashMap::get(self, key).cloned()
+    }
+}
+
+// The time by which connecting must finish, when it is bounded,
+// measured by a clock
+#[derive(Clone, Debug)]
+pub(crate) struct Deadline {
+    at: Instant,
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl Deadline {
+    // The deadline `timeout` from now on `clock`, or `None` if that
+    // is too far off to be represented, which is as good as no
+    // deadline
+    pub(crate) fn after(timeout: Duration, clock: Arc<dyn Clock>) -> Option<Self> {
+        let at = clock.now_instant().checked_add(timeout)?;
+        Some(Self { at, timeout, clock })
+    }
+
+    // The time left, or an `Error::Timeout` once there is none
+    pub(crate) fn remaining(&self) -> std::result::Result<Duration, error::Error> {
+        let remaining = self.at.saturating_duration_since(self.clock.now_instant());
+        if remaining.is_zero() {
+            return Err(self.expired());
+        }
+        Ok(remaining)
+    }
+
+    pub(crate) fn expired(&self) -> error::Error {
+        error::Error::Timeout {
+            timeout: self.timeout,
+        }
+    }
+}
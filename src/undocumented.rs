@@ -0,0 +1,187 @@
+//! Wrappers for RPC commands that Discord's local client accepts,
+//! but which are absent from the official documentation.
+//!
+//! This module is only available with the `undocumented` feature.
+//!
+//! # Stability
+//!
+//! Every command here is **unstable**. Discord has never committed
+//! to these commands, and may change or remove any of them in a
+//! client update without notice. They are provided as a supported
+//! escape hatch for tools such as overlay managers, and are exempt
+//! from this crate's semver guarantees.
+use crate::{activity::Activity, discord_ipc::DiscordIpc, models::User, pid};
+use serde::{Deserialize, Deserializer};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Undocumented commands, available on every [`DiscordIpc`] client.
+///
+/// See the [module documentation](self) for stability caveats.
+pub trait UndocumentedCommands: DiscordIpc {
+    /// Locks or unlocks the in-game overlay for this process.
+    ///
+    /// Sends the `SET_OVERLAY_LOCKED` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn set_overlay_locked(&mut self, locked: bool) -> Result<()> {
+        self.command(
+            "SET_OVERLAY_LOCKED",
+            json!({
+                "locked": locked,
+                "pid": pid::pid()
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    /// Fetches the user's relationships (friends, blocked users,
+    /// and pending friend requests), including the presence of
+    /// each user where Discord provides one.
+    ///
+    /// Sends the `GET_RELATIONSHIPS` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed,
+    /// if Discord responded with an error, or if the response
+    /// could not be parsed.
+    fn get_relationships(&mut self) -> Result<Vec<Relationship>> {
+        let mut data = self.command("GET_RELATIONSHIPS", json!({}))?;
+        let relationships = data
+            .get_mut("relationships")
+            .map(Value::take)
+            .unwrap_or_default();
+        let relationships = serde_json::from_value(relationships)?;
+
+        Ok(relationships)
+    }
+
+    /// Opens the voice settings panel of the in-game overlay.
+    ///
+    /// Sends the `OPEN_OVERLAY_VOICE_SETTINGS` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn open_overlay_voice_settings(&mut self) -> Result<()> {
+        self.command("OPEN_OVERLAY_VOICE_SETTINGS", json!({ "pid": pid::pid() }))?;
+
+        Ok(())
+    }
+
+    /// Opens the guild invite modal of the in-game overlay for
+    /// the given invite code.
+    ///
+    /// Sends the `OPEN_OVERLAY_GUILD_INVITE` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn open_overlay_guild_invite(&mut self, code: &str) -> Result<()> {
+        self.command(
+            "OPEN_OVERLAY_GUILD_INVITE",
+            json!({
+                "code": code,
+                "pid": pid::pid()
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    /// Opens the activity invite modal of the in-game overlay,
+    /// inviting others to join the current activity.
+    ///
+    /// Sends the `OPEN_OVERLAY_ACTIVITY_INVITE` command.
+    ///
+    /// # Errors
+    /// Returns an `Err` variant if sending the command failed, or
+    /// if Discord responded with an error.
+    fn open_overlay_activity_invite(&mut self) -> Result<()> {
+        self.command(
+            "OPEN_OVERLAY_ACTIVITY_INVITE",
+            json!({
+                // 1 is the only action type Discord accepts (join)
+                "type": 1,
+                "pid": pid::pid()
+            }),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<T: DiscordIpc + ?Sized> UndocumentedCommands for T {}
+
+/// A struct representing the relationship between the
+/// current user and another user
+///
+/// Returned by [`UndocumentedCommands::get_relationships`]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Relationship {
+    /// The kind of relationship
+    #[serde(rename = "type")]
+    pub kind: RelationshipType,
+
+    /// The other user
+    pub user: User,
+
+    /// The other user's presence, if Discord provided one
+    #[serde(default)]
+    pub presence: Option<Presence>,
+}
+
+/// An enum representing the kind of a [`Relationship`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RelationshipType {
+    /// No relationship
+    #[default]
+    None,
+    /// The user is a friend
+    Friend,
+    /// The user is blocked
+    Blocked,
+    /// The user has sent a friend request to the current user
+    PendingIncoming,
+    /// The current user has sent a friend request to the user
+    PendingOutgoing,
+    /// The user is an implicit friend (e.g. shares a server)
+    Implicit,
+    /// A relationship type not known to this crate, with its
+    /// raw value
+    Unknown(u64),
+}
+
+impl<'de> Deserialize<'de> for RelationshipType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(match u64::deserialize(deserializer)? {
+            0 => RelationshipType::None,
+            1 => RelationshipType::Friend,
+            2 => RelationshipType::Blocked,
+            3 => RelationshipType::PendingIncoming,
+            4 => RelationshipType::PendingOutgoing,
+            5 => RelationshipType::Implicit,
+            other => RelationshipType::Unknown(other),
+        })
+    }
+}
+
+/// A struct representing a user's presence, as attached to a
+/// [`Relationship`]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Presence {
+    /// The user's status (`online`, `idle`, `dnd`, or `offline`)
+    pub status: String,
+
+    /// The user's current activity, if any
+    #[serde(default)]
+    pub activity: Option<Activity<'static>>,
+}
@@ -0,0 +1,90 @@
+use discord_rich_presence::{
+    activity::{Activity, ActivityType},
+    clock::SystemClock,
+    error,
+};
+use serde_json::json;
+use std::error::Error;
+
+#[test]
+fn test_shorthand_constructors() -> Result<(), Box<dyn Error>> {
+    let cases = [
+        (
+            Activity::playing("Ranked"),
+            json!({ "type": 0, "details": "Ranked" }),
+        ),
+        (
+            Activity::listening("Some Artist"),
+            json!({ "type": 2, "state": "Some Artist" }),
+        ),
+        (
+            Activity::watching("Episode 5"),
+            json!({ "type": 3, "state": "Episode 5" }),
+        ),
+        (
+            Activity::competing("Finals"),
+            json!({ "type": 5, "state": "Finals" }),
+        ),
+    ];
+
+    for (activity, expected) in cases {
+        assert_eq!(serde_json::to_value(&activity)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_type_round_trips() -> Result<(), Box<dyn Error>> {
+    // Custom statuses can't be set over RPC, but are received
+    for value in [0, 1, 2, 3, 4, 5] {
+        let activity: Activity = serde_json::from_value(json!({ "type": value }))?;
+        assert_eq!(serde_json::to_value(&activity)?, json!({ "type": value }));
+    }
+
+    assert_eq!(ActivityType::from(4), ActivityType::Unknown(4));
+    assert_eq!(ActivityType::Competing.value(), 5);
+    Ok(())
+}
+
+#[test]
+fn test_preview_uses_verb() {
+    let activity = Activity::listening("Some Artist").details("Some Track");
+    assert_eq!(
+        activity.preview_at(&SystemClock),
+        "Listening to a game\nSome Track\nSome Artist"
+    );
+
+    let activity = Activity::new().activity_type_opt(None).state("Idle");
+    assert_eq!(activity.preview_at(&SystemClock), "Playing a game\nIdle");
+}
+
+#[test]
+fn test_streaming() -> Result<(), Box<dyn Error>> {
+    let activity = Activity::streaming("Speedrunning", "https://twitch.tv/example")?;
+    assert_eq!(
+        serde_json::to_value(&activity)?,
+        json!({ "type": 1, "details": "Speedrunning", "url": "https://twitch.tv/example" })
+    );
+
+    assert!(matches!(
+        Activity::streaming("Speedrunning", "twitch.tv/example"),
+        Err(error::Error::InvalidStreamUrl { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_streaming_needs_url() {
+    let streaming = Activity::new().activity_type(ActivityType::Streaming);
+    assert!(matches!(
+        streaming.validate(),
+        Err(error::Error::MissingStreamUrl)
+    ));
+    assert!(streaming
+        .url("https://youtube.com/watch?v=example")
+        .validate()
+        .is_ok());
+
+    // Only streaming activities need one
+    assert!(Activity::playing("Ranked").validate().is_ok());
+}
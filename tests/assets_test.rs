@@ -0,0 +1,242 @@
+use discord_rich_presence::{
+    activity::{Activity, AssetSource, Assets, ImageUrlMode, MAX_IMAGE_KEY_LENGTH},
+    assets::{self, ApplicationAsset},
+    error,
+};
+use serde_json::json;
+#[cfg(feature = "http")]
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread::{self, JoinHandle},
+};
+
+fn asset(name: &str) -> ApplicationAsset {
+    ApplicationAsset::new(format!("{}-id", name), name, 1)
+}
+
+fn unknown_key(activity: &Activity, known: &[ApplicationAsset]) -> Option<String> {
+    match assets::validate(activity, known) {
+        Ok(()) => None,
+        Err(error::Error::UnknownAsset { key }) => Some(key),
+        Err(err) => panic!("expected UnknownAsset, got {}", err),
+    }
+}
+
+// Serves one request with the given status and body, returning
+// the request line it received
+#[cfg(feature = "http")]
+fn stub_server(status: &'static str, body: &'static str) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut header = String::new();
+        while reader.read_line(&mut header).unwrap() > 2 {
+            header.clear();
+        }
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .unwrap();
+        request_line.trim_end().to_string()
+    });
+
+    (base, handle)
+}
+
+#[test]
+fn test_validate_known_keys_and_urls() {
+    let known = [asset("logo"), asset("online")];
+
+    let activity = Activity::new().assets(Assets::new().large_image("logo").small_image("online"));
+    assert_eq!(unknown_key(&activity, &known), None);
+
+    let activity = Activity::new().assets(
+        Assets::new()
+            .large_image("https://example.com/cover.png")
+            .small_image("mp:external/abc/image.png"),
+    );
+    assert_eq!(unknown_key(&activity, &[]), None);
+
+    // Activities without images have nothing to check
+    assert_eq!(unknown_key(&Activity::new().state("Idle"), &[]), None);
+}
+
+#[test]
+fn test_validate_reports_typo() {
+    let known = [asset("logo"), asset("online")];
+
+    let activity = Activity::new().assets(Assets::new().large_image("logo").small_image("onlin"));
+    assert_eq!(unknown_key(&activity, &known), Some("onlin".to_string()));
+
+    let activity =
+        Activity::new().assets(Assets::new().large_image("large-imag").small_image("onlin"));
+    assert_eq!(
+        unknown_key(&activity, &known),
+        Some("large-imag".to_string())
+    );
+}
+
+#[test]
+fn test_string_images_are_guessed() {
+    assert_eq!(AssetSource::guess("logo"), AssetSource::Key("logo".into()));
+    assert_eq!(
+        AssetSource::guess("https://example.com/a.png"),
+        AssetSource::Url("https://example.com/a.png".into())
+    );
+    assert_eq!(
+        AssetSource::guess("mp:external/abc/https/example.com/a.png"),
+        AssetSource::External("external/abc/https/example.com/a.png".into())
+    );
+
+    // Guessing is also how images sent by Discord are read
+    let assets: Assets = serde_json::from_value(
+        json!({ "large_image": "mp:external/abc/https/example.com/a.png", "small_image": "logo" }),
+    )
+    .unwrap();
+    assert_eq!(
+        json!(assets),
+        json!({ "large_image": "mp:external/abc/https/example.com/a.png", "small_image": "logo" })
+    );
+}
+
+#[test]
+fn test_validate_only_checks_keys() {
+    // An explicit key is checked even when it looks like a URL
+    let activity = Activity::new().assets(
+        Assets::new()
+            .large_image_source(AssetSource::External("external/abc/https/a.png".into()))
+            .small_image_source(AssetSource::Key("https://example.com/a.png".into())),
+    );
+    assert_eq!(
+        unknown_key(&activity, &[]),
+        Some("https://example.com/a.png".to_string())
+    );
+
+    let err = assets::validate(&activity, &[]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Key \"https://example.com/a.png\" not found among uploaded assets"
+    );
+}
+
+fn invalid_reason(url: &str) -> &'static str {
+    match Assets::new().external_large_image(url, ImageUrlMode::Direct) {
+        Err(error::Error::InvalidImageUrl { reason, .. }) => reason,
+        Err(err) => panic!("expected InvalidImageUrl, got {}", err),
+        Ok(_) => panic!("expected {:?} to be refused", url),
+    }
+}
+
+#[test]
+fn test_external_images_direct() -> Result<(), error::Error> {
+    let assets = Assets::new()
+        .external_large_image("https://example.com/covers/1.png", ImageUrlMode::Direct)?
+        .external_small_image("https://example.com/icon.png", ImageUrlMode::default())?;
+
+    assert_eq!(
+        json!(assets),
+        json!({
+            "large_image": "https://example.com/covers/1.png",
+            "small_image": "https://example.com/icon.png"
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_external_images_media_proxy() -> Result<(), error::Error> {
+    let assets = Assets::new()
+        .external_large_image("https://example.com/covers/1.png", ImageUrlMode::MediaProxy)?
+        .external_small_image(
+            "https://example.com/icon.png?size=64",
+            ImageUrlMode::MediaProxy,
+        )?;
+
+    assert_eq!(
+        json!(assets),
+        json!({
+            "large_image": "mp:external/https/example.com/covers/1.png",
+            "small_image": "mp:external/https/example.com/icon.png?size=64"
+        })
+    );
+
+    // Media proxy keys pass validation without being assets
+    let activity = Activity::new().assets(assets);
+    assert_eq!(unknown_key(&activity, &[]), None);
+    Ok(())
+}
+
+#[test]
+fn test_external_images_are_checked() {
+    assert_eq!(
+        invalid_reason("http://example.com/a.png"),
+        "it isn't an https URL"
+    );
+    assert_eq!(invalid_reason("logo"), "it isn't an https URL");
+    assert_eq!(invalid_reason("https://"), "it isn't an https URL");
+
+    let long = format!("https://example.com/{}", "a".repeat(MAX_IMAGE_KEY_LENGTH));
+    assert_eq!(invalid_reason(&long), "it is too long");
+
+    // The media proxy form is longer, so a URL can fit directly
+    // but not through the proxy
+    let url = format!(
+        "https://example.com/{}",
+        "a".repeat(MAX_IMAGE_KEY_LENGTH - 20)
+    );
+    assert!(Assets::new()
+        .external_large_image(&url, ImageUrlMode::Direct)
+        .is_ok());
+    assert!(Assets::new()
+        .external_large_image(&url, ImageUrlMode::MediaProxy)
+        .is_err());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_fetch_parses_assets() -> Result<(), Box<dyn std::error::Error>> {
+    let (base, server) = stub_server(
+        "200 OK",
+        r#"[{"id":"101","type":2,"name":"logo"},{"id":"102","type":1,"name":"online"}]"#,
+    );
+
+    let fetched = assets::fetch_application_assets_from(&base, "771124766517755954")?;
+    assert_eq!(
+        server.join().unwrap(),
+        "GET /api/oauth2/applications/771124766517755954/assets HTTP/1.1"
+    );
+    assert_eq!(
+        fetched,
+        vec![
+            ApplicationAsset::new("101", "logo", 2),
+            ApplicationAsset::new("102", "online", 1),
+        ]
+    );
+
+    let activity = Activity::new().assets(Assets::new().large_image("logo"));
+    assert_eq!(unknown_key(&activity, &fetched), None);
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_fetch_unknown_application_fails() {
+    let (base, server) = stub_server(
+        "404 Not Found",
+        r#"{"message":"Unknown Application","code":10002}"#,
+    );
+
+    assert!(assets::fetch_application_assets_from(&base, "0").is_err());
+    server.join().unwrap();
+}
@@ -0,0 +1,207 @@
+#![cfg(feature = "capture")]
+
+use discord_rich_presence::{
+    activity::{Activity, Secrets},
+    capture::{FrameRecorder, FrameReplayer, REDACTED},
+    event::{Event, EventKind},
+    test_util::{default_response, response, MockDiscordServer},
+    transport::IpcTransport,
+    DiscordIpc, DiscordIpcClient,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+
+const SECRET: &str = "join-secret";
+
+// Runs the same session against any transport, returning what
+// the client observed
+fn session<T: IpcTransport>(
+    client: &mut DiscordIpcClient<T>,
+) -> Result<(Value, Vec<Event>), Box<dyn Error>> {
+    client.connect()?;
+    client.set_activity(
+        Activity::new()
+            .state("Recorded")
+            .secrets(Secrets::new().join(SECRET)),
+    )?;
+    let guilds = client.command("GET_GUILDS", json!({}))?;
+    let events = vec![client.recv_event()?, client.recv_event()?];
+
+    Ok((guilds, events))
+}
+
+#[test]
+fn test_record_and_replay() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.respond_with(|opcode, request| {
+        if request["cmd"] != "GET_GUILDS" {
+            return default_response(opcode, request);
+        }
+
+        vec![
+            response(
+                request,
+                json!({ "guilds": [{ "id": "1", "name": "Guild" }] }),
+            ),
+            (
+                1,
+                json!({
+                    "cmd": "DISPATCH",
+                    "evt": "ACTIVITY_JOIN",
+                    "data": { "secret": SECRET },
+                    "nonce": null
+                }),
+            ),
+        ]
+    });
+
+    let mut client = DiscordIpcClient::with_transport(
+        "771124766517755954",
+        FrameRecorder::new(server.transport(), Vec::new()),
+    );
+    let (guilds, events) = session(&mut client)?;
+    let capture = client.transport().output().clone();
+
+    // Every frame is a line, and no secret is written
+    let text = String::from_utf8(capture.clone())?;
+    assert!(!text.contains(SECRET));
+    let lines: Vec<Value> = text
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    let directions: Vec<_> = lines
+        .iter()
+        .map(|line| {
+            (
+                line["direction"].as_str().unwrap(),
+                line["opcode"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        directions,
+        [
+            ("outbound", 0),
+            ("inbound", 1),
+            ("outbound", 1),
+            ("inbound", 1),
+            ("outbound", 1),
+            ("inbound", 1),
+            ("inbound", 1),
+        ]
+    );
+    assert_eq!(
+        lines[2]["payload"]["args"]["activity"]["secrets"]["join"],
+        REDACTED
+    );
+    assert!(lines.iter().all(|line| line["elapsed_ms"].is_u64()));
+
+    // Replaying the capture reproduces the session
+    let mut replay = DiscordIpcClient::with_transport(
+        "771124766517755954",
+        FrameReplayer::from_reader(&capture[..])?,
+    );
+    let (replayed_guilds, replayed_events) = session(&mut replay)?;
+    assert_eq!(replayed_guilds, guilds);
+    assert!(matches!(replayed_events[0], Event::Ready(_)));
+    match (&events[1], &replayed_events[1]) {
+        (
+            Event::Dispatch {
+                kind: EventKind::ActivityJoin,
+                data: recorded,
+            },
+            Event::Dispatch {
+                kind: EventKind::ActivityJoin,
+                data: replayed,
+            },
+        ) => {
+            assert_eq!(recorded["secret"], SECRET);
+            assert_eq!(replayed["secret"], REDACTED);
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    let replayer = replay.transport();
+    assert!(replayer.is_finished());
+    let commands: Vec<_> = replayer
+        .sent()
+        .iter()
+        .map(|(_, payload)| payload["cmd"].clone())
+        .collect();
+    assert_eq!(
+        commands,
+        [Value::Null, json!("SET_ACTIVITY"), json!("GET_GUILDS")]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_replay_rejects_invalid_capture() {
+    let capture = br#"{"elapsed_ms":0,"direction":"sideways","opcode":1,"payload":{}}"#;
+    assert!(FrameReplayer::from_reader(&capture[..]).is_err());
+}
+
+// Records an authorization, with the responses Discord gives to
+// `AUTHORIZE` and `AUTHENTICATE`
+fn record_authorization(redact_secrets: bool) -> Result<Vec<Value>, Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.respond_with(|opcode, request| match request["cmd"].as_str() {
+        Some("AUTHORIZE") => vec![response(request, json!({ "code": "oauth-code" }))],
+        Some("AUTHENTICATE") => vec![response(
+            request,
+            json!({
+                "access_token": "access-token",
+                "application": { "id": "771124766517755954", "name": "App" },
+                "expires": "2030-01-01T00:00:00.000000+00:00",
+                "scopes": ["rpc"],
+                "user": { "id": "1", "username": "mock" }
+            }),
+        )],
+        _ => default_response(opcode, request),
+    });
+
+    let mut client = DiscordIpcClient::with_transport(
+        "771124766517755954",
+        FrameRecorder::new(server.transport(), Vec::new()).redact_secrets(redact_secrets),
+    );
+    client.connect()?;
+    client.command(
+        "AUTHORIZE",
+        json!({ "client_id": "771124766517755954", "scopes": ["rpc"] }),
+    )?;
+    client.command("AUTHENTICATE", json!({ "access_token": "access-token" }))?;
+
+    let capture = String::from_utf8(client.transport().output().clone())?;
+    Ok(capture
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?)
+}
+
+#[test]
+fn test_authorization_is_redacted() -> Result<(), Box<dyn Error>> {
+    let lines = record_authorization(true)?;
+    let text = lines
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(!text.contains("oauth-code"));
+    assert!(!text.contains("access-token"));
+
+    assert_eq!(lines[3]["payload"]["data"], json!({ "code": REDACTED }));
+    assert_eq!(lines[4]["payload"]["args"]["access_token"], REDACTED);
+    assert_eq!(lines[5]["payload"]["data"]["access_token"], REDACTED);
+    // Everything else is kept
+    assert_eq!(lines[5]["payload"]["data"]["scopes"], json!(["rpc"]));
+    assert_eq!(lines[5]["payload"]["data"]["user"]["username"], "mock");
+    Ok(())
+}
+
+#[test]
+fn test_redaction_can_be_turned_off() -> Result<(), Box<dyn Error>> {
+    let lines = record_authorization(false)?;
+    assert_eq!(lines[3]["payload"]["data"]["code"], "oauth-code");
+    assert_eq!(lines[5]["payload"]["data"]["access_token"], "access-token");
+    Ok(())
+}
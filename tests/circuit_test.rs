@@ -0,0 +1,100 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    circuit::{CircuitBreaker, CircuitState},
+    error,
+    test_util::MockClock,
+    DiscordIpc,
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+const COOL_DOWN: Duration = Duration::from_secs(30);
+
+#[test]
+fn test_transitions() {
+    let start = Instant::now();
+    let mut breaker = CircuitBreaker::new(3, COOL_DOWN);
+    assert_eq!(breaker.state(start), CircuitState::Closed);
+
+    // Failures below the threshold keep it closed
+    breaker.record_failure(start);
+    breaker.record_failure(start);
+    assert_eq!(breaker.state(start), CircuitState::Closed);
+    assert!(breaker.try_attempt(start).is_ok());
+
+    breaker.record_failure(start);
+    assert_eq!(
+        breaker.state(start),
+        CircuitState::Open {
+            retry_in: COOL_DOWN
+        }
+    );
+    let later = start + Duration::from_secs(10);
+    assert_eq!(breaker.try_attempt(later), Err(Duration::from_secs(20)));
+
+    // A failed probe opens it again at once
+    let probe = start + COOL_DOWN;
+    assert_eq!(breaker.state(probe), CircuitState::HalfOpen);
+    assert!(breaker.try_attempt(probe).is_ok());
+    breaker.record_failure(probe);
+    assert_eq!(
+        breaker.state(probe),
+        CircuitState::Open {
+            retry_in: COOL_DOWN
+        }
+    );
+
+    // And a successful one closes it
+    let probe = probe + COOL_DOWN;
+    assert_eq!(breaker.state(probe), CircuitState::HalfOpen);
+    breaker.record_success();
+    assert_eq!(breaker.state(probe), CircuitState::Closed);
+    assert_eq!(breaker.failures(), 0);
+}
+
+#[test]
+fn test_success_resets_failures() {
+    let now = Instant::now();
+    let mut breaker = CircuitBreaker::new(2, COOL_DOWN);
+
+    breaker.record_failure(now);
+    breaker.record_success();
+    breaker.record_failure(now);
+    assert_eq!(breaker.state(now), CircuitState::Closed);
+}
+
+#[test]
+fn test_client_refuses_while_open() -> Result<(), Box<dyn Error>> {
+    let clock = MockClock::new();
+    let mut client = MockClient::new("771124766517755954").fail_connects(2);
+    client.set_circuit_breaker(Some(CircuitBreaker::new(2, COOL_DOWN).clock(clock.clone())));
+
+    assert!(client.connect().is_err());
+    assert!(client.connect().is_err());
+    assert_eq!(
+        client.circuit_state(),
+        CircuitState::Open {
+            retry_in: COOL_DOWN
+        }
+    );
+
+    // Discord is back, but no attempt is made until cooled down
+    for attempt in [client.connect(), client.reconnect()] {
+        let err = attempt.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<error::Error>(),
+            Some(error::Error::CircuitOpen { retry_in }) if *retry_in == COOL_DOWN
+        ));
+    }
+    assert!(client.sent.is_empty());
+
+    clock.advance(COOL_DOWN);
+    assert_eq!(client.circuit_state(), CircuitState::HalfOpen);
+    client.connect()?;
+    assert_eq!(client.circuit_state(), CircuitState::Closed);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "test-util")]
+
+use discord_rich_presence::{
+    activity::Activity,
+    rate_limit::{RateLimitPolicy, RateLimiter},
+    test_util::MockDiscordServer,
+    DiscordIpc,
+};
+use serde_json::json;
+use std::{error::Error, time::Duration};
+
+#[test]
+fn test_switching_handshakes_with_new_id() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    client.set_client_id("1024391201209057341")?;
+    assert_eq!(client.client_id(), "1024391201209057341");
+
+    let received = server.received();
+    let opcodes: Vec<_> = received.iter().map(|(opcode, _)| *opcode).collect();
+    assert_eq!(opcodes, [0, 2, 0]);
+    assert_eq!(
+        received[2].1,
+        json!({ "v": 1, "client_id": "1024391201209057341" })
+    );
+    assert_eq!(server.connections(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_switching_unconnected_client_stays_unconnected() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    client.set_client_id("1024391201209057341")?;
+    assert_eq!(server.connections(), 0);
+
+    client.connect()?;
+    assert_eq!(server.received()[0].1["client_id"], "1024391201209057341");
+    Ok(())
+}
+
+#[test]
+fn test_switching_drops_held_back_activity() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    client.set_rate_limiter(Some(
+        RateLimiter::new(1, Duration::from_secs(60)).policy(RateLimitPolicy::Coalesce),
+    ));
+
+    client.set_activity(Activity::new().state("Old game"))?;
+    client.set_activity(Activity::new().state("Still the old game"))?;
+    client.set_client_id("1024391201209057341")?;
+
+    // Nothing is held back for the new application
+    assert!(client.flush_activity()?);
+    let updates = server
+        .received()
+        .iter()
+        .filter(|(_, frame)| frame["cmd"] == "SET_ACTIVITY")
+        .count();
+    assert_eq!(updates, 1);
+    Ok(())
+}
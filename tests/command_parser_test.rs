@@ -0,0 +1,195 @@
+mod common;
+
+use common::{default_response, MockClient};
+use discord_rich_presence::{error, DiscordIpc};
+use quickcheck::{quickcheck, Arbitrary, Gen};
+use serde_json::{json, Map, Value};
+
+// Any JSON value, nested a few levels deep
+#[derive(Clone, Debug)]
+struct Json(Value);
+
+impl Json {
+    fn generate(g: &mut Gen, depth: usize) -> Value {
+        let kinds: &[u8] = if depth == 0 {
+            &[0, 1, 2, 3]
+        } else {
+            &[0, 1, 2, 3, 4, 5]
+        };
+        match g.choose(kinds).unwrap() {
+            0 => Value::Null,
+            1 => Value::Bool(bool::arbitrary(g)),
+            2 => match u8::arbitrary(g) % 3 {
+                0 => json!(i64::arbitrary(g)),
+                1 => json!(u64::arbitrary(g)),
+                // Exactly representable, as serde_json may round
+                // other floats when parsing
+                _ => json!(f64::from(i32::arbitrary(g)) / 8.0),
+            },
+            3 => Value::String(String::arbitrary(g)),
+            4 => Value::Array(
+                (0..usize::arbitrary(g) % 4)
+                    .map(|_| Json::generate(g, depth - 1))
+                    .collect(),
+            ),
+            _ => Value::Object(
+                (0..usize::arbitrary(g) % 4)
+                    .map(|_| (String::arbitrary(g), Json::generate(g, depth - 1)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Arbitrary for Json {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Json(Json::generate(g, 3))
+    }
+}
+
+// How a response's nonce relates to its command's
+#[derive(Clone, Debug)]
+enum Nonce {
+    Matching,
+    Missing,
+    Other(Json),
+}
+
+impl Arbitrary for Nonce {
+    fn arbitrary(g: &mut Gen) -> Self {
+        match u8::arbitrary(g) % 3 {
+            0 => Nonce::Matching,
+            1 => Nonce::Missing,
+            _ => Nonce::Other(Json::arbitrary(g)),
+        }
+    }
+}
+
+// A response to a command, shaped like Discord's but with each
+// part present, absent or of the wrong type
+#[derive(Clone, Debug)]
+struct Response {
+    dispatch: bool,
+    evt: Option<Json>,
+    error: bool,
+    code: Option<Json>,
+    data: Option<Json>,
+    nonce: Nonce,
+}
+
+impl Arbitrary for Response {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Response {
+            dispatch: u8::arbitrary(g) % 4 == 0,
+            evt: Option::arbitrary(g),
+            error: bool::arbitrary(g),
+            code: Option::arbitrary(g),
+            data: Option::arbitrary(g),
+            nonce: Nonce::arbitrary(g),
+        }
+    }
+}
+
+impl Response {
+    fn payload(&self, request: &Value) -> Value {
+        let mut payload = Map::new();
+        let cmd = if self.dispatch {
+            "DISPATCH"
+        } else {
+            "GET_GUILDS"
+        };
+        payload.insert("cmd".to_string(), json!(cmd));
+
+        if self.error {
+            payload.insert("evt".to_string(), json!("ERROR"));
+            let mut data = match &self.data {
+                Some(Json(Value::Object(data))) => data.clone(),
+                _ => Map::new(),
+            };
+            if let Some(Json(code)) = &self.code {
+                data.insert("code".to_string(), code.clone());
+            }
+            payload.insert("data".to_string(), Value::Object(data));
+        } else {
+            if let Some(Json(evt)) = &self.evt {
+                payload.insert("evt".to_string(), evt.clone());
+            }
+            if let Some(Json(data)) = &self.data {
+                payload.insert("data".to_string(), data.clone());
+            }
+        }
+
+        match &self.nonce {
+            Nonce::Matching => {
+                payload.insert("nonce".to_string(), request["nonce"].clone());
+            }
+            Nonce::Missing => (),
+            Nonce::Other(Json(nonce)) => {
+                payload.insert("nonce".to_string(), nonce.clone());
+            }
+        }
+        Value::Object(payload)
+    }
+}
+
+// Sends a command answered with the single frame `respond` builds
+// from it, returning the command's result and the command sent
+fn command_answered_with(
+    respond: impl Fn(&Value) -> (u32, Value) + Send + 'static,
+) -> (Result<Value, Box<dyn std::error::Error>>, Value) {
+    let mut client =
+        MockClient::new("771124766517755954").respond_with(move |opcode, request| match opcode {
+            1 => vec![respond(request)],
+            _ => default_response(opcode, request),
+        });
+
+    client.connect().expect("handshake is answered by default");
+    let result = client.command("GET_GUILDS", json!({}));
+    (result, client.sent[1].1.clone())
+}
+
+quickcheck! {
+    fn prop_responses_are_classified(response: Response) -> bool {
+        let shape = response.clone();
+        let (result, request) = command_answered_with(move |request| (1, shape.payload(request)));
+        let payload = response.payload(&request);
+
+        let matched = payload.get("nonce") == Some(&request["nonce"]);
+        let command_error = result
+            .as_ref()
+            .err()
+            .and_then(|err| err.downcast_ref::<error::Error>());
+        if payload["cmd"] == "DISPATCH" || !matched {
+            // An event or someone else's response, after which the
+            // mock has nothing more to read
+            return result.is_err() && command_error.is_none();
+        }
+
+        if payload["evt"] == "ERROR" {
+            let data = &payload["data"];
+            return matches!(
+                command_error,
+                Some(error::Error::CommandError { code, message })
+                    if *code == data["code"].as_u64().unwrap_or_default()
+                        && message == data["message"].as_str().unwrap_or_default()
+            );
+        }
+        result.ok() == Some(payload["data"].clone())
+    }
+
+    fn prop_any_frame_never_panics(opcode: u8, payload: Json) -> bool {
+        // Any outcome is fine, as long as it is returned
+        let opcode = u32::from(opcode % 5);
+        let _ = command_answered_with(move |_| (opcode, payload.0.clone()));
+        true
+    }
+}
+
+// Payloads which once panicked while being classified
+#[test]
+fn test_non_object_payloads() {
+    for payload in [json!("data"), json!([1, 2]), json!(4000), json!(true)] {
+        let (result, _) = command_answered_with(move |_| (1, payload.clone()));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! An in-memory stand-in for the Discord IPC, for tests that
+//! shouldn't depend on a running Discord client.
+#![allow(dead_code)]
+
+use discord_rich_presence::{ClientState, DiscordIpc, IpcConnection};
+use serde_json::{json, Value};
+use std::{collections::VecDeque, convert::TryInto, error::Error, thread, time::Duration};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+type Responder = Box<dyn FnMut(u32, &Value) -> Vec<(u32, Value)> + Send>;
+
+/// A client which records every frame written to it, and replies
+/// to each one using a scriptable responder.
+///
+/// By default, commands are answered with an empty success
+/// response carrying the command's nonce, and handshakes with a
+/// `READY` dispatch.
+pub struct MockClient {
+    client_id: String,
+    /// Every frame written by the client, as `(opcode, payload)`
+    pub sent: Vec<(u32, Value)>,
+    written: Vec<u8>,
+    incoming: VecDeque<u8>,
+    responder: Responder,
+    state: ClientState,
+    connect_failures: usize,
+}
+
+impl MockClient {
+    pub fn new(client_id: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            sent: Vec::new(),
+            written: Vec::new(),
+            incoming: VecDeque::new(),
+            responder: Box::new(default_response),
+            state: ClientState::default(),
+            connect_failures: 0,
+        }
+    }
+
+    /// Replaces the responder used to answer written frames
+    pub fn respond_with(
+        mut self,
+        responder: impl FnMut(u32, &Value) -> Vec<(u32, Value)> + Send + 'static,
+    ) -> Self {
+        self.responder = Box::new(responder);
+        self
+    }
+
+    /// Makes the next `count` connection attempts fail
+    pub fn fail_connects(mut self, count: usize) -> Self {
+        self.connect_failures = count;
+        self
+    }
+
+    /// Queues a frame to be read by the client
+    pub fn push_frame(&mut self, opcode: u32, payload: Value) {
+        let data = payload.to_string();
+        self.incoming.extend(opcode.to_le_bytes());
+        self.incoming.extend((data.len() as u32).to_le_bytes());
+        self.incoming.extend(data.into_bytes());
+    }
+
+    /// The payload of the last frame written, with its nonce removed
+    pub fn last_sent(&self) -> Value {
+        let (_, payload) = self.sent.last().expect("no frames were sent");
+        without_nonce(payload)
+    }
+}
+
+/// Returns a copy of `payload` with its `nonce` key removed
+pub fn without_nonce(payload: &Value) -> Value {
+    let mut payload = payload.clone();
+    if let Some(object) = payload.as_object_mut() {
+        object.remove("nonce");
+    }
+    payload
+}
+
+/// Builds a successful response to `request` carrying `data`
+pub fn response(request: &Value, data: Value) -> (u32, Value) {
+    (
+        1,
+        json!({
+            "cmd": request["cmd"],
+            "data": data,
+            "evt": null,
+            "nonce": request["nonce"]
+        }),
+    )
+}
+
+/// Returns the activity set by each `SET_ACTIVITY` command among
+/// `frames`, in order, with `null` for each clear
+pub fn activity_updates(frames: &[(u32, Value)]) -> Vec<Value> {
+    frames
+        .iter()
+        .filter(|(_, payload)| payload["cmd"] == "SET_ACTIVITY")
+        .map(|(_, payload)| payload["args"]["activity"].clone())
+        .collect()
+}
+
+/// The responder used when none is given, which custom responders
+/// can fall back to
+pub fn default_response(opcode: u32, request: &Value) -> Vec<(u32, Value)> {
+    match opcode {
+        0 => vec![(
+            1,
+            json!({
+                "cmd": "DISPATCH",
+                "evt": "READY",
+                "data": { "v": 1, "config": {}, "user": { "id": "1", "username": "mock" } },
+                "nonce": null
+            }),
+        )],
+        1 => vec![response(request, Value::Null)],
+        _ => Vec::new(),
+    }
+}
+
+impl IpcConnection for MockClient {
+    fn connect_ipc(&mut self) -> Result<()> {
+        if self.connect_failures > 0 {
+            self.connect_failures -= 1;
+            return Err("mock connection refused".into());
+        }
+
+        // A new connection starts without any unread data
+        self.written.clear();
+        self.incoming.clear();
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.written.extend_from_slice(data);
+
+        while self.written.len() >= 8 {
+            let opcode = u32::from_le_bytes(self.written[..4].try_into()?);
+            let length = u32::from_le_bytes(self.written[4..8].try_into()?) as usize;
+            if self.written.len() < 8 + length {
+                break;
+            }
+
+            let frame: Vec<u8> = self.written.drain(..8 + length).collect();
+            let payload: Value = serde_json::from_slice(&frame[8..])?;
+            for (opcode, reply) in (self.responder)(opcode, &payload) {
+                self.push_frame(opcode, reply);
+            }
+            self.sent.push((opcode, payload));
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        if self.incoming.len() < buffer.len() {
+            return Err("mock connection has no more data".into());
+        }
+
+        for byte in buffer.iter_mut() {
+            *byte = self.incoming.pop_front().unwrap();
+        }
+
+        Ok(())
+    }
+
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool> {
+        if self.incoming.is_empty() {
+            // Nothing can arrive until the client writes, but avoid
+            // a busy loop in anything polling
+            thread::sleep(timeout.min(Duration::from_millis(1)));
+        }
+
+        Ok(!self.incoming.is_empty())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.send(json!({}), 2)
+    }
+
+    fn get_client_id(&self) -> &String {
+        &self.client_id
+    }
+
+    fn client_state(&mut self) -> &mut ClientState {
+        &mut self.state
+    }
+}
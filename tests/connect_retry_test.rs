@@ -0,0 +1,76 @@
+mod common;
+
+use common::{default_response, MockClient};
+use discord_rich_presence::{error, test_util::MockClock, DiscordIpc};
+use serde_json::json;
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn test_retries_until_connected() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").fail_connects(2);
+    let clock = MockClock::new();
+    client.set_clock(clock.clone());
+
+    let start = Instant::now();
+    client.connect_with_retries(5, Duration::from_secs(20))?;
+
+    // Waited 20s, then 40s, on the client's clock only
+    assert_eq!(clock.elapsed(), Duration::from_secs(60));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(client.sent.len(), 1);
+    assert_eq!(client.sent[0].0, 0);
+    Ok(())
+}
+
+#[test]
+fn test_reports_attempts_made() {
+    let mut client = MockClient::new("771124766517755954").fail_connects(usize::MAX);
+
+    let err = client
+        .connect_with_retries(3, Duration::from_millis(1))
+        .unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::RetriesExhausted { attempts, last }) => {
+            assert_eq!(*attempts, 3);
+            assert_eq!(last.to_string(), "mock connection refused");
+        }
+        other => panic!("expected RetriesExhausted, got {:?}", other),
+    }
+    assert_eq!(
+        err.to_string(),
+        "Couldn't connect to Discord after 3 attempts: mock connection refused"
+    );
+    assert!(err.source().is_some());
+
+    // No attempts still means one
+    let err = client.connect_with_retries(0, Duration::ZERO).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::RetriesExhausted { attempts: 1, .. })
+    ));
+}
+
+#[test]
+fn test_rejected_handshake_not_retried() {
+    let mut client = MockClient::new("0").respond_with(|opcode, request| match opcode {
+        0 => vec![(2, json!({ "code": 4000, "message": "Invalid Client ID" }))],
+        _ => default_response(opcode, request),
+    });
+
+    let err = client
+        .connect_with_retries(5, Duration::from_secs(1))
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::ConnectionClosed { code: 4000, .. })
+    ));
+    let handshakes = client
+        .sent
+        .iter()
+        .filter(|(opcode, _)| *opcode == 0)
+        .count();
+    assert_eq!(handshakes, 1);
+}
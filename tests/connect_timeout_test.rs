@@ -0,0 +1,117 @@
+#![cfg(unix)]
+
+use discord_rich_presence::{
+    error,
+    test_util::{MockClock, MockDiscordServer},
+    transport::{IpcTransport, NativeTransport},
+    DiscordIpc,
+};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs, thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn test_connects_within_timeout() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    client.connect_with_timeout(Duration::from_secs(5))?;
+    assert!(client.connected_endpoint().is_some());
+    Ok(())
+}
+
+#[test]
+fn test_unrepresentable_timeout_is_unbounded() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    client.connect_with_timeout(Duration::MAX)?;
+    assert!(client.connected_endpoint().is_some());
+
+    let mut transport = server.transport();
+    transport.connect_timeout(Duration::MAX)?;
+    Ok(())
+}
+
+#[test]
+fn test_unanswered_handshake_times_out() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    server.delay_next_response(Duration::from_secs(2));
+    let start = Instant::now();
+    let err = client
+        .connect_with_timeout(Duration::from_millis(200))
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::Timeout { timeout }) if *timeout == Duration::from_millis(200)
+    ));
+    assert_eq!(err.to_string(), "Couldn't connect to Discord within 200ms");
+
+    // The half-open connection was closed
+    assert_eq!(client.connected_endpoint(), None);
+
+    client.connect_with_timeout(Duration::from_secs(5))?;
+    Ok(())
+}
+
+#[test]
+fn test_timeout_measured_by_client_clock() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    let clock = MockClock::new();
+    client.set_clock(clock.clone());
+
+    // The timeout passes once the clock does, however little real
+    // time that took
+    server.delay_next_response(Duration::from_secs(5));
+    let advancing = clock.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        advancing.advance(Duration::from_secs(60));
+    });
+    let start = Instant::now();
+    let err = client
+        .connect_with_timeout(Duration::from_secs(60))
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::Timeout { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_search_keeps_its_errors() -> Result<(), Box<dyn Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-timeout-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let mut env = HashMap::new();
+    env.insert("XDG_RUNTIME_DIR".to_string(), dir.display().to_string());
+
+    // Finding nothing in time is reported as it is without a timeout
+    let err = NativeTransport::with_env(env.clone())
+        .connect_timeout(Duration::from_secs(5))
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::DiscordNotRunning)
+    ));
+
+    let err = NativeTransport::with_env(env)
+        .connect_timeout(Duration::ZERO)
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::Timeout { .. })
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::Activity, error, test_util::MockDiscordServer, DiscordIpc, IpcConnection,
+};
+use serde_json::json;
+use std::error::Error;
+
+fn is_not_connected(err: &(dyn Error + 'static)) -> bool {
+    matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::NotConnected)
+    )
+}
+
+#[test]
+fn test_connection_state() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    assert!(!client.is_connected());
+
+    // Commands fail before anything is written
+    let err = client
+        .set_activity(Activity::new().state("early"))
+        .unwrap_err();
+    assert!(is_not_connected(&*err));
+    assert_eq!(err.to_string(), "The client isn't connected to Discord");
+    assert!(server.received().is_empty());
+
+    client.connect()?;
+    assert!(client.is_connected());
+    client.set_activity(Activity::new().state("connected"))?;
+
+    client.close()?;
+    assert!(!client.is_connected());
+    let err = client.clear_activity().unwrap_err();
+    assert!(is_not_connected(&*err));
+    Ok(())
+}
+
+#[test]
+fn test_lost_connection() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    // The update that finds Discord gone fails as it did, and the
+    // ones after it fail early
+    server.hang_up_next_frame();
+    let err = client
+        .set_activity(Activity::new().state("lost"))
+        .unwrap_err();
+    assert!(!is_not_connected(&*err));
+    assert!(!client.is_connected());
+    let err = client
+        .set_activity(Activity::new().state("lost"))
+        .unwrap_err();
+    assert!(is_not_connected(&*err));
+
+    client.reconnect()?;
+    assert!(client.is_connected());
+    client.set_activity(Activity::new().state("found"))?;
+    Ok(())
+}
+
+#[test]
+fn test_reconnect_without_connecting() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    // There's nothing to close, so only the handshake is sent
+    client.reconnect()?;
+    assert!(client.is_connected());
+    let opcodes: Vec<u32> = server
+        .received()
+        .iter()
+        .map(|(opcode, _)| *opcode)
+        .collect();
+    assert_eq!(opcodes, [0]);
+    Ok(())
+}
+
+#[test]
+fn test_fresh_client() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+
+    // Closing a client that never connected has nothing to do
+    client.close()?;
+    assert!(!client.is_connected());
+
+    let err = client.send(json!({}), 1).unwrap_err();
+    assert!(is_not_connected(&*err));
+    let err = client.recv().unwrap_err();
+    assert!(is_not_connected(&*err));
+
+    assert!(server.received().is_empty());
+    assert_eq!(server.connections(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_close_any_connection() -> Result<(), Box<dyn Error>> {
+    // Connections other than `DiscordIpcClient` are closed alike
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    assert!(client.client_state().is_connected());
+    client.close()?;
+    assert!(!client.client_state().is_connected());
+    Ok(())
+}
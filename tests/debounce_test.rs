@@ -0,0 +1,138 @@
+mod common;
+
+use common::{activity_updates, MockClient};
+use discord_rich_presence::{
+    activity::Activity,
+    debounce::{DebouncedActivityUpdater, Debouncer},
+    shared::SharedDiscordIpcClient,
+    test_util::MockClock,
+};
+use serde_json::Value;
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+const QUIET: Duration = Duration::from_millis(50);
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+fn sent(client: &SharedDiscordIpcClient<MockClient>) -> Vec<(u32, Value)> {
+    client.with_client(|client| client.sent.clone())
+}
+
+fn shared_client() -> Result<SharedDiscordIpcClient<MockClient>, Box<dyn Error>> {
+    let client = SharedDiscordIpcClient::new(MockClient::new("771124766517755954"));
+    client.connect()?;
+    Ok(client)
+}
+
+#[test]
+fn test_debouncer_keeps_last_of_burst() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(ms(100));
+    assert_eq!(debouncer.deadline(), None);
+
+    for (i, &at) in [0, 30, 60, 90].iter().enumerate() {
+        debouncer.update(i, start + ms(at));
+        assert_eq!(debouncer.poll(start + ms(at)), None);
+    }
+
+    // Each update restarted the quiet period
+    assert_eq!(debouncer.deadline(), Some(start + ms(190)));
+    assert_eq!(debouncer.poll(start + ms(150)), None);
+    assert_eq!(debouncer.poll(start + ms(190)), Some(3));
+    assert_eq!(debouncer.poll(start + ms(1000)), None);
+}
+
+#[test]
+fn test_debouncer_take() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(ms(100));
+
+    debouncer.update("a", start);
+    assert_eq!(debouncer.take(), Some("a"));
+    assert_eq!(debouncer.take(), None);
+    assert_eq!(debouncer.deadline(), None);
+}
+
+#[test]
+fn test_only_last_update_is_sent() -> Result<(), Box<dyn Error>> {
+    let client = shared_client()?;
+    let updater = DebouncedActivityUpdater::new(client.clone(), QUIET)?;
+
+    for state in ["1", "2", "3", "4"] {
+        updater.update(Activity::new().state(state));
+    }
+    assert!(activity_updates(&sent(&client)).is_empty());
+
+    thread::sleep(QUIET * 4);
+    let updates = activity_updates(&sent(&client));
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["state"], "4");
+    Ok(())
+}
+
+#[test]
+fn test_flush() -> Result<(), Box<dyn Error>> {
+    let client = shared_client()?;
+    let updater = DebouncedActivityUpdater::new(client.clone(), Duration::from_secs(60))?;
+
+    updater.update(Activity::new().state("1"));
+    updater.clear();
+    updater.flush()?;
+    assert_eq!(activity_updates(&sent(&client)), [Value::Null]);
+
+    // Nothing is left to flush
+    updater.flush()?;
+    assert_eq!(activity_updates(&sent(&client)).len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_drop_discards_pending_update() -> Result<(), Box<dyn Error>> {
+    let client = shared_client()?;
+    let updater = DebouncedActivityUpdater::new(client.clone(), Duration::from_secs(60))?;
+
+    updater.update(Activity::new().state("1"));
+    drop(updater);
+    assert!(activity_updates(&sent(&client)).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_flush_on_drop() -> Result<(), Box<dyn Error>> {
+    let client = shared_client()?;
+    let updater =
+        DebouncedActivityUpdater::new(client.clone(), Duration::from_secs(60))?.flush_on_drop(true);
+
+    updater.update(Activity::new().state("1"));
+    drop(updater);
+    assert_eq!(activity_updates(&sent(&client))[0]["state"], "1");
+    Ok(())
+}
+
+#[test]
+fn test_quiet_period_measured_by_clock() -> Result<(), Box<dyn Error>> {
+    let client = shared_client()?;
+    let clock = MockClock::new();
+    let updater = DebouncedActivityUpdater::new(client.clone(), Duration::from_secs(60))?
+        .clock(clock.clone());
+
+    updater.update(Activity::new().state("1"));
+    thread::sleep(ms(200));
+    assert!(activity_updates(&sent(&client)).is_empty());
+
+    // Moving the clock past the quiet period sends the update
+    clock.advance(Duration::from_secs(60));
+    let start = Instant::now();
+    while activity_updates(&sent(&client)).is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(ms(10));
+    }
+    assert_eq!(activity_updates(&sent(&client))[0]["state"], "1");
+    Ok(())
+}
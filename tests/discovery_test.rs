@@ -0,0 +1,502 @@
+#![cfg(unix)]
+
+use discord_rich_presence::{
+    error,
+    test_util::MockDiscordServer,
+    transport::{
+        ConnectedEndpoint, EndpointSource, IpcTransport, NativeTransport, DEFAULT_PIPE_RANGE,
+        IPC_PATH_VAR,
+    },
+    DiscordIpc, DiscordIpcClient,
+};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, ErrorKind},
+    os::unix::net::UnixListener,
+    path::{Path, PathBuf},
+};
+
+fn environment(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+// The sockets searched for each of the first `count` instances,
+// in `dir`
+fn expected_paths(dir: &str, count: u32) -> Vec<PathBuf> {
+    (0..count)
+        .flat_map(|i| {
+            [
+                "",
+                "app/com.discordapp.Discord/",
+                "snap.discord-canary/",
+                "snap.discord/",
+            ]
+            .iter()
+            .map(move |subpath| {
+                Path::new(dir)
+                    .join(subpath)
+                    .join(format!("discord-ipc-{}", i))
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_candidate_paths() {
+    let cases: [(&[(&str, &str)], &str); 5] = [
+        (&[("XDG_RUNTIME_DIR", "/run/user/1000")], "/run/user/1000"),
+        (
+            &[("XDG_RUNTIME_DIR", "/run/user/1000"), ("TMPDIR", "/tmp")],
+            "/run/user/1000",
+        ),
+        (&[("TMPDIR", "/var/tmp"), ("TMP", "/tmp")], "/var/tmp"),
+        (&[("TMP", "/tmp"), ("TEMP", "/temp")], "/tmp"),
+        (&[("TEMP", "/temp"), ("HOME", "/home/user")], "/temp"),
+    ];
+
+    for (vars, dir) in cases {
+        let transport = NativeTransport::with_env(environment(vars));
+        let paths = transport.candidate_paths();
+        assert_eq!(paths.len(), 40, "for {:?}", vars);
+        assert_eq!(paths, expected_paths(dir, 10), "for {:?}", vars);
+    }
+}
+
+#[test]
+fn test_fallback_dirs() {
+    // Without any directory named, /tmp is searched, then /var/tmp,
+    // rather than the working directory
+    for vars in [&[][..], &[("HOME", "/home/user"), ("TMPDIRS", "/tmp")]] {
+        let transport = NativeTransport::with_env(environment(vars));
+        let mut expected = expected_paths("/tmp", 10);
+        expected.extend(expected_paths("/var/tmp", 10));
+        assert_eq!(transport.candidate_paths(), expected, "for {:?}", vars);
+    }
+}
+
+#[test]
+fn test_client_lists_transport_candidates() -> Result<(), Box<dyn std::error::Error>> {
+    let client = DiscordIpcClient::new("771124766517755954")?;
+    assert_eq!(
+        client.candidate_paths(),
+        NativeTransport::new().candidate_paths()
+    );
+    assert_eq!(client.candidate_paths().len() % 40, 0);
+    Ok(())
+}
+
+#[test]
+fn test_explicit_path_skips_discovery() {
+    let transport = NativeTransport::with_path("/somewhere/discord-ipc-3");
+    assert_eq!(
+        transport.candidate_paths(),
+        [PathBuf::from("/somewhere/discord-ipc-3")]
+    );
+}
+
+#[test]
+fn test_client_with_socket_path() -> Result<(), Box<dyn std::error::Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = DiscordIpcClient::with_socket_path("771124766517755954", server.path());
+    client.connect()?;
+    assert_eq!(
+        client.connected_endpoint(),
+        Some(&ConnectedEndpoint {
+            path: server.path().to_path_buf(),
+            source: EndpointSource::Explicit,
+        })
+    );
+    assert_eq!(client.connected_path(), Some(server.path()));
+
+    // Reconnecting finds the same path again, and closing forgets it
+    client.reconnect()?;
+    assert_eq!(client.connected_path(), Some(server.path()));
+    client.close()?;
+    assert_eq!(client.connected_path(), None);
+
+    // A path which doesn't work is reported, rather than searched
+    // past or taken for Discord not running
+    let missing = server.path().with_file_name("missing-discord-ipc");
+    let mut client = DiscordIpcClient::with_socket_path("771124766517755954", &missing);
+    let err = client.connect().unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::ConnectFailed { attempts }) => {
+            let paths: Vec<_> = attempts.iter().map(|(path, _)| path).collect();
+            assert_eq!(paths, [&missing]);
+        }
+        other => panic!("expected ConnectFailed, got {:?}", other),
+    }
+    assert!(err.to_string().contains(&missing.display().to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_path_override() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-override-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let instance = UnixListener::bind(dir.join("discord-ipc-1"))?;
+    let custom = UnixListener::bind(dir.join("custom-ipc"))?;
+
+    // A directory is searched for each instance's socket, and
+    // nothing else is
+    let dir_name = dir.display().to_string();
+    let env = environment(&[
+        (IPC_PATH_VAR, &dir_name),
+        ("XDG_RUNTIME_DIR", "/run/user/1000"),
+    ]);
+    let mut transport = NativeTransport::with_env(env);
+    let expected: Vec<PathBuf> = (0..10)
+        .map(|i| dir.join(format!("discord-ipc-{}", i)))
+        .collect();
+    assert_eq!(transport.candidate_paths(), expected);
+    transport.connect()?;
+    assert!(instance.accept().is_ok());
+    assert_eq!(
+        transport.connected_endpoint(),
+        Some(&ConnectedEndpoint {
+            path: dir.join("discord-ipc-1"),
+            source: EndpointSource::Overridden,
+        })
+    );
+
+    // Any other path is the only socket tried
+    let custom_name = dir.join("custom-ipc").display().to_string();
+    let mut transport = NativeTransport::with_env(environment(&[(IPC_PATH_VAR, &custom_name)]));
+    assert_eq!(transport.candidate_paths(), [dir.join("custom-ipc")]);
+    transport.connect()?;
+    assert!(custom.accept().is_ok());
+
+    // Failing to connect says the override was in effect, rather
+    // than that Discord isn't running
+    let missing = dir.join("missing-ipc");
+    let env = environment(&[(IPC_PATH_VAR, &missing.display().to_string())]);
+    let err = connect_error(&mut NativeTransport::with_env(env));
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::PathOverrideFailed { attempts }) => {
+            assert_eq!(attempts.len(), 1);
+            assert_eq!(attempts[0].0, missing);
+        }
+        other => panic!("expected PathOverrideFailed, got {:?}", other),
+    }
+    assert!(err.to_string().contains(IPC_PATH_VAR));
+    assert!(err.to_string().contains(&missing.display().to_string()));
+
+    // A trailing separator marks a directory, even one which
+    // doesn't exist
+    let env = environment(&[(IPC_PATH_VAR, "/nowhere/")]);
+    let paths = NativeTransport::with_env(env).candidate_paths();
+    assert_eq!(paths.len(), 10);
+    assert_eq!(paths[3], PathBuf::from("/nowhere/discord-ipc-3"));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_connects_to_first_socket_found() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-discovery-{}", std::process::id()));
+    fs::create_dir_all(dir.join("snap.discord"))?;
+
+    // A Snap install of the first instance is preferred to a
+    // second instance in the directory itself
+    let snap = UnixListener::bind(dir.join("snap.discord/discord-ipc-0"))?;
+    let second = UnixListener::bind(dir.join("discord-ipc-1"))?;
+    snap.set_nonblocking(true)?;
+    second.set_nonblocking(true)?;
+
+    let env = environment(&[("TMPDIR", &dir.display().to_string())]);
+    let mut transport = NativeTransport::with_env(env);
+    assert_eq!(transport.connected_endpoint(), None);
+    transport.connect()?;
+    assert!(snap.accept().is_ok());
+    assert!(second.accept().is_err());
+    assert_eq!(
+        transport.connected_endpoint(),
+        Some(&ConnectedEndpoint {
+            path: dir.join("snap.discord/discord-ipc-0"),
+            source: EndpointSource::Discovered {
+                env_key: Some("TMPDIR"),
+                subpath: "snap.discord/",
+                index: 0,
+            },
+        })
+    );
+
+    // Once it is gone, the second instance is found
+    drop(snap);
+    fs::remove_file(dir.join("snap.discord/discord-ipc-0"))?;
+    transport.connect()?;
+    assert!(second.accept().is_ok());
+    assert_eq!(
+        transport.connected_endpoint(),
+        Some(&ConnectedEndpoint {
+            path: dir.join("discord-ipc-1"),
+            source: EndpointSource::Discovered {
+                env_key: Some("TMPDIR"),
+                subpath: "",
+                index: 1,
+            },
+        })
+    );
+    assert!(format!("{:?}", transport).contains(&format!("{:?}", dir.join("discord-ipc-1"))));
+
+    transport.shutdown()?;
+    assert_eq!(transport.connected_endpoint(), None);
+
+    // An explicit path is reported as such
+    let mut explicit = NativeTransport::with_path(dir.join("discord-ipc-1"));
+    explicit.connect()?;
+    assert_eq!(
+        explicit
+            .connected_endpoint()
+            .map(|endpoint| endpoint.source),
+        Some(EndpointSource::Explicit)
+    );
+
+    let missing = environment(&[("TMPDIR", &dir.join("missing").display().to_string())]);
+    assert!(NativeTransport::with_env(missing).connect().is_err());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_pipe_index_restricts_search() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-index-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let first = UnixListener::bind(dir.join("discord-ipc-0"))?;
+    let third = UnixListener::bind(dir.join("discord-ipc-2"))?;
+    first.set_nonblocking(true)?;
+
+    let dir_name = dir.display().to_string();
+    let mut transport = NativeTransport::with_env(environment(&[("TMPDIR", &dir_name)]));
+    transport.set_pipe_index(Some(2))?;
+    let expected = expected_paths(&dir_name, 3).split_off(8);
+    assert_eq!(transport.candidate_paths(), expected);
+
+    // The first instance is passed over for the one chosen
+    transport.connect()?;
+    assert!(third.accept().is_ok());
+    assert!(first.accept().is_err());
+    assert_eq!(
+        transport
+            .connected_endpoint()
+            .map(|endpoint| &endpoint.path),
+        Some(&dir.join("discord-ipc-2"))
+    );
+
+    // A directory given by the override is restricted the same way
+    let mut transport = NativeTransport::with_env(environment(&[(IPC_PATH_VAR, &dir_name)]));
+    transport.set_pipe_index(Some(2))?;
+    assert_eq!(transport.candidate_paths(), [dir.join("discord-ipc-2")]);
+
+    transport.set_pipe_index(None)?;
+    assert_eq!(transport.candidate_paths().len(), 10);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_pipe_index_out_of_range() {
+    let mut transport = NativeTransport::with_env(HashMap::new());
+    assert!(matches!(
+        transport.set_pipe_index(Some(10)),
+        Err(error::Error::InvalidPipeIndex { index: 10, pipes }) if pipes == DEFAULT_PIPE_RANGE
+    ));
+    assert_eq!(transport.candidate_paths().len(), 80);
+
+    let mut client = DiscordIpcClient::new("771124766517755954").unwrap();
+    let err = client.connect_to(10).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::InvalidPipeIndex { index: 10, .. })
+    ));
+    assert!(err.to_string().contains("discord-ipc-9"));
+}
+
+#[test]
+fn test_pipe_range_extends_search() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-range-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let listener = UnixListener::bind(dir.join("discord-ipc-15"))?;
+
+    // Past the default range, the socket isn't found
+    let env = environment(&[("TMPDIR", &dir.display().to_string())]);
+    let mut transport = NativeTransport::with_env(env.clone());
+    assert!(transport.connect().is_err());
+
+    let mut transport = NativeTransport::with_env(env);
+    transport.set_pipe_range(0..=31);
+    assert_eq!(transport.candidate_paths().len(), 32 * 4);
+    transport.connect()?;
+    assert!(listener.accept().is_ok());
+    assert!(matches!(
+        transport
+            .connected_endpoint()
+            .map(|endpoint| endpoint.source),
+        Some(EndpointSource::Discovered { index: 15, .. })
+    ));
+
+    // Reconnecting searches the same range
+    transport.shutdown()?;
+    transport.connect()?;
+    assert!(listener.accept().is_ok());
+
+    // Indexes are checked against the range set
+    transport.set_pipe_range(20..=31);
+    assert!(matches!(
+        transport.set_pipe_index(Some(15)),
+        Err(error::Error::InvalidPipeIndex { index: 15, pipes }) if pipes == (20..=31)
+    ));
+
+    // The client keeps the range it was given
+    let client = DiscordIpcClient::new("771124766517755954")?.with_pipe_range(0..=31);
+    assert_eq!(client.candidate_paths().len() % 32, 0);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+fn attempts(kinds: &[ErrorKind]) -> Vec<(PathBuf, io::Error)> {
+    kinds
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| {
+            (
+                PathBuf::from(format!("/run/user/1000/discord-ipc-{}", i)),
+                io::Error::from(*kind),
+            )
+        })
+        .collect()
+}
+
+fn connect_error(transport: &mut NativeTransport) -> Box<dyn std::error::Error> {
+    transport
+        .connect()
+        .expect_err("expected connecting to fail")
+}
+
+#[test]
+fn test_classifies_connect_failures() {
+    let not_running: [&[ErrorKind]; 4] = [
+        &[],
+        &[ErrorKind::NotFound; 40],
+        &[ErrorKind::ConnectionRefused],
+        &[ErrorKind::NotFound, ErrorKind::ConnectionRefused],
+    ];
+    for kinds in not_running {
+        assert!(
+            matches!(
+                error::Error::from_connect_attempts(attempts(kinds)),
+                error::Error::DiscordNotRunning
+            ),
+            "for {:?}",
+            kinds
+        );
+    }
+
+    // The first path access to was denied is named, whatever else
+    // went wrong
+    let denied: [&[ErrorKind]; 3] = [
+        &[ErrorKind::PermissionDenied],
+        &[ErrorKind::NotFound, ErrorKind::PermissionDenied],
+        &[
+            ErrorKind::Other,
+            ErrorKind::PermissionDenied,
+            ErrorKind::PermissionDenied,
+        ],
+    ];
+    for kinds in denied {
+        let expected = attempts(kinds)
+            .into_iter()
+            .find(|(_, err)| err.kind() == ErrorKind::PermissionDenied)
+            .map(|(path, _)| path);
+        match error::Error::from_connect_attempts(attempts(kinds)) {
+            error::Error::PermissionDenied { path, source } => {
+                assert_eq!(Some(path), expected);
+                assert_eq!(source.kind(), ErrorKind::PermissionDenied);
+            }
+            other => panic!("expected PermissionDenied for {:?}, got {:?}", kinds, other),
+        }
+    }
+
+    let failed: [&[ErrorKind]; 2] = [
+        &[ErrorKind::Other],
+        &[ErrorKind::ConnectionRefused, ErrorKind::Other],
+    ];
+    for kinds in failed {
+        match error::Error::from_connect_attempts(attempts(kinds)) {
+            error::Error::ConnectFailed { attempts } => assert_eq!(attempts.len(), kinds.len()),
+            other => panic!("expected ConnectFailed for {:?}, got {:?}", kinds, other),
+        }
+    }
+}
+
+#[test]
+fn test_connect_failures_chain_io_errors() {
+    use std::error::Error;
+
+    let err = error::Error::from_connect_attempts(attempts(&[
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+    ]));
+    assert_eq!(
+        err.to_string(),
+        "Permission denied connecting to the Discord IPC socket /run/user/1000/discord-ipc-1: permission denied"
+    );
+    let source = err
+        .source()
+        .and_then(|source| source.downcast_ref::<io::Error>());
+    assert_eq!(
+        source.map(io::Error::kind),
+        Some(ErrorKind::PermissionDenied)
+    );
+
+    // The last attempt's error is the source of the others
+    let err =
+        error::Error::from_connect_attempts(attempts(&[ErrorKind::Other, ErrorKind::TimedOut]));
+    let source = err
+        .source()
+        .and_then(|source| source.downcast_ref::<io::Error>());
+    assert_eq!(source.map(io::Error::kind), Some(ErrorKind::TimedOut));
+    assert!(error::Error::DiscordNotRunning.source().is_none());
+}
+
+#[test]
+fn test_connect_failure_message_names_real_failures() {
+    let err =
+        error::Error::from_connect_attempts(attempts(&[ErrorKind::NotFound, ErrorKind::TimedOut]));
+    assert_eq!(
+        err.to_string(),
+        "Couldn't connect to the Discord IPC socket: /run/user/1000/discord-ipc-1: timed out"
+    );
+}
+
+#[test]
+fn test_reports_discord_not_running() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-not-running-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let env = environment(&[("XDG_RUNTIME_DIR", &dir.display().to_string())]);
+
+    // No sockets at all
+    let err = connect_error(&mut NativeTransport::with_env(env.clone()));
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::DiscordNotRunning)
+    ));
+
+    // A socket left behind by a Discord which has exited
+    drop(UnixListener::bind(dir.join("discord-ipc-0"))?);
+    let err = connect_error(&mut NativeTransport::with_env(env));
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::DiscordNotRunning)
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
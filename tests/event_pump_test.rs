@@ -0,0 +1,181 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{
+    activity::Activity,
+    error,
+    event::{Event, EventKind, Subscription},
+    persist::{Persistence, PresenceStore, SavedPresence},
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn is_stopped(err: &(dyn Error + 'static)) -> bool {
+    matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::EventPumpStopped)
+    )
+}
+
+#[test]
+fn test_commands_and_events() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        let mut frames = vec![response(request, json!({ "cmd": request["cmd"] }))];
+        if request["cmd"] == "SUBSCRIBE" {
+            // An event dispatched straight after the subscription
+            frames.push((
+                1,
+                json!({
+                    "cmd": "DISPATCH",
+                    "evt": "ACTIVITY_JOIN",
+                    "data": { "secret": "abc" },
+                    "nonce": null
+                }),
+            ));
+        }
+        frames
+    });
+    client.connect()?;
+
+    let (pump, events) = client.spawn_event_pump()?;
+    assert!(matches!(events.recv_timeout(TIMEOUT)?, Event::Ready(_)));
+
+    pump.subscribe(&Subscription::activity_join())?;
+    match events.recv_timeout(TIMEOUT)? {
+        Event::Dispatch { kind, data } => {
+            assert_eq!(kind, EventKind::ActivityJoin);
+            assert_eq!(data, json!({ "secret": "abc" }));
+        }
+        other => panic!("expected ACTIVITY_JOIN, got {:?}", other),
+    }
+
+    assert_eq!(
+        pump.command("GET_GUILDS", json!({}))?,
+        json!({ "cmd": "GET_GUILDS" })
+    );
+    pump.set_activity(Activity::new().state("Hello world!"))?;
+    pump.clear_activity()?;
+    pump.close()?;
+
+    // The thread has stopped, so the channel disconnects
+    assert!(events.recv_timeout(TIMEOUT).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_drop_stops_thread() -> Result<(), Box<dyn Error>> {
+    let client = MockClient::new("771124766517755954");
+    let (pump, events) = client.spawn_event_pump()?;
+
+    drop(pump);
+    assert!(events.recv_timeout(TIMEOUT).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_in_flight_commands_fail_on_shutdown() -> Result<(), Box<dyn Error>> {
+    let client = MockClient::new("771124766517755954").respond_with(|_, request| {
+        match request["cmd"].as_str() {
+            // Never answered
+            Some("HANG") => Vec::new(),
+            // Discord closes the connection
+            Some("CLOSE_ME") => vec![(2, json!({ "code": 1000, "message": "Closing" }))],
+            _ => vec![response(request, Value::Null)],
+        }
+    });
+    let (pump, _events) = client.spawn_event_pump()?;
+
+    thread::scope(|scope| {
+        let hanging = scope.spawn(|| {
+            pump.command("HANG", json!({}))
+                .map_err(|err| is_stopped(&*err))
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let err = pump.command("CLOSE_ME", json!({})).unwrap_err();
+        assert!(is_stopped(&*err));
+        assert_eq!(hanging.join().unwrap(), Err(true));
+    });
+
+    // Requests made after the thread stopped fail immediately
+    let err = pump.set_activity(Activity::new()).unwrap_err();
+    assert!(is_stopped(&*err));
+    Ok(())
+}
+
+// Keeps the saved presence in memory
+#[derive(Debug, Default)]
+struct MemoryStore(Mutex<Option<SavedPresence>>);
+
+impl PresenceStore for MemoryStore {
+    fn load(&self) -> Result<Option<SavedPresence>, Box<dyn Error>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn save(&self, presence: &SavedPresence) -> Result<(), Box<dyn Error>> {
+        *self.0.lock().unwrap() = Some(presence.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_activity_goes_through_client() -> Result<(), Box<dyn Error>> {
+    let store = Arc::new(MemoryStore::default());
+    let mut client = MockClient::new("771124766517755954");
+    client.persist_to(Some(Persistence::new(store.clone())));
+    client.connect()?;
+
+    // Updates through the pump are saved as the client's own are
+    let (pump, _events) = client.spawn_event_pump()?;
+    pump.set_activity(Activity::new().state("Hello world!"))?;
+    let saved = store.load()?.and_then(|saved| saved.activity);
+    assert_eq!(json!(saved)["state"], "Hello world!");
+
+    pump.clear_activity()?;
+    assert!(store.load()?.unwrap().activity.is_none());
+    pump.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_pending_responses_carried_over() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        match request["cmd"].as_str() {
+            Some("GET_GUILDS") => vec![response(request, json!({ "guilds": [] }))],
+            _ => default_response(opcode, request),
+        }
+    });
+    client.connect()?;
+    let nonce = client.send_command("GET_GUILDS", json!({}))?;
+
+    let (pump, _events) = client.spawn_event_pump()?;
+    let start = Instant::now();
+    let data = loop {
+        if let Some(data) = pump.poll_response(&nonce)? {
+            break data;
+        }
+        assert!(start.elapsed() < TIMEOUT);
+        thread::sleep(Duration::from_millis(1));
+    };
+    assert_eq!(data, json!({ "guilds": [] }));
+
+    // Once collected, the nonce is forgotten
+    let err = pump.poll_response(&nonce).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::UnknownNonce { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    error,
+    event::{Event, EventKind},
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+
+fn dispatch(evt: &str) -> Value {
+    json!({ "cmd": "DISPATCH", "evt": evt, "data": {}, "nonce": null })
+}
+
+fn connected_client() -> Result<MockClient, Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    client.push_frame(1, dispatch("ACTIVITY_JOIN"));
+    // A ping, and a response to a command nobody is waiting on
+    client.push_frame(3, json!({ "ping": 1 }));
+    client.push_frame(
+        1,
+        json!({ "cmd": "SET_ACTIVITY", "data": {}, "evt": null, "nonce": "stale" }),
+    );
+    client.push_frame(1, dispatch("ACTIVITY_SPECTATE"));
+    Ok(client)
+}
+
+#[test]
+fn test_ends_on_normal_close() -> Result<(), Box<dyn Error>> {
+    let mut client = connected_client()?;
+    client.push_frame(2, json!({ "code": 1000, "message": "Closing" }));
+
+    let mut events = client.events();
+    assert!(matches!(events.next(), Some(Ok(Event::Ready(_)))));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(Event::Dispatch {
+            kind: EventKind::ActivityJoin,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(Event::Dispatch {
+            kind: EventKind::ActivitySpectate,
+            ..
+        }))
+    ));
+    assert!(events.next().is_none());
+    assert!(events.next().is_none());
+
+    // The ping was answered
+    let (opcode, payload) = client.sent.last().unwrap();
+    assert_eq!((*opcode, payload), (4, &json!({ "ping": 1 })));
+    Ok(())
+}
+
+#[test]
+fn test_yields_error_on_abnormal_close() -> Result<(), Box<dyn Error>> {
+    let mut client = connected_client()?;
+    client.push_frame(2, json!({ "code": 4000, "message": "Invalid Client ID" }));
+
+    let results: Vec<_> = client.events().collect();
+    assert_eq!(results.len(), 4);
+    assert!(results[..3].iter().all(Result::is_ok));
+
+    let err = results[3].as_ref().unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::ConnectionClosed { code, .. }) => assert_eq!(*code, 4000),
+        _ => panic!("expected ConnectionClosed, got {}", err),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_yields_error_on_connection_failure() -> Result<(), Box<dyn Error>> {
+    // The mock fails reads once it runs out of data
+    let mut client = connected_client()?;
+
+    let mut events = client.events();
+    assert_eq!(events.by_ref().take_while(Result::is_ok).count(), 3);
+    assert!(events.next().is_none());
+    Ok(())
+}
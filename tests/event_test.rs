@@ -0,0 +1,164 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{
+    error,
+    event::{Event, EventKind},
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+fn dispatch(evt: &str) -> Value {
+    json!({ "cmd": "DISPATCH", "evt": evt, "data": {}, "nonce": null })
+}
+
+#[test]
+fn test_ready_is_queued() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    match client.recv_event()? {
+        Event::Ready(info) => {
+            assert_eq!(info.v, 1);
+            assert_eq!(info.user.unwrap().username, "mock");
+        }
+        other => panic!("expected READY, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rejected_handshake() {
+    let mut client = MockClient::new("0")
+        .respond_with(|_, _| vec![(2, json!({ "code": 4000, "message": "Invalid Client ID" }))]);
+
+    let err = client.connect().unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::ConnectionClosed { code, message }) => {
+            assert_eq!(*code, 4000);
+            assert_eq!(message, "Invalid Client ID");
+        }
+        _ => panic!("expected ConnectionClosed, got {}", err),
+    }
+}
+
+#[test]
+fn test_unsolicited_error_between_commands() -> Result<(), Box<dyn Error>> {
+    let mut commands = 0;
+    let mut client = MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        if opcode == 0 {
+            return default_response(opcode, request);
+        }
+        commands += 1;
+        let mut frames = Vec::new();
+        if commands == 2 {
+            // An ERROR dispatched before the second command's response
+            frames.push((
+                1,
+                json!({
+                    "cmd": "DISPATCH",
+                    "evt": "ERROR",
+                    "data": { "code": 1000, "message": "Unknown error" },
+                    "nonce": null
+                }),
+            ));
+        }
+        frames.push(response(request, json!({ "n": commands })));
+        frames
+    });
+    client.connect()?;
+
+    assert_eq!(client.command("GET_GUILDS", json!({}))?, json!({ "n": 1 }));
+    assert_eq!(client.command("GET_GUILDS", json!({}))?, json!({ "n": 2 }));
+    assert_eq!(client.command("GET_GUILDS", json!({}))?, json!({ "n": 3 }));
+
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    match client.recv_event()? {
+        Event::Error { code, message } => {
+            assert_eq!(code, 1000);
+            assert_eq!(message, "Unknown error");
+        }
+        other => panic!("expected ERROR, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_events_and_pings() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.push_frame(3, json!({ "ping": 1 }));
+    client.push_frame(
+        1,
+        json!({
+            "cmd": "DISPATCH",
+            "evt": "ACTIVITY_JOIN",
+            "data": { "secret": "abc" },
+            "nonce": null
+        }),
+    );
+    client.push_frame(
+        1,
+        json!({
+            "cmd": "DISPATCH",
+            "evt": "SOME_NEW_EVENT",
+            "data": {},
+            "nonce": null
+        }),
+    );
+
+    match client.recv_event()? {
+        Event::Dispatch { kind, data } => {
+            assert_eq!(kind.as_str(), "ACTIVITY_JOIN");
+            assert_eq!(data, json!({ "secret": "abc" }));
+        }
+        other => panic!("expected ACTIVITY_JOIN, got {:?}", other),
+    }
+    assert_eq!(client.sent, vec![(4, json!({ "ping": 1 }))]);
+
+    match client.recv_event()? {
+        Event::Unknown { name, .. } => assert_eq!(name, "SOME_NEW_EVENT"),
+        other => panic!("expected an unknown event, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_callbacks() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let log = calls.clone();
+    let speaking = client.on_event(EventKind::SpeakingStart, move |_| {
+        log.lock().unwrap().push("speaking")
+    });
+    let log = calls.clone();
+    client.on_any_event(move |event| {
+        let name = match event {
+            Event::Dispatch { kind, .. } => kind.as_str(),
+            _ => "other",
+        };
+        log.lock().unwrap().push(name)
+    });
+
+    client.push_frame(1, dispatch("SPEAKING_START"));
+    client.push_frame(1, dispatch("SPEAKING_STOP"));
+    client.recv_event()?;
+    client.recv_event()?;
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["speaking", "SPEAKING_START", "SPEAKING_STOP"]
+    );
+
+    assert!(client.remove_callback(speaking));
+    assert!(!client.remove_callback(speaking));
+    calls.lock().unwrap().clear();
+
+    client.push_frame(1, dispatch("SPEAKING_START"));
+    client.recv_event()?;
+    assert_eq!(*calls.lock().unwrap(), vec!["SPEAKING_START"]);
+    Ok(())
+}
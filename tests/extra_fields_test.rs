@@ -0,0 +1,50 @@
+use discord_rich_presence::activity::Activity;
+use serde_json::{json, Value};
+use std::error::Error;
+
+#[test]
+fn test_extra_fields_at_top_level() -> Result<(), Box<dyn Error>> {
+    let activity = Activity::new()
+        .state("In a match")
+        .extra_field("instance", true)
+        .extra_field("status_display_type", json!(1));
+
+    assert_eq!(
+        serde_json::to_value(&activity)?,
+        json!({ "state": "In a match", "instance": true, "status_display_type": 1 })
+    );
+    assert_eq!(
+        activity.to_payload_json(),
+        r#"{"instance":true,"state":"In a match","status_display_type":1}"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_modeled_field_wins() -> Result<(), Box<dyn Error>> {
+    let activity = Activity::new()
+        .state("In a match")
+        .extra_field("state", "Overridden")
+        .extra_field("details", "Also overridden");
+
+    assert_eq!(
+        serde_json::to_value(&activity)?,
+        json!({ "state": "In a match" })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_unknown_fields_preserved() -> Result<(), Box<dyn Error>> {
+    let received = json!({
+        "state": "In a match",
+        "type": 0,
+        "application_id": "771124766517755954",
+        "flags": { "instance": true }
+    });
+
+    let activity: Activity = serde_json::from_value(received.clone())?;
+    let sent: Value = serde_json::to_value(&activity)?;
+    assert_eq!(sent, received);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+/* Exercises the C interface without Discord running: every call
+ * which needs Discord must fail cleanly, with a message. */
+#include <stdio.h>
+#include <string.h>
+
+#include "discord_rich_presence.h"
+
+#define CHECK(cond)                                                       \
+    do {                                                                  \
+        if (!(cond)) {                                                    \
+            const char *message = drp_last_error_message();               \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n", \
+                    __FILE__, __LINE__, #cond,                            \
+                    message ? message : "none");                          \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+int main(void) {
+    CHECK(drp_last_error_message() == NULL);
+
+    /* Invalid arguments are errors, not crashes */
+    CHECK(drp_client_new(NULL) == NULL);
+    CHECK(strstr(drp_last_error_message(), "client_id is NULL") != NULL);
+    CHECK(drp_connect(NULL) == -1);
+    CHECK(strstr(drp_last_error_message(), "client is NULL") != NULL);
+    CHECK(drp_client_new("\xff") == NULL);
+    CHECK(strstr(drp_last_error_message(), "not valid UTF-8") != NULL);
+    drp_client_free(NULL);
+
+    DrpClient *client = drp_client_new("771124766517755954");
+    CHECK(client != NULL);
+
+    CHECK(drp_set_activity_json(client, "{\"state\": ") == -1);
+    CHECK(drp_set_activity_json(client, NULL) == -1);
+    CHECK(strstr(drp_last_error_message(), "json is NULL") != NULL);
+
+    /* The test runs with no Discord to connect to */
+    CHECK(drp_connect(client) == -1);
+    CHECK(strstr(drp_last_error_message(), "Discord") != NULL);
+    CHECK(drp_set_activity_json(client, "{\"state\": \"Hello world!\"}") == -1);
+    CHECK(drp_clear_activity(client) == -1);
+
+    drp_client_free(client);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "ffi")]
+
+use discord_rich_presence::ffi::*;
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+};
+
+fn last_error() -> String {
+    let message = drp_last_error_message();
+    assert!(!message.is_null());
+    unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_rejects_invalid_activity_json() {
+    let client_id = CString::new("771124766517755954").unwrap();
+    unsafe {
+        let client = drp_client_new(client_id.as_ptr());
+        assert!(!client.is_null());
+
+        for json in [r#"{"state": "#, r#"{"state": 1}"#, "[]"] {
+            let json = CString::new(json).unwrap();
+            assert_eq!(drp_set_activity_json(client, json.as_ptr()), -1);
+        }
+        assert_eq!(drp_set_activity_json(client, ptr::null()), -1);
+        assert_eq!(last_error(), "json is NULL");
+
+        drp_client_free(client);
+    }
+}
+
+#[test]
+fn test_errors_are_per_thread() {
+    unsafe {
+        assert!(drp_client_new(ptr::null()).is_null());
+    }
+    assert_eq!(last_error(), "client_id is NULL");
+
+    let other = std::thread::spawn(|| drp_last_error_message().is_null());
+    assert!(other.join().unwrap());
+}
+
+// Builds the library as a shared object, and runs a C program
+// against it and the generated header
+#[cfg(unix)]
+#[test]
+fn test_c_program() -> Result<(), Box<dyn std::error::Error>> {
+    use std::{env, fs, path::Path, process::Command};
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let work = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let target = work.join("target");
+    let runtime = work.join("runtime");
+    fs::create_dir_all(&runtime)?;
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let status = Command::new(cargo)
+        .current_dir(root)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "cdylib",
+        ])
+        .arg("--target-dir")
+        .arg(&target)
+        .status()?;
+    assert!(status.success());
+
+    let lib_dir = target.join("debug");
+    let program = work.join("smoke");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_owned()))
+        .arg(root.join("tests/ffi/smoke.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-ldiscord_rich_presence")
+        .arg("-o")
+        .arg(&program)
+        .status()?;
+    assert!(status.success());
+
+    // Points discovery at an empty directory, so that no running
+    // Discord is found
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .env("DYLD_LIBRARY_PATH", &lib_dir)
+        .env("XDG_RUNTIME_DIR", &runtime)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use discord_rich_presence::{
+    error, transport::IpcTransport, DiscordIpc, DiscordIpcClient, MAX_FRAME_LENGTH,
+};
+use quickcheck::{quickcheck, TestResult};
+use serde_json::json;
+use std::{collections::VecDeque, error::Error, io, time::Duration};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+// A transport which reads back whatever was written to it, or
+// whatever bytes it was given, then reads as a closed socket does
+#[derive(Default)]
+struct Loopback(VecDeque<u8>);
+
+impl IpcTransport for Loopback {
+    fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        if self.0.len() < buffer.len() {
+            return Err(
+                io::Error::new(io::ErrorKind::UnexpectedEof, "loopback has no more data").into(),
+            );
+        }
+        for byte in buffer.iter_mut() {
+            *byte = self.0.pop_front().unwrap();
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.0.extend(data);
+        Ok(())
+    }
+
+    fn wait_readable(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(!self.0.is_empty())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn client(bytes: &[u8]) -> DiscordIpcClient<Loopback> {
+    DiscordIpcClient::with_transport(
+        "771124766517755954",
+        Loopback(bytes.iter().copied().collect()),
+    )
+}
+
+fn header(opcode: u32, length: u32) -> Vec<u8> {
+    let mut bytes = opcode.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&length.to_le_bytes());
+    bytes
+}
+
+quickcheck! {
+    fn prop_send_recv_round_trip(opcode: u8, text: String, number: i64) -> bool {
+        let payload = json!({ "text": text, "number": number });
+        let mut client = client(&[]);
+        client.send(payload.clone(), opcode).unwrap();
+
+        client.recv().unwrap() == (u32::from(opcode), payload)
+    }
+
+    fn prop_recv_never_panics(bytes: Vec<u8>) -> bool {
+        // Any outcome is fine, as long as it is returned
+        let _ = client(&bytes).recv();
+        true
+    }
+
+    fn prop_recv_rejects_oversized_lengths(opcode: u32, length: u32) -> TestResult {
+        if length <= MAX_FRAME_LENGTH {
+            return TestResult::discard();
+        }
+
+        let err = client(&header(opcode, length)).recv().unwrap_err();
+        TestResult::from_bool(matches!(
+            err.downcast_ref::<error::Error>(),
+            Some(error::Error::FrameTooLarge { length: found }) if *found == length
+        ))
+    }
+}
+
+#[test]
+fn test_truncated_header() {
+    for length in 1..8 {
+        let err = client(&header(1, 2)[..length]).recv().unwrap_err();
+        assert!(err.downcast_ref::<error::Error>().is_none());
+    }
+}
+
+#[test]
+fn test_closed_between_frames() {
+    // Nothing more to read is a clean close, unlike a frame cut off
+    // three bytes into its header
+    let err = client(&[]).recv().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::SocketClosed)
+    ));
+
+    let err = client(&header(1, 2)[..3]).recv().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<io::Error>().map(io::Error::kind),
+        Some(io::ErrorKind::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn test_truncated_payload() {
+    let mut bytes = header(1, 10);
+    bytes.extend_from_slice(b"{}");
+    assert!(client(&bytes).recv().is_err());
+}
+
+#[test]
+fn test_maximum_length() {
+    // The maximum itself is accepted, and fails only for lack of data
+    let err = client(&header(1, MAX_FRAME_LENGTH)).recv().unwrap_err();
+    assert!(err.downcast_ref::<error::Error>().is_none());
+
+    let err = client(&header(1, u32::MAX)).recv().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::FrameTooLarge { length: u32::MAX })
+    ));
+}
+
+#[test]
+fn test_invalid_payloads() {
+    for payload in [&b"not json"[..], b"{\"unterminated\":", b"\xff\xfe", b""].iter() {
+        let mut bytes = header(1, payload.len() as u32);
+        bytes.extend_from_slice(payload);
+        assert!(client(&bytes).recv().is_err());
+    }
+}
+
+#[test]
+fn test_valid_frame() -> Result<()> {
+    let payload = br#"{"cmd":"DISPATCH"}"#;
+    let mut bytes = header(3, payload.len() as u32);
+    bytes.extend_from_slice(payload);
+
+    assert_eq!(client(&bytes).recv()?, (3, json!({ "cmd": "DISPATCH" })));
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Pins what is sent to Discord against the fixtures in
+//! `tests/fixtures`. See `tests/fixtures/README.md` before changing
+//! a fixture.
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::{self, AssetSource},
+    test_util::MockDiscordServer,
+    DiscordIpc,
+};
+use serde_json::Value;
+use std::{error::Error, fs, path::Path};
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("couldn't read {}: {}", path.display(), err))
+        .trim_end()
+        .to_string()
+}
+
+// Every field populated
+fn maximal_activity() -> activity::Activity<'static> {
+    activity::Activity::new()
+        .state("In a match")
+        .details("Ranked, map 3")
+        .timestamps(
+            activity::Timestamps::new()
+                .start(1700000000)
+                .end(1700003600),
+        )
+        .party(activity::Party::new().id("party-1").size([2, 4]))
+        .assets(
+            activity::Assets::new()
+                .large_image("large-image")
+                .large_text("Large text")
+                .small_image("small-image")
+                .small_text("Small text"),
+        )
+        .secrets(
+            activity::Secrets::new()
+                .join("join-secret")
+                .spectate("spectate-secret")
+                .r#match("match-secret"),
+        )
+        .buttons(vec![
+            activity::Button::new("Website", "https://example.com"),
+            activity::Button::new("Source", "https://github.com/vionya/discord-rich-presence"),
+        ])
+}
+
+// Replaces the values which differ between runs
+fn normalized(mut payload: Value) -> String {
+    if payload.get("nonce").is_some() {
+        payload["nonce"] = "<nonce>".into();
+    }
+    if payload["args"].get("pid").is_some() {
+        payload["args"]["pid"] = 0.into();
+    }
+    payload.to_string()
+}
+
+#[test]
+fn test_activity_payload() {
+    assert_eq!(
+        maximal_activity().to_payload_json(),
+        fixture("activity.json")
+    );
+}
+
+#[test]
+fn test_asset_source_payloads() {
+    let sources = [
+        AssetSource::Key("logo".into()),
+        AssetSource::Url("https://example.com/cover.png".into()),
+        AssetSource::External("external/abc/https/example.com/cover.png".into()),
+    ];
+    let assets: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            activity::Assets::new()
+                .large_image_source(source.clone())
+                .small_image_source(source.clone())
+        })
+        .collect();
+
+    assert_eq!(
+        serde_json::to_string(&assets).unwrap(),
+        fixture("asset_sources.json")
+    );
+}
+
+#[test]
+fn test_handshake_payload() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    let (opcode, handshake) = client.sent[0].clone();
+    assert_eq!(opcode, 0);
+    assert_eq!(handshake.to_string(), fixture("handshake.json"));
+    Ok(())
+}
+
+#[test]
+fn test_set_activity_payload() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(maximal_activity())?;
+
+    let (opcode, payload) = client.sent.last().unwrap().clone();
+    assert_eq!(opcode, 1);
+    assert_eq!(normalized(payload.clone()), fixture("set_activity.json"));
+    assert_eq!(
+        payload["args"]["activity"].to_string(),
+        maximal_activity().to_payload_json()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_clear_activity_payload() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.clear_activity()?;
+
+    let (_, payload) = client.sent.last().unwrap().clone();
+    assert_eq!(normalized(payload), fixture("clear_activity.json"));
+    Ok(())
+}
+
+#[test]
+fn test_set_then_clear_through_server() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    client.set_activity(maximal_activity())?;
+    client.clear_activity()?;
+
+    // The activity is left out, not sent as null
+    let (opcode, payload) = server.received().last().unwrap().clone();
+    assert_eq!(opcode, 1);
+    assert!(payload["args"].get("activity").is_none());
+    assert_eq!(normalized(payload), fixture("clear_activity.json"));
+    Ok(())
+}
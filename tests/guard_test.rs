@@ -0,0 +1,61 @@
+#![cfg(feature = "test-util")]
+
+mod common;
+
+use common::activity_updates;
+use discord_rich_presence::{activity::Activity, test_util::MockDiscordServer, DiscordIpc};
+use serde_json::Value;
+use std::error::Error;
+
+#[test]
+fn test_clears_once_on_drop() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    {
+        let mut presence = client.set_activity_scoped(Activity::new().state("In a match"))?;
+        presence.update(Activity::new().state("Winning"))?;
+        presence.client().command("GET_GUILDS", Value::Null)?;
+    }
+
+    let updates = activity_updates(&server.received());
+    assert_eq!(updates.len(), 3);
+    assert_eq!(updates[1]["state"], "Winning");
+    assert_eq!(updates[2], Value::Null);
+
+    // The client is usable again once the guard is gone
+    client.set_activity(Activity::new().state("In the menu"))?;
+    assert_eq!(activity_updates(&server.received()).len(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_forget_leaves_activity_set() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    client
+        .set_activity_scoped(Activity::new().state("Staying up"))?
+        .forget();
+
+    let updates = activity_updates(&server.received());
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["state"], "Staying up");
+    Ok(())
+}
+
+#[test]
+fn test_drop_after_close_sends_nothing() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    let mut presence = client.set_activity_scoped(Activity::new().state("Leaving"))?;
+    presence.client().close()?;
+    drop(presence);
+
+    assert_eq!(activity_updates(&server.received()).len(), 1);
+    Ok(())
+}
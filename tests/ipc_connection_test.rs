@@ -0,0 +1,95 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::Activity,
+    event::{EventKind, Subscription},
+    join_request::JoinDecision,
+    DiscordIpc, DiscordIpcClient,
+};
+use serde_json::json;
+use std::{error::Error, time::Duration};
+
+// Only `DiscordIpc` is imported, as in code written before the
+// transport methods moved to `IpcConnection`. The closure is never
+// called; it only has to compile
+#[test]
+fn test_commands_need_only_discord_ipc() {
+    let _ = |mut client: DiscordIpcClient| -> Result<(), Box<dyn Error>> {
+        client.connect()?;
+        client.reconnect()?;
+        client.send_handshake()?;
+        client.send(json!({}), 1)?;
+        client.recv()?;
+        client.recv_event()?;
+        client.try_recv_event()?;
+        client.events().next();
+        let id = client.on_event(EventKind::ActivityJoin, |_| {});
+        client.on_any_event(|_| {});
+        client.remove_callback(id);
+        client.command("GET_GUILDS", json!({}))?;
+        client.subscribe(&Subscription::activity_join())?;
+        client.unsubscribe(&Subscription::activity_join())?;
+        client.send_activity_join_invite("1")?;
+        client.close_activity_request("1")?;
+        client.on_join_request(|_| JoinDecision::Decline)?;
+        client.pending_join_requests();
+        client.accept_join_request("1")?;
+        client.decline_join_request("1")?;
+        client.set_activity(Activity::new())?;
+        drop(client.set_activity_scoped(Activity::new())?);
+        client.set_activity_no_wait(Activity::new())?;
+        client.update_activity(|activity| *activity = Activity::new())?;
+        client.update_progress(Duration::ZERO, Duration::from_secs(1))?;
+        client.update_progress_paused(Duration::ZERO)?;
+        client.clear_activity()?;
+        client.set_rate_limiter(None);
+        client.persist_to(None);
+        client.flush_activity()?;
+        client.close()?;
+        client.spawn_event_pump()?;
+        Ok(())
+    };
+}
+
+// `MockClient` implements only `IpcConnection`
+#[test]
+fn test_connections_get_commands() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(Activity::new().state("Hello world!"))?;
+    client.close()?;
+
+    let opcodes: Vec<u32> = client.sent.iter().map(|(opcode, _)| *opcode).collect();
+    assert_eq!(opcodes, [0, 1, 2]);
+    assert_eq!(
+        client.sent[1].1["args"]["activity"]["state"],
+        "Hello world!"
+    );
+    Ok(())
+}
+
+// Takes the client by value, as a plugin host storing it would
+fn set_state(mut client: impl DiscordIpc, state: &str) -> Result<(), Box<dyn Error>> {
+    client.set_activity(Activity::new().state(state))
+}
+
+#[test]
+fn test_forwarding_impls() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    set_state(&mut client, "1")?;
+    set_state(&mut &mut client, "2")?;
+    set_state(&mut client as &mut dyn DiscordIpc, "3")?;
+    assert_eq!(client.last_sent()["args"]["activity"]["state"], "3");
+
+    let mut boxed = Box::new(client);
+    set_state(&mut boxed, "4")?;
+    assert_eq!(boxed.last_sent()["args"]["activity"]["state"], "4");
+
+    let mut object: Box<dyn DiscordIpc + Send> = boxed;
+    set_state(&mut object, "5")?;
+    set_state(object, "6")?;
+    Ok(())
+}
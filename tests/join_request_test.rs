@@ -0,0 +1,199 @@
+mod common;
+
+use common::{default_response, MockClient};
+use discord_rich_presence::{
+    error, event::Event, join_request::JoinDecision, test_util::MockClock, DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{error::Error, time::Duration};
+
+fn join_request(user_id: &str) -> Value {
+    json!({
+        "cmd": "DISPATCH",
+        "evt": "ACTIVITY_JOIN_REQUEST",
+        "data": {
+            "user": { "id": user_id, "username": "user", "discriminator": "0", "avatar": null }
+        },
+        "nonce": null
+    })
+}
+
+#[test]
+fn test_accept_and_decline() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|request| {
+        if request.user.id == "1" {
+            JoinDecision::Accept
+        } else {
+            JoinDecision::Decline
+        }
+    })?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "SUBSCRIBE", "args": {}, "evt": "ACTIVITY_JOIN_REQUEST" })
+    );
+
+    client.push_frame(1, join_request("1"));
+    client.push_frame(1, join_request("2"));
+    client.recv_event()?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "SEND_ACTIVITY_JOIN_INVITE", "args": { "user_id": "1" } })
+    );
+
+    client.recv_event()?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "CLOSE_ACTIVITY_REQUEST", "args": { "user_id": "2" } })
+    );
+    assert!(client.pending_join_requests().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_deferred_requests() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|_| JoinDecision::Defer)?;
+
+    client.push_frame(1, join_request("1"));
+    client.push_frame(1, join_request("2"));
+    client.recv_event()?;
+    client.recv_event()?;
+
+    let pending: Vec<_> = client
+        .pending_join_requests()
+        .iter()
+        .map(|request| request.user.id.clone())
+        .collect();
+    assert_eq!(pending, vec!["1", "2"]);
+
+    client.decline_join_request("2")?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "CLOSE_ACTIVITY_REQUEST", "args": { "user_id": "2" } })
+    );
+    client.accept_join_request("1")?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "SEND_ACTIVITY_JOIN_INVITE", "args": { "user_id": "1" } })
+    );
+
+    // Answered requests are no longer pending
+    let sent = client.sent.len();
+    let err = client.accept_join_request("1").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::JoinRequestExpired { user_id }) if user_id == "1"
+    ));
+    assert_eq!(client.sent.len(), sent);
+    Ok(())
+}
+
+#[test]
+fn test_expired_request_is_dropped() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if request["cmd"] == "SEND_ACTIVITY_JOIN_INVITE" {
+            vec![(
+                1,
+                json!({
+                    "cmd": request["cmd"],
+                    "data": { "code": 4010, "message": "Invalid user" },
+                    "evt": "ERROR",
+                    "nonce": request["nonce"]
+                }),
+            )]
+        } else {
+            default_response(opcode, request)
+        }
+    });
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|_| JoinDecision::Accept)?;
+
+    client.push_frame(1, join_request("1"));
+    client.recv_event()?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "SEND_ACTIVITY_JOIN_INVITE", "args": { "user_id": "1" } })
+    );
+    assert!(client.pending_join_requests().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_malformed_request_keeps_handler() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|_| JoinDecision::Accept)?;
+
+    let mut malformed = join_request("1");
+    malformed["data"]["user"] = json!("not a user");
+    client.push_frame(1, malformed);
+    client.push_frame(1, join_request("2"));
+    assert!(client.recv_event().is_err());
+
+    client.recv_event()?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "SEND_ACTIVITY_JOIN_INVITE", "args": { "user_id": "2" } })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_other_command_errors_are_returned() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if request["cmd"] == "CLOSE_ACTIVITY_REQUEST" {
+            vec![(
+                1,
+                json!({
+                    "cmd": request["cmd"],
+                    "data": { "code": 4000, "message": "Invalid payload" },
+                    "evt": "ERROR",
+                    "nonce": request["nonce"]
+                }),
+            )]
+        } else {
+            default_response(opcode, request)
+        }
+    });
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|_| JoinDecision::Decline)?;
+
+    client.push_frame(1, join_request("1"));
+    let err = client.recv_event().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::CommandError { code: 4000, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_expiry_measured_by_client_clock() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+    client.set_clock(clock.clone());
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    client.on_join_request(|_| JoinDecision::Defer)?;
+
+    client.push_frame(1, join_request("1"));
+    client.recv_event()?;
+    assert!(!client.pending_join_requests()[0].is_expired_at(&*clock));
+
+    clock.advance(Duration::from_secs(30));
+    assert!(client.pending_join_requests().is_empty());
+    let err = client.accept_join_request("1").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::JoinRequestExpired { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "test-util")]
+
+use discord_rich_presence::{activity::Activity, test_util::MockDiscordServer, DiscordIpc};
+use serde_json::json;
+use std::{error::Error, mem};
+
+#[test]
+fn test_last_activity_follows_updates() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    assert!(client.last_activity().is_none());
+
+    client.set_activity(Activity::new().state("First"))?;
+    let last = client.last_activity().expect("an activity was set");
+    assert_eq!(json!(last), json!({ "state": "First" }));
+
+    client.update_activity(|activity| *activity = mem::take(activity).details("Updated"))?;
+    let last = client.last_activity().expect("an activity was set");
+    assert_eq!(
+        json!(last),
+        json!({ "details": "Updated", "state": "First" })
+    );
+
+    client.clear_activity()?;
+    assert!(client.last_activity().is_none());
+
+    client.set_activity(Activity::new().state("Second"))?;
+    let last = client.last_activity().expect("an activity was set");
+    assert_eq!(json!(last), json!({ "state": "Second" }));
+
+    client.close()?;
+    assert!(client.last_activity().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_last_activity_is_what_was_sent() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    // Empty button lists are never sent
+    client.set_activity(Activity::new().state("No buttons").buttons(Vec::new()))?;
+    let sent = server
+        .received()
+        .into_iter()
+        .rev()
+        .find(|(_, frame)| frame["cmd"] == "SET_ACTIVITY")
+        .map(|(_, frame)| frame["args"]["activity"].clone());
+    assert_eq!(sent, Some(json!(client.last_activity())));
+    Ok(())
+}
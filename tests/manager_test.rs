@@ -0,0 +1,585 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{
+    activity::Activity,
+    circuit::CircuitBreaker,
+    error,
+    manager::{DiscordIpcManager, ManagerConfig, ManagerStatus},
+    persist::{FileStore, Persistence, PresenceStore},
+    rate_limit::RateLimiter,
+    test_util::MockClock,
+};
+use serde_json::{json, Value};
+use std::{
+    env,
+    error::Error,
+    fs,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    time::Duration,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// A mock which reports every frame written to it on a channel
+fn logged_client(
+    mut responder: impl FnMut(u32, &Value) -> Vec<(u32, Value)> + Send + 'static,
+) -> (MockClient, Receiver<(u32, Value)>) {
+    let (log, frames) = mpsc::channel();
+    let client = MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        let _ = log.send((opcode, request.clone()));
+        responder(opcode, request)
+    });
+    (client, frames)
+}
+
+fn fast_config() -> ManagerConfig {
+    ManagerConfig::new()
+        .update_interval(Duration::ZERO)
+        .reconnect_backoff(Duration::from_millis(1), Duration::from_millis(4))
+}
+
+// Waits for the next SET_ACTIVITY, returning the activity it set
+fn next_update(frames: &Receiver<(u32, Value)>) -> Value {
+    loop {
+        let (_, frame) = frames.recv_timeout(TIMEOUT).expect("no update was sent");
+        if frame["cmd"] == "SET_ACTIVITY" {
+            return frame["args"]["activity"].clone();
+        }
+    }
+}
+
+#[test]
+fn test_connects_on_demand() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let (manager, statuses) = DiscordIpcManager::with_config(client, fast_config())?;
+
+    // Nothing happens until there is an activity to set
+    assert!(statuses.recv_timeout(Duration::from_millis(50)).is_err());
+
+    let done = manager.update_activity(Activity::new().state("Hello world!"));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert_eq!(next_update(&frames), json!({ "state": "Hello world!" }));
+
+    assert!(manager.clear().recv_timeout(TIMEOUT)?.is_ok());
+    assert_eq!(next_update(&frames), Value::Null);
+
+    assert!(manager.shutdown().recv_timeout(TIMEOUT)?.is_ok());
+    let closed = frames.iter().any(|(opcode, _)| opcode == 2);
+    assert!(closed);
+    Ok(())
+}
+
+#[test]
+fn test_coalesces_rate_limited_updates() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let config = fast_config().update_interval(Duration::from_millis(300));
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let first = manager.update_activity(Activity::new().state("1"));
+    assert!(first.recv_timeout(TIMEOUT)?.is_ok());
+
+    // Sent within the interval, so only the latest is applied
+    let second = manager.update_activity(Activity::new().state("2"));
+    let third = manager.update_activity(Activity::new().state("3"));
+    assert!(third.recv_timeout(TIMEOUT)?.is_ok());
+    assert!(second.recv_timeout(TIMEOUT)?.is_ok());
+
+    assert_eq!(next_update(&frames), json!({ "state": "1" }));
+    assert_eq!(next_update(&frames), json!({ "state": "3" }));
+    drop(manager);
+    assert!(frames
+        .iter()
+        .all(|(_, frame)| frame["cmd"] != "SET_ACTIVITY"));
+    Ok(())
+}
+
+#[test]
+fn test_retries_connection_with_backoff() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(default_response);
+    let client = client.fail_connects(3);
+    let (manager, statuses) = DiscordIpcManager::with_config(client, fast_config())?;
+
+    let done = manager.update_activity(Activity::new().state("Hello world!"));
+    let mut delays = Vec::new();
+    loop {
+        match statuses.recv_timeout(TIMEOUT)? {
+            ManagerStatus::ConnectFailed { retry_in, .. } => delays.push(retry_in.as_millis()),
+            ManagerStatus::Connected => break,
+            other => panic!("unexpected status {:?}", other),
+        }
+    }
+    assert_eq!(delays, [1, 2, 4]);
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_restores_activity_after_reconnecting() -> Result<(), Box<dyn Error>> {
+    let mut updates = 0;
+    let (client, frames) = logged_client(move |opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        // Discord closes the connection after the first update
+        updates += 1;
+        let mut frames = vec![response(request, Value::Null)];
+        if updates == 1 {
+            frames.push((2, json!({ "code": 1000, "message": "Restarting" })));
+        }
+        frames
+    });
+    let (manager, statuses) = DiscordIpcManager::with_config(client, fast_config())?;
+
+    manager.update_activity(Activity::new().state("Hello world!"));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Disconnected(_)
+    ));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+
+    assert_eq!(next_update(&frames), json!({ "state": "Hello world!" }));
+    assert_eq!(next_update(&frames), json!({ "state": "Hello world!" }));
+    Ok(())
+}
+
+#[test]
+fn test_reports_rejected_updates() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(|opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        let mut error = response(request, json!({ "code": 4000, "message": "Bad activity" }));
+        error.1["evt"] = "ERROR".into();
+        vec![error]
+    });
+    let (manager, statuses) = DiscordIpcManager::with_config(client, fast_config())?;
+
+    let err = manager
+        .update_activity(Activity::new())
+        .recv_timeout(TIMEOUT)?
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::CommandError { code: 4000, .. })
+    ));
+
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::UpdateFailed(_)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_drops_pending_updates() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(default_response);
+    let client = client.fail_connects(usize::MAX);
+    let config = fast_config().reconnect_backoff(Duration::from_secs(60), Duration::from_secs(60));
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new());
+    assert!(manager.shutdown().recv_timeout(TIMEOUT)?.is_ok());
+
+    let err = done.recv_timeout(TIMEOUT)?.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::ManagerStopped)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_longest_backoff_doesnt_overflow() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(default_response);
+    let client = client.fail_connects(usize::MAX);
+    let config = fast_config().reconnect_backoff(Duration::MAX, Duration::MAX);
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let _done = manager.update_activity(Activity::new());
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::ConnectFailed { .. }
+    ));
+    assert!(manager.shutdown().recv_timeout(TIMEOUT)?.is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_terminate_clears_despite_rate_limit() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let config = fast_config()
+        .rate_limiter(RateLimiter::new(1, Duration::from_secs(60)))
+        .clock(MockClock::new());
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    assert!(manager
+        .update_activity(Activity::new().state("1"))
+        .recv_timeout(TIMEOUT)?
+        .is_ok());
+    let pending = manager.update_activity(Activity::new().state("2"));
+
+    assert!(manager.terminate().recv_timeout(TIMEOUT)?.is_ok());
+    assert_eq!(next_update(&frames), json!({ "state": "1" }));
+    assert_eq!(next_update(&frames), Value::Null);
+    let closed = frames.iter().any(|(opcode, _)| opcode == 2);
+    assert!(closed);
+
+    // The pending update fails, and the manager is stopped
+    let err = pending.recv_timeout(TIMEOUT)?.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::ManagerStopped)
+    ));
+    let later = manager.update_activity(Activity::new());
+    assert!(later.recv_timeout(TIMEOUT).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sends_only_latest_update_after_reconnecting() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let client = client.fail_connects(1);
+    let config =
+        fast_config().reconnect_backoff(Duration::from_millis(200), Duration::from_secs(1));
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    // The first update fails to connect, and the rest arrive while
+    // waiting to retry
+    let first = manager.update_activity(Activity::new().state("0"));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::ConnectFailed { .. }
+    ));
+    let states: Vec<_> = (1..10).map(|i| i.to_string()).collect();
+    let mut completions: Vec<_> = states
+        .iter()
+        .map(|state| manager.update_activity(Activity::new().state(state)))
+        .collect();
+    completions.push(first);
+
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    for done in completions {
+        assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    }
+
+    assert_eq!(next_update(&frames), json!({ "state": "9" }));
+    drop(manager);
+    assert!(frames
+        .iter()
+        .all(|(_, frame)| frame["cmd"] != "SET_ACTIVITY"));
+    Ok(())
+}
+
+#[test]
+fn test_clear_supersedes_pending_update() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let config = fast_config().update_interval(Duration::from_millis(200));
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    assert!(manager
+        .update_activity(Activity::new().state("1"))
+        .recv_timeout(TIMEOUT)?
+        .is_ok());
+    manager.update_activity(Activity::new().state("2"));
+    assert!(manager.clear().recv_timeout(TIMEOUT)?.is_ok());
+
+    // And an update supersedes a pending clear
+    manager.clear();
+    assert!(manager
+        .update_activity(Activity::new().state("3"))
+        .recv_timeout(TIMEOUT)?
+        .is_ok());
+
+    assert_eq!(next_update(&frames), json!({ "state": "1" }));
+    assert_eq!(next_update(&frames), Value::Null);
+    assert_eq!(next_update(&frames), json!({ "state": "3" }));
+    Ok(())
+}
+
+#[test]
+fn test_follows_rate_limiter_budget() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .rate_limiter(RateLimiter::new(2, Duration::from_secs(40)))
+        .clock(clock.clone());
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    // A burst within the budget is sent as is
+    for state in ["1", "2"] {
+        let done = manager.update_activity(Activity::new().state(state));
+        assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    }
+    for state in ["3", "4"] {
+        manager.update_activity(Activity::new().state(state));
+    }
+    let cleared = manager.clear();
+    assert!(cleared.recv_timeout(Duration::from_millis(200)).is_err());
+
+    clock.advance(Duration::from_secs(20));
+    assert!(cleared.recv_timeout(TIMEOUT)?.is_ok());
+
+    for expected in [
+        json!({ "state": "1" }),
+        json!({ "state": "2" }),
+        Value::Null,
+    ] {
+        assert_eq!(next_update(&frames), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_backoff_follows_clock() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(default_response);
+    let client = client.fail_connects(1);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .reconnect_backoff(Duration::from_secs(60), Duration::from_secs(60))
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new().state("Hello world!"));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::ConnectFailed { .. }
+    ));
+
+    // No time passes on the clock, so the retry waits
+    assert!(statuses.recv_timeout(Duration::from_millis(200)).is_err());
+    clock.advance(Duration::from_secs(60));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_circuit_breaker_pauses_retries() -> Result<(), Box<dyn Error>> {
+    let (client, _frames) = logged_client(default_response);
+    let client = client.fail_connects(3);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .reconnect_backoff(Duration::from_secs(1), Duration::from_secs(1))
+        .circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(30)))
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new().state("Hello world!"));
+    let mut retries = Vec::new();
+    for _ in 0..2 {
+        match statuses.recv_timeout(TIMEOUT)? {
+            ManagerStatus::ConnectFailed { retry_in, .. } => retries.push(retry_in),
+            status => panic!("unexpected status {:?}", status),
+        }
+        clock.advance(Duration::from_secs(1));
+    }
+    assert_eq!(retries, [Duration::from_secs(1), Duration::from_secs(30)]);
+
+    // Open, so the backoff passing isn't enough to retry
+    assert!(statuses.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // The probe fails, and opens it again
+    clock.advance(Duration::from_secs(29));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::ConnectFailed { retry_in, .. } if retry_in == Duration::from_secs(30)
+    ));
+    assert!(statuses.recv_timeout(Duration::from_millis(200)).is_err());
+
+    clock.advance(Duration::from_secs(30));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_activity_ttl_refreshed_by_updates() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .activity_ttl(Duration::from_secs(30))
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new().state("1"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+
+    // Each update restarts the time to live
+    clock.advance(Duration::from_secs(20));
+    let done = manager.update_activity(Activity::new().state("2"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    clock.advance(Duration::from_secs(20));
+    assert!(statuses.recv_timeout(Duration::from_millis(300)).is_err());
+
+    assert_eq!(next_update(&frames), json!({ "state": "1" }));
+    assert_eq!(next_update(&frames), json!({ "state": "2" }));
+    assert!(frames.recv_timeout(Duration::from_millis(100)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_activity_ttl_expiry_clears() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .rate_limiter(RateLimiter::new(1, Duration::from_secs(60)))
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new().state("Stale"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+
+    // Armed from now, for the activity already set
+    let armed = manager.set_activity_ttl(Some(Duration::from_secs(10)));
+    assert!(armed.recv_timeout(TIMEOUT)?.is_ok());
+    clock.advance(Duration::from_secs(10));
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::ActivityExpired
+    ));
+
+    // The clear waits for the rate limiter like any update
+    assert_eq!(next_update(&frames), json!({ "state": "Stale" }));
+    assert!(frames.recv_timeout(Duration::from_millis(200)).is_err());
+    clock.advance(Duration::from_secs(50));
+    assert_eq!(next_update(&frames), Value::Null);
+
+    // It only fires once
+    clock.advance(Duration::from_secs(60));
+    assert!(statuses.recv_timeout(Duration::from_millis(200)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_unrepresentable_activity_ttl_never_expires() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .activity_ttl(Duration::MAX)
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    let done = manager.update_activity(Activity::new().state("Forever"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    clock.advance(Duration::from_secs(365 * 24 * 60 * 60));
+    assert!(statuses.recv_timeout(Duration::from_millis(300)).is_err());
+
+    // The worker is still running
+    let done = manager.update_activity(Activity::new().state("Still here"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert_eq!(next_update(&frames), json!({ "state": "Forever" }));
+    assert_eq!(next_update(&frames), json!({ "state": "Still here" }));
+    Ok(())
+}
+
+#[test]
+fn test_activity_ttl_stopped_by_clear() -> Result<(), Box<dyn Error>> {
+    let (client, frames) = logged_client(default_response);
+    let clock = MockClock::new();
+    let config = fast_config()
+        .activity_ttl(Duration::from_secs(10))
+        .clock(clock.clone());
+    let (manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+
+    assert!(manager
+        .update_activity(Activity::new().state("Playing"))
+        .recv_timeout(TIMEOUT)?
+        .is_ok());
+    assert!(manager.clear().recv_timeout(TIMEOUT)?.is_ok());
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+
+    clock.advance(Duration::from_secs(30));
+    assert!(statuses.recv_timeout(Duration::from_millis(300)).is_err());
+
+    assert_eq!(next_update(&frames), json!({ "state": "Playing" }));
+    assert_eq!(next_update(&frames), Value::Null);
+    assert!(frames.recv_timeout(Duration::from_millis(100)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_persisted_activity_is_restored() -> Result<(), Box<dyn Error>> {
+    let dir = env::temp_dir().join(format!(
+        "discord-ipc-manager-persist-{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir)?;
+    let store = Arc::new(FileStore::new(dir.join("presence.json")));
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+    let persistence = Persistence::new(store.clone())
+        .restore_on_connect(Duration::from_secs(60))
+        .clock(clock.clone());
+
+    let (client, frames) = logged_client(default_response);
+    let config = fast_config().persist_to(persistence.clone());
+    let (manager, _statuses) = DiscordIpcManager::with_config(client, config)?;
+    let done = manager.update_activity(Activity::new().state("Before restarting"));
+    assert!(done.recv_timeout(TIMEOUT)?.is_ok());
+    assert!(manager.shutdown().recv_timeout(TIMEOUT)?.is_ok());
+    assert_eq!(
+        next_update(&frames),
+        json!({ "state": "Before restarting" })
+    );
+    assert_eq!(store.load()?.unwrap().saved_at, 1_700_000_000_000);
+
+    // The saved activity is set without waiting for an update
+    let (client, frames) = logged_client(default_response);
+    let config = fast_config().persist_to(persistence);
+    let (_manager, statuses) = DiscordIpcManager::with_config(client, config)?;
+    assert!(matches!(
+        statuses.recv_timeout(TIMEOUT)?,
+        ManagerStatus::Connected
+    ));
+    assert_eq!(
+        next_update(&frames),
+        json!({ "state": "Before restarting" })
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
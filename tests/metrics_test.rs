@@ -0,0 +1,104 @@
+#![cfg(feature = "metrics")]
+
+use discord_rich_presence::{
+    activity::Activity,
+    metrics::{
+        COMMAND_DURATION, CONNECTS, CONNECT_ATTEMPTS, FRAMES_RECEIVED, FRAMES_SENT, RATE_LIMITED,
+        RECONNECTS,
+    },
+    rate_limit::{RateLimitPolicy, RateLimiter},
+    test_util::MockDiscordServer,
+    DiscordIpc,
+};
+use metrics_util::{
+    debugging::{DebugValue, DebuggingRecorder},
+    CompositeKey,
+};
+use serde_json::json;
+use std::{error::Error, time::Duration};
+
+type Snapshot = Vec<(CompositeKey, DebugValue)>;
+
+// The value of a counter, summed over the label values given
+fn counter(snapshot: &Snapshot, name: &str, labels: &[(&str, &str)]) -> u64 {
+    snapshot
+        .iter()
+        .filter(|(key, ..)| {
+            let key = key.key();
+            key.name() == name
+                && labels.iter().all(|(label, value)| {
+                    key.labels()
+                        .any(|found| found.key() == *label && found.value() == *value)
+                })
+        })
+        .map(|(_, value)| match value {
+            DebugValue::Counter(count) => *count,
+            other => panic!("{} is not a counter: {:?}", name, other),
+        })
+        .sum()
+}
+
+// How many samples of a histogram were recorded for a command
+fn command_samples(snapshot: &Snapshot, cmd: &str) -> usize {
+    snapshot
+        .iter()
+        .filter(|(key, ..)| {
+            let key = key.key();
+            key.name() == COMMAND_DURATION
+                && key
+                    .labels()
+                    .any(|label| label.key() == "cmd" && label.value() == cmd)
+        })
+        .map(|(_, value)| match value {
+            DebugValue::Histogram(samples) => samples.len(),
+            other => panic!("{} is not a histogram: {:?}", COMMAND_DURATION, other),
+        })
+        .sum()
+}
+
+#[test]
+fn test_records_session_metrics() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    metrics::with_local_recorder(&recorder, || -> Result<(), Box<dyn Error>> {
+        let mut client = server.client("771124766517755954");
+        client.connect()?;
+        client.set_rate_limiter(Some(
+            RateLimiter::new(1, Duration::from_secs(60)).policy(RateLimitPolicy::Coalesce),
+        ));
+        client.set_activity(Activity::new().state("1"))?;
+        client.set_activity(Activity::new().state("2"))?;
+        client.command("GET_GUILDS", json!({}))?;
+        client.reconnect()?;
+        Ok(())
+    })?;
+
+    // Taking a snapshot resets the counters, so only one is taken
+    let snapshot: Snapshot = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key, value))
+        .collect();
+
+    assert_eq!(counter(&snapshot, CONNECT_ATTEMPTS, &[]), 2);
+    assert_eq!(counter(&snapshot, CONNECTS, &[]), 2);
+    assert_eq!(counter(&snapshot, RECONNECTS, &[]), 1);
+
+    // Two handshakes, two commands and a close were sent, and
+    // answered with two READY dispatches and two responses
+    assert_eq!(counter(&snapshot, FRAMES_SENT, &[("opcode", "0")]), 2);
+    assert_eq!(counter(&snapshot, FRAMES_SENT, &[("opcode", "1")]), 2);
+    assert_eq!(counter(&snapshot, FRAMES_SENT, &[("opcode", "2")]), 1);
+    assert_eq!(counter(&snapshot, FRAMES_RECEIVED, &[("opcode", "1")]), 4);
+
+    assert_eq!(command_samples(&snapshot, "SET_ACTIVITY"), 1);
+    assert_eq!(command_samples(&snapshot, "GET_GUILDS"), 1);
+    assert_eq!(
+        counter(&snapshot, RATE_LIMITED, &[("policy", "coalesce")]),
+        1
+    );
+    Ok(())
+}
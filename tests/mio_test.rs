@@ -0,0 +1,98 @@
+#![cfg(all(unix, feature = "mio"))]
+
+use discord_rich_presence::{
+    event::{Event, EventKind},
+    transport::NativeTransport,
+    DiscordIpc, DiscordIpcClient, IpcConnection,
+};
+use mio::{Events, Interest, Poll, Token};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::Write,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+const DISCORD: Token = Token(0);
+
+fn frame(opcode: u32, data: serde_json::Value) -> Vec<u8> {
+    let payload = data.to_string().into_bytes();
+    let mut bytes = opcode.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend(payload);
+    bytes
+}
+
+// Connects a client to a stand-in for Discord's socket, found
+// through a fabricated XDG_RUNTIME_DIR
+fn connect(dir: &PathBuf) -> Result<(DiscordIpcClient, UnixStream), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let listener = UnixListener::bind(dir.join("discord-ipc-0"))?;
+
+    let env: HashMap<_, _> = [("XDG_RUNTIME_DIR".to_string(), dir.display().to_string())].into();
+    let mut client =
+        DiscordIpcClient::with_transport("771124766517755954", NativeTransport::with_env(env));
+    client.connect_ipc()?;
+    let (peer, _) = listener.accept()?;
+    Ok((client, peer))
+}
+
+fn readable(poll: &mut Poll, timeout: Duration) -> std::io::Result<bool> {
+    let mut events = Events::with_capacity(4);
+    poll.poll(&mut events, Some(timeout))?;
+    Ok(events.iter().any(|event| event.token() == DISCORD))
+}
+
+#[test]
+fn test_register_and_deregister() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-mio-{}", std::process::id()));
+    let (mut client, mut peer) = connect(&dir)?;
+
+    let mut poll = Poll::new()?;
+    poll.registry()
+        .register(&mut client, DISCORD, Interest::READABLE)?;
+    assert!(!readable(&mut poll, Duration::ZERO)?);
+    assert!(client.try_recv_event()?.is_none());
+
+    let dispatch = json!({ "cmd": "DISPATCH", "evt": "ACTIVITY_JOIN", "data": {}, "nonce": null });
+    peer.write_all(&frame(1, dispatch.clone()))?;
+    peer.write_all(&frame(1, dispatch.clone()))?;
+    assert!(readable(&mut poll, Duration::from_secs(1))?);
+
+    for _ in 0..2 {
+        assert!(matches!(
+            client.try_recv_event()?,
+            Some(Event::Dispatch {
+                kind: EventKind::ActivityJoin,
+                ..
+            })
+        ));
+    }
+    assert!(client.try_recv_event()?.is_none());
+
+    poll.registry()
+        .reregister(&mut client, DISCORD, Interest::READABLE)?;
+    poll.registry().deregister(&mut client)?;
+    peer.write_all(&frame(1, dispatch))?;
+    assert!(!readable(&mut poll, Duration::from_millis(100))?);
+    assert!(client.try_recv_event()?.is_some());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_register_unconnected_client_fails() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = DiscordIpcClient::new("771124766517755954")?;
+    let poll = Poll::new()?;
+
+    let err = poll
+        .registry()
+        .register(&mut client, DISCORD, Interest::READABLE)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotConnected);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "test-util")]
+
+use discord_rich_presence::{
+    activity::Activity,
+    error,
+    event::{Event, EventKind},
+    test_util::{default_response, response, MockDiscordServer},
+    DiscordIpc,
+};
+use serde_json::json;
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn test_records_handshake_and_commands() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    client.set_activity(Activity::new().state("Mocked"))?;
+
+    let received = server.received();
+    assert_eq!(
+        received[0],
+        (0, json!({ "v": 1, "client_id": "771124766517755954" }))
+    );
+    assert_eq!(received[1].1["cmd"], "SET_ACTIVITY");
+    assert_eq!(received[1].1["args"]["activity"]["state"], "Mocked");
+    assert_eq!(server.connections(), 1);
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    Ok(())
+}
+
+#[test]
+fn test_scripted_responses_and_responder() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.respond_with(|opcode, request| match opcode {
+        1 => vec![response(request, json!({ "from": "responder" }))],
+        _ => default_response(opcode, request),
+    });
+    server.push_response(vec![(
+        1,
+        json!({ "cmd": "GET_GUILDS", "data": { "from": "queue" }, "evt": null, "nonce": null }),
+    )]);
+
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    assert_eq!(
+        client.command("GET_GUILDS", json!({}))?,
+        json!({ "from": "queue" })
+    );
+    assert_eq!(
+        client.command("GET_GUILDS", json!({}))?,
+        json!({ "from": "responder" })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_delay_next_response() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    server.delay_next_response(Duration::from_millis(100));
+    let start = Instant::now();
+    client.command("GET_GUILDS", json!({}))?;
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    Ok(())
+}
+
+#[test]
+fn test_unsolicited_event() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+
+    server.send_event("ACTIVITY_JOIN", json!({ "secret": "s" }))?;
+    match client.recv_event()? {
+        Event::Dispatch { kind, data } => {
+            assert_eq!(kind, EventKind::ActivityJoin);
+            assert_eq!(data, json!({ "secret": "s" }));
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_close_connection_and_reconnect() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+
+    server.close_connection(4002, "Invalid payload")?;
+    let err = client.recv_event().unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::ConnectionClosed { code, .. }) => assert_eq!(*code, 4002),
+        _ => panic!("expected ConnectionClosed, got {}", err),
+    }
+
+    client.reconnect()?;
+    assert_eq!(server.connections(), 2);
+    let handshakes = server
+        .received()
+        .iter()
+        .filter(|(opcode, _)| *opcode == 0)
+        .count();
+    assert_eq!(handshakes, 2);
+    Ok(())
+}
+
+#[test]
+fn test_rejected_handshake() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.respond_with(|opcode, request| match opcode {
+        0 => vec![(2, json!({ "code": 4000, "message": "Invalid client ID" }))],
+        _ => default_response(opcode, request),
+    });
+
+    let mut client = server.client("0");
+    assert!(client.connect().is_err());
+    Ok(())
+}
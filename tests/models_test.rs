@@ -1,12 +1,14 @@
-use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
-use std::error::Error;
+mod common;
 
-#[test]
-fn test_models() -> Result<(), Box<dyn Error>> {
-    let mut client = DiscordIpcClient::new("771124766517755954")?;
-    client.connect()?;
+use common::without_nonce;
+use discord_rich_presence::{
+    activity, pid, test_util::MockDiscordServer, DiscordIpc, DiscordIpcClient,
+};
+use serde_json::json;
+use std::error::Error;
 
-    let activity = activity::Activity::new()
+fn activity() -> activity::Activity<'static> {
+    activity::Activity::new()
         .state("A test")
         .details("A placeholder")
         .assets(
@@ -17,8 +19,46 @@ fn test_models() -> Result<(), Box<dyn Error>> {
         .buttons(vec![activity::Button::new(
             "A button",
             "https://github.com",
-        )]);
-    client.set_activity(activity)?;
+        )])
+}
+
+#[test]
+fn test_models() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    client.set_activity(activity())?;
+
+    let (opcode, payload) = server.received().pop().unwrap();
+    assert_eq!(opcode, 1);
+    assert_eq!(
+        without_nonce(&payload),
+        json!({
+            "cmd": "SET_ACTIVITY",
+            "args": {
+                "pid": pid::pid(),
+                "activity": {
+                    "state": "A test",
+                    "details": "A placeholder",
+                    "assets": { "large_image": "large-image", "large_text": "Large text" },
+                    "buttons": [{ "label": "A button", "url": "https://github.com" }]
+                }
+            }
+        })
+    );
+
+    client.close()?;
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires a running Discord client"]
+fn test_models_live() -> Result<(), Box<dyn Error>> {
+    let mut client = DiscordIpcClient::new("771124766517755954")?;
+    client.connect()?;
+
+    client.set_activity(activity())?;
 
     std::thread::sleep(std::time::Duration::from_secs(10));
 
@@ -0,0 +1,130 @@
+#![cfg(feature = "test-util")]
+
+use discord_rich_presence::{
+    activity::Activity,
+    multi_app::MultiAppPresence,
+    test_util::{MockClock, MockDiscordServer},
+};
+use serde_json::Value;
+use std::{error::Error, time::Duration};
+
+const MENU: &str = "771124766517755954";
+const MATCH: &str = "1024391201209057341";
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+// Describes each frame the server received, in order
+fn summary(server: &MockDiscordServer) -> Vec<String> {
+    server
+        .received()
+        .iter()
+        .map(|(opcode, payload)| match opcode {
+            0 => format!("handshake {}", payload["client_id"].as_str().unwrap()),
+            2 => "close".to_string(),
+            _ => match &payload["args"]["activity"] {
+                Value::Null => "clear".to_string(),
+                activity => format!("set {}", activity["state"].as_str().unwrap()),
+            },
+        })
+        .collect()
+}
+
+#[test]
+fn test_switching_clears_then_handshakes() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let clock = MockClock::new();
+    let mut presence =
+        MultiAppPresence::new(server.client(MENU), QUIET_PERIOD).clock(clock.clone());
+    presence.set_activity(MENU, Activity::new().state("menu"))?;
+    presence.set_activity(MATCH, Activity::new().state("match"))?;
+    assert!(server.received().is_empty());
+
+    presence.focus(Some(MENU));
+    assert!(!presence.poll()?);
+    clock.advance(QUIET_PERIOD);
+    assert!(presence.poll()?);
+    assert_eq!(presence.active_app(), Some(MENU));
+
+    presence.focus(Some(MATCH));
+    clock.advance(QUIET_PERIOD);
+    assert!(presence.poll()?);
+    assert_eq!(presence.active_app(), Some(MATCH));
+
+    // Updates to the active application are sent at once, and to
+    // others only remembered
+    presence.set_activity(MATCH, Activity::new().state("overtime"))?;
+    presence.set_activity(MENU, Activity::new().state("settings"))?;
+
+    presence.switch(Some(MENU))?;
+    assert_eq!(
+        summary(&server),
+        [
+            "handshake 771124766517755954",
+            "set menu",
+            "clear",
+            "close",
+            "handshake 1024391201209057341",
+            "set match",
+            "set overtime",
+            "clear",
+            "close",
+            "handshake 771124766517755954",
+            "set settings",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_focus_flapping_is_debounced() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let clock = MockClock::new();
+    let mut presence =
+        MultiAppPresence::new(server.client(MENU), QUIET_PERIOD).clock(clock.clone());
+    presence.set_activity(MENU, Activity::new().state("menu"))?;
+    presence.switch(Some(MENU))?;
+
+    for _ in 0..5 {
+        presence.focus(Some(MATCH));
+        clock.advance(QUIET_PERIOD / 4);
+        presence.focus(Some(MENU));
+        clock.advance(QUIET_PERIOD / 4);
+        assert!(!presence.poll()?);
+    }
+
+    // Focus settled back where it was, so nothing changes
+    clock.advance(QUIET_PERIOD);
+    assert!(!presence.poll()?);
+    assert_eq!(presence.deadline(), None);
+    assert_eq!(server.connections(), 1);
+    assert_eq!(
+        summary(&server),
+        ["handshake 771124766517755954", "set menu"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_switching_to_none_closes() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut presence = MultiAppPresence::new(server.client(MENU), QUIET_PERIOD);
+    presence.set_activity(MENU, Activity::new().state("menu"))?;
+
+    presence.switch(Some(MENU))?;
+    presence.switch(None)?;
+    assert_eq!(presence.active_app(), None);
+
+    // Clearing an inactive application sends nothing
+    presence.clear_activity(MENU)?;
+    presence.switch(Some(MENU))?;
+    assert_eq!(
+        summary(&server),
+        [
+            "handshake 771124766517755954",
+            "set menu",
+            "clear",
+            "close",
+            "handshake 771124766517755954",
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::DiscordIpc;
+use serde_json::json;
+use std::{collections::HashSet, error::Error};
+
+#[test]
+fn test_nonces_are_unique() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    for _ in 0..1000 {
+        client.command("GET_GUILDS", json!({}))?;
+    }
+
+    let nonces: HashSet<_> = client
+        .sent
+        .iter()
+        .filter_map(|(_, payload)| payload["nonce"].as_str())
+        .collect();
+    assert_eq!(nonces.len(), 1000);
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use discord_rich_presence::models::{
+    Channel, Message, PartialGuild, User, VoiceState, VoiceStateFlags,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+
+fn parse<T: serde::de::DeserializeOwned>(fixture: Value) -> Result<T, Box<dyn Error>> {
+    Ok(serde_json::from_value(fixture)?)
+}
+
+#[test]
+fn test_user() -> Result<(), Box<dyn Error>> {
+    let minimal: User = parse(json!({ "id": "53908232506183680", "username": "mason" }))?;
+    assert_eq!(minimal.discriminator, None);
+    assert_eq!(minimal.avatar, None);
+    assert!(!minimal.bot);
+    assert_eq!(
+        minimal.avatar_url(128),
+        "https://cdn.discordapp.com/embed/avatars/2.png"
+    );
+
+    let maximal: User = parse(json!({
+        "id": "190320984123768832",
+        "username": "test 2",
+        "discriminator": "7479",
+        "global_name": "Test",
+        "avatar": "b004ec1740a63ca06ae2e14c5cee11f3",
+        "bot": false,
+        "flags": 0,
+        "premium_type": 2
+    }))?;
+    assert_eq!(maximal.global_name.as_deref(), Some("Test"));
+    assert_eq!(
+        maximal.avatar_url(256),
+        "https://cdn.discordapp.com/avatars/190320984123768832/b004ec1740a63ca06ae2e14c5cee11f3.png?size=256"
+    );
+
+    let mut animated = maximal.clone();
+    animated.avatar = Some("a_1234".to_string());
+    assert!(animated.avatar_url(64).ends_with("a_1234.gif?size=64"));
+
+    let mut legacy = maximal;
+    legacy.avatar = None;
+    assert_eq!(
+        legacy.avatar_url(64),
+        "https://cdn.discordapp.com/embed/avatars/4.png"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_partial_guild() -> Result<(), Box<dyn Error>> {
+    let minimal: PartialGuild = parse(json!({ "id": "199737254929760256", "name": "test" }))?;
+    assert_eq!(minimal.icon_url, None);
+
+    let maximal: PartialGuild = parse(json!({
+        "id": "199737254929760256",
+        "name": "test",
+        "icon_url": "https://cdn.discordapp.com/icons/199737254929760256/abc.jpg",
+        "members": []
+    }))?;
+    assert_eq!(
+        maximal.icon_url.as_deref(),
+        Some("https://cdn.discordapp.com/icons/199737254929760256/abc.jpg")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_channel() -> Result<(), Box<dyn Error>> {
+    let minimal: Channel = parse(json!({
+        "id": "199737254929760257",
+        "name": "general",
+        "type": 0
+    }))?;
+    assert_eq!(minimal.kind, 0);
+    assert_eq!(minimal.guild_id, None);
+    assert!(minimal.voice_states.is_empty());
+    assert!(minimal.messages.is_empty());
+
+    let maximal: Channel = parse(json!({
+        "id": "199737254929760257",
+        "guild_id": "199737254929760256",
+        "name": "General",
+        "type": 2,
+        "topic": "",
+        "bitrate": 64000,
+        "user_limit": 0,
+        "position": 0,
+        "voice_states": [{
+            "voice_state": {
+                "mute": false,
+                "deaf": false,
+                "self_mute": false,
+                "self_deaf": false,
+                "suppress": false
+            },
+            "user": {
+                "id": "190320984123768832",
+                "username": "test 2",
+                "discriminator": "7479",
+                "avatar": "b004ec1740a63ca06ae2e14c5cee11f3",
+                "bot": false
+            },
+            "nick": "test user 2",
+            "volume": 110,
+            "mute": false,
+            "pan": { "left": 1.0, "right": 1.0 }
+        }],
+        "messages": [{
+            "id": "199743874640379904",
+            "content": "test",
+            "timestamp": "2016-07-05T04:30:50.776Z",
+            "tts": false
+        }]
+    }))?;
+    assert_eq!(maximal.bitrate, Some(64000));
+    assert_eq!(maximal.voice_states[0].user.username, "test 2");
+    assert_eq!(maximal.messages[0].content.as_deref(), Some("test"));
+    Ok(())
+}
+
+#[test]
+fn test_message() -> Result<(), Box<dyn Error>> {
+    let minimal: Message = parse(json!({ "id": "199743874640379904" }))?;
+    assert_eq!(minimal.author, None);
+    assert_eq!(minimal.content, None);
+
+    let maximal: Message = parse(json!({
+        "id": "199743874640379904",
+        "blocked": false,
+        "bot": false,
+        "content": "test",
+        "content_parsed": [{ "content": "test", "type": "text" }],
+        "nick": "test test",
+        "author_color": "#ffffff",
+        "edited_timestamp": null,
+        "timestamp": "2016-07-05T04:30:50.776Z",
+        "tts": false,
+        "mentions": [],
+        "mention_roles": [],
+        "mention_everyone": false,
+        "embeds": [],
+        "attachments": [],
+        "type": 0,
+        "pinned": false,
+        "author": {
+            "id": "53908232506183680",
+            "username": "Mason",
+            "discriminator": "1337",
+            "avatar": null,
+            "bot": false
+        }
+    }))?;
+    assert_eq!(maximal.author.unwrap().username, "Mason");
+    assert_eq!(maximal.nick.as_deref(), Some("test test"));
+    assert_eq!(maximal.edited_timestamp, None);
+    Ok(())
+}
+
+#[test]
+fn test_voice_state() -> Result<(), Box<dyn Error>> {
+    let minimal: VoiceState = parse(json!({
+        "voice_state": {},
+        "user": { "id": "190320984123768832", "username": "test 2" }
+    }))?;
+    assert_eq!(minimal.voice_state, VoiceStateFlags::default());
+    assert_eq!(minimal.volume, None);
+    assert_eq!(minimal.pan, None);
+
+    let maximal: VoiceState = parse(json!({
+        "voice_state": {
+            "mute": true,
+            "deaf": false,
+            "self_mute": true,
+            "self_deaf": false,
+            "suppress": false
+        },
+        "user": { "id": "190320984123768832", "username": "test 2" },
+        "nick": "test user 2",
+        "volume": 50.5,
+        "mute": true,
+        "pan": { "left": 0.25, "right": 1.0 }
+    }))?;
+    assert!(maximal.voice_state.mute);
+    assert!(maximal.voice_state.self_mute);
+    assert!(maximal.mute);
+    assert_eq!(maximal.volume, Some(50.5));
+    let pan = maximal.pan.unwrap();
+    assert_eq!((pan.left, pan.right), (0.25, 1.0));
+    Ok(())
+}
+
+// Models can't be built with struct literals outside the crate,
+// but can be from their defaults
+#[test]
+fn test_models_build_from_default() {
+    let mut user = User::default();
+    user.id = "190320984123768832".to_string();
+    user.discriminator = Some("0".to_string());
+    assert_eq!(
+        user.avatar_url(64),
+        "https://cdn.discordapp.com/embed/avatars/4.png"
+    );
+
+    let mut state = VoiceState::default();
+    state.user = user;
+    state.voice_state.self_mute = true;
+    assert!(state.voice_state.self_mute && !state.mute);
+}
@@ -0,0 +1,58 @@
+use discord_rich_presence::activity::{Activity, Assets, Party, Secrets, Timestamps};
+use serde_json::json;
+use std::error::Error;
+
+#[test]
+fn test_only_some_values_serialize() -> Result<(), Box<dyn Error>> {
+    let album: Option<&str> = None;
+    let artist = Some("Some Artist".to_string());
+
+    let activity = Activity::new()
+        .details_opt(Some("Some Track"))
+        .state_opt(album)
+        .timestamps_opt(Some(
+            Timestamps::new().start_opt(Some(1650000000)).end_opt(None),
+        ))
+        .party_opt(Some(
+            Party::new().id_opt(None::<&str>).size_opt(Some([1, 4])),
+        ))
+        .assets_opt(Some(
+            Assets::new()
+                .large_image_opt(Some("cover"))
+                .large_text_opt(album)
+                .small_image_opt(None::<&str>)
+                .small_text_opt(artist),
+        ))
+        .secrets_opt(Some(
+            Secrets::new()
+                .join_opt(Some("join"))
+                .spectate_opt(None::<&str>)
+                .match_opt(None::<&str>),
+        ));
+
+    assert_eq!(
+        serde_json::to_value(&activity)?,
+        json!({
+            "details": "Some Track",
+            "timestamps": { "start": 1650000000 },
+            "party": { "size": [1, 4] },
+            "assets": { "large_image": "cover", "small_text": "Some Artist" },
+            "secrets": { "join": "join" }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_none_keeps_earlier_value() -> Result<(), Box<dyn Error>> {
+    let activity = Activity::new()
+        .state("In the menu")
+        .state_opt(None::<&str>)
+        .assets_opt(None);
+
+    assert_eq!(
+        serde_json::to_value(&activity)?,
+        json!({ "state": "In the menu" })
+    );
+    Ok(())
+}
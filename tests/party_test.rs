@@ -0,0 +1,81 @@
+use discord_rich_presence::{
+    activity::Activity,
+    event::{Event, EventKind},
+    party::PartySession,
+};
+use serde_json::{json, Value};
+
+fn join(secret: &str) -> Event {
+    Event::Dispatch {
+        kind: EventKind::ActivityJoin,
+        data: json!({ "secret": secret }),
+    }
+}
+
+fn applied(session: &PartySession) -> Value {
+    json!(session.apply(Activity::new().state("In a lobby")))
+}
+
+#[test]
+fn test_apply_attaches_party_and_secret() {
+    let mut session = PartySession::new();
+    session.set_size(2, 4);
+
+    let secret = session.secret().unwrap().to_string();
+    assert_ne!(session.id(), secret);
+    assert_eq!(
+        applied(&session),
+        json!({
+            "state": "In a lobby",
+            "party": { "id": session.id(), "size": [2, 4] },
+            "secrets": { "join": secret }
+        })
+    );
+    assert!(session.accepts(&join(&secret)));
+    assert!(!session.accepts(&join("someone else's secret")));
+}
+
+#[test]
+fn test_rotation_invalidates_old_secret() {
+    let mut session = PartySession::new();
+    let id = session.id().to_string();
+    let old = session.secret().unwrap().to_string();
+
+    session.rotate_secret();
+    let new = session.secret().unwrap().to_string();
+    assert_ne!(old, new);
+    assert_eq!(session.id(), id);
+    assert_eq!(applied(&session)["secrets"]["join"], new.as_str());
+
+    assert!(!session.accepts(&join(&old)));
+    assert!(session.accepts(&join(&new)));
+}
+
+#[test]
+fn test_closed_session_emits_no_secrets() {
+    let mut session = PartySession::new();
+    let secret = session.secret().unwrap().to_string();
+    session.close();
+    session.rotate_secret();
+
+    assert!(!session.is_open());
+    assert!(session.secrets().is_none());
+    assert_eq!(
+        applied(&session),
+        json!({ "state": "In a lobby", "party": { "id": session.id() } })
+    );
+    assert!(!session.accepts(&join(&secret)));
+}
+
+#[test]
+fn test_only_join_events_are_accepted() {
+    let session = PartySession::new();
+    let secret = session.secret().unwrap();
+
+    let spectate = Event::Dispatch {
+        kind: EventKind::ActivitySpectate,
+        data: json!({ "secret": secret }),
+    };
+    assert!(!session.accepts(&spectate));
+    assert!(session.is_valid_secret(secret));
+}
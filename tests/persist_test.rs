@@ -0,0 +1,242 @@
+mod common;
+
+use common::{activity_updates, default_response, MockClient};
+use discord_rich_presence::{
+    activity::Activity,
+    persist::{FileStore, Persistence, PresenceStore, SavedPresence},
+    test_util::{MockClock, MockDiscordServer},
+    DiscordIpc, IpcConnection,
+};
+use serde_json::json;
+use std::{env, error::Error, fs, path::PathBuf, sync::Arc, time::Duration};
+
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+// A directory of its own for each test, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("discord-ipc-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn store(&self) -> Arc<FileStore> {
+        Arc::new(FileStore::new(self.0.join("presence.json")))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn persisting_client(store: Arc<FileStore>, clock: &Arc<MockClock>) -> MockClient {
+    let mut client = MockClient::new("771124766517755954");
+    client.persist_to(Some(
+        Persistence::new(store)
+            .restore_on_connect(MAX_AGE)
+            .clock(clock.clone()),
+    ));
+    client
+}
+
+#[test]
+fn test_file_store_round_trip() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("file-store");
+    let store = dir.store();
+    assert!(store.load()?.is_none());
+
+    store.save(&SavedPresence::new(
+        "771124766517755954",
+        Some(Activity::new().state("Saved").details("Long running")),
+        1_700_000_000_000,
+    ))?;
+    let loaded = store.load()?.unwrap();
+    assert_eq!(loaded.client_id, "771124766517755954");
+    assert_eq!(loaded.saved_at, 1_700_000_000_000);
+    assert_eq!(
+        json!(loaded.activity),
+        json!({ "state": "Saved", "details": "Long running" })
+    );
+
+    store.save(&SavedPresence::new(
+        "771124766517755954",
+        None,
+        1_700_000_001_000,
+    ))?;
+    assert!(store.load()?.unwrap().activity.is_none());
+
+    // Nothing is left behind from replacing the file
+    assert_eq!(fs::read_dir(&dir.0)?.count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_failed_save_leaves_nothing_behind() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("file-store-failed");
+    // A directory can't be replaced by the saved file
+    fs::create_dir(dir.0.join("presence.json"))?;
+    let store = dir.store();
+
+    assert!(store
+        .save(&SavedPresence::new("771124766517755954", None, 0))
+        .is_err());
+    assert_eq!(fs::read_dir(&dir.0)?.count(), 1);
+
+    // Updates still succeed when the activity can't be saved
+    let mut client = persisting_client(store, &MockClock::new());
+    client.connect()?;
+    client.set_activity(Activity::new().state("Unsaved"))?;
+    assert_eq!(fs::read_dir(&dir.0)?.count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_restores_after_restart() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("restore");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+
+    let mut before = persisting_client(dir.store(), &clock);
+    before.connect()?;
+    before.set_activity(Activity::new().state("Compiling"))?;
+    assert_eq!(activity_updates(&before.sent).len(), 1);
+
+    clock.advance(Duration::from_secs(30 * 60));
+    let mut after = persisting_client(dir.store(), &clock);
+    after.connect()?;
+    assert_eq!(
+        activity_updates(&after.sent),
+        [json!({ "state": "Compiling" })]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_failed_restore_still_connects() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("restore-failed");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+
+    let mut before = persisting_client(dir.store(), &clock);
+    before.connect()?;
+    before.set_activity(Activity::new().state("Compiling"))?;
+
+    // Discord rejects the restored activity
+    let mut after = persisting_client(dir.store(), &clock).respond_with(|opcode, request| {
+        if request["cmd"] == "SET_ACTIVITY" {
+            vec![(
+                1,
+                json!({
+                    "cmd": request["cmd"],
+                    "data": { "code": 4000, "message": "Invalid payload" },
+                    "evt": "ERROR",
+                    "nonce": request["nonce"]
+                }),
+            )]
+        } else {
+            default_response(opcode, request)
+        }
+    });
+    after.connect()?;
+    assert!(after.client_state().is_connected());
+    assert_eq!(activity_updates(&after.sent).len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_stale_activity_is_not_restored() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("stale");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+
+    let mut before = persisting_client(dir.store(), &clock);
+    before.connect()?;
+    before.set_activity(Activity::new().state("Compiling"))?;
+
+    clock.advance(MAX_AGE + Duration::from_secs(1));
+    let mut after = persisting_client(dir.store(), &clock);
+    after.connect()?;
+    assert!(activity_updates(&after.sent).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_cleared_activity_is_not_restored() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("cleared");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+
+    let mut before = persisting_client(dir.store(), &clock);
+    before.connect()?;
+    before.set_activity(Activity::new().state("Compiling"))?;
+    before.clear_activity()?;
+
+    let mut after = persisting_client(dir.store(), &clock);
+    after.connect()?;
+    assert!(activity_updates(&after.sent).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_other_client_id_is_not_restored() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("client-id");
+    let server = MockDiscordServer::start()?;
+
+    let mut client = server.client("771124766517755954");
+    client.persist_to(Some(
+        Persistence::new(dir.store()).restore_on_connect(MAX_AGE),
+    ));
+    client.connect()?;
+    client.set_activity(Activity::new().state("Compiling"))?;
+
+    let updates = || activity_updates(&server.received()).len();
+
+    // The activity belongs to the old application
+    client.set_client_id("1024391201209057341")?;
+    assert_eq!(updates(), 1);
+
+    // Switching back restores it
+    client.set_client_id("771124766517755954")?;
+    assert_eq!(updates(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_write_half_saves() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("split");
+    let store = dir.store();
+    let server = MockDiscordServer::start()?;
+
+    let mut client = server.client("771124766517755954");
+    client.persist_to(Some(Persistence::new(store.clone())));
+    client.connect()?;
+    let (reader, mut writer) = client.split()?;
+    writer.set_activity_no_wait(Activity::new().state("Split"))?;
+    assert_eq!(
+        json!(store.load()?.unwrap().activity),
+        json!({ "state": "Split" })
+    );
+
+    // Persistence moves back with the rest of the client
+    let mut client = reader.reunite(writer)?;
+    client.clear_activity()?;
+    assert!(store.load()?.unwrap().activity.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_saving_without_restoring() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new("save-only");
+    let store = dir.store();
+
+    let mut client = MockClient::new("771124766517755954");
+    client.persist_to(Some(Persistence::new(store.clone())));
+    client.connect()?;
+    client.set_activity(Activity::new().state("Saved"))?;
+    client.close()?;
+    client.connect()?;
+
+    assert_eq!(activity_updates(&client.sent).len(), 1);
+    assert!(store.load()?.unwrap().activity.is_some());
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use discord_rich_presence::pid::{self, outermost_pid};
+
+// `/proc/self/status` of a process in a Flatpak sandbox, as read
+// with the host's `/proc`
+const NESTED: &str = "Name:\tgame\n\
+                      Umask:\t0022\n\
+                      State:\tS (sleeping)\n\
+                      Tgid:\t2\n\
+                      Ngid:\t0\n\
+                      Pid:\t2\n\
+                      PPid:\t1\n\
+                      NStgid:\t48213\t2\n\
+                      NSpid:\t48213\t2\n\
+                      NSpgid:\t48213\t2\n";
+
+const HOST: &str = "Name:\tgame\nPid:\t48213\nPPid:\t1\nNSpid:\t48213\n";
+
+// Before Linux 4.1
+const OLD_KERNEL: &str = "Name:\tgame\nPid:\t48213\nPPid:\t1\n";
+
+#[test]
+fn test_outermost_pid() {
+    assert_eq!(outermost_pid(NESTED), Some(48213));
+    assert_eq!(outermost_pid(HOST), Some(48213));
+    assert_eq!(outermost_pid(OLD_KERNEL), None);
+    assert_eq!(outermost_pid("NSpid:\t\n"), None);
+}
+
+#[test]
+fn test_set_pid_overrides() {
+    let detected = pid::pid();
+
+    pid::set_pid(Some(1234));
+    assert_eq!(pid::pid(), 1234);
+    pid::set_pid(None);
+    assert_eq!(pid::pid(), detected);
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{error, test_util::MockClock, DiscordIpc, MAX_PENDING_COMMANDS};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Answers nothing until three commands have been sent, then answers
+// all of them at once, last first, as a single round trip
+fn batched_client() -> MockClient {
+    let mut held = Vec::new();
+    MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+        held.push(request.clone());
+        if held.len() < 3 {
+            return Vec::new();
+        }
+        held.drain(..)
+            .rev()
+            .map(|request| {
+                let id = request["args"]["channel_id"].clone();
+                response(&request, json!({ "id": id }))
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn test_responses_matched_out_of_order() -> Result<(), Box<dyn Error>> {
+    let mut client = batched_client();
+    client.connect()?;
+
+    let nonces = ["1", "2", "3"]
+        .iter()
+        .map(|id| client.send_command("GET_CHANNEL", json!({ "channel_id": id })))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every command went out before any response was read, where
+    // `command` would have waited a round trip after each
+    let commands: Vec<&Value> = client.sent[1..].iter().map(|(_, frame)| frame).collect();
+    assert_eq!(commands.len(), 3);
+    for (frame, nonce) in commands.iter().zip(&nonces) {
+        assert_eq!(frame["nonce"], nonce.as_str());
+    }
+
+    assert_eq!(
+        client.poll_response(&nonces[1])?,
+        Some(json!({ "id": "2" }))
+    );
+    assert_eq!(
+        client.poll_response(&nonces[0])?,
+        Some(json!({ "id": "1" }))
+    );
+    assert_eq!(
+        client.wait_response(&nonces[2], Duration::from_secs(1))?,
+        Some(json!({ "id": "3" }))
+    );
+
+    // A collected response is forgotten
+    let err = client.poll_response(&nonces[1]).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::UnknownNonce { nonce }) if nonce == nonces[1].as_str()
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_responses_kept_through_other_reads() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        match (opcode, request["cmd"].as_str()) {
+            (1, Some("GET_GUILD")) => Vec::new(),
+            (1, Some("GET_CHANNEL")) => vec![
+                response(&json!({ "cmd": "NOT_SENT", "nonce": "x" }), Value::Null),
+                response(request, json!({ "id": "channel" })),
+            ],
+            _ => default_response(opcode, request),
+        }
+    });
+    client.connect()?;
+
+    // Nothing has arrived yet
+    let nonce = client.send_command("GET_GUILD", json!({ "guild_id": "1" }))?;
+    assert_eq!(client.poll_response(&nonce)?, None);
+
+    // A blocking command reading the pipelined response keeps it,
+    // and drops the one nobody is waiting on
+    client.push_frame(
+        1,
+        json!({ "cmd": "GET_GUILD", "data": { "id": "guild" }, "nonce": nonce.as_str() }),
+    );
+    assert_eq!(
+        client.command("GET_CHANNEL", json!({}))?,
+        json!({ "id": "channel" })
+    );
+    assert_eq!(
+        client.poll_response(&nonce)?,
+        Some(json!({ "id": "guild" }))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_command_errors_are_collected() -> Result<(), Box<dyn Error>> {
+    let mut client =
+        MockClient::new("771124766517755954").respond_with(|opcode, request| match opcode {
+            1 => vec![(
+                1,
+                json!({
+                    "cmd": request["cmd"],
+                    "evt": "ERROR",
+                    "data": { "code": 4000, "message": "Invalid channel" },
+                    "nonce": request["nonce"]
+                }),
+            )],
+            _ => default_response(opcode, request),
+        });
+    client.connect()?;
+
+    let nonce = client.send_command("GET_CHANNEL", json!({ "channel_id": "0" }))?;
+    let err = client.poll_response(&nonce).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::CommandError { code: 4000, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_outstanding_commands_bounded() -> Result<(), Box<dyn Error>> {
+    let mut client =
+        MockClient::new("771124766517755954").respond_with(|opcode, request| match opcode {
+            1 => Vec::new(),
+            _ => default_response(opcode, request),
+        });
+    client.connect()?;
+
+    for _ in 0..MAX_PENDING_COMMANDS {
+        client.send_command("GET_GUILDS", json!({}))?;
+    }
+    let err = client.send_command("GET_GUILDS", json!({})).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::TooManyPendingCommands { limit }) if *limit == MAX_PENDING_COMMANDS
+    ));
+    assert_eq!(client.sent.len(), 1 + MAX_PENDING_COMMANDS);
+
+    // Reconnecting forgets them, as they will never be answered
+    client.reconnect()?;
+    client.send_command("GET_GUILDS", json!({}))?;
+    Ok(())
+}
+
+#[test]
+fn test_wait_measured_by_clock() -> Result<(), Box<dyn Error>> {
+    let mut client = batched_client();
+    let clock = MockClock::new();
+    client.set_clock(clock.clone());
+    client.connect()?;
+    let nonce = client.send_command("GET_GUILDS", json!({}))?;
+
+    // The wait ends once the clock passes the timeout, however
+    // little real time that took
+    let advancing = clock.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        advancing.advance(Duration::from_secs(60));
+    });
+    let start = Instant::now();
+    assert_eq!(client.wait_response(&nonce, Duration::from_secs(60))?, None);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    Ok(())
+}
+
+#[test]
+fn test_unrepresentable_timeout_waits() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    let nonce = client.send_command("GET_GUILDS", json!({}))?;
+
+    assert_eq!(
+        client.wait_response(&nonce, Duration::MAX)?,
+        Some(Value::Null)
+    );
+    Ok(())
+}
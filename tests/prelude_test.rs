@@ -0,0 +1,31 @@
+#![cfg(feature = "test-util")]
+
+use discord_rich_presence::{prelude::*, test_util::MockDiscordServer};
+
+#[test]
+fn test_prelude_covers_a_session() -> Result<(), Box<dyn std::error::Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client: DiscordIpcClient =
+        DiscordIpcClient::with_transport("771124766517755954", server.transport());
+    client.connect()?;
+    client.set_rate_limiter(Some(RateLimiter::discord().policy(RateLimitPolicy::Error)));
+
+    client.set_activity(
+        Activity::new()
+            .state("Prelude")
+            .timestamps(Timestamps::new().start(1))
+            .assets(Assets::new().large_image("logo"))
+            .party(Party::new().id("party").size([1, 4]))
+            .buttons(vec![Button::new("Site", "https://example.com")]),
+    )?;
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+
+    let err = client
+        .subscribe(&Subscription::new(EventKind::MessageCreate))
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::MissingSubscriptionArgument { .. })
+    ));
+    Ok(())
+}
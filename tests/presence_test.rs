@@ -0,0 +1,81 @@
+#![cfg(feature = "test-util")]
+
+mod common;
+
+use common::activity_updates;
+use discord_rich_presence::{
+    activity::Activity, manager::ManagerConfig, presence::RichPresence,
+    test_util::MockDiscordServer,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn fast_config() -> ManagerConfig {
+    ManagerConfig::new()
+        .update_interval(Duration::ZERO)
+        .reconnect_backoff(Duration::from_millis(1), Duration::from_millis(4))
+}
+
+// Waits until the server has received `count` activity updates
+fn wait_for_updates(server: &MockDiscordServer, count: usize) -> Vec<Value> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let updates = activity_updates(&server.received());
+        if updates.len() >= count || Instant::now() >= deadline {
+            return updates;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn test_survives_discord_restart() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let presence = RichPresence::with_client(server.client("771124766517755954"), fast_config())?;
+
+    presence.set(Activity::new().state("Reliable"));
+    assert_eq!(
+        wait_for_updates(&server, 1),
+        [json!({ "state": "Reliable" })]
+    );
+
+    // Discord goes away, and comes back
+    server.close_connection(1000, "Restarting")?;
+    let updates = wait_for_updates(&server, 2);
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[1], json!({ "state": "Reliable" }));
+    assert_eq!(server.connections(), 2);
+
+    // Two handshakes, three updates, and the close frame
+    presence.shutdown()?;
+    let received = server.wait_for_frames(6, TIMEOUT);
+    assert_eq!(activity_updates(&received).last(), Some(&Value::Null));
+    assert_eq!(received.last().map(|(opcode, _)| *opcode), Some(2));
+    Ok(())
+}
+
+#[test]
+fn test_restoring_can_be_turned_off() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let config = fast_config().restore_on_reconnect(false);
+    let presence = RichPresence::with_client(server.client("771124766517755954"), config)?;
+
+    presence.set(Activity::new().state("Once"));
+    assert_eq!(wait_for_updates(&server, 1).len(), 1);
+
+    // Nothing needs a connection until the next update
+    server.close_connection(1000, "Restarting")?;
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(server.connections(), 1);
+
+    presence.set(Activity::new().state("Twice"));
+    let updates = wait_for_updates(&server, 2);
+    assert_eq!(updates[1], json!({ "state": "Twice" }));
+    assert_eq!(server.connections(), 2);
+    Ok(())
+}
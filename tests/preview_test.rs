@@ -0,0 +1,70 @@
+use discord_rich_presence::{
+    activity::{self, Activity, Assets, Button, Party, Timestamps},
+    test_util::MockClock,
+};
+use std::time::Duration;
+
+// 2023-11-14T22:13:20Z
+const NOW: u64 = 1_700_000_000_000;
+
+#[test]
+fn test_minimal_preview() {
+    let clock = MockClock::at_unix_ms(NOW);
+    assert_eq!(Activity::new().preview_at(&*clock), "Playing a game");
+}
+
+#[test]
+fn test_maximal_preview() {
+    let clock = MockClock::at_unix_ms(NOW);
+    let activity = Activity::new()
+        .details("Ranked, map 3")
+        .state("In a match")
+        .party(Party::new().id("party-1").size([2, 4]))
+        .timestamps(Timestamps::new().start(1_700_000_000 - 754))
+        .assets(Assets::new().large_image("logo").large_text("Hidden"))
+        .buttons(vec![
+            Button::new("Website", "https://example.com"),
+            Button::new("Source", "https://github.com/vionya/discord-rich-presence"),
+        ]);
+
+    assert_eq!(
+        activity.preview_at(&*clock),
+        "Playing a game\nRanked, map 3\nIn a match (2 of 4)\n12:34 elapsed\n[Website] [Source]"
+    );
+}
+
+#[test]
+fn test_preview_times() {
+    let clock = MockClock::at_unix_ms(NOW);
+
+    // An end is shown as the time left, in either unit
+    let activity = Activity::new()
+        .state("Listening")
+        .timestamps(activity::progress_at(
+            &*clock,
+            Duration::from_secs(30),
+            Duration::from_secs(231),
+        ));
+    assert_eq!(
+        activity.preview_at(&*clock),
+        "Playing a game\nListening\n03:21 left"
+    );
+
+    let activity = Activity::new().timestamps(Timestamps::new().start(1_700_000_000 - 3725));
+    assert_eq!(
+        activity.preview_at(&*clock),
+        "Playing a game\n1:02:05 elapsed"
+    );
+
+    // Times past the end don't go negative
+    clock.advance(Duration::from_secs(600));
+    let activity = Activity::new().timestamps(Timestamps::new().end(1_700_000_000));
+    assert_eq!(activity.preview_at(&*clock), "Playing a game\n00:00 left");
+}
+
+#[test]
+fn test_party_without_state() {
+    let clock = MockClock::at_unix_ms(NOW);
+    let activity = Activity::new().party(Party::new().size([1, 5]));
+    assert_eq!(activity.preview_at(&*clock), "Playing a game\n(1 of 5)");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::{self, Activity},
+    test_util::MockClock,
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{error::Error, time::Duration};
+
+const NOW: u64 = 1_700_000_000_000;
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+fn last_update(client: &MockClient) -> Value {
+    client
+        .sent
+        .iter()
+        .rev()
+        .find(|(_, payload)| payload["cmd"] == "SET_ACTIVITY")
+        .map(|(_, payload)| payload["args"]["activity"].clone())
+        .expect("no activity was sent")
+}
+
+#[test]
+fn test_progress_timestamps() {
+    let clock = MockClock::at_unix_ms(NOW);
+
+    let timestamps = activity::progress_at(&*clock, secs(30), secs(200));
+    assert_eq!(
+        json!(timestamps),
+        json!({ "start": 1_699_999_970_000i64, "end": 1_700_000_170_000i64 })
+    );
+
+    // The same playback, 10 seconds later, gives the same timestamps
+    clock.advance(secs(10));
+    let timestamps = activity::progress_at(&*clock, secs(40), secs(200));
+    assert_eq!(
+        json!(timestamps),
+        json!({ "start": 1_699_999_970_000i64, "end": 1_700_000_170_000i64 })
+    );
+
+    // Positions past the end are clamped to it
+    let timestamps = activity::progress_at(&*clock, secs(500), secs(200));
+    assert_eq!(
+        json!(timestamps),
+        json!({ "start": 1_699_999_810_000i64, "end": 1_700_000_010_000i64 })
+    );
+}
+
+#[test]
+fn test_paused_progress_has_no_end() {
+    let clock = MockClock::at_unix_ms(NOW);
+    let timestamps = activity::paused_progress_at(&*clock, Duration::from_millis(1500));
+    assert_eq!(json!(timestamps), json!({ "start": 1_699_999_998_500i64 }));
+}
+
+#[test]
+fn test_update_progress_keeps_activity() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(Activity::new().details("Listening").state("Track"))?;
+
+    client.update_progress(secs(30), secs(200))?;
+    let update = last_update(&client);
+    assert_eq!(update["details"], "Listening");
+    assert_eq!(update["state"], "Track");
+    let (start, end) = (
+        update["timestamps"]["start"].as_i64().unwrap(),
+        update["timestamps"]["end"].as_i64().unwrap(),
+    );
+    assert_eq!(end - start, 200_000);
+
+    client.update_progress_paused(secs(30))?;
+    let update = last_update(&client);
+    assert_eq!(update["state"], "Track");
+    assert!(update["timestamps"]["start"].is_i64());
+    assert!(update["timestamps"].get("end").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_pause_and_resume_timestamps() {
+    let clock = MockClock::at_unix_ms(NOW);
+    let paused_at = secs(60 + 23);
+
+    let paused = activity::Timestamps::paused_at_with_clock(&*clock, paused_at);
+    assert_eq!(json!(paused), json!({ "start": 1_699_999_917_000i64 }));
+
+    // Resuming 10 minutes later moves the start forward by as much,
+    // so the time shown carries on from 1:23
+    clock.advance(secs(600));
+    let resumed = activity::Timestamps::resumed_with_clock(&*clock, paused_at);
+    assert_eq!(json!(resumed), json!({ "start": 1_700_000_517_000i64 }));
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{activity_updates, MockClient};
+use discord_rich_presence::{
+    activity::Activity,
+    error,
+    rate_limit::{RateLimitPolicy, RateLimiter},
+    test_util::MockClock,
+    DiscordIpc,
+};
+use serde_json::Value;
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[test]
+fn test_burst_then_refill() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::discord();
+
+    for _ in 0..5 {
+        assert_eq!(limiter.try_acquire(start), Ok(()));
+    }
+    assert_eq!(limiter.try_acquire(start), Err(secs(4)));
+    assert_eq!(limiter.try_acquire(start + secs(3)), Err(secs(1)));
+
+    // One update refills every 4 seconds
+    assert_eq!(limiter.try_acquire(start + secs(4)), Ok(()));
+    assert_eq!(limiter.try_acquire(start + secs(5)), Err(secs(3)));
+    assert_eq!(limiter.try_acquire(start + secs(8)), Ok(()));
+}
+
+#[test]
+fn test_refill_is_capped() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(2, secs(10));
+
+    assert_eq!(limiter.try_acquire(start), Ok(()));
+    assert_eq!(limiter.try_acquire(start), Ok(()));
+
+    // Long idle periods refill the budget, but no further
+    let later = start + secs(3600);
+    assert_eq!(limiter.try_acquire(later), Ok(()));
+    assert_eq!(limiter.try_acquire(later), Ok(()));
+    assert_eq!(limiter.try_acquire(later), Err(secs(5)));
+}
+
+#[test]
+fn test_partial_refill_keeps_remainder() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(3, secs(30));
+    for _ in 0..3 {
+        limiter.try_acquire(start).unwrap();
+    }
+
+    // 15 seconds refill one update, and half of the next
+    assert_eq!(limiter.try_acquire(start + secs(15)), Ok(()));
+    assert_eq!(limiter.try_acquire(start + secs(15)), Err(secs(5)));
+    assert_eq!(limiter.try_acquire(start + secs(20)), Ok(()));
+}
+
+#[test]
+fn test_reset() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(1, secs(60));
+    limiter.try_acquire(start).unwrap();
+    assert!(limiter.try_acquire(start).is_err());
+
+    limiter.reset();
+    assert_eq!(limiter.try_acquire(start), Ok(()));
+}
+
+#[test]
+fn test_error_policy() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_rate_limiter(Some(
+        RateLimiter::new(1, secs(60)).policy(RateLimitPolicy::Error),
+    ));
+
+    client.set_activity(Activity::new().state("1"))?;
+    let err = client.set_activity(Activity::new().state("2")).unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::RateLimitedLocally { retry_in }) => assert!(*retry_in <= secs(60)),
+        _ => panic!("expected RateLimitedLocally, got {}", err),
+    }
+    assert_eq!(activity_updates(&client.sent).len(), 1);
+
+    // Reconnecting refills the budget
+    client.reconnect()?;
+    client.set_activity(Activity::new().state("3"))?;
+    assert_eq!(activity_updates(&client.sent).len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_block_policy() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    let clock = MockClock::new();
+    client.set_rate_limiter(Some(RateLimiter::new(1, secs(60)).clock(clock.clone())));
+
+    client.set_activity(Activity::new().state("1"))?;
+    client.set_activity(Activity::new().state("2"))?;
+    assert_eq!(clock.elapsed(), secs(60));
+    assert_eq!(activity_updates(&client.sent).len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_limiter_uses_client_clock() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    let clock = MockClock::new();
+    client.set_clock(clock.clone());
+    client.connect()?;
+    client.set_rate_limiter(Some(RateLimiter::new(1, secs(60))));
+
+    client.set_activity(Activity::new().state("1"))?;
+    client.set_activity(Activity::new().state("2"))?;
+    assert_eq!(clock.elapsed(), secs(60));
+    assert_eq!(activity_updates(&client.sent).len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_coalesce_policy() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    let clock = MockClock::new();
+    client.set_rate_limiter(Some(
+        RateLimiter::new(1, secs(60))
+            .policy(RateLimitPolicy::Coalesce)
+            .clock(clock.clone()),
+    ));
+
+    for state in ["1", "2", "3"] {
+        client.set_activity(Activity::new().state(state))?;
+    }
+    assert!(!client.flush_activity()?);
+    assert_eq!(activity_updates(&client.sent).len(), 1);
+
+    clock.advance(secs(59));
+    assert!(!client.flush_activity()?);
+    clock.advance(secs(1));
+    assert!(client.flush_activity()?);
+    assert!(client.flush_activity()?);
+
+    let updates = activity_updates(&client.sent);
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[1]["state"], "3");
+    Ok(())
+}
+
+#[test]
+fn test_clear_bypasses_limit() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_rate_limiter(Some(
+        RateLimiter::new(1, secs(60)).policy(RateLimitPolicy::Coalesce),
+    ));
+
+    client.set_activity(Activity::new().state("1"))?;
+    client.set_activity(Activity::new().state("2"))?;
+    client.clear_activity()?;
+
+    // The held back update was superseded by the clear
+    assert!(client.flush_activity()?);
+    let updates = activity_updates(&client.sent);
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[1], Value::Null);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+#![cfg(unix)]
+
+use discord_rich_presence::{transport::NativeTransport, DiscordIpcClient, IpcConnection};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::Write,
+    os::{
+        raw::{c_int, c_short},
+        unix::{io::RawFd, net::UnixListener},
+    },
+};
+
+#[cfg(target_os = "linux")]
+type NfdsT = std::os::raw::c_ulong;
+#[cfg(not(target_os = "linux"))]
+type NfdsT = std::os::raw::c_uint;
+
+const POLLIN: c_short = 0x1;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+extern "C" {
+    fn poll(fds: *mut PollFd, nfds: NfdsT, timeout: c_int) -> c_int;
+}
+
+fn readable(fd: RawFd, timeout_ms: c_int) -> bool {
+    let mut fds = [PollFd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    }];
+    let ready = unsafe { poll(fds.as_mut_ptr(), 1, timeout_ms) };
+    assert!(ready >= 0, "poll failed");
+
+    fds[0].revents & POLLIN != 0
+}
+
+#[test]
+fn test_raw_fd_with_poll() -> Result<(), Box<dyn std::error::Error>> {
+    // A stand-in for Discord's socket, found through a fabricated
+    // XDG_RUNTIME_DIR
+    let dir = env::temp_dir().join(format!("discord-ipc-raw-fd-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let listener = UnixListener::bind(dir.join("discord-ipc-0"))?;
+
+    let env: HashMap<_, _> = [("XDG_RUNTIME_DIR".to_string(), dir.display().to_string())].into();
+    let mut client =
+        DiscordIpcClient::with_transport("771124766517755954", NativeTransport::with_env(env));
+    assert_eq!(client.raw_fd(), None);
+    client.connect_ipc()?;
+    let (mut peer, _) = listener.accept()?;
+
+    let fd = client.raw_fd().expect("client is connected");
+    assert!(!readable(fd, 0));
+
+    peer.write_all(&[0; 8])?;
+    assert!(readable(fd, 1000));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -1,32 +1,124 @@
-use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
-use std::error::Error;
+use discord_rich_presence::{
+    activity, error,
+    test_util::{default_response, MockClock, MockDiscordServer},
+    DiscordIpc, DiscordIpcClient,
+};
+use serde_json::json;
+use std::{error::Error, time::Duration};
+
+const ATTEMPTS: usize = 10;
+
+fn activity() -> activity::Activity<'static> {
+    activity::Activity::new()
+        .state("part 1 (test)")
+        .details("a placeholder")
+        .assets(
+            activity::Assets::new()
+                .large_image("large-image")
+                .large_text("a thing"),
+        )
+}
 
 #[test]
 fn test_reconnect() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+    client.set_activity(activity())?;
+
+    // Discord going away is noticed by the next update
+    server.close_connection(1000, "Restarting")?;
+    assert!(client.set_activity(activity()).is_err());
+
+    let reconnected = (0..ATTEMPTS).any(|_| client.reconnect().is_ok());
+    assert!(reconnected);
+    client.set_activity(activity())?;
+
+    assert_eq!(server.connections(), 2);
+    let updates = server
+        .received()
+        .iter()
+        .filter(|(_, payload)| payload["cmd"] == "SET_ACTIVITY")
+        .count();
+    assert_eq!(updates, 3);
+    Ok(())
+}
+
+#[test]
+fn test_handshake_retried_while_starting_up() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.hang_up_next_frame();
+
+    let mut client = server.client("771124766517755954");
+    let clock = MockClock::new();
+    client.set_clock(clock.clone());
+    client.connect()?;
+    client.set_activity(activity())?;
+
+    // The retry waited on the client's clock
+    assert_eq!(clock.elapsed(), Duration::from_millis(250));
+    assert_eq!(server.connections(), 2);
+    let opcodes: Vec<u32> = server
+        .received()
+        .iter()
+        .map(|(opcode, _)| *opcode)
+        .collect();
+    assert_eq!(opcodes, [0, 0, 1]);
+    Ok(())
+}
+
+#[test]
+fn test_rejected_handshake_not_retried() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    server.respond_with(|opcode, request| match opcode {
+        0 => vec![(2, json!({ "code": 4000, "message": "Invalid Client ID" }))],
+        _ => default_response(opcode, request),
+    });
+
+    let err = server.client("0").connect().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::ConnectionClosed { code: 4000, .. })
+    ));
+    assert_eq!(server.connections(), 1);
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires a running Discord client"]
+fn test_reconnect_live() -> Result<(), Box<dyn Error>> {
     let mut client = DiscordIpcClient::new("771124766517755954")?;
-    loop {
-        if client.connect().is_ok() {
-            break;
-        }
-    }
+    client.connect_with_retries(5, std::time::Duration::from_secs(1))?;
 
-    loop {
-        let payload = activity::Activity::new()
-            .state("part 1 (test)")
-            .details("a placeholder")
-            .assets(
-                activity::Assets::new()
-                    .large_image("large-image")
-                    .large_text("a thing"),
-            );
-
-        if client.set_activity(payload).is_err() && client.reconnect().is_ok() {
+    // Restart Discord while this runs to exercise reconnecting
+    for _ in 0..ATTEMPTS {
+        if client.set_activity(activity()).is_err() && client.reconnect().is_ok() {
             continue;
         }
 
         std::thread::sleep(std::time::Duration::from_secs(2));
     }
 
-    #[allow(unreachable_code)]
+    client.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_socket_closed_between_frames() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    server.hang_up_next_frame();
+    let err = client.set_activity(activity()).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::SocketClosed)
+    ));
+
+    // The client knows it is no longer connected, so changing its ID
+    // doesn't try to re-handshake over the closed socket
+    client.set_client_id("1024391201209057341")?;
+    assert_eq!(server.connections(), 1);
     Ok(())
 }
@@ -0,0 +1,181 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{
+    error,
+    event::{Event, EventKind},
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{error::Error, thread};
+
+fn dispatch(evt: &str) -> (u32, Value) {
+    (
+        1,
+        json!({ "cmd": "DISPATCH", "evt": evt, "data": {}, "nonce": null }),
+    )
+}
+
+fn unknown_nonce_error() -> (u32, Value) {
+    (
+        1,
+        json!({
+            "cmd": "GET_GUILDS",
+            "evt": "ERROR",
+            "data": { "code": 4000, "message": "Stale" },
+            "nonce": "unknown"
+        }),
+    )
+}
+
+#[test]
+fn test_command_skips_events_pings_and_unknown_nonces() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        vec![
+            unknown_nonce_error(),
+            dispatch("ACTIVITY_JOIN"),
+            (3, json!({ "ping": 1 })),
+            response(request, json!({ "guilds": [] })),
+        ]
+    });
+    client.connect()?;
+
+    assert_eq!(
+        client.command("GET_GUILDS", json!({}))?,
+        json!({ "guilds": [] })
+    );
+    assert!(client.sent.contains(&(4, json!({ "ping": 1 }))));
+
+    assert!(matches!(client.recv_event()?, Event::Ready(_)));
+    assert!(matches!(
+        client.recv_event()?,
+        Event::Dispatch {
+            kind: EventKind::ActivityJoin,
+            ..
+        }
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_command_skips_stale_response_to_same_command() -> Result<(), Box<dyn Error>> {
+    let mut commands = 0;
+    let mut client = MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        // The first command goes unanswered until the second is sent
+        commands += 1;
+        match commands {
+            1 => Vec::new(),
+            _ => {
+                let mut stale = request.clone();
+                stale["nonce"] = "stale".into();
+                vec![
+                    response(&stale, json!("stale")),
+                    response(request, json!("fresh")),
+                ]
+            }
+        }
+    });
+    client.connect()?;
+
+    // The first command fails once the mock runs out of data
+    assert!(client.command("GET_GUILD", json!({})).is_err());
+    assert_eq!(client.command("GET_GUILD", json!({}))?, json!("fresh"));
+    Ok(())
+}
+
+#[test]
+fn test_error_for_unknown_nonce_is_ignored() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        let mut frames = default_response(opcode, request);
+        if opcode == 1 {
+            frames.insert(0, unknown_nonce_error());
+        }
+        frames
+    });
+    client.connect()?;
+
+    assert_eq!(client.command("GET_GUILDS", json!({}))?, Value::Null);
+    Ok(())
+}
+
+#[test]
+fn test_pump_routes_out_of_order_responses() -> Result<(), Box<dyn Error>> {
+    let mut withheld: Option<Value> = None;
+    let mut client = MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        // Whichever command arrives first is answered last
+        match withheld.take() {
+            None => {
+                withheld = Some(request.clone());
+                Vec::new()
+            }
+            Some(first) => vec![
+                response(request, request["args"].clone()),
+                dispatch("ACTIVITY_JOIN"),
+                unknown_nonce_error(),
+                response(&first, first["args"].clone()),
+            ],
+        }
+    });
+    client.connect()?;
+    let (pump, events) = client.spawn_event_pump()?;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = ["first", "second"]
+            .iter()
+            .map(|name| {
+                let pump = &pump;
+                scope.spawn(move || {
+                    pump.command("GET_CHANNEL", json!({ "channel_id": name }))
+                        .map_err(|err| err.to_string())
+                })
+            })
+            .collect();
+
+        for (handle, name) in handles.into_iter().zip(["first", "second"]) {
+            assert_eq!(handle.join().unwrap(), Ok(json!({ "channel_id": name })));
+        }
+    });
+
+    assert!(matches!(events.recv()?, Event::Ready(_)));
+    assert!(matches!(
+        events.recv()?,
+        Event::Dispatch {
+            kind: EventKind::ActivityJoin,
+            ..
+        }
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_pump_delivers_command_errors_by_nonce() -> Result<(), Box<dyn Error>> {
+    let client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        let mut error = response(request, json!({ "code": 4005, "message": "Invalid user" }));
+        error.1["evt"] = "ERROR".into();
+        vec![unknown_nonce_error(), error]
+    });
+    let (pump, _events) = client.spawn_event_pump()?;
+
+    let err = pump.command("GET_USER", json!({ "id": "0" })).unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::CommandError { code, .. }) => assert_eq!(*code, 4005),
+        _ => panic!("expected CommandError, got {}", err),
+    }
+    Ok(())
+}
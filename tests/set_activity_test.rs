@@ -0,0 +1,81 @@
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{activity::Activity, error, DiscordIpc};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+// A mock which holds back the response to every SET_ACTIVITY until
+// the next command is sent
+fn delaying_client() -> MockClient {
+    let mut delayed: Vec<(u32, Value)> = Vec::new();
+    MockClient::new("771124766517755954").respond_with(move |opcode, request| {
+        if opcode == 1 && request["cmd"] == "SET_ACTIVITY" {
+            delayed.push(response(request, Value::Null));
+            return Vec::new();
+        }
+
+        let mut frames = std::mem::take(&mut delayed);
+        frames.extend(default_response(opcode, request));
+        frames
+    })
+}
+
+#[test]
+fn test_set_activity_waits_for_response() -> Result<(), Box<dyn Error>> {
+    let mut client = delaying_client();
+    client.connect()?;
+
+    // The response never arrives, so reading it fails
+    assert!(client
+        .set_activity(Activity::new().state("Hello world!"))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_set_activity_no_wait() -> Result<(), Box<dyn Error>> {
+    let mut client = delaying_client();
+    client.connect()?;
+
+    let start = Instant::now();
+    client.set_activity_no_wait(Activity::new().state("Hello world!"))?;
+    assert!(start.elapsed() < Duration::from_millis(100));
+    assert_eq!(
+        client.last_sent()["args"]["activity"]["state"],
+        "Hello world!"
+    );
+
+    // The late response is discarded rather than taken as the
+    // response to the next command
+    client.set_activity_no_wait(Activity::new().state("Goodbye"))?;
+    assert_eq!(client.command("GET_GUILDS", json!({}))?, Value::Null);
+    Ok(())
+}
+
+#[test]
+fn test_set_activity_rejected() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode != 1 {
+            return default_response(opcode, request);
+        }
+
+        let mut error = response(request, json!({ "code": 4000, "message": "Bad activity" }));
+        error.1["evt"] = "ERROR".into();
+        vec![error]
+    });
+    client.connect()?;
+
+    let err = client.set_activity(Activity::new()).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::CommandError { code: 4000, .. })
+    ));
+
+    // Without waiting, the rejection goes unnoticed
+    client.set_activity_no_wait(Activity::new())?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::Activity, error, shared::SharedDiscordIpcClient, DiscordIpc, DiscordIpcClient,
+    IpcConnection,
+};
+use std::{error::Error, thread};
+
+fn assert_clone_send_sync<T: Clone + Send + Sync>() {}
+
+#[test]
+fn test_handle_is_clone_send_sync() {
+    assert_clone_send_sync::<SharedDiscordIpcClient>();
+    assert_clone_send_sync::<SharedDiscordIpcClient<DiscordIpcClient>>();
+}
+
+#[test]
+fn test_concurrent_set_activity() -> Result<(), Box<dyn Error>> {
+    let client = SharedDiscordIpcClient::new(MockClient::new("771124766517755954"));
+    client.connect()?;
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let client = client.clone();
+            thread::spawn(move || {
+                let state = format!("Update {}", i);
+                for _ in 0..10 {
+                    client
+                        .set_activity(Activity::new().state(&state))
+                        .map_err(|err| err.to_string())?;
+                }
+                Ok::<(), String>(())
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+
+    // Every frame was written whole, and parsed by the mock
+    let updates = client.with_client(|client| {
+        client
+            .sent
+            .iter()
+            .filter(|(_, payload)| payload["cmd"] == "SET_ACTIVITY")
+            .count()
+    });
+    assert_eq!(updates, 80);
+    Ok(())
+}
+
+#[test]
+fn test_recovers_from_panic() -> Result<(), Box<dyn Error>> {
+    let client = SharedDiscordIpcClient::new(MockClient::new("771124766517755954"));
+    client.connect()?;
+    client.with_client(|client| {
+        client.on_any_event(|_| panic!("callback failed"));
+    });
+
+    let panicking = client.clone();
+    assert!(thread::spawn(move || panicking.recv_event().is_ok())
+        .join()
+        .is_err());
+
+    // The next call reconnects, sending a second handshake
+    client.set_activity(Activity::new().state("Hello world!"))?;
+    let handshakes =
+        client.with_client(|client| client.sent.iter().filter(|(op, _)| *op == 0).count());
+    assert_eq!(handshakes, 2);
+    Ok(())
+}
+
+// Panics while holding the client, leaving its lock poisoned
+fn poison(client: &SharedDiscordIpcClient<MockClient>) {
+    let panicking = client.clone();
+    assert!(thread::spawn(move || {
+        panicking.with_client(|_| panic!("call failed"));
+    })
+    .join()
+    .is_err());
+}
+
+fn handshakes(client: &SharedDiscordIpcClient<MockClient>) -> usize {
+    client.with_client(|client| client.sent.iter().filter(|(op, _)| *op == 0).count())
+}
+
+#[test]
+fn test_close_after_panic() -> Result<(), Box<dyn Error>> {
+    let client = SharedDiscordIpcClient::new(MockClient::new("771124766517755954"));
+    client.connect()?;
+    poison(&client);
+
+    // Closing doesn't reconnect first, and leaves nothing to recover
+    client.close()?;
+    let err = client
+        .set_activity(Activity::new().state("Hello world!"))
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::NotConnected)
+    ));
+    assert_eq!(handshakes(&client), 1);
+    Ok(())
+}
+
+#[test]
+fn test_panic_before_connecting() -> Result<(), Box<dyn Error>> {
+    let client = SharedDiscordIpcClient::new(MockClient::new("771124766517755954"));
+    poison(&client);
+
+    // A client that was never connected isn't connected by recovering
+    assert!(!client.with_client(|client| client.client_state().is_connected()));
+    assert!(client.clear_activity().is_err());
+    assert_eq!(handshakes(&client), 0);
+
+    // Connecting recovers it, without a second handshake
+    client.connect()?;
+    client.set_activity(Activity::new().state("Hello world!"))?;
+    assert_eq!(handshakes(&client), 1);
+    Ok(())
+}
@@ -0,0 +1,216 @@
+#![cfg(unix)]
+
+use discord_rich_presence::{
+    activity::Activity,
+    error,
+    event::{Event, EventKind},
+    transport::NativeTransport,
+    DiscordIpc, DiscordIpcClient,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const UPDATES: usize = 20;
+
+fn write_frame(stream: &mut UnixStream, opcode: u32, data: Value) -> std::io::Result<()> {
+    let payload = data.to_string().into_bytes();
+    let mut frame = opcode.to_le_bytes().to_vec();
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend(payload);
+    stream.write_all(&frame)
+}
+
+fn read_frame(stream: &mut UnixStream) -> std::io::Result<(u32, Value)> {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header)?;
+    let opcode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((opcode, serde_json::from_slice(&payload)?))
+}
+
+// Connects a client to a stand-in for Discord's socket, found
+// through a fabricated XDG_RUNTIME_DIR, returning the client, its
+// peer and the directory
+fn connect(
+    name: &str,
+) -> Result<(DiscordIpcClient, UnixStream, std::path::PathBuf), Box<dyn std::error::Error>> {
+    let dir = env::temp_dir().join(format!("discord-ipc-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let listener = UnixListener::bind(dir.join("discord-ipc-0"))?;
+
+    let env: HashMap<_, _> = [("XDG_RUNTIME_DIR".to_string(), dir.display().to_string())].into();
+    let mut client =
+        DiscordIpcClient::with_transport("771124766517755954", NativeTransport::with_env(env));
+    // Answers the handshake, handing back Discord's end of the socket
+    let accept = thread::spawn(move || {
+        let (mut peer, _) = listener.accept().unwrap();
+        read_frame(&mut peer).unwrap();
+        let ready = json!({ "cmd": "DISPATCH", "evt": "READY", "data": { "v": 1 }, "nonce": null });
+        write_frame(&mut peer, 1, ready).unwrap();
+        peer
+    });
+    client.connect()?;
+    let peer = accept.join().unwrap();
+    Ok((client, peer, dir))
+}
+
+#[test]
+fn test_reader_thread_drains_events_while_writing() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, mut peer, dir) = connect("split")?;
+    let (mut reader, mut writer) = client.split()?;
+
+    // Every update is answered with a ping, an event and its
+    // response, returning the frames the client sent
+    let discord = thread::spawn(move || {
+        let mut received = Vec::new();
+        while received.len() < UPDATES * 2 {
+            let (opcode, payload) = read_frame(&mut peer).unwrap();
+            if opcode == 1 {
+                write_frame(&mut peer, 3, json!({ "ping": received.len() })).unwrap();
+                let dispatch =
+                    json!({ "cmd": "DISPATCH", "evt": "ACTIVITY_JOIN", "data": {}, "nonce": null });
+                write_frame(&mut peer, 1, dispatch).unwrap();
+                let mut response = payload.clone();
+                response["data"] = Value::Null;
+                write_frame(&mut peer, 1, response).unwrap();
+            }
+            received.push((opcode, payload));
+        }
+        received
+    });
+
+    let events = thread::spawn(move || {
+        let mut events = 0;
+        while events < UPDATES {
+            match reader.recv_event() {
+                Ok(Event::Dispatch {
+                    kind: EventKind::ActivityJoin,
+                    ..
+                }) => events += 1,
+                Ok(Event::Ready(_)) => {}
+                other => panic!("unexpected {:?}", other.map_err(|err| err.to_string())),
+            }
+        }
+        reader
+    });
+
+    for i in 0..UPDATES {
+        writer.set_activity_no_wait(Activity::new().state(i.to_string()))?;
+    }
+
+    let reader = events.join().unwrap();
+    let received = discord.join().unwrap();
+    let states: Vec<_> = received
+        .iter()
+        .filter(|(opcode, _)| *opcode == 1)
+        .map(|(_, payload)| payload["args"]["activity"]["state"].clone())
+        .collect();
+    assert_eq!(
+        states,
+        (0..UPDATES)
+            .map(|i| json!(i.to_string()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        received.iter().filter(|(opcode, _)| *opcode == 4).count(),
+        UPDATES
+    );
+
+    let client = reader.reunite(writer)?;
+    assert!(client.raw_fd().is_some());
+    assert_eq!(
+        json!(client.last_activity()),
+        json!({ "state": (UPDATES - 1).to_string() })
+    );
+
+    // The handshake, the updates and the pongs
+    let stats = client.stats();
+    assert_eq!(stats.lifetime.frames_sent, 1 + UPDATES as u64 * 2);
+    assert_eq!(stats.lifetime.commands, UPDATES as u64);
+    assert!(stats.last_activity_update.is_some());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_split_unconnected_client_fails() -> Result<(), Box<dyn std::error::Error>> {
+    let client = DiscordIpcClient::new("771124766517755954")?;
+    let err = client.split().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::NotConnected)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_split_closed_client_fails() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut client, _peer, dir) = connect("split-closed")?;
+    client.close()?;
+
+    let err = client.split().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<error::Error>(),
+        Some(error::Error::NotConnected)
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_polling_reader_doesnt_disturb_writer() -> Result<(), Box<dyn std::error::Error>> {
+    const FRAMES: usize = 16;
+    let (client, mut peer, dir) = connect("split-poll")?;
+    let (mut reader, mut writer) = client.split()?;
+
+    // Discord reads slowly, so that the writer fills the socket's
+    // buffer and has to wait for room
+    let discord = thread::spawn(move || {
+        let mut received = 0;
+        while received < FRAMES {
+            thread::sleep(Duration::from_millis(5));
+            read_frame(&mut peer).unwrap();
+            received += 1;
+        }
+        peer
+    });
+
+    let done = Arc::new(AtomicBool::new(false));
+    let polling = Arc::clone(&done);
+    let events = thread::spawn(move || {
+        while !polling.load(Ordering::Relaxed) {
+            // The READY dispatch is the only frame ever sent
+            reader.try_recv_event().unwrap();
+        }
+        reader
+    });
+
+    let padding = "x".repeat(256 * 1024);
+    for i in 0..FRAMES {
+        writer.send(json!({ "frame": i, "padding": padding }), 1)?;
+    }
+    done.store(true, Ordering::Relaxed);
+
+    let reader = events.join().unwrap();
+    discord.join().unwrap();
+    reader.reunite(writer)?;
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use discord_rich_presence::{
+    activity::Activity,
+    test_util::{default_response, MockClock, MockDiscordServer},
+    DiscordIpc,
+};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
+};
+
+// The bytes a frame takes on the wire
+fn frame_bytes(frames: &[(u32, Value)]) -> u64 {
+    frames
+        .iter()
+        .map(|(_, payload)| 8 + payload.to_string().len() as u64)
+        .sum()
+}
+
+#[test]
+fn test_counts_across_reconnects() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let answered = Arc::new(Mutex::new(Vec::new()));
+    let sent = answered.clone();
+    server.respond_with(move |opcode, request| {
+        let frames = match request["cmd"].as_str() {
+            Some("GET_GUILDS") => vec![(
+                1,
+                json!({
+                    "cmd": "GET_GUILDS",
+                    "evt": "ERROR",
+                    "data": { "code": 1000, "message": "Unknown error" },
+                    "nonce": request["nonce"]
+                }),
+            )],
+            _ => default_response(opcode, request),
+        };
+        sent.lock().unwrap().extend(frames.clone());
+        frames
+    });
+
+    let mut client = server.client("771124766517755954");
+    assert_eq!(client.stats().lifetime.frames_sent, 0);
+    client.connect()?;
+    client.set_activity(Activity::new().state("first"))?;
+    assert!(client.command("GET_GUILDS", json!({})).is_err());
+
+    let stats = client.stats();
+    assert_eq!(stats.connection, stats.lifetime);
+    assert_eq!(stats.connection.frames_sent, 3);
+    assert_eq!(stats.connection.frames_received, 3);
+    assert_eq!(stats.connection.commands, 2);
+    assert_eq!(stats.connection.command_failures, 1);
+    assert_eq!(stats.connection.bytes_sent, frame_bytes(&server.received()));
+    assert_eq!(
+        stats.connection.bytes_received,
+        frame_bytes(&answered.lock().unwrap())
+    );
+    assert!(stats.last_activity_update.is_some());
+    assert!(stats.uptime.is_some());
+
+    // Reconnecting starts the connection's counts over, after the
+    // close frame ending the first connection
+    let before = stats.lifetime;
+    client.reconnect()?;
+    client.set_activity(Activity::new().state("second"))?;
+
+    let stats = client.stats();
+    assert_eq!(stats.reconnects, 1);
+    assert_eq!(stats.connection.frames_sent, 2);
+    assert_eq!(stats.connection.frames_received, 2);
+    assert_eq!(stats.connection.commands, 1);
+    assert_eq!(stats.connection.command_failures, 0);
+    assert_eq!(stats.lifetime.frames_sent, before.frames_sent + 3);
+    assert_eq!(stats.lifetime.frames_received, before.frames_received + 2);
+    assert_eq!(stats.lifetime.commands, 3);
+    assert_eq!(stats.lifetime.command_failures, 1);
+
+    let received = server.wait_for_frames(6, Duration::from_secs(5));
+    assert_eq!(stats.lifetime.bytes_sent, frame_bytes(&received));
+    assert_eq!(
+        stats.lifetime.bytes_received,
+        frame_bytes(&answered.lock().unwrap())
+    );
+
+    client.close()?;
+    assert_eq!(client.stats().uptime, None);
+    Ok(())
+}
+
+#[test]
+fn test_times_measured_by_client_clock() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    let clock = MockClock::at_unix_ms(1_700_000_000_000);
+    client.set_clock(clock.clone());
+
+    client.connect()?;
+    client.set_activity(Activity::new().state("Timed"))?;
+    clock.advance(Duration::from_secs(90));
+
+    let stats = client.stats();
+    assert_eq!(stats.uptime, Some(Duration::from_secs(90)));
+    assert_eq!(
+        stats.last_activity_update,
+        Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_000))
+    );
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use discord_rich_presence::activity::{Activity, Assets, Button, ImageUrlMode, Party, Secrets};
+use serde_json::json;
+use std::error::Error;
+
+// Owned strings move into the activity, so it can outlive them
+fn owned_activity(track: String, artist: String) -> Result<Activity<'static>, Box<dyn Error>> {
+    Ok(Activity::new()
+        .details(track)
+        .state(format!("by {}", artist))
+        .party(Party::new().id(String::from("party")))
+        .assets(
+            Assets::new()
+                .external_large_image(
+                    String::from("https://example.com/cover.png"),
+                    ImageUrlMode::Direct,
+                )?
+                .large_text(artist),
+        )
+        .secrets(Secrets::new().join(String::from("join")))
+        .buttons(vec![Button::new(
+            String::from("Listen"),
+            String::from("https://example.com"),
+        )]))
+}
+
+#[test]
+fn test_setters_take_any_string() -> Result<(), Box<dyn Error>> {
+    let artist = String::from("Some Artist");
+    let from_literals = Activity::new()
+        .details("Some Track")
+        .state("by Some Artist")
+        .party(Party::new().id("party"))
+        .assets(
+            Assets::new()
+                .external_large_image("https://example.com/cover.png", ImageUrlMode::Direct)?
+                .large_text(&artist),
+        )
+        .secrets(Secrets::new().join("join"))
+        .buttons(vec![Button::new("Listen", "https://example.com")]);
+
+    let from_owned = owned_activity("Some Track".to_string(), artist.clone())?;
+    assert_eq!(
+        serde_json::to_value(&from_literals)?,
+        serde_json::to_value(&from_owned)?
+    );
+    assert_eq!(
+        serde_json::to_value(&from_owned)?,
+        json!({
+            "details": "Some Track",
+            "state": "by Some Artist",
+            "party": { "id": "party" },
+            "assets": {
+                "large_image": "https://example.com/cover.png",
+                "large_text": "Some Artist"
+            },
+            "secrets": { "join": "join" },
+            "buttons": [{ "label": "Listen", "url": "https://example.com" }]
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_invalid_owned_url_reported() {
+    let url = String::from("http://example.com/cover.png");
+    let err = Assets::new()
+        .external_small_image(url, ImageUrlMode::MediaProxy)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Can't use \"http://example.com/cover.png\" as an image, as it isn't an https URL"
+    );
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    error,
+    event::{EventKind, Subscription},
+    DiscordIpc,
+};
+use serde_json::json;
+use std::error::Error;
+
+#[test]
+fn test_validation() {
+    for kind in EventKind::ALL.iter().copied() {
+        let bare = Subscription::new(kind);
+        match kind.required_argument() {
+            None => assert!(bare.validate().is_ok(), "{} needs no arguments", kind),
+            Some(argument) => match bare.validate() {
+                Err(error::Error::MissingSubscriptionArgument {
+                    event,
+                    argument: missing,
+                }) => {
+                    assert_eq!(event, kind);
+                    assert_eq!(missing, argument);
+                }
+                other => panic!("expected {} to require {}, got {:?}", kind, argument, other),
+            },
+        }
+
+        let scoped = Subscription::new(kind).channel("123").guild("456");
+        assert!(scoped.validate().is_ok());
+    }
+
+    // A guild is no substitute for a channel, and vice versa
+    assert!(Subscription::message_create()
+        .guild("456")
+        .validate()
+        .is_err());
+    assert!(Subscription::guild_status()
+        .channel("123")
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn test_serialization() {
+    for kind in EventKind::ALL.iter().copied() {
+        assert_eq!(EventKind::from_name(kind.as_str()), Some(kind));
+    }
+    assert_eq!(EventKind::from_name("READY"), None);
+
+    assert_eq!(Subscription::activity_join().args(), json!({}));
+    assert_eq!(
+        Subscription::speaking_start().channel("123").args(),
+        json!({ "channel_id": "123" })
+    );
+    assert_eq!(
+        Subscription::voice_state_update().channel("123").args(),
+        json!({ "channel_id": "123" })
+    );
+    assert_eq!(
+        Subscription::message_delete().channel("123").args(),
+        json!({ "channel_id": "123" })
+    );
+    assert_eq!(
+        Subscription::guild_status().guild("456").args(),
+        json!({ "guild_id": "456" })
+    );
+}
+
+#[test]
+fn test_subscribe() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    client.subscribe(&Subscription::speaking_start().channel("123"))?;
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "SUBSCRIBE",
+            "args": { "channel_id": "123" },
+            "evt": "SPEAKING_START"
+        })
+    );
+
+    client.unsubscribe(&Subscription::activity_join_request())?;
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "UNSUBSCRIBE",
+            "args": {},
+            "evt": "ACTIVITY_JOIN_REQUEST"
+        })
+    );
+
+    // Invalid subscriptions never reach the wire
+    let sent = client.sent.len();
+    assert!(client.subscribe(&Subscription::guild_status()).is_err());
+    assert_eq!(client.sent.len(), sent);
+    Ok(())
+}
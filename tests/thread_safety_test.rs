@@ -0,0 +1,61 @@
+mod common;
+
+use common::MockClient;
+use discord_rich_presence::{
+    activity::Activity, event::Event, pump::EventPump, DiscordIpc, DiscordIpcClient,
+};
+use serde_json::json;
+use std::{error::Error, sync::Mutex, thread};
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn test_clients_are_send() {
+    assert_send::<DiscordIpcClient>();
+    assert_send::<Mutex<DiscordIpcClient>>();
+    assert_sync::<Mutex<DiscordIpcClient>>();
+    assert_send::<Box<dyn DiscordIpc + Send>>();
+}
+
+#[test]
+fn test_event_pump_is_shareable() {
+    assert_send::<EventPump>();
+    assert_sync::<EventPump>();
+    assert_send::<Event>();
+}
+
+#[test]
+fn test_trait_object() -> Result<(), Box<dyn Error>> {
+    let mut client: Box<dyn DiscordIpc + Send> = Box::new(MockClient::new("771124766517755954"));
+    client.connect()?;
+    client.set_activity(Activity::new().state("Hello world!"))?;
+
+    // Methods requiring `Sized` are available through the box
+    client.on_any_event(|_| {});
+    let (pump, events) = client.spawn_event_pump()?;
+    assert!(matches!(events.recv()?, Event::Ready(_)));
+    pump.command("GET_GUILDS", json!({}))?;
+    Ok(())
+}
+
+#[test]
+fn test_move_between_threads() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    let client = Mutex::new(client);
+    thread::scope(|scope| {
+        for _ in 0..2 {
+            scope.spawn(|| {
+                let mut client = client.lock().unwrap();
+                client.command("GET_GUILDS", json!({})).unwrap();
+            });
+        }
+    });
+
+    let client = client.into_inner().unwrap();
+    let commands = client.sent.iter().filter(|(opcode, _)| *opcode == 1);
+    assert_eq!(commands.count(), 2);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use discord_rich_presence::{
+    activity::{Activity, Timestamps},
+    error,
+};
+use serde_json::{json, Value};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const START: i64 = 1650000000000;
+const END: i64 = 1650000200000;
+
+fn to_value(timestamps: Timestamps) -> Value {
+    serde_json::to_value(timestamps).unwrap()
+}
+
+fn unix_time(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis as u64)
+}
+
+#[test]
+fn test_from_pair() {
+    assert_eq!(
+        to_value((START, END).into()),
+        json!({ "start": START, "end": END })
+    );
+}
+
+#[test]
+fn test_from_range() {
+    assert_eq!(
+        to_value((1650000000..1650000200).into()),
+        json!({ "start": 1650000000, "end": 1650000200 })
+    );
+}
+
+#[test]
+fn test_from_system_time() {
+    assert_eq!(to_value(unix_time(START).into()), json!({ "start": START }));
+
+    let before_epoch = UNIX_EPOCH - Duration::from_secs(1);
+    assert_eq!(to_value(before_epoch.into()), json!({ "start": 0 }));
+}
+
+#[test]
+fn test_try_from_system_times() -> Result<(), Box<dyn Error>> {
+    let timestamps = Timestamps::try_from((unix_time(START), unix_time(END)))?;
+    assert_eq!(to_value(timestamps), json!({ "start": START, "end": END }));
+
+    let before_epoch = UNIX_EPOCH - Duration::from_secs(1);
+    for times in [
+        (before_epoch, unix_time(END)),
+        (unix_time(START), before_epoch),
+        (unix_time(END), unix_time(START)),
+    ] {
+        assert!(matches!(
+            Timestamps::try_from(times),
+            Err(error::Error::InvalidTimestamps { .. })
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_activity_takes_conversions() -> Result<(), Box<dyn Error>> {
+    let activity = Activity::new().timestamps((START, END));
+    assert_eq!(
+        serde_json::to_value(&activity)?["timestamps"],
+        json!({ "start": START, "end": END })
+    );
+
+    let activity = Activity::new().timestamps(unix_time(START));
+    assert_eq!(
+        serde_json::to_value(&activity)?["timestamps"],
+        json!({ "start": START })
+    );
+    Ok(())
+}
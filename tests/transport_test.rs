@@ -0,0 +1,103 @@
+mod common;
+
+use common::{default_response, without_nonce};
+use discord_rich_presence::{activity, pid, transport::IpcTransport, DiscordIpc, DiscordIpcClient};
+use serde_json::{json, Value};
+use std::{collections::VecDeque, convert::TryInto, error::Error, time::Duration};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+// An in-memory transport, answering frames with the mock's
+// default responses
+#[derive(Default)]
+struct MemoryTransport {
+    connects: usize,
+    sent: Vec<(u32, Value)>,
+    written: Vec<u8>,
+    incoming: VecDeque<u8>,
+    shut_down: bool,
+}
+
+impl IpcTransport for MemoryTransport {
+    fn connect(&mut self) -> Result<()> {
+        self.connects += 1;
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        if self.incoming.len() < buffer.len() {
+            return Err("no more data".into());
+        }
+        for byte in buffer.iter_mut() {
+            *byte = self.incoming.pop_front().unwrap();
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.written.extend_from_slice(data);
+        while self.written.len() >= 8 {
+            let opcode = u32::from_le_bytes(self.written[..4].try_into()?);
+            let length = u32::from_le_bytes(self.written[4..8].try_into()?) as usize;
+            if self.written.len() < 8 + length {
+                break;
+            }
+
+            let frame: Vec<u8> = self.written.drain(..8 + length).collect();
+            let payload: Value = serde_json::from_slice(&frame[8..])?;
+            for (opcode, reply) in default_response(opcode, &payload) {
+                let data = reply.to_string();
+                self.incoming.extend(opcode.to_le_bytes());
+                self.incoming.extend((data.len() as u32).to_le_bytes());
+                self.incoming.extend(data.into_bytes());
+            }
+            self.sent.push((opcode, payload));
+        }
+        Ok(())
+    }
+
+    fn wait_readable(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(!self.incoming.is_empty())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        self.shut_down = true;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_connect_and_set_activity_through_transport() -> Result<()> {
+    let mut client =
+        DiscordIpcClient::with_transport("771124766517755954", MemoryTransport::default());
+    client.connect()?;
+    client.set_activity(activity::Activity::new().state("In memory"))?;
+
+    let sent = &client.transport().sent;
+    assert_eq!(client.transport().connects, 1);
+    assert_eq!(
+        sent[0],
+        (0, json!({ "v": 1, "client_id": "771124766517755954" }))
+    );
+    assert_eq!(
+        without_nonce(&sent[1].1),
+        json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": pid::pid(), "activity": { "state": "In memory" } }
+        })
+    );
+
+    client.close()?;
+    assert_eq!(client.transport().sent.last().unwrap().0, 2);
+    assert!(client.transport().shut_down);
+    Ok(())
+}
+
+#[test]
+fn test_boxed_transport() -> Result<()> {
+    let transport: Box<dyn IpcTransport + Send> = Box::new(MemoryTransport::default());
+    let mut client = DiscordIpcClient::with_transport("771124766517755954", transport);
+    client.connect()?;
+    client.clear_activity()?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+#![cfg(feature = "undocumented")]
+
+mod common;
+
+use common::{default_response, response, MockClient};
+use discord_rich_presence::{
+    error, pid,
+    undocumented::{RelationshipType, UndocumentedCommands},
+    DiscordIpc,
+};
+use serde_json::json;
+use std::error::Error;
+
+#[test]
+fn test_set_overlay_locked() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_overlay_locked(true)?;
+
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "SET_OVERLAY_LOCKED",
+            "args": { "locked": true, "pid": pid::pid() }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_get_relationships() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode == 0 {
+            return default_response(opcode, request);
+        }
+        vec![response(
+            request,
+            json!({
+                "relationships": [
+                    {
+                        "type": 1,
+                        "user": { "id": "1", "username": "friend", "discriminator": "0", "avatar": null },
+                        "presence": {
+                            "status": "online",
+                            "activity": {
+                                "name": "Some Game",
+                                "type": 0,
+                                "state": "In a match",
+                                "timestamps": { "start": 1650000000000i64 },
+                                "buttons": ["Watch"]
+                            }
+                        }
+                    },
+                    {
+                        "type": 2,
+                        "user": { "id": "2", "username": "blocked" }
+                    },
+                    {
+                        "type": 3,
+                        "user": { "id": "3", "username": "pending", "bot": false },
+                        "presence": { "status": "offline", "activity": null }
+                    },
+                    {
+                        "type": 99,
+                        "user": { "id": "4", "username": "mystery" }
+                    }
+                ]
+            }),
+        )]
+    });
+    client.connect()?;
+
+    let relationships = client.get_relationships()?;
+    assert_eq!(
+        client.last_sent(),
+        json!({ "cmd": "GET_RELATIONSHIPS", "args": {} })
+    );
+
+    let kinds: Vec<_> = relationships.iter().map(|r| r.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            RelationshipType::Friend,
+            RelationshipType::Blocked,
+            RelationshipType::PendingIncoming,
+            RelationshipType::Unknown(99),
+        ]
+    );
+
+    let friend = &relationships[0];
+    assert_eq!(friend.user.username, "friend");
+    let presence = friend.presence.as_ref().unwrap();
+    assert_eq!(presence.status, "online");
+    assert_eq!(
+        serde_json::to_value(presence.activity.as_ref().unwrap())?,
+        json!({
+            "name": "Some Game",
+            "type": 0,
+            "state": "In a match",
+            "timestamps": { "start": 1650000000000i64 },
+            "buttons": [{ "label": "Watch", "url": "" }]
+        })
+    );
+
+    assert!(relationships[1].presence.is_none());
+    assert!(relationships[2]
+        .presence
+        .as_ref()
+        .unwrap()
+        .activity
+        .is_none());
+    Ok(())
+}
+
+#[test]
+fn test_open_overlay_voice_settings() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.open_overlay_voice_settings()?;
+
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "OPEN_OVERLAY_VOICE_SETTINGS",
+            "args": { "pid": pid::pid() }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_open_overlay_guild_invite() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.open_overlay_guild_invite("discord-developers")?;
+
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "OPEN_OVERLAY_GUILD_INVITE",
+            "args": { "code": "discord-developers", "pid": pid::pid() }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_open_overlay_activity_invite() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.open_overlay_activity_invite()?;
+
+    assert_eq!(
+        client.last_sent(),
+        json!({
+            "cmd": "OPEN_OVERLAY_ACTIVITY_INVITE",
+            "args": { "type": 1, "pid": pid::pid() }
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_command_error() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954").respond_with(|opcode, request| {
+        if opcode == 0 {
+            return default_response(opcode, request);
+        }
+        vec![(
+            1,
+            json!({
+                "cmd": request["cmd"],
+                "data": { "code": 4000, "message": "Invalid overlay" },
+                "evt": "ERROR",
+                "nonce": request["nonce"]
+            }),
+        )]
+    });
+    client.connect()?;
+
+    let err = client.set_overlay_locked(false).unwrap_err();
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::CommandError { code, message }) => {
+            assert_eq!(*code, 4000);
+            assert_eq!(message, "Invalid overlay");
+        }
+        _ => panic!("expected a CommandError, got {}", err),
+    }
+
+    client.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_relationships_of_wrong_type() {
+    // Once a panic, indexing into data which isn't an object
+    let mut client =
+        MockClient::new("771124766517755954").respond_with(|opcode, request| match opcode {
+            0 => default_response(opcode, request),
+            _ => vec![response(request, json!("relationships"))],
+        });
+    client.connect().unwrap();
+
+    assert!(client.get_relationships().is_err());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{activity_updates, MockClient};
+use discord_rich_presence::{
+    activity::{Activity, Timestamps},
+    error, DiscordIpc,
+};
+use serde_json::json;
+use std::{error::Error, mem};
+
+fn assert_no_activity_set(err: Box<dyn Error>) {
+    match err.downcast_ref::<error::Error>() {
+        Some(error::Error::NoActivitySet) => (),
+        _ => panic!("expected NoActivitySet, got {}", err),
+    }
+}
+
+#[test]
+fn test_update_without_activity_fails() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+
+    let err = client
+        .update_activity(|activity| *activity = mem::take(activity).state("Unsent"))
+        .unwrap_err();
+    assert_no_activity_set(err);
+    assert!(activity_updates(&client.sent).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_update_changes_one_field() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(
+        Activity::new()
+            .details("Listening")
+            .state("First track")
+            .timestamps(Timestamps::new().start(1)),
+    )?;
+
+    let track = String::from("Second track");
+    client.update_activity(|activity| *activity = mem::take(activity).state(&track))?;
+    client
+        .update_activity(|activity| *activity = mem::take(activity).details("Still listening"))?;
+
+    let updates = activity_updates(&client.sent);
+    assert_eq!(updates.len(), 3);
+    assert_eq!(
+        updates[1],
+        json!({ "details": "Listening", "state": "Second track", "timestamps": { "start": 1 } })
+    );
+    assert_eq!(
+        updates[2],
+        json!({ "details": "Still listening", "state": "Second track", "timestamps": { "start": 1 } })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_unchanged_update_is_not_sent() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(Activity::new().state("Idle"))?;
+
+    client.update_activity(|_| ())?;
+    client.update_activity(|activity| *activity = mem::take(activity).state("Idle"))?;
+    assert_eq!(activity_updates(&client.sent).len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_clear_forgets_activity() -> Result<(), Box<dyn Error>> {
+    let mut client = MockClient::new("771124766517755954");
+    client.connect()?;
+    client.set_activity(Activity::new().state("Playing"))?;
+    client.clear_activity()?;
+
+    let err = client.update_activity(|_| ()).unwrap_err();
+    assert_no_activity_set(err);
+
+    // Setting an activity again gives updates a starting point
+    client.set_activity(Activity::new().state("Playing again"))?;
+    client.update_activity(|activity| *activity = mem::take(activity).details("Level 2"))?;
+    assert_eq!(
+        activity_updates(&client.sent).last(),
+        Some(&json!({ "details": "Level 2", "state": "Playing again" }))
+    );
+    Ok(())
+}
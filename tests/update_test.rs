@@ -1,33 +1,72 @@
-use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
-use std::error::Error;
+mod common;
+
+use common::without_nonce;
+use discord_rich_presence::{
+    activity, pid, test_util::MockDiscordServer, DiscordIpc, DiscordIpcClient,
+};
+use serde_json::json;
+use std::{error::Error, time::Duration};
+
+fn activity<'a>(state: &'a str, large_image: &'a str) -> activity::Activity<'a> {
+    activity::Activity::new()
+        .state(state)
+        .details("a placeholder")
+        .assets(
+            activity::Assets::new()
+                .large_image(large_image)
+                .large_text("a thing"),
+        )
+}
 
 #[test]
 fn test_updating() -> Result<(), Box<dyn Error>> {
+    let server = MockDiscordServer::start()?;
+    let mut client = server.client("771124766517755954");
+    client.connect()?;
+
+    client.set_activity(activity("part 1 (test)", "large-image"))?;
+    client.set_activity(activity("part 2 (test)", "small-image"))?;
+    client.close()?;
+
+    let received = server.wait_for_frames(4, Duration::from_secs(1));
+    let updates: Vec<_> = received[1..3]
+        .iter()
+        .map(|(_, payload)| without_nonce(payload))
+        .collect();
+    assert_eq!(
+        updates,
+        [
+            ("part 1 (test)", "large-image"),
+            ("part 2 (test)", "small-image")
+        ]
+        .iter()
+        .map(|(state, large_image)| json!({
+            "cmd": "SET_ACTIVITY",
+            "args": {
+                "pid": pid::pid(),
+                "activity": {
+                    "state": state,
+                    "details": "a placeholder",
+                    "assets": { "large_image": large_image, "large_text": "a thing" }
+                }
+            }
+        }))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(received[3], (2, json!({})));
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires a running Discord client"]
+fn test_updating_live() -> Result<(), Box<dyn Error>> {
     let mut client = DiscordIpcClient::new("771124766517755954")?;
     client.connect()?;
 
-    client.set_activity(
-        activity::Activity::new()
-            .state("part 1 (test)")
-            .details("a placeholder")
-            .assets(
-                activity::Assets::new()
-                    .large_image("large-image")
-                    .large_text("a thing"),
-            ),
-    )?;
+    client.set_activity(activity("part 1 (test)", "large-image"))?;
     std::thread::sleep(std::time::Duration::from_secs(2));
 
-    client.set_activity(
-        activity::Activity::new()
-            .state("part 2 (test)")
-            .details("a placeholder")
-            .assets(
-                activity::Assets::new()
-                    .large_image("small-image")
-                    .large_text("a thing"),
-            ),
-    )?;
+    client.set_activity(activity("part 2 (test)", "small-image"))?;
     std::thread::sleep(std::time::Duration::from_secs(2));
 
     client.close()?;
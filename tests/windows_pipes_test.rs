@@ -0,0 +1,82 @@
+use discord_rich_presence::transport::{windows_pipe_paths, APP_CONTAINER_SUBPATH};
+use std::path::PathBuf;
+
+const SIDS: [&str; 2] = [
+    "S-1-15-2-1861897761-1695161497-2927542615-642690995-327840285-2659745135-2630312742",
+    "S-1-15-2-4294967295-1-2-3-4-5-6",
+];
+
+#[test]
+fn test_store_pipes_follow_each_regular_pipe() {
+    let paths = windows_pipe_paths(Some(2), &SIDS);
+    assert_eq!(paths.len(), 30);
+
+    for index in 0..10 {
+        let expected: Vec<PathBuf> = [
+            format!(r"\\?\pipe\discord-ipc-{}", index),
+            format!(
+                r"\\?\pipe\Sessions\2\{}\{}\discord-ipc-{}",
+                APP_CONTAINER_SUBPATH, SIDS[0], index
+            ),
+            format!(
+                r"\\?\pipe\Sessions\2\{}\{}\discord-ipc-{}",
+                APP_CONTAINER_SUBPATH, SIDS[1], index
+            ),
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(paths[index * 3..index * 3 + 3], expected[..]);
+    }
+}
+
+#[test]
+fn test_regular_pipes_only_without_packages_or_session() {
+    let regular: Vec<PathBuf> = (0..10)
+        .map(|index| PathBuf::from(format!(r"\\?\pipe\discord-ipc-{}", index)))
+        .collect();
+
+    assert_eq!(windows_pipe_paths::<&str>(Some(2), &[]), regular);
+    assert_eq!(windows_pipe_paths(None, &SIDS), regular);
+}
+
+#[cfg(windows)]
+#[test]
+#[ignore = "needs Discord installed from the Microsoft Store, and running"]
+fn test_connects_to_store_install() -> Result<(), Box<dyn std::error::Error>> {
+    use discord_rich_presence::transport::{EndpointSource, IpcTransport, NativeTransport};
+
+    let mut transport = NativeTransport::new();
+    transport.connect()?;
+    assert!(matches!(
+        transport.connected_endpoint().map(|endpoint| endpoint.source),
+        Some(EndpointSource::Discovered { subpath, .. }) if subpath == APP_CONTAINER_SUBPATH
+    ));
+    Ok(())
+}
+
+#[cfg(windows)]
+#[test]
+fn test_discovery_reads_the_given_environment() -> Result<(), Box<dyn std::error::Error>> {
+    use discord_rich_presence::transport::{NativeTransport, IPC_PATH_VAR};
+    use std::{collections::HashMap, env, fs};
+
+    let pipe = r"\\?\pipe\my-custom-ipc";
+    let env: HashMap<_, _> = [(IPC_PATH_VAR.to_string(), pipe.to_string())].into();
+    assert_eq!(
+        NativeTransport::with_env(env).candidate_paths(),
+        vec![PathBuf::from(pipe)]
+    );
+
+    // No packages are installed in a fabricated LOCALAPPDATA
+    let dir = env::temp_dir().join(format!("discord-ipc-packages-{}", std::process::id()));
+    fs::create_dir_all(dir.join("Packages"))?;
+    let env: HashMap<_, _> = [("LOCALAPPDATA".to_string(), dir.display().to_string())].into();
+    assert_eq!(
+        NativeTransport::with_env(env).candidate_paths(),
+        windows_pipe_paths::<&str>(None, &[])
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}